@@ -1,4 +1,4 @@
-use std::{process::ExitCode, str::FromStr, time::Duration};
+use std::{path::Path, process::ExitCode, str::FromStr, time::Duration};
 
 use alumet::{
     agent::{
@@ -6,13 +6,18 @@ use alumet::{
         config::{AutoDefaultConfigProvider, DefaultConfigProvider, NoDefaultConfigProvider, merge_override},
         exec,
         plugin::{PluginFilter, PluginSet, UnknownPluginInConfigPolicy},
-        watch,
+        schema, watch,
     },
     pipeline,
     plugin::PluginMetadata,
     static_plugins,
 };
-use alumet_agent::{exec_hints, init_logger};
+use alumet_agent::{config_check, exec_hints, exec_report::ReportCollector, health, init_logger};
+#[cfg(unix)]
+use alumet_agent::{
+    reload::{self, AgentConfig, Reloader},
+    top::{self, LiveTable},
+};
 use anyhow::Context;
 use clap::{Args, FromArgMatches};
 use cli::{ConfigArgs, ConfigCommand, PluginsArgs, PluginsCommand};
@@ -39,6 +44,7 @@ fn load_plugins_metadata() -> Vec<PluginMetadata> {
         plugin_kwollect_output::KwollectPlugin,
         plugin_filter::FilterPlugin,
         plugin_energy_to_carbon::EnergyToCarbonPlugin,
+        plugin_parquet::ParquetPlugin,
     ];
 
     // plugins that only work on Linux
@@ -100,6 +106,23 @@ fn main() -> anyhow::Result<ExitCode> {
         plugins.enable_only(enabled_plugins);
     }
 
+    // Check the config file and report all its problems, instead of stopping at the first one.
+    if let Some(cli::Command::Config(ConfigArgs {
+        command: ConfigCommand::Check,
+    })) = &args.command
+    {
+        return check_config(&args, plugins);
+    }
+
+    // Print the config file, or the effective config.
+    if let Some(cli::Command::Config(ConfigArgs {
+        command: ConfigCommand::Show { merged },
+    })) = &args.command
+    {
+        show_config(&args, *merged)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
     // Run CLI commands that run before the config is loaded.
     if run_command_no_config(&args, &plugins)? {
         return Ok(ExitCode::SUCCESS);
@@ -115,6 +138,8 @@ fn main() -> anyhow::Result<ExitCode> {
     let mut config = agent::config::Loader::parse_file(&args.common.config)
         .or_default_boxed(default_config_provider, true)
         .substitute_env_variables(true)
+        .include_files(true)
+        .drop_in_dir(agent::config::default_drop_in_dir(Path::new(&args.common.config)))
         .with_override(config_override)
         .load()
         .context("could not load config file")?;
@@ -129,6 +154,12 @@ fn main() -> anyhow::Result<ExitCode> {
         )
         .context("invalid plugins config")?;
 
+    // Keep the applied config, in order to compare it with the new one when the `run` command reloads it.
+    // Reloading relies on SIGHUP, which only exists on unix.
+    #[cfg(unix)]
+    let reloader = matches!(args.command, None | Some(cli::Command::Run))
+        .then(|| Reloader::new(AgentConfig::new(config.clone(), &plugins)));
+
     // Extract non-plugin config.
     let config = config.try_into::<GeneralConfig>().context("invalid general config")?;
 
@@ -143,21 +174,84 @@ fn main() -> anyhow::Result<ExitCode> {
     // begin the creation of the pipeline (we have some settings to apply to it)
     let mut pipeline = pipeline::Builder::new();
     apply_pipeline_settings(&args, &config, &mut pipeline);
+    let (report, run_tagger) = match &args.command {
+        Some(cli::Command::Exec(exec_args)) => setup_exec_report(exec_args, &mut pipeline)?,
+        _ => (None, None),
+    };
+    #[cfg(unix)]
+    let live_table = match &args.command {
+        Some(cli::Command::Top(_)) => {
+            let table = LiveTable::new();
+            table.install(&mut pipeline)?;
+            Some(table)
+        }
+        _ => None,
+    };
 
     // start Alumet with the pipeline and plugins
-    let agent = agent::Builder::from_pipeline(plugins, pipeline)
+    #[cfg_attr(not(unix), allow(unused_mut))] // only the reloader modifies the agent
+    let mut agent = agent::Builder::from_pipeline(plugins, pipeline)
         .build_and_start()
         .context("startup failure")?;
 
+    // serve the health endpoint, if enabled
+    if let Some(health_config) = health_settings(&args, &config) {
+        health::serve(&agent.pipeline, &health_config).context("could not start the health endpoint")?;
+    }
+
     // run the provided command, the default is Run
     match args.command.take().unwrap_or(cli::Command::Run) {
         cli::Command::Run => {
-            // execute the pipeline until Alumet is externally stopped (e.g. by Ctrl+C)
+            // execute the pipeline until Alumet is externally stopped (e.g. by Ctrl+C),
+            // and reload the config on SIGHUP
+            #[cfg(unix)]
+            if let Some(mut reloader) = reloader {
+                let sighup = reload::on_sighup(&agent.pipeline)?;
+                // the channel is closed when the pipeline begins to shut down
+                for () in sighup {
+                    log::info!("SIGHUP received, reloading the configuration...");
+                    match reload_config(&args) {
+                        Ok((general, plugins)) => reloader.reload(&mut agent, general, plugins).log(),
+                        Err(e) => log::error!("Could not reload the configuration, the current one is kept. {e:?}"),
+                    }
+                }
+            }
             agent.wait_for_shutdown(Duration::MAX).context("error while running")?;
         }
         cli::Command::Exec(exec_args) => {
             let timeout = Duration::from_secs(5);
-            let res = exec::exec_process(agent, exec_args.program, exec_args.args, timeout);
+            let command = [vec![exec_args.program.clone()], exec_args.args.clone()].concat();
+            let res = match (&report, &run_tagger) {
+                (Some(report), Some(tagger)) => {
+                    let settings = exec_args.benchmark_settings();
+                    let res =
+                        exec::exec_benchmark(agent, exec_args.program, exec_args.args, &settings, tagger, timeout);
+                    res.map(|phases| {
+                        let report = report.finish_benchmark(command, &phases);
+                        println!("{report}");
+                        if let Some(path) = &exec_args.report_json {
+                            write_exec_report(|| report.write_json(path), path);
+                        }
+                        // Propagate the exit status of the first run that failed, if any.
+                        phases
+                            .iter()
+                            .filter_map(|p| p.exit_status)
+                            .find(|status| !status.success())
+                            .unwrap_or_default()
+                    })
+                }
+                _ => {
+                    let res = exec::exec_process(agent, exec_args.program, exec_args.args, timeout);
+                    if let (Some(report), Ok(exit_status)) = (report, &res) {
+                        let report = report.finish(command, exit_status.code());
+                        println!("{report}");
+                        if let Some(path) = &exec_args.report_json {
+                            write_exec_report(|| report.write_json(path), path);
+                        }
+                    }
+                    res
+                }
+            };
             match res {
                 Ok(_) if exec_args.ignore_exit_code => (),
                 Ok(process_exit_code) => {
@@ -202,7 +296,12 @@ fn main() -> anyhow::Result<ExitCode> {
         }
         cli::Command::Watch(process) => {
             let shutdown_timeout = Duration::from_secs(5);
-            let res = watch::watch_process(agent, process.pid, shutdown_timeout);
+            let targets = watch::WatchTargets {
+                pids: process.pids,
+                name: process.name,
+                children: process.children,
+            };
+            let res = watch::watch_processes(agent, &targets, shutdown_timeout);
 
             if let Err(watch::WatchError::ProcessWait(pid, e)) = &res
                 && e.kind() == std::io::ErrorKind::NotFound
@@ -213,11 +312,57 @@ fn main() -> anyhow::Result<ExitCode> {
                 panic!("{err}");
             }
         }
+        #[cfg(unix)]
+        cli::Command::Top(top_args) => {
+            let table = live_table.expect("the live table should be installed for the top command");
+            let view = top::View::new(top_args.sort, top_args.reverse, top_args.filter);
+            let settings = top::TopSettings {
+                refresh: top_args.refresh,
+                batch: top_args.batch,
+                iterations: top_args.iterations,
+            };
+            top::run(&agent.pipeline, &table, view, &settings).context("could not display the measurements")?;
+            agent
+                .wait_for_shutdown(Duration::from_secs(5))
+                .context("error while running")?;
+        }
         _ => unreachable!("every command should have been handled at this point"),
     }
     Ok(ExitCode::SUCCESS)
 }
 
+/// Prepares the collection of the measurements for the summary of the `exec` command, if it is requested.
+///
+/// In benchmark mode, the measurements are also tagged with the id of the run.
+fn setup_exec_report(
+    exec_args: &cli::ExecArgs,
+    pipeline: &mut pipeline::Builder,
+) -> anyhow::Result<(Option<ReportCollector>, Option<exec::RunTagger>)> {
+    let run_tagger = if exec_args.is_benchmark() {
+        let tagger = exec::RunTagger::default();
+        tagger.install(pipeline)?;
+        Some(tagger)
+    } else {
+        None
+    };
+    let report = if exec_args.report || exec_args.report_json.is_some() || run_tagger.is_some() {
+        let report = ReportCollector::new();
+        report.install(pipeline)?;
+        Some(report)
+    } else {
+        None
+    };
+    Ok((report, run_tagger))
+}
+
+/// Writes the summary of the `exec` command. Failing to write it is not fatal.
+fn write_exec_report(write: impl FnOnce() -> anyhow::Result<()>, path: &std::path::Path) {
+    match write() {
+        Ok(()) => log::info!("Report written to: {}", path.display()),
+        Err(e) => log::error!("Could not write the report: {e:?}"),
+    }
+}
+
 /// Prints a short welcome message.
 fn print_welcome() {
     // It is useful to have the precise version of the agent in the logs.
@@ -248,6 +393,13 @@ fn run_command_no_config(args: &cli::Cli, plugins: &PluginSet) -> anyhow::Result
             log::info!("Default configuration file written to: {file}");
             Ok(true)
         }
+        Some(Command::Config(ConfigArgs {
+            command: ConfigCommand::Schema,
+        })) => {
+            // print the schema of the config, with every available plugin
+            print_config_schema(plugins)?;
+            Ok(true)
+        }
         Some(Command::Plugins(PluginsArgs {
             status: false,
             command: PluginsCommand::List,
@@ -264,6 +416,56 @@ fn run_command_no_config(args: &cli::Cli, plugins: &PluginSet) -> anyhow::Result
     }
 }
 
+/// Prints the JSON Schema of the config file: the general options and the config of each plugin.
+fn print_config_schema(plugins: &PluginSet) -> anyhow::Result<()> {
+    let general = schema::schema_of::<GeneralConfig>();
+    let mut schema = agent::config::generate_config_schema(general, plugins.metadata(PluginFilter::Any))?;
+    schema.insert(
+        String::from("$schema"),
+        "https://json-schema.org/draft/2020-12/schema".into(),
+    );
+    schema.insert(String::from("title"), "Alumet agent configuration".into());
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+/// Checks the config file, prints the problems and returns a failure exit code if there is any.
+fn check_config(args: &cli::Cli, plugins: PluginSet) -> anyhow::Result<ExitCode> {
+    let config_override = parse_config_overrides(args).context("invalid config overrides")?;
+    let report = config_check::check_file::<GeneralConfig>(
+        args.common.config.as_ref(),
+        config_override,
+        plugins,
+        args.common.plugins.is_none(),
+    );
+    print!("{report}");
+    if report.is_ok() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Prints the config file, or the effective config with the origin of each value if `merged` is true.
+fn show_config(args: &cli::Cli, merged: bool) -> anyhow::Result<()> {
+    let file = Path::new(&args.common.config);
+    let loader = agent::config::Loader::parse_file(file).substitute_env_variables(true);
+    if merged {
+        let config_override = parse_config_overrides(args).context("invalid config overrides")?;
+        let (config, origins) = loader
+            .include_files(true)
+            .drop_in_dir(agent::config::default_drop_in_dir(file))
+            .with_override(config_override)
+            .load_with_origins()
+            .context("could not load config file")?;
+        print!("{}", origins.annotate(&config));
+    } else {
+        let config = loader.load().context("could not load config file")?;
+        print!("{}", toml::to_string_pretty(&config)?);
+    }
+    Ok(())
+}
+
 /// If selected by the CLI user, runs a command that does not need the measurement pipeline.
 ///
 /// Returns `true` if a command was run (in which case you probably should stop here).
@@ -300,6 +502,9 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(source_channel_size) = config.source_channel_size {
         *pipeline.source_channel_size() = source_channel_size;
     }
+    if let Some(interval) = config.self_monitoring_interval {
+        pipeline.enable_self_monitoring(interval.into_inner());
+    }
 
     // cli arguments
     if let Some(max_update_interval) = args.common.max_update_interval {
@@ -308,6 +513,9 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(source_channel_size) = args.common.source_channel_size {
         *pipeline.source_channel_size() = source_channel_size;
     }
+    if let Some(interval) = args.common.self_monitoring {
+        pipeline.enable_self_monitoring(interval);
+    }
     if matches!(args.command, Some(cli::Command::Exec(_))) {
         // the "exec" command requires event-based source trigger
         pipeline.trigger_constraints_mut().allow_manual_trigger = true;
@@ -320,6 +528,57 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     }
 }
 
+/// Returns the settings of the health endpoint, or `None` if it is disabled.
+///
+/// The listening address given on the command line takes precedence over the config file.
+fn health_settings(args: &cli::Cli, config: &GeneralConfig) -> Option<health::HealthConfig> {
+    match (&args.common.health, &config.health) {
+        (Some(listen), Some(config)) => Some(health::HealthConfig {
+            listen: listen.to_owned(),
+            critical: config.critical.clone(),
+        }),
+        (Some(listen), None) => Some(health::HealthConfig {
+            listen: listen.to_owned(),
+            critical: Vec::new(),
+        }),
+        (None, config) => config.clone(),
+    }
+}
+
+/// Loads the config file again, in order to apply it to the running agent (see [`reload`]).
+///
+/// Unlike the first loading, the default config is never used: the file must exist and be valid.
+#[cfg(unix)]
+fn reload_config(args: &cli::Cli) -> anyhow::Result<(toml::Table, PluginSet)> {
+    let config_override = parse_config_overrides(args).context("invalid config overrides")?;
+    let mut config = agent::config::Loader::parse_file(&args.common.config)
+        .substitute_env_variables(true)
+        .include_files(true)
+        .drop_in_dir(agent::config::default_drop_in_dir(Path::new(&args.common.config)))
+        .with_override(config_override)
+        .load()
+        .context("could not load config file")?;
+
+    let mut plugins = PluginSet::from(load_plugins_metadata());
+    if let Some(enabled_plugins) = &args.common.plugins {
+        plugins.enable_only(enabled_plugins);
+    }
+    plugins
+        .extract_config(
+            &mut config,
+            args.common.plugins.is_none(),
+            UnknownPluginInConfigPolicy::Error,
+        )
+        .context("invalid plugins config")?;
+
+    // Check the general config before applying anything.
+    config
+        .clone()
+        .try_into::<GeneralConfig>()
+        .context("invalid general config")?;
+    Ok((config, plugins))
+}
+
 /// Parses the config overrides provided on the command line, and merges them into a single table.
 fn parse_config_overrides(args: &cli::Cli) -> anyhow::Result<toml::Table> {
     let mut config_override = toml::Table::new();
@@ -399,8 +658,12 @@ fn agent_version() -> String {
 /// To apply "advanced" tweaks, we combine the "derive" and "builder" APIs of clap.
 /// See https://docs.rs/clap/latest/clap/_derive/index.html#mixing-builder-and-derive-apis
 mod cli {
+    use alumet::agent::exec::BenchmarkSettings;
+    #[cfg(unix)]
+    use alumet_agent::top::SortColumn;
     use clap::{Args, Parser, Subcommand};
-    use std::time::Duration;
+    use regex::Regex;
+    use std::{path::PathBuf, time::Duration};
 
     // NOTE: the doc comment attached to `Cli` is used by clap as the description of
     // the application. It is displayed at the start of the help message.
@@ -425,9 +688,16 @@ mod cli {
         /// Execute a command and observe its process.
         Exec(ExecArgs),
 
-        /// Watch a PID and observe it until its end
+        /// Watch processes and observe them until their end.
         Watch(Process),
 
+        /// Show the latest measurements in a live table, like `top`.
+        ///
+        /// Keys: `s` changes the sort column, `r` reverses the order, `/` filters the metrics,
+        /// `c` clears the filter and `q` quits.
+        #[cfg(unix)]
+        Top(TopArgs),
+
         /// Manipulate the configuration.
         Config(ConfigArgs),
 
@@ -446,6 +716,32 @@ mod cli {
         #[arg(long, default_value_t = false)]
         pub ignore_exit_code: bool,
 
+        /// If set, prints a summary of the run when the program exits: wall time,
+        /// energy consumed by the machine, energy attributed to the process, CPU and memory peaks.
+        ///
+        /// The summary is computed from the measurements produced by the enabled plugins.
+        #[arg(long, default_value_t = false)]
+        pub report: bool,
+
+        /// Also writes the summary to this file, in the JSON format. Implies `--report`.
+        #[arg(long)]
+        pub report_json: Option<PathBuf>,
+
+        /// Number of times to run the program (benchmark mode).
+        ///
+        /// In benchmark mode, every measurement gets a `run_id` attribute, and the agent
+        /// prints statistics about the energy and duration of the runs.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        pub runs: u32,
+
+        /// Number of warm-up runs, executed before the measured runs and excluded from the statistics.
+        #[arg(long, default_value_t = 0)]
+        pub warmup: u32,
+
+        /// Duration of the idle periods to measure before and after the runs, ex. `10s`.
+        #[arg(long, value_parser = humantime_serde::re::humantime::parse_duration)]
+        pub idle: Option<Duration>,
+
         /// The program to run.
         pub program: String,
 
@@ -454,11 +750,68 @@ mod cli {
         pub args: Vec<String>,
     }
 
+    impl ExecArgs {
+        /// Returns `true` if the program must be run in benchmark mode.
+        pub fn is_benchmark(&self) -> bool {
+            self.runs > 1 || self.warmup > 0 || self.idle.is_some()
+        }
+
+        pub fn benchmark_settings(&self) -> BenchmarkSettings {
+            BenchmarkSettings {
+                runs: self.runs as usize,
+                warmup_runs: self.warmup as usize,
+                idle: self.idle,
+            }
+        }
+    }
+
     /// CLI arguments for the `watch` command.
     #[derive(Args)]
     pub struct Process {
-        /// The PID to watch.
-        pub pid: u32,
+        /// The PIDs to watch.
+        #[arg(required_unless_present = "name")]
+        pub pids: Vec<u32>,
+
+        /// Watch the processes whose name matches this regular expression, ex. `^python3?$`.
+        ///
+        /// Only the processes that are running when the agent starts are matched.
+        #[arg(long)]
+        pub name: Option<Regex>,
+
+        /// Also watch the descendants of the processes, including the ones that are created during the watch.
+        #[arg(long, default_value_t = false)]
+        pub children: bool,
+    }
+
+    /// CLI arguments for the `top` command.
+    #[cfg(unix)]
+    #[derive(Args)]
+    pub struct TopArgs {
+        /// Time between two refreshes of the table, ex. `500ms`.
+        #[arg(long, default_value = "1s", value_parser = humantime_serde::re::humantime::parse_duration)]
+        pub refresh: Duration,
+
+        /// Column used to sort the table.
+        #[arg(long, value_enum, default_value_t = SortColumn::Metric)]
+        pub sort: SortColumn,
+
+        /// Sort the table in descending order.
+        #[arg(long, default_value_t = false)]
+        pub reverse: bool,
+
+        /// Only show the metrics whose name matches this regular expression, ex. `^rapl_`.
+        #[arg(long)]
+        pub filter: Option<Regex>,
+
+        /// Print the table at each refresh, without redrawing the screen nor reading the keyboard.
+        ///
+        /// This is useful when the output is not a terminal.
+        #[arg(long, default_value_t = false)]
+        pub batch: bool,
+
+        /// Stop the agent after this number of refreshes.
+        #[arg(short = 'n', long)]
+        pub iterations: Option<usize>,
     }
 
     #[derive(Args)]
@@ -473,6 +826,23 @@ mod cli {
         ///
         /// If the file exists, it will be overwritten.
         Regen,
+
+        /// Check the configuration file and report every problem found.
+        ///
+        /// The enabled plugins are initialized, but not started. The exit code is non-zero
+        /// if the configuration is invalid.
+        Check,
+
+        /// Print the JSON Schema of the configuration file, with every available plugin.
+        Schema,
+
+        /// Print the configuration file.
+        Show {
+            /// Print the effective configuration, i.e. the config file merged with the files that it includes,
+            /// the drop-in directory and the overrides, with the origin of each value.
+            #[arg(long)]
+            merged: bool,
+        },
     }
 
     #[derive(Args)]
@@ -549,6 +919,20 @@ mod cli {
         #[arg(long)]
         pub source_channel_size: Option<usize>,
 
+        /// Enables the self-monitoring of the pipeline, with the given reporting interval.
+        ///
+        /// The statistics of the pipeline elements (latencies, errors, dropped buffers, channel occupancy)
+        /// are then measured like any other metric, and sent to the outputs.
+        #[arg(long, value_parser = humantime_serde::re::humantime::parse_duration)]
+        pub self_monitoring: Option<Duration>,
+
+        /// Serves the health endpoint on this address and port, for instance `0.0.0.0:8099`.
+        ///
+        /// `GET /live` checks that the agent is alive, `GET /ready` checks that the critical
+        /// pipeline elements (see the `health` section of the config file) are up.
+        #[arg(long)]
+        pub health: Option<String>,
+
         /// How many "normal" worker threads to spawn.
         #[arg(long, env = "ALUMET_NORMAL_THREADS")]
         pub normal_worker_threads: Option<usize>,
@@ -579,13 +963,20 @@ mod cli {
 mod config {
     use std::time::Duration;
 
+    use alumet::agent::schema::JsonSchema;
+    use alumet_agent::health::HealthConfig;
     use serde::{Deserialize, Serialize};
 
     /// General config options, which are not specific to a particular plugin.
-    #[derive(Deserialize, Serialize, Default)]
+    #[derive(Deserialize, Serialize, Default, JsonSchema)]
     pub struct GeneralConfig {
         // TODO move these to an "advanced" table
+        #[schemars(with = "Option<String>")]
         pub max_update_interval: Option<humantime_serde::Serde<Duration>>,
         pub source_channel_size: Option<usize>,
+        #[schemars(with = "Option<String>")]
+        pub self_monitoring_interval: Option<humantime_serde::Serde<Duration>>,
+        /// Health endpoint, disabled if not set.
+        pub health: Option<HealthConfig>,
     }
 }
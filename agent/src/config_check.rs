@@ -0,0 +1,649 @@
+//! Validation of the configuration file, for the `config check` command.
+//!
+//! Unlike the normal startup, which stops at the first error, the check reports every problem at once:
+//! invalid TOML, unknown plugins, unknown keys and invalid values, in the general options and in the
+//! configuration of every enabled plugin. To check the configuration of a plugin, its `init` function is
+//! called, but the plugin is not started.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    agent::{
+        check::{self, ConfigIssue, ConfigPath, IssueKind, PathSegment},
+        config::{ConfigOrigin, ConfigOrigins, Loader, default_drop_in_dir, error::LoadError, substitute_env},
+        plugin::{PluginFilter, PluginSet, UnknownPluginInConfigPolicy},
+    },
+    plugin::{ConfigTable, PluginMetadata},
+};
+use serde::de::DeserializeOwned;
+use toml::de::{DeTable, DeValue};
+
+use crate::word_distance::distance_with_adjacent_transposition;
+
+/// Maximum distance between an unknown key and a known one, to suggest the latter.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The problems found in a configuration file.
+pub struct CheckReport {
+    pub file: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem found in the configuration.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Path of the problematic key, for instance `plugins.rapl.poll_interval`.
+    pub path: String,
+    /// The file that defines the key: the config file, an included file or a drop-in file.
+    /// `None` if the key does not come from a file.
+    pub file: Option<PathBuf>,
+    /// Line and column (starting at 1) of the key in the file, if it has been found.
+    pub location: Option<(usize, usize)>,
+    /// The plugin whose configuration is invalid, if any.
+    pub plugin: Option<String>,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// The configuration could not be loaded: missing file, invalid TOML, include cycle...
+    LoadFailed(String),
+    /// The configuration refers to a plugin that does not exist.
+    UnknownPlugin { suggestion: Option<String> },
+    /// The key is not expected at this place, it would be ignored.
+    UnknownKey { suggestion: Option<String> },
+    /// The value has a wrong type or is invalid.
+    InvalidValue(String),
+    /// The plugin rejected its configuration.
+    PluginInit(String),
+}
+
+/// Checks a configuration file, with the files that it includes and its drop-in directory.
+///
+/// `G` is the structure of the general options, i.e. everything but the `plugins` table.
+/// If `update_status` is true, the plugins are enabled or disabled according to the configuration,
+/// like [`PluginSet::extract_config`] does. The enabled plugins are consumed by the check.
+pub fn check_file<G: DeserializeOwned>(
+    path: &Path,
+    config_override: toml::Table,
+    plugins: PluginSet,
+    update_status: bool,
+) -> CheckReport {
+    let loaded = Loader::parse_file(path)
+        .substitute_env_variables(true)
+        .include_files(true)
+        .drop_in_dir(default_drop_in_dir(path))
+        .with_override(config_override)
+        .load_with_origins();
+    let diagnostics = match loaded {
+        Ok((config, origins)) => {
+            let sources = Sources {
+                main: path.to_owned(),
+                origins,
+                contents: RefCell::new(HashMap::new()),
+            };
+            check_config::<G>(config, &sources, plugins, update_status)
+        }
+        Err(e) => vec![load_error(&e)],
+    };
+    CheckReport {
+        file: path.display().to_string(),
+        diagnostics,
+    }
+}
+
+fn check_config<G: DeserializeOwned>(
+    mut config: toml::Table,
+    sources: &Sources,
+    mut plugins: PluginSet,
+    update_status: bool,
+) -> Vec<Diagnostic> {
+    // Extract the config of the plugins.
+    let mut diagnostics = Vec::new();
+    let order = match plugins.extract_config(&mut config, update_status, UnknownPluginInConfigPolicy::Ignore) {
+        Ok(order) => order,
+        Err(e) => {
+            diagnostics.push(Diagnostic::global(DiagnosticKind::InvalidValue(format!("{e:#}"))));
+            return diagnostics;
+        }
+    };
+    let plugin_names: Vec<String> = plugins.metadata(PluginFilter::Any).map(|m| m.name.clone()).collect();
+    for name in order.iter().filter(|name| plugins.get_plugin(name).is_none()) {
+        let path = ConfigPath(vec![
+            PathSegment::Key(String::from("plugins")),
+            PathSegment::Key(name.clone()),
+        ]);
+        let (file, location) = sources.locate(&path);
+        diagnostics.push(Diagnostic {
+            path: path.to_string(),
+            file,
+            location,
+            plugin: None,
+            kind: DiagnosticKind::UnknownPlugin {
+                suggestion: suggest(name, plugin_names.iter().map(String::as_str)),
+            },
+        });
+    }
+
+    // Check the general options.
+    let (_, issues) = check::deserialize::<G>(config);
+    diagnostics.extend(issues.into_iter().map(|issue| sources.diagnostic(issue, None)));
+
+    // Check the config of each enabled plugin.
+    let (enabled, _) = plugins.into_partition();
+    for plugin in enabled {
+        diagnostics.extend(check_plugin(plugin.metadata, plugin.config, sources));
+    }
+    diagnostics
+}
+
+/// Turns a loading error into a diagnostic, with the location of the TOML syntax error, if any.
+fn load_error(e: &LoadError) -> Diagnostic {
+    let file = e.failed_file().to_owned();
+    let mut message = String::new();
+    let mut location = None;
+    let mut cause: Option<&dyn Error> = Some(e);
+    while let Some(err) = cause {
+        if !message.is_empty() {
+            message.push_str(": ");
+        }
+        match err.downcast_ref::<toml::de::Error>() {
+            Some(toml_err) => {
+                // the Display impl of toml errors prints the location and a snippet, we do it differently
+                message.push_str(toml_err.message().trim_end());
+                location = toml_err
+                    .span()
+                    .zip(read_config(&file))
+                    .map(|(span, text)| line_column(&text, span.start));
+            }
+            None => message.push_str(&err.to_string()),
+        }
+        cause = err.source();
+    }
+    Diagnostic {
+        path: String::new(),
+        file: Some(file),
+        location,
+        plugin: None,
+        kind: DiagnosticKind::LoadFailed(message),
+    }
+}
+
+/// Initializes a plugin with its configuration, and reports the problems.
+fn check_plugin(metadata: PluginMetadata, config: Option<toml::Table>, sources: &Sources) -> Vec<Diagnostic> {
+    let name = metadata.name.clone();
+    let config = match config {
+        Some(config) => config,
+        None => match (metadata.default_config)() {
+            Ok(default) => default.unwrap_or_default().0,
+            Err(e) => {
+                return vec![Diagnostic {
+                    path: format!("plugins.{name}"),
+                    file: None,
+                    location: None,
+                    plugin: Some(name.clone()),
+                    kind: DiagnosticKind::PluginInit(format!("could not generate the default config: {e:#}")),
+                }];
+            }
+        },
+    };
+
+    let (res, issues) = check::collect_issues(|| (metadata.init)(ConfigTable(config)));
+    let prefix = ConfigPath(vec![
+        PathSegment::Key(String::from("plugins")),
+        PathSegment::Key(name.clone()),
+    ]);
+    let deserialization_failed = issues.iter().any(|i| matches!(i.kind, IssueKind::Invalid { .. }));
+    let mut diagnostics: Vec<Diagnostic> = issues
+        .into_iter()
+        .map(|mut issue| {
+            issue.path = ConfigPath([prefix.0.clone(), issue.path.0].concat());
+            sources.diagnostic(issue, Some(&name))
+        })
+        .collect();
+
+    // The plugin can reject a configuration that deserializes fine.
+    if let Err(e) = res
+        && !deserialization_failed
+    {
+        let (file, location) = sources.locate(&prefix);
+        diagnostics.push(Diagnostic {
+            path: prefix.to_string(),
+            file,
+            location,
+            plugin: Some(name.clone()),
+            kind: DiagnosticKind::PluginInit(format!("{e:#}")),
+        });
+    }
+    diagnostics
+}
+
+/// The configuration files, to find the position of each key.
+struct Sources {
+    main: PathBuf,
+    origins: ConfigOrigins,
+    /// Content of the files that have been read, after environment variable substitution.
+    contents: RefCell<HashMap<PathBuf, Option<String>>>,
+}
+
+impl Sources {
+    fn diagnostic(&self, issue: ConfigIssue, plugin: Option<&String>) -> Diagnostic {
+        let kind = match issue.kind {
+            IssueKind::UnknownKey { expected } => {
+                let suggestion = match issue.path.0.last() {
+                    Some(PathSegment::Key(key)) => suggest(key, expected.into_iter()),
+                    _ => None,
+                };
+                DiagnosticKind::UnknownKey { suggestion }
+            }
+            IssueKind::Invalid { message } => DiagnosticKind::InvalidValue(message),
+        };
+        let (file, location) = self.locate(&issue.path);
+        Diagnostic {
+            path: issue.path.to_string(),
+            file,
+            location,
+            plugin: plugin.cloned(),
+            kind,
+        }
+    }
+
+    /// Finds the file that defines a key or array element, and the line and column of the key in this file.
+    fn locate(&self, path: &ConfigPath) -> (Option<PathBuf>, Option<(usize, usize)>) {
+        let keys: Vec<String> = path
+            .0
+            .iter()
+            .map_while(|segment| match segment {
+                PathSegment::Key(key) => Some(key.to_owned()),
+                PathSegment::Index(_) => None,
+            })
+            .collect();
+        let file = match self.origins.get(&keys) {
+            Some(ConfigOrigin::File(file)) => file.to_owned(),
+            Some(ConfigOrigin::Default | ConfigOrigin::Override) => return (None, None),
+            // a missing field has no origin, look for its parent in the main file
+            None => self.main.to_owned(),
+        };
+        let mut contents = self.contents.borrow_mut();
+        let content = contents.entry(file.clone()).or_insert_with(|| read_config(&file));
+        let location = content.as_deref().and_then(|text| locate_in(text, path));
+        (Some(file), location)
+    }
+}
+
+/// Finds the line and column of a key or array element in a TOML document.
+///
+/// If the path does not exist in the document, returns the location of its closest parent,
+/// or `None` if there is no parent.
+fn locate_in(text: &str, path: &ConfigPath) -> Option<(usize, usize)> {
+    let table = DeTable::parse(text).ok()?.into_inner();
+    let mut span: Option<Range<usize>> = None;
+    let mut current = Some(&table);
+    let mut array: Option<&[toml::Spanned<DeValue>]> = None;
+    for segment in &path.0 {
+        let value = match (segment, current, array) {
+            (PathSegment::Key(key), Some(table), _) => {
+                let Some((k, v)) = table.iter().find(|(k, _)| k.get_ref() == key) else {
+                    break;
+                };
+                span = Some(k.span());
+                v
+            }
+            (PathSegment::Index(i), _, Some(elements)) => {
+                let Some(v) = elements.get(*i) else {
+                    break;
+                };
+                span = Some(v.span());
+                v
+            }
+            _ => break,
+        };
+        (current, array) = match value.get_ref() {
+            DeValue::Table(table) => (Some(table), None),
+            DeValue::Array(elements) => (None, Some(elements.as_ref())),
+            _ => (None, None),
+        };
+    }
+    span.map(|span| line_column(text, span.start))
+}
+
+/// Reads a config file like the [`Loader`] does.
+fn read_config(file: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(file).ok()?;
+    substitute_env(&content).ok().map(|c| c.into_owned())
+}
+
+/// Returns the known word that is the closest to `word`, if it is close enough.
+fn suggest<'a>(word: &str, known: impl Iterator<Item = &'a str>) -> Option<String> {
+    known
+        .map(|k| (k, distance_with_adjacent_transposition(word, k)))
+        .filter(|(_, d)| *d <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(_, d)| *d)
+        .map(|(k, _)| k.to_owned())
+}
+
+/// Converts a byte offset to a line and column, starting at 1.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+impl Diagnostic {
+    fn global(kind: DiagnosticKind) -> Self {
+        Self {
+            path: String::new(),
+            file: None,
+            location: None,
+            plugin: None,
+            kind,
+        }
+    }
+}
+
+impl CheckReport {
+    /// Returns `true` if no problem has been found.
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in &self.diagnostics {
+            match &d.file {
+                Some(file) => write!(f, "{}", file.display())?,
+                None => write!(f, "{}", self.file)?,
+            }
+            if let Some((line, column)) = d.location {
+                write!(f, ":{line}:{column}")?;
+            }
+            write!(f, ": ")?;
+            if let Some(plugin) = &d.plugin {
+                write!(f, "[{plugin}] ")?;
+            }
+            writeln!(f, "{d}")?;
+        }
+        match self.diagnostics.len() {
+            0 => writeln!(f, "{}: the configuration is valid", self.file),
+            1 => writeln!(f, "{}: found 1 problem", self.file),
+            n => writeln!(f, "{}: found {n} problems", self.file),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = &self.path;
+        match &self.kind {
+            DiagnosticKind::LoadFailed(message) => write!(f, "{message}"),
+            DiagnosticKind::UnknownPlugin { suggestion } => {
+                write!(f, "unknown plugin `{path}`")?;
+                if let Some(s) = suggestion {
+                    write!(f, ", did you mean `{s}`?")?;
+                }
+                Ok(())
+            }
+            DiagnosticKind::UnknownKey { suggestion } => {
+                write!(f, "unknown key `{path}`")?;
+                if let Some(s) = suggestion {
+                    write!(f, ", did you mean `{s}`?")?;
+                }
+                Ok(())
+            }
+            DiagnosticKind::InvalidValue(message) if path.is_empty() => write!(f, "invalid config: {message}"),
+            DiagnosticKind::InvalidValue(message) => write!(f, "invalid value for `{path}`: {message}"),
+            DiagnosticKind::PluginInit(message) => write!(f, "plugin initialization failed: {message}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        agent::plugin::PluginSet,
+        plugin::{AlumetPluginStart, ConfigTable, PluginMetadata, rust::AlumetPlugin, rust::deserialize_config},
+    };
+    use std::path::{Path, PathBuf};
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    use super::{Diagnostic, DiagnosticKind, check_file};
+
+    #[derive(Deserialize)]
+    struct GeneralConfig {
+        #[allow(unused)]
+        max_update_interval: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct PluginConfig {
+        poll_interval: String,
+        #[serde(default)]
+        groups: Vec<Group>,
+    }
+
+    #[derive(Deserialize)]
+    struct Group {
+        #[allow(unused)]
+        name: String,
+    }
+
+    struct TestPlugin;
+
+    impl AlumetPlugin for TestPlugin {
+        fn name() -> &'static str {
+            "test"
+        }
+
+        fn version() -> &'static str {
+            "0.1.0"
+        }
+
+        fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+            Ok(None)
+        }
+
+        fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+            let config: PluginConfig = deserialize_config(config)?;
+            if config.poll_interval.is_empty() {
+                anyhow::bail!("poll_interval must not be empty");
+            }
+            let _ = config.groups;
+            Ok(Box::new(TestPlugin))
+        }
+
+        fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+            unreachable!("the plugin should not be started by the check")
+        }
+
+        fn stop(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn check_path(path: &Path) -> Vec<Diagnostic> {
+        let plugins = PluginSet::from(vec![PluginMetadata::from_static::<TestPlugin>()]);
+        check_file::<GeneralConfig>(path, toml::Table::new(), plugins, true).diagnostics
+    }
+
+    /// Checks a config file with the given content, returns the problems and the path of the file.
+    fn check(content: &str) -> (Vec<Diagnostic>, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alumet-config.toml");
+        std::fs::write(&path, content).unwrap();
+        (check_path(&path), path)
+    }
+
+    fn diagnostic(path: &str, file: &Path, location: (usize, usize), plugin: bool, kind: DiagnosticKind) -> Diagnostic {
+        Diagnostic {
+            path: path.to_owned(),
+            file: Some(file.to_owned()),
+            location: Some(location),
+            plugin: plugin.then(|| String::from("test")),
+            kind,
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let content = indoc! {r#"
+            max_update_interval = "1s"
+
+            [plugins.test]
+            poll_interval = "1s"
+        "#};
+        assert_eq!(check(content).0, vec![]);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let content = indoc! {r#"
+            max_update_intreval = "1s"
+
+            [plugins.tset]
+            enabled = false
+
+            [plugins.test]
+            poll_interval = "1s"
+            pol_interval = "1s"
+
+            [[plugins.test.groups]]
+            name = "a"
+
+            [[plugins.test.groups]]
+            name = 1
+        "#};
+        let (diagnostics, file) = check(content);
+        let expected = vec![
+            diagnostic(
+                "plugins.tset",
+                &file,
+                (3, 10),
+                false,
+                DiagnosticKind::UnknownPlugin {
+                    suggestion: Some(String::from("test")),
+                },
+            ),
+            diagnostic(
+                "max_update_intreval",
+                &file,
+                (1, 1),
+                false,
+                DiagnosticKind::UnknownKey {
+                    suggestion: Some(String::from("max_update_interval")),
+                },
+            ),
+            diagnostic(
+                "plugins.test.pol_interval",
+                &file,
+                (8, 1),
+                true,
+                DiagnosticKind::UnknownKey {
+                    suggestion: Some(String::from("poll_interval")),
+                },
+            ),
+            diagnostic(
+                "plugins.test.groups[1].name",
+                &file,
+                (14, 1),
+                true,
+                DiagnosticKind::InvalidValue(String::from("invalid type: integer `1`, expected a string")),
+            ),
+        ];
+        assert_eq!(diagnostics, expected);
+    }
+
+    #[test]
+    fn plugin_init_error() {
+        let content = indoc! {r#"
+            [plugins.test]
+            poll_interval = ""
+        "#};
+        let (diagnostics, _) = check(content);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((1, 10)));
+        assert!(
+            matches!(&diagnostics[0].kind, DiagnosticKind::PluginInit(msg) if msg.contains("must not be empty")),
+            "{diagnostics:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_toml() {
+        let (diagnostics, file) = check("[plugins.test\npoll_interval = 1");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file.as_ref(), Some(&file));
+        assert_eq!(diagnostics[0].location, Some((1, 14)));
+        assert!(matches!(diagnostics[0].kind, DiagnosticKind::LoadFailed(_)));
+    }
+
+    #[test]
+    fn included_and_drop_in_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("alumet-config.toml");
+        let common = dir.path().join("common.toml");
+        let drop_in_dir = dir.path().join("alumet-config.d");
+        let drop_in = drop_in_dir.join("10-test.toml");
+        std::fs::create_dir(&drop_in_dir).unwrap();
+        std::fs::write(&main, "include = [\"common.toml\"]\n").unwrap();
+        std::fs::write(&common, "max_update_intreval = \"1s\"\n").unwrap();
+        std::fs::write(
+            &drop_in,
+            indoc! {r#"
+                [plugins.test]
+                poll_interval = "1s"
+                pol_interval = "1s"
+            "#},
+        )
+        .unwrap();
+
+        let expected = vec![
+            diagnostic(
+                "max_update_intreval",
+                &common,
+                (1, 1),
+                false,
+                DiagnosticKind::UnknownKey {
+                    suggestion: Some(String::from("max_update_interval")),
+                },
+            ),
+            diagnostic(
+                "plugins.test.pol_interval",
+                &drop_in,
+                (3, 1),
+                true,
+                DiagnosticKind::UnknownKey {
+                    suggestion: Some(String::from("poll_interval")),
+                },
+            ),
+        ];
+        assert_eq!(check_path(&main), expected);
+    }
+
+    #[test]
+    fn invalid_included_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("alumet-config.toml");
+        let common = dir.path().join("common.toml");
+        std::fs::write(&main, "include = [\"common.toml\"]\n").unwrap();
+        std::fs::write(&common, "\n[plugins.test\n").unwrap();
+
+        let diagnostics = check_path(&main);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file.as_ref(), Some(&common));
+        assert_eq!(diagnostics[0].location, Some((2, 14)));
+        assert!(matches!(diagnostics[0].kind, DiagnosticKind::LoadFailed(_)));
+    }
+}
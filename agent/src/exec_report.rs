@@ -0,0 +1,737 @@
+//! Summary of the consumption of a process launched by the `exec` command.
+//!
+//! The report is computed in-process: a dedicated output receives the measurements
+//! that flow through the pipeline and accumulates them, while the events published by
+//! [`alumet::agent::exec`] delimit the execution of the child process.
+//!
+//! In benchmark mode, the measurements are tagged with the `run_id` of their phase
+//! (see [`RunTagger`](alumet::agent::exec::RunTagger)), which allows to compute statistics over the runs.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alumet::{
+    agent::exec::{PhaseKind, PhaseOutcome, RUN_ID_ATTRIBUTE},
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    pipeline::{
+        self, Output,
+        elements::{error::WriteError, output::OutputContext, output::builder::OutputBuilder},
+        naming::PluginName,
+    },
+    plugin::event,
+    resources::ResourceConsumer,
+    units::{PrefixedUnit, Unit, UnitPrefix},
+};
+use anyhow::Context;
+use serde::Serialize;
+
+/// Name of the output that collects the measurements for the report.
+const OUTPUT_NAME: &str = "exec-report";
+
+/// Collects the data needed to produce an [`ExecReport`].
+///
+/// Create the collector with [`ReportCollector::new`], register it with [`ReportCollector::install`]
+/// before the pipeline is built, and call [`ReportCollector::finish`] after the pipeline has stopped.
+#[derive(Clone, Default)]
+pub struct ReportCollector {
+    state: Arc<Mutex<Accumulator>>,
+}
+
+/// Summary of the execution of a process.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExecReport {
+    /// Program that has been executed, with its arguments.
+    pub command: Vec<String>,
+    /// PID of the child process.
+    pub pid: Option<u32>,
+    /// Exit code of the child process.
+    pub exit_code: Option<i32>,
+    /// Time between the start and the end of the child process, in seconds.
+    pub wall_time_seconds: Option<f64>,
+    /// Total energy measured for each domain of the machine (RAPL package, GPU, ...).
+    pub energy: Vec<DomainEnergy>,
+    /// Energy attributed to the child process.
+    pub attributed_energy: Vec<AttributedEnergy>,
+    /// Maximum CPU and memory usage of the child process.
+    pub peaks: Vec<Peak>,
+}
+
+/// An energy metric measured on a part of the machine.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct EnergyDomain {
+    pub metric: String,
+    pub resource_kind: String,
+    pub resource_id: Option<String>,
+    /// Value of the `domain` attribute of the measurements, if any.
+    pub domain: Option<String>,
+}
+
+/// Total energy of one domain, over the whole execution.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DomainEnergy {
+    #[serde(flatten)]
+    pub domain: EnergyDomain,
+    pub joules: f64,
+}
+
+/// Total energy attributed to the child process, for one metric.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttributedEnergy {
+    pub metric: String,
+    pub joules: f64,
+}
+
+/// Peak value of a metric for the child process.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Peak {
+    pub metric: String,
+    /// Value of the `kind` attribute of the measurements, if any (e.g. `resident` memory).
+    pub kind: Option<String>,
+    pub unit: String,
+    pub value: f64,
+}
+
+/// Key of a peak: metric and `kind` attribute.
+type PeakKey = (String, Option<String>);
+
+/// Summary of a benchmark, made of several runs of the same program.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    /// Program that has been executed, with its arguments.
+    pub command: Vec<String>,
+    /// Every phase of the benchmark, in order.
+    pub phases: Vec<PhaseSummary>,
+    /// Average power of the machine during the idle phases.
+    pub idle_power: Vec<DomainPower>,
+    /// Statistics over the measured runs, warm-up runs and idle phases excluded.
+    pub statistics: RunStatistics,
+}
+
+/// Summary of one phase of a benchmark.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseSummary {
+    pub run_id: String,
+    /// `idle`, `warmup` or `run`.
+    pub kind: &'static str,
+    pub exit_code: Option<i32>,
+    pub duration_seconds: f64,
+    /// Total energy measured for each domain of the machine, during the phase.
+    pub energy: Vec<DomainEnergy>,
+}
+
+/// Average power of one domain.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DomainPower {
+    #[serde(flatten)]
+    pub domain: EnergyDomain,
+    pub watts: f64,
+}
+
+/// Statistics over the measured runs of a benchmark.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunStatistics {
+    /// Number of measured runs.
+    pub runs: usize,
+    pub duration_seconds: Stats,
+    pub energy: Vec<DomainStats>,
+}
+
+/// Statistics about the energy of one domain.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DomainStats {
+    #[serde(flatten)]
+    pub domain: EnergyDomain,
+    pub joules: Stats,
+}
+
+/// Basic statistics over a set of values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub mean: f64,
+    /// Sample standard deviation, zero if there is only one value.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    pid: Option<u32>,
+    start: Option<Instant>,
+    end: Option<Instant>,
+    /// Time at which the child has been spawned, to compare with the timestamps of the measurements.
+    spawned_at: Option<Timestamp>,
+    /// Time at which the child has exited.
+    exited_at: Option<Timestamp>,
+    /// Energy of the machine while the child was running.
+    energy: BTreeMap<EnergyDomain, f64>,
+    /// Energy of the machine, by `run_id`.
+    run_energy: BTreeMap<String, BTreeMap<EnergyDomain, f64>>,
+    attributed_energy: BTreeMap<String, f64>,
+    peaks: BTreeMap<PeakKey, (String, f64)>,
+}
+
+/// Output that feeds the accumulator.
+struct ReportOutput {
+    state: Arc<Mutex<Accumulator>>,
+}
+
+impl ReportCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the report output to the pipeline, and subscribes to the start and end of the child process.
+    pub fn install(&self, pipeline: &mut pipeline::Builder) -> anyhow::Result<()> {
+        let state = self.state.clone();
+        event::start_consumer_measurement().subscribe(move |e| {
+            let mut state = state.lock().unwrap();
+            if state.pid.is_none() {
+                state.pid = e.0.iter().find_map(|c| match c {
+                    ResourceConsumer::Process { pid } => Some(*pid),
+                    _ => None,
+                });
+                state.start = Some(Instant::now());
+                state.spawned_at = Some(Timestamp::now());
+            }
+            Ok(())
+        });
+
+        let state = self.state.clone();
+        event::end_consumer_measurement().subscribe(move |_| {
+            let mut state = state.lock().unwrap();
+            if state.end.is_none() {
+                state.end = Some(Instant::now());
+                state.exited_at = Some(Timestamp::now());
+            }
+            Ok(())
+        });
+
+        let state = self.state.clone();
+        pipeline
+            .add_output_builder(
+                PluginName(String::from(env!("CARGO_PKG_NAME"))),
+                OUTPUT_NAME,
+                OutputBuilder::Blocking(Box::new(move |_| Ok(Box::new(ReportOutput { state })))),
+            )
+            .context("failed to add the report output")?;
+        Ok(())
+    }
+
+    /// Produces the report from the measurements received so far.
+    ///
+    /// Call this after the shutdown of the pipeline, so that every measurement has been taken into account.
+    pub fn finish(&self, command: Vec<String>, exit_code: Option<i32>) -> ExecReport {
+        let state = self.state.lock().unwrap();
+        let wall_time = match (state.start, state.end) {
+            (Some(start), Some(end)) => Some(end.duration_since(start)),
+            _ => None,
+        };
+        ExecReport {
+            command,
+            pid: state.pid,
+            exit_code,
+            wall_time_seconds: wall_time.as_ref().map(Duration::as_secs_f64),
+            energy: domain_energy(&state.energy),
+            attributed_energy: state
+                .attributed_energy
+                .iter()
+                .map(|(metric, joules)| AttributedEnergy {
+                    metric: metric.clone(),
+                    joules: *joules,
+                })
+                .collect(),
+            peaks: state
+                .peaks
+                .iter()
+                .map(|((metric, kind), (unit, value))| Peak {
+                    metric: metric.clone(),
+                    kind: kind.clone(),
+                    unit: unit.clone(),
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ReportCollector {
+    /// Produces the report of a benchmark from the measurements received so far.
+    ///
+    /// Call this after the shutdown of the pipeline, so that every measurement has been taken into account.
+    pub fn finish_benchmark(&self, command: Vec<String>, phases: &[PhaseOutcome]) -> BenchmarkReport {
+        let state = self.state.lock().unwrap();
+        let no_energy = BTreeMap::new();
+        let energy_of = |phase: &PhaseOutcome| state.run_energy.get(&phase.run_id).unwrap_or(&no_energy);
+
+        // average power of the idle machine
+        let mut idle_energy: BTreeMap<EnergyDomain, f64> = BTreeMap::new();
+        let mut idle_duration = Duration::ZERO;
+        for phase in phases.iter().filter(|p| p.kind == PhaseKind::Idle) {
+            for (domain, joules) in energy_of(phase) {
+                *idle_energy.entry(domain.clone()).or_default() += joules;
+            }
+            idle_duration += phase.duration;
+        }
+        let idle_power = idle_energy
+            .into_iter()
+            .filter(|_| !idle_duration.is_zero())
+            .map(|(domain, joules)| DomainPower {
+                domain,
+                watts: joules / idle_duration.as_secs_f64(),
+            })
+            .collect();
+
+        // statistics over the measured runs
+        let runs: Vec<&PhaseOutcome> = phases.iter().filter(|p| p.kind == PhaseKind::Run).collect();
+        let domains: BTreeSet<&EnergyDomain> = runs.iter().flat_map(|r| energy_of(r).keys()).collect();
+        let durations: Vec<f64> = runs.iter().map(|r| r.duration.as_secs_f64()).collect();
+        let energy = domains
+            .into_iter()
+            .map(|domain| {
+                // a run without any measurement of the domain counts as zero
+                let values: Vec<f64> = runs
+                    .iter()
+                    .map(|r| energy_of(r).get(domain).copied().unwrap_or_default())
+                    .collect();
+                DomainStats {
+                    domain: domain.clone(),
+                    joules: Stats::of(&values),
+                }
+            })
+            .collect();
+
+        BenchmarkReport {
+            command,
+            phases: phases
+                .iter()
+                .map(|phase| PhaseSummary {
+                    run_id: phase.run_id.clone(),
+                    kind: match phase.kind {
+                        PhaseKind::Idle => "idle",
+                        PhaseKind::Warmup => "warmup",
+                        PhaseKind::Run => "run",
+                    },
+                    exit_code: phase.exit_status.and_then(|s| s.code()),
+                    duration_seconds: phase.duration.as_secs_f64(),
+                    energy: domain_energy(energy_of(phase)),
+                })
+                .collect(),
+            idle_power,
+            statistics: RunStatistics {
+                runs: runs.len(),
+                duration_seconds: Stats::of(&durations),
+                energy,
+            },
+        }
+    }
+}
+
+impl Stats {
+    /// Computes the statistics of `values`. Returns zeros if there is no value.
+    pub fn of(values: &[f64]) -> Stats {
+        if values.is_empty() {
+            return Stats::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let stddev = if values.len() > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        Stats {
+            mean,
+            stddev,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl Accumulator {
+    /// Returns `true` if the measurement has been taken while the child was running.
+    fn during_execution(&self, m: &MeasurementPoint) -> bool {
+        self.spawned_at.is_none_or(|t| m.timestamp >= t) && self.exited_at.is_none_or(|t| m.timestamp <= t)
+    }
+
+    fn add(&mut self, m: &MeasurementPoint, metric_name: &str, unit: &PrefixedUnit) {
+        let value = m.value.as_f64();
+        let is_child =
+            matches!((&m.consumer, self.pid), (ResourceConsumer::Process { pid }, Some(child)) if *pid == child);
+        match (&unit.base_unit, &m.consumer) {
+            (Unit::Joule, _) if is_child => {
+                *self.attributed_energy.entry(metric_name.to_owned()).or_default() +=
+                    value * prefix_factor(&unit.prefix);
+            }
+            (Unit::Joule, ResourceConsumer::LocalMachine) => {
+                let joules = value * prefix_factor(&unit.prefix);
+                let domain = EnergyDomain {
+                    metric: metric_name.to_owned(),
+                    resource_kind: m.resource.kind().to_owned(),
+                    resource_id: m.resource.id_string(),
+                    domain: attribute(m, "domain"),
+                };
+                if let Some(run_id) = attribute(m, RUN_ID_ATTRIBUTE) {
+                    *self
+                        .run_energy
+                        .entry(run_id)
+                        .or_default()
+                        .entry(domain.clone())
+                        .or_default() += joules;
+                }
+                // The phases of a benchmark are delimited by their run_id, not by the first execution.
+                if self.during_execution(m) {
+                    *self.energy.entry(domain).or_default() += joules;
+                }
+            }
+            (Unit::Percent | Unit::Byte, _) if is_child => {
+                let key = (metric_name.to_owned(), attribute(m, "kind"));
+                let unit = unit.display_name();
+                self.peaks
+                    .entry(key)
+                    .and_modify(|(_, peak)| *peak = peak.max(value))
+                    .or_insert((unit, value));
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Output for ReportOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut state = self.state.lock().unwrap();
+        for m in measurements {
+            if let Some(metric) = ctx.metrics.by_id(&m.metric) {
+                state.add(m, &metric.name, &metric.unit);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExecReport {
+    /// Writes the report to a JSON file.
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        write_json(self, path)
+    }
+}
+
+impl fmt::Display for EnergyDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.metric, self.resource_kind)?;
+        if let Some(id) = &self.resource_id {
+            write!(f, " {id}")?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, " ({domain})")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExecReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Summary of `{}`", self.command.join(" "))?;
+        if let Some(pid) = self.pid {
+            writeln!(f, "  pid: {pid}")?;
+        }
+        if let Some(code) = self.exit_code {
+            writeln!(f, "  exit code: {code}")?;
+        }
+        if let Some(wall_time) = self.wall_time_seconds {
+            writeln!(f, "  wall time: {wall_time:.3} s")?;
+        }
+        if !self.energy.is_empty() {
+            writeln!(f, "  energy consumed by the machine:")?;
+            for e in &self.energy {
+                writeln!(f, "    {}: {:.3} J", e.domain, e.joules)?;
+            }
+        }
+        if !self.attributed_energy.is_empty() {
+            writeln!(f, "  energy attributed to the process:")?;
+            for e in &self.attributed_energy {
+                writeln!(f, "    {}: {:.3} J", e.metric, e.joules)?;
+            }
+        }
+        if !self.peaks.is_empty() {
+            writeln!(f, "  peak usage of the process:")?;
+            for p in &self.peaks {
+                write!(f, "    {}", p.metric)?;
+                if let Some(kind) = &p.kind {
+                    write!(f, " ({kind})")?;
+                }
+                writeln!(f, ": {:.2} {}", p.value, p.unit)?;
+            }
+        }
+        if self.energy.is_empty() && self.attributed_energy.is_empty() && self.peaks.is_empty() {
+            writeln!(f, "  no energy, CPU or memory measurement has been received")?;
+        }
+        Ok(())
+    }
+}
+
+impl BenchmarkReport {
+    /// Writes the report to a JSON file.
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        write_json(self, path)
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Benchmark of `{}`", self.command.join(" "))?;
+        writeln!(f, "  phases:")?;
+        for phase in &self.phases {
+            write!(
+                f,
+                "    {} ({}): {:.3} s",
+                phase.run_id, phase.kind, phase.duration_seconds
+            )?;
+            match phase.exit_code {
+                Some(code) if code != 0 => writeln!(f, ", exit code {code}")?,
+                _ => writeln!(f)?,
+            }
+            for e in &phase.energy {
+                writeln!(f, "      {}: {:.3} J", e.domain, e.joules)?;
+            }
+        }
+        if !self.idle_power.is_empty() {
+            writeln!(f, "  idle power:")?;
+            for p in &self.idle_power {
+                writeln!(f, "    {}: {:.3} W", p.domain, p.watts)?;
+            }
+        }
+        let stats = &self.statistics;
+        writeln!(f, "  statistics over {} runs (mean ± stddev [min, max]):", stats.runs)?;
+        writeln!(f, "    duration: {} s", stats.duration_seconds)?;
+        for e in &stats.energy {
+            writeln!(f, "    {}: {} J", e.domain, e.joules)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3} ± {:.3} [{:.3}, {:.3}]",
+            self.mean, self.stddev, self.min, self.max
+        )
+    }
+}
+
+fn write_json(value: &impl Serialize, path: &Path) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    std::fs::write(path, json).with_context(|| format!("failed to write the report to {}", path.display()))
+}
+
+fn domain_energy(energy: &BTreeMap<EnergyDomain, f64>) -> Vec<DomainEnergy> {
+    energy
+        .iter()
+        .map(|(domain, joules)| DomainEnergy {
+            domain: domain.clone(),
+            joules: *joules,
+        })
+        .collect()
+}
+
+fn prefix_factor(prefix: &UnitPrefix) -> f64 {
+    match prefix {
+        UnitPrefix::Nano => 1e-9,
+        UnitPrefix::Micro => 1e-6,
+        UnitPrefix::Milli => 1e-3,
+        UnitPrefix::Plain => 1.0,
+        UnitPrefix::Kilo => 1e3,
+        UnitPrefix::Mega => 1e6,
+        UnitPrefix::Giga => 1e9,
+    }
+}
+
+fn attribute(m: &MeasurementPoint, key: &str) -> Option<String> {
+    m.attributes().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::ExitStatus, time::Duration};
+
+    use alumet::{
+        agent::exec::{PhaseKind, PhaseOutcome, RUN_ID_ATTRIBUTE},
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+
+    use super::{Accumulator, ReportCollector, Stats};
+
+    fn point(resource: Resource, consumer: ResourceConsumer, value: f64) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            RawMetricId::from_u64(0),
+            resource,
+            consumer,
+            WrappedMeasurementValue::F64(value),
+        )
+    }
+
+    #[test]
+    fn accumulate() {
+        let collector = ReportCollector::new();
+        {
+            let mut state = collector.state.lock().unwrap();
+            *state = Accumulator {
+                pid: Some(42),
+                ..Default::default()
+            };
+            let joule = PrefixedUnit::from(Unit::Joule);
+            let pkg = Resource::CpuPackage { id: 0 };
+            for _ in 0..2 {
+                let m = point(pkg.clone(), ResourceConsumer::LocalMachine, 10.0).with_attr("domain", "package");
+                state.add(&m, "rapl_consumed_energy", &joule);
+            }
+            let gpu = Resource::Gpu {
+                bus_id: "0000:01:00.0".into(),
+            };
+            let m = point(gpu, ResourceConsumer::LocalMachine, 1500.0);
+            state.add(&m, "nvml_energy_consumption", &PrefixedUnit::milli(Unit::Joule));
+
+            let child = ResourceConsumer::Process { pid: 42 };
+            let other = ResourceConsumer::Process { pid: 7 };
+            state.add(&point(pkg.clone(), child.clone(), 4.0), "attributed_energy", &joule);
+            state.add(&point(pkg, other.clone(), 100.0), "attributed_energy", &joule);
+
+            let byte = PrefixedUnit::from(Unit::Byte);
+            for value in [100.0, 300.0, 200.0] {
+                let m = point(Resource::LocalMachine, child.clone(), value).with_attr("kind", "resident");
+                state.add(&m, "memory_usage", &byte);
+            }
+            let m = point(Resource::LocalMachine, other, 1000.0).with_attr("kind", "resident");
+            state.add(&m, "memory_usage", &byte);
+        }
+
+        let report = collector.finish(vec![String::from("sleep"), String::from("1")], Some(0));
+        assert_eq!(report.pid, Some(42));
+        assert_eq!(report.wall_time_seconds, None);
+
+        assert_eq!(report.energy.len(), 2);
+        let gpu = &report.energy[0];
+        assert_eq!(gpu.domain.metric, "nvml_energy_consumption");
+        assert_eq!(gpu.domain.resource_kind, "gpu");
+        assert_eq!(gpu.joules, 1.5);
+        let pkg = &report.energy[1];
+        assert_eq!(pkg.domain.metric, "rapl_consumed_energy");
+        assert_eq!(pkg.domain.resource_id.as_deref(), Some("0"));
+        assert_eq!(pkg.domain.domain.as_deref(), Some("package"));
+        assert_eq!(pkg.joules, 20.0);
+
+        assert_eq!(report.attributed_energy.len(), 1);
+        assert_eq!(report.attributed_energy[0].joules, 4.0);
+
+        assert_eq!(report.peaks.len(), 1);
+        assert_eq!(report.peaks[0].kind.as_deref(), Some("resident"));
+        assert_eq!(report.peaks[0].value, 300.0);
+
+        let text = report.to_string();
+        assert!(
+            text.contains("rapl_consumed_energy cpu_package 0 (package): 20.000 J"),
+            "{text}"
+        );
+        assert!(text.contains("memory_usage (resident): 300.00 B"), "{text}");
+    }
+
+    #[test]
+    fn energy_outside_of_the_execution() {
+        let collector = ReportCollector::new();
+        let joule = PrefixedUnit::from(Unit::Joule);
+        let pkg = Resource::CpuPackage { id: 0 };
+        let spawned_at = Timestamp::now();
+        let exited_at = spawned_at + Duration::from_secs(2);
+        {
+            let mut state = collector.state.lock().unwrap();
+            *state = Accumulator {
+                pid: Some(42),
+                spawned_at: Some(spawned_at),
+                exited_at: Some(exited_at),
+                ..Default::default()
+            };
+            // before the spawn, during the execution, and after the exit (the point arrives late)
+            for (t, value) in [
+                (spawned_at - Duration::from_secs(1), 100.0),
+                (spawned_at + Duration::from_secs(1), 10.0),
+                (exited_at, 5.0),
+                (exited_at + Duration::from_secs(1), 1000.0),
+            ] {
+                let mut m = point(pkg.clone(), ResourceConsumer::LocalMachine, value);
+                m.timestamp = t;
+                state.add(&m, "rapl_consumed_energy", &joule);
+            }
+        }
+
+        let report = collector.finish(vec![String::from("true")], Some(0));
+        assert_eq!(report.energy.len(), 1);
+        assert_eq!(report.energy[0].joules, 15.0);
+    }
+
+    #[test]
+    fn benchmark() {
+        let collector = ReportCollector::new();
+        let joule = PrefixedUnit::from(Unit::Joule);
+        let pkg = Resource::CpuPackage { id: 0 };
+        {
+            let mut state = collector.state.lock().unwrap();
+            for (run_id, value) in [("idle-before", 20.0), ("warmup-1", 50.0), ("1", 10.0), ("2", 14.0)] {
+                let m = point(pkg.clone(), ResourceConsumer::LocalMachine, value).with_attr(RUN_ID_ATTRIBUTE, run_id);
+                state.add(&m, "rapl_consumed_energy", &joule);
+            }
+        }
+        let phase = |run_id: &str, kind, secs| PhaseOutcome {
+            run_id: run_id.to_owned(),
+            kind,
+            exit_status: (kind != PhaseKind::Idle).then(ExitStatus::default),
+            duration: Duration::from_secs(secs),
+        };
+        let phases = [
+            phase("idle-before", PhaseKind::Idle, 10),
+            phase("warmup-1", PhaseKind::Warmup, 5),
+            phase("1", PhaseKind::Run, 1),
+            phase("2", PhaseKind::Run, 3),
+        ];
+        let report = collector.finish_benchmark(vec![String::from("true")], &phases);
+
+        assert_eq!(report.phases.len(), 4);
+        assert_eq!(report.phases[1].kind, "warmup");
+        assert_eq!(report.phases[1].exit_code, Some(0));
+        assert_eq!(report.phases[1].energy[0].joules, 50.0);
+
+        assert_eq!(report.idle_power.len(), 1);
+        assert_eq!(report.idle_power[0].watts, 2.0);
+
+        let stats = &report.statistics;
+        assert_eq!(stats.runs, 2);
+        assert_eq!(stats.duration_seconds.mean, 2.0);
+        assert_eq!(stats.duration_seconds.min, 1.0);
+        assert_eq!(stats.duration_seconds.max, 3.0);
+        assert_eq!(stats.energy.len(), 1);
+        let joules = stats.energy[0].joules;
+        assert_eq!(joules.mean, 12.0);
+        assert!((joules.stddev - 8f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(Stats::of(&[]), Stats::default());
+        let single = Stats::of(&[4.0]);
+        assert_eq!(
+            (single.mean, single.stddev, single.min, single.max),
+            (4.0, 0.0, 4.0, 4.0)
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Health and readiness HTTP endpoint of the agent.
+//!
+//! The endpoint is served from the async runtime of the pipeline and provides two routes:
+//! - `GET /live` returns `200 OK` as long as the pipeline controller responds (liveness probe).
+//! - `GET /ready` returns the state of every pipeline element, in JSON, with the status code
+//!   `200 OK` if all the critical elements are up, or `503 Service Unavailable` otherwise (readiness probe).
+//!
+//! An element is "up" if it is running or paused. A degraded source, which keeps failing, is down. A critical pattern that matches no element is
+//! considered to be down, which allows to detect that a plugin has not started its sources.
+
+use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use alumet::agent::schema::JsonSchema;
+use alumet::pipeline::{
+    MeasurementPipeline,
+    control::{
+        AnonymousControlHandle,
+        request::{self, ElementListFilter},
+        status::ElementState,
+    },
+    matching::ElementNamePattern,
+    naming::ElementName,
+};
+use anyhow::Context;
+use hyper::{
+    Body, Method, Request, Response, Server, StatusCode,
+    service::{make_service_fn, service_fn},
+};
+use serde::{Deserialize, Serialize};
+
+/// Maximum time to wait for the pipeline controller to respond.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Configuration of the health endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Address and port to listen to, for instance `0.0.0.0:8099`.
+    pub listen: String,
+    /// Patterns of the elements that must be up for the agent to be ready, for instance `sources/rapl/*`.
+    #[serde(default)]
+    pub critical: Vec<String>,
+}
+
+/// Readiness of the agent, as returned by `GET /ready`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct HealthReport {
+    /// `true` if all the critical elements are up.
+    pub ready: bool,
+    /// The critical patterns that are not satisfied.
+    pub critical_down: Vec<String>,
+    /// The state of every pipeline element.
+    pub elements: Vec<ElementReport>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ElementReport {
+    pub name: String,
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Endpoint {
+    control: AnonymousControlHandle,
+    critical: Vec<(String, ElementNamePattern)>,
+}
+
+/// Starts the health endpoint on the async runtime of the pipeline.
+///
+/// Returns the address that the endpoint listens to.
+pub fn serve(pipeline: &MeasurementPipeline, config: &HealthConfig) -> anyhow::Result<SocketAddr> {
+    let addr = SocketAddr::from_str(&config.listen)
+        .with_context(|| format!("invalid address for the health endpoint: '{}'", config.listen))?;
+    let critical = config
+        .critical
+        .iter()
+        .map(|pat| {
+            let parsed =
+                ElementNamePattern::from_str(pat).with_context(|| format!("invalid critical element '{pat}'"))?;
+            Ok((pat.to_owned(), parsed))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let endpoint = Arc::new(Endpoint {
+        control: pipeline.control_handle(),
+        critical,
+    });
+
+    // The listener must be created in the context of the runtime.
+    let _guard = pipeline.async_runtime().enter();
+    let make_svc = make_service_fn(move |_conn| {
+        let endpoint = endpoint.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(endpoint.clone(), req))) }
+    });
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("could not listen to {addr}"))?
+        .serve(make_svc);
+    let local_addr = server.local_addr();
+    pipeline.async_runtime().spawn(async move {
+        if let Err(e) = server.await {
+            log::error!("Error in the health endpoint: {e}");
+        }
+    });
+    log::info!("Health endpoint listening on http://{local_addr}");
+    Ok(local_addr)
+}
+
+async fn handle(endpoint: Arc<Endpoint>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::GET {
+        return Ok(text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"));
+    }
+    let ready = match req.uri().path() {
+        "/live" => false,
+        "/ready" => true,
+        _ => return Ok(text_response(StatusCode::NOT_FOUND, "not found, try /live or /ready")),
+    };
+
+    let states = endpoint
+        .control
+        .send_wait(request::element_states(ElementListFilter::kind_any()), CONTROL_TIMEOUT)
+        .await;
+    let response = match states {
+        Err(e) => {
+            log::warn!("Health endpoint: the pipeline controller did not respond: {e}");
+            text_response(StatusCode::SERVICE_UNAVAILABLE, "pipeline controller not available")
+        }
+        Ok(_) if !ready => text_response(StatusCode::OK, "ok"),
+        Ok(states) => {
+            let report = evaluate(&states, &endpoint.critical);
+            let status = if report.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let body = serde_json::to_string(&report).expect("the report should serialize to JSON");
+            Response::builder()
+                .status(status)
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        }
+    };
+    Ok(response)
+}
+
+fn text_response(status: StatusCode, text: &'static str) -> Response<Body> {
+    Response::builder().status(status).body(Body::from(text)).unwrap()
+}
+
+/// Checks the state of the elements against the critical patterns.
+pub fn evaluate(states: &[(ElementName, ElementState)], critical: &[(String, ElementNamePattern)]) -> HealthReport {
+    fn is_up(state: &ElementState) -> bool {
+        matches!(state, ElementState::Running | ElementState::Paused)
+    }
+
+    let critical_down: Vec<String> = critical
+        .iter()
+        .filter(|(_, pat)| {
+            let mut matching = states.iter().filter(|(name, _)| pat.matches(name)).peekable();
+            matching.peek().is_none() || matching.any(|(_, state)| !is_up(state))
+        })
+        .map(|(pat, _)| pat.to_owned())
+        .collect();
+    let elements = states
+        .iter()
+        .map(|(name, state)| ElementReport {
+            name: name.to_string(),
+            state: state.to_string(),
+            error: match state {
+                ElementState::Failed { error } | ElementState::Degraded { last_error: error } => Some(error.to_owned()),
+                _ => None,
+            },
+        })
+        .collect();
+    HealthReport {
+        ready: critical_down.is_empty(),
+        critical_down,
+        elements,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use alumet::pipeline::{
+        control::status::ElementState,
+        matching::ElementNamePattern,
+        naming::{ElementKind, ElementName},
+    };
+
+    use super::evaluate;
+
+    fn critical(pat: &str) -> (String, ElementNamePattern) {
+        (pat.to_owned(), ElementNamePattern::from_str(pat).unwrap())
+    }
+
+    #[test]
+    fn readiness() {
+        let states = vec![
+            (
+                ElementName::from_str(ElementKind::Source, "rapl", "in"),
+                ElementState::Running,
+            ),
+            (
+                ElementName::from_str(ElementKind::Source, "procfs", "memory"),
+                ElementState::Failed {
+                    error: String::from("permission denied"),
+                },
+            ),
+            (
+                ElementName::from_str(ElementKind::Output, "csv", "out"),
+                ElementState::Paused,
+            ),
+        ];
+
+        // no critical element: always ready
+        let report = evaluate(&states, &[]);
+        assert!(report.ready);
+        assert_eq!(report.elements.len(), 3);
+        assert_eq!(report.elements[1].state, "failed");
+        assert_eq!(report.elements[1].error.as_deref(), Some("permission denied"));
+
+        let report = evaluate(&states, &[critical("sources/rapl/*"), critical("outputs")]);
+        assert!(report.ready, "running and paused elements are up");
+
+        let report = evaluate(&states, &[critical("sources/rapl/*"), critical("sources")]);
+        assert!(!report.ready);
+        assert_eq!(report.critical_down, vec![String::from("sources")]);
+
+        let report = evaluate(&states, &[critical("sources/nvml/*")]);
+        assert!(!report.ready, "a critical pattern that matches nothing is down");
+
+        let degraded = vec![(
+            ElementName::from_str(ElementKind::Source, "rapl", "in"),
+            ElementState::Degraded {
+                last_error: String::from("no such device"),
+            },
+        )];
+        let report = evaluate(&degraded, &[critical("sources/rapl/*")]);
+        assert!(!report.ready, "a degraded element is down");
+        assert_eq!(report.elements[0].state, "degraded");
+        assert_eq!(report.elements[0].error.as_deref(), Some("no such device"));
+    }
+}
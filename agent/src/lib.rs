@@ -2,7 +2,13 @@ use std::path::PathBuf;
 
 use env_logger::Env;
 
+pub mod config_check;
 pub mod exec_hints;
+pub mod exec_report;
+pub mod health;
+pub mod reload;
+#[cfg(unix)]
+pub mod top;
 pub mod word_distance;
 
 /// Returns the absolute path of the currently running executable.
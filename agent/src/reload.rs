@@ -0,0 +1,571 @@
+//! Hot reload of the agent configuration.
+//!
+//! When the `run` command receives `SIGHUP`, the configuration file is loaded again and compared
+//! to the configuration that is currently applied. The changes that can be applied to the running
+//! pipeline are performed through the control API:
+//! - a change of the `poll_interval` or `flush_interval` option of a plugin updates the trigger of its sources,
+//!   if the default config of the plugin has a top-level `poll_interval` (it applies to all the sources of the plugin);
+//! - disabling a plugin pauses its sources, transforms and outputs, enabling it again resumes them;
+//! - enabling a plugin that has not been started yet starts it (see [`RunningAgent::start_plugin`]).
+//!   This fails if the plugin adds a transform or an output to a simplified pipeline.
+//!
+//! The other changes require a restart of the agent: they are reported but not applied.
+//! The counters of the running sources are preserved, because the sources are not recreated.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::mpsc,
+    time::Duration,
+};
+
+use alumet::{
+    agent::{
+        RunningAgent,
+        plugin::{PluginFilter, PluginInfo, PluginSet},
+    },
+    pipeline::{
+        MeasurementPipeline,
+        control::request,
+        elements::source::trigger::TriggerSpec,
+        matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern},
+    },
+};
+use anyhow::{Context, anyhow};
+use indexmap::IndexMap;
+
+/// Maximum time to wait for the pipeline to process a control request.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Options of a plugin configuration that can be changed without restarting the plugin.
+const TRIGGER_OPTIONS: [&str; 2] = ["poll_interval", "flush_interval"];
+
+/// Configuration of the agent, as seen by the reloader.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentConfig {
+    /// General options (everything but the plugins).
+    general: toml::Table,
+    /// Status and config of each plugin.
+    plugins: IndexMap<String, PluginSettings>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PluginSettings {
+    enabled: bool,
+    /// `None` if the config does not contain a section for the plugin (its default config is used).
+    config: Option<toml::Table>,
+    /// `true` if the plugin documents a top-level `poll_interval` in its default config,
+    /// which means that the [`TRIGGER_OPTIONS`] apply to all its sources.
+    trigger_options: bool,
+}
+
+/// A change that can be applied to the running agent, or that requires a restart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Initializes and starts a plugin.
+    StartPlugin(String),
+    /// Resumes the elements of a plugin that has been disabled.
+    EnablePlugin(String),
+    /// Pauses the elements of a plugin.
+    DisablePlugin(String),
+    /// Sets the trigger of the sources of a plugin.
+    SetTrigger {
+        plugin: String,
+        poll_interval: Duration,
+        flush_interval: Option<Duration>,
+    },
+    /// The change cannot be applied while the agent is running.
+    RestartRequired(String),
+}
+
+/// Outcome of a reload.
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// Changes that have been applied.
+    pub applied: Vec<Change>,
+    /// Changes that have not been applied, because they require a restart.
+    pub restart_required: Vec<String>,
+    /// Changes that could not be applied, with the error.
+    pub failed: Vec<(Change, anyhow::Error)>,
+}
+
+/// Applies the changes of the configuration to a running agent.
+pub struct Reloader {
+    /// The configuration that is currently applied.
+    running: AgentConfig,
+    /// Plugins that have been initialized, including the ones that have been disabled since then.
+    initialized: HashSet<String>,
+}
+
+impl AgentConfig {
+    /// Gathers the configuration of the agent.
+    ///
+    /// `general` is the content of the config file without the `plugins` section,
+    /// which has been extracted to `plugins` by [`PluginSet::extract_config`].
+    pub fn new(general: toml::Table, plugins: &PluginSet) -> Self {
+        let plugins = plugins
+            .metadata(PluginFilter::Any)
+            .filter_map(|m| plugins.get_plugin(&m.name))
+            .map(|p| {
+                let settings = PluginSettings {
+                    enabled: p.enabled,
+                    config: p.config.clone(),
+                    trigger_options: has_trigger_options(p),
+                };
+                (p.metadata.name.clone(), settings)
+            })
+            .collect();
+        Self { general, plugins }
+    }
+
+    fn is_enabled(&self, plugin: &str) -> bool {
+        self.plugins.get(plugin).is_some_and(|p| p.enabled)
+    }
+}
+
+impl Reloader {
+    /// Creates a reloader for an agent that has been started with the given config.
+    pub fn new(running: AgentConfig) -> Self {
+        let initialized = running
+            .plugins
+            .iter()
+            .filter(|(_, p)| p.enabled)
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        Self { running, initialized }
+    }
+
+    /// Compares the running configuration with a new one and returns the changes to apply.
+    pub fn diff(&self, new: &AgentConfig) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        // general options
+        let keys: HashSet<&String> = self.running.general.keys().chain(new.general.keys()).collect();
+        let mut changed_keys: Vec<&String> = keys
+            .into_iter()
+            .filter(|k| self.running.general.get(*k) != new.general.get(*k))
+            .collect();
+        changed_keys.sort();
+        for key in changed_keys {
+            changes.push(Change::RestartRequired(format!("option '{key}' has changed")));
+        }
+
+        // plugins
+        for (name, settings) in &new.plugins {
+            let was_enabled = self.running.is_enabled(name);
+            let old_config = self.running.plugins.get(name).and_then(|p| p.config.as_ref());
+            match (was_enabled, settings.enabled) {
+                (false, true) if !self.initialized.contains(name) => {
+                    changes.push(Change::StartPlugin(name.to_owned()));
+                }
+                (false, true) => {
+                    changes.push(Change::EnablePlugin(name.to_owned()));
+                    changes.extend(diff_plugin_config(name, old_config, settings));
+                }
+                (true, true) => {
+                    changes.extend(diff_plugin_config(name, old_config, settings));
+                }
+                (true, false) => changes.push(Change::DisablePlugin(name.to_owned())),
+                (false, false) => (),
+            }
+        }
+        changes
+    }
+
+    /// Applies a new configuration to the running agent.
+    ///
+    /// `general` and `plugins` are obtained in the same way as [`AgentConfig::new`].
+    /// The changes that have been applied become part of the running configuration,
+    /// the others will be detected again on the next reload.
+    ///
+    /// # Blocking
+    /// This is a blocking function, it should not be called from within an async runtime.
+    pub fn reload(&mut self, agent: &mut RunningAgent, general: toml::Table, plugins: PluginSet) -> ReloadReport {
+        let new_config = AgentConfig::new(general, &plugins);
+        let (enabled_plugins, _) = plugins.into_partition();
+        let mut enabled_plugins: HashMap<String, PluginInfo> = enabled_plugins
+            .into_iter()
+            .map(|p| (p.metadata.name.clone(), p))
+            .collect();
+
+        let mut report = ReloadReport::default();
+        for change in self.diff(&new_config) {
+            let res = match &change {
+                Change::RestartRequired(reason) => {
+                    report.restart_required.push(reason.to_owned());
+                    continue;
+                }
+                Change::StartPlugin(name) => match enabled_plugins.remove(name) {
+                    Some(plugin) => agent.start_plugin(plugin),
+                    None => Err(anyhow!("plugin {name} is not available")),
+                },
+                Change::EnablePlugin(name) => set_plugin_enabled(&agent.pipeline, name, true),
+                Change::DisablePlugin(name) => set_plugin_enabled(&agent.pipeline, name, false),
+                Change::SetTrigger {
+                    plugin,
+                    poll_interval,
+                    flush_interval,
+                } => set_trigger(&agent.pipeline, plugin, *poll_interval, *flush_interval),
+            };
+            match res {
+                Ok(()) => {
+                    self.update_running(&change, &new_config);
+                    report.applied.push(change);
+                }
+                Err(e) => report.failed.push((change, e)),
+            }
+        }
+        report
+    }
+
+    /// Updates the running configuration after a change has been applied.
+    fn update_running(&mut self, change: &Change, new_config: &AgentConfig) {
+        match change {
+            Change::StartPlugin(name) => {
+                if let Some(settings) = new_config.plugins.get(name) {
+                    self.running.plugins.insert(name.to_owned(), settings.clone());
+                }
+                self.initialized.insert(name.to_owned());
+            }
+            Change::EnablePlugin(name) | Change::DisablePlugin(name) => {
+                let enabled = matches!(change, Change::EnablePlugin(_));
+                self.running.plugins.entry(name.to_owned()).or_default().enabled = enabled;
+            }
+            Change::SetTrigger { plugin, .. } => {
+                let new_plugin_config = new_config.plugins.get(plugin).and_then(|p| p.config.as_ref());
+                let running = self.running.plugins.entry(plugin.to_owned()).or_default();
+                let config = running.config.get_or_insert_default();
+                for key in TRIGGER_OPTIONS {
+                    match new_plugin_config.and_then(|c| c.get(key)) {
+                        Some(value) => config.insert(key.to_owned(), value.clone()),
+                        None => config.remove(key),
+                    };
+                }
+            }
+            Change::RestartRequired(_) => (),
+        }
+    }
+}
+
+/// Returns `true` if the default config of the plugin has a top-level `poll_interval`.
+fn has_trigger_options(plugin: &PluginInfo) -> bool {
+    match (plugin.metadata.default_config)() {
+        Ok(Some(config)) => config.0.contains_key("poll_interval"),
+        _ => false,
+    }
+}
+
+/// Compares two configurations of the same plugin.
+fn diff_plugin_config(plugin: &str, old: Option<&toml::Table>, new: &PluginSettings) -> Vec<Change> {
+    let empty = toml::Table::new();
+    let old = old.unwrap_or(&empty);
+    let trigger_options = new.trigger_options;
+    let new = new.config.as_ref().unwrap_or(&empty);
+
+    let keys: HashSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut changed_keys: Vec<&str> = keys
+        .into_iter()
+        .filter(|k| old.get(*k) != new.get(*k))
+        .map(|k| k.as_str())
+        .collect();
+    if changed_keys.is_empty() {
+        return Vec::new();
+    }
+    changed_keys.sort();
+
+    let restart = |reason: String| {
+        vec![Change::RestartRequired(format!(
+            "config of plugin '{plugin}': {reason}"
+        ))]
+    };
+    if let Some(key) = changed_keys
+        .iter()
+        .find(|k| !trigger_options || !TRIGGER_OPTIONS.contains(k))
+    {
+        return restart(format!("option '{key}' has changed"));
+    }
+    let parse = |key: &str| -> Result<Option<Duration>, String> {
+        match new.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(s)) => humantime::parse_duration(s)
+                .map(Some)
+                .map_err(|e| format!("invalid {key}: {e}")),
+            Some(bad) => Err(format!("invalid {key}: expected a duration, got {bad}")),
+        }
+    };
+    match (parse("poll_interval"), parse("flush_interval")) {
+        (Ok(Some(poll_interval)), Ok(flush_interval)) => vec![Change::SetTrigger {
+            plugin: plugin.to_owned(),
+            poll_interval,
+            flush_interval,
+        }],
+        (Ok(None), _) => restart(String::from("option 'poll_interval' has been removed")),
+        (Err(e), _) | (_, Err(e)) => restart(e),
+    }
+}
+
+fn set_plugin_enabled(pipeline: &MeasurementPipeline, plugin: &str, enabled: bool) -> anyhow::Result<()> {
+    let plugin_pat = || StringPattern::Exact(plugin.to_owned());
+    let sources = request::source(SourceNamePattern::new(plugin_pat(), StringPattern::Any));
+    let transforms = request::transform(TransformNamePattern::new(plugin_pat(), StringPattern::Any));
+    let outputs = request::output(OutputNamePattern::new(plugin_pat(), StringPattern::Any));
+
+    let control = pipeline.control_handle();
+    pipeline.async_runtime().block_on(async {
+        if enabled {
+            // Outputs and transforms first, in order not to lose any measurement.
+            // The measurements that have been produced while the plugin was disabled are not written.
+            control.send_wait(outputs.enable_discard(), CONTROL_TIMEOUT).await?;
+            control.send_wait(transforms.enable(), CONTROL_TIMEOUT).await?;
+            control.send_wait(sources.enable(), CONTROL_TIMEOUT).await
+        } else {
+            control.send_wait(sources.disable(), CONTROL_TIMEOUT).await?;
+            control.send_wait(transforms.disable(), CONTROL_TIMEOUT).await?;
+            control.send_wait(outputs.disable(), CONTROL_TIMEOUT).await
+        }
+    })?;
+    Ok(())
+}
+
+fn set_trigger(
+    pipeline: &MeasurementPipeline,
+    plugin: &str,
+    poll_interval: Duration,
+    flush_interval: Option<Duration>,
+) -> anyhow::Result<()> {
+    let mut trigger = TriggerSpec::builder(poll_interval);
+    if let Some(flush_interval) = flush_interval {
+        trigger.flush_interval(flush_interval);
+    }
+    let trigger = trigger.build()?;
+    let sources = SourceNamePattern::new(StringPattern::Exact(plugin.to_owned()), StringPattern::Any);
+    let control = pipeline.control_handle();
+    pipeline
+        .async_runtime()
+        .block_on(control.send_wait(request::source(sources).set_trigger(trigger), CONTROL_TIMEOUT))?;
+    Ok(())
+}
+
+/// Forwards the `SIGHUP` signals received by the agent to a channel.
+///
+/// The channel is closed when the pipeline begins to shut down.
+#[cfg(unix)]
+pub fn on_sighup(pipeline: &MeasurementPipeline) -> anyhow::Result<mpsc::Receiver<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let _guard = pipeline.async_runtime().enter();
+    let mut hangup = signal(SignalKind::hangup()).context("could not listen to SIGHUP")?;
+    let control = pipeline.control_handle();
+    let (tx, rx) = mpsc::channel();
+    pipeline.async_runtime().spawn(async move {
+        loop {
+            tokio::select! {
+                _ = control.shutdown_requested() => break,
+                Some(()) = hangup.recv() => {
+                    if tx.send(()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    Ok(rx)
+}
+
+impl ReloadReport {
+    /// Logs the outcome of the reload.
+    pub fn log(&self) {
+        if self.applied.is_empty() && self.failed.is_empty() && self.restart_required.is_empty() {
+            log::info!("Configuration reloaded: nothing has changed.");
+            return;
+        }
+        for change in &self.applied {
+            log::info!("Configuration reloaded: {change}.");
+        }
+        for (change, e) in &self.failed {
+            log::error!("Configuration reloaded, but this change could not be applied: {change}. {e:?}");
+        }
+        for reason in &self.restart_required {
+            log::warn!("Configuration reloaded, but the agent must be restarted to apply this change: {reason}.");
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::StartPlugin(name) => write!(f, "plugin {name} started"),
+            Change::EnablePlugin(name) => write!(f, "plugin {name} enabled"),
+            Change::DisablePlugin(name) => write!(f, "plugin {name} disabled"),
+            Change::SetTrigger {
+                plugin,
+                poll_interval,
+                flush_interval,
+            } => {
+                write!(
+                    f,
+                    "sources of plugin {plugin} now polled every {}",
+                    humantime::format_duration(*poll_interval)
+                )?;
+                if let Some(flush) = flush_interval {
+                    write!(f, " and flushed every {}", humantime::format_duration(*flush))?;
+                }
+                Ok(())
+            }
+            Change::RestartRequired(reason) => write!(f, "{reason} (restart required)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use indexmap::IndexMap;
+
+    use super::{AgentConfig, Change, PluginSettings, Reloader};
+
+    fn config(general: &str, plugins: &[(&str, bool, &str)]) -> AgentConfig {
+        let plugins: IndexMap<String, PluginSettings> = plugins
+            .iter()
+            .map(|(name, enabled, config)| {
+                let settings = PluginSettings {
+                    enabled: *enabled,
+                    config: Some(toml::from_str(config).unwrap()),
+                    trigger_options: true,
+                };
+                (name.to_string(), settings)
+            })
+            .collect();
+        AgentConfig {
+            general: toml::from_str(general).unwrap(),
+            plugins,
+        }
+    }
+
+    #[test]
+    fn no_change() {
+        let running = config(
+            "max_update_interval = '500ms'",
+            &[("rapl", true, "poll_interval = '1s'")],
+        );
+        let reloader = Reloader::new(running.clone());
+        assert_eq!(reloader.diff(&running), Vec::new());
+    }
+
+    #[test]
+    fn trigger_change() {
+        let running = config(
+            "",
+            &[(
+                "rapl",
+                true,
+                "poll_interval = '1s'\nflush_interval = '5s'\nno_perf_events = false",
+            )],
+        );
+        let reloader = Reloader::new(running);
+
+        let new = config(
+            "",
+            &[(
+                "rapl",
+                true,
+                "poll_interval = '100ms'\nflush_interval = '5s'\nno_perf_events = false",
+            )],
+        );
+        assert_eq!(
+            reloader.diff(&new),
+            vec![Change::SetTrigger {
+                plugin: String::from("rapl"),
+                poll_interval: Duration::from_millis(100),
+                flush_interval: Some(Duration::from_secs(5)),
+            }]
+        );
+
+        // other options require a restart
+        let new = config(
+            "",
+            &[(
+                "rapl",
+                true,
+                "poll_interval = '100ms'\nflush_interval = '5s'\nno_perf_events = true",
+            )],
+        );
+        assert!(
+            matches!(&reloader.diff(&new)[..], [Change::RestartRequired(reason)] if reason.contains("no_perf_events"))
+        );
+
+        // invalid durations are not applied
+        let new = config(
+            "",
+            &[(
+                "rapl",
+                true,
+                "poll_interval = 'often'\nflush_interval = '5s'\nno_perf_events = false",
+            )],
+        );
+        assert!(matches!(&reloader.diff(&new)[..], [Change::RestartRequired(_)]));
+    }
+
+    #[test]
+    fn undocumented_trigger_requires_restart() {
+        let mut running = config("", &[("example", true, "poll_interval = '1s'")]);
+        running.plugins[0].trigger_options = false;
+        let reloader = Reloader::new(running);
+
+        // the plugin does not document a top-level poll_interval: it may not apply to all its sources
+        let mut new = config("", &[("example", true, "poll_interval = '2s'")]);
+        new.plugins[0].trigger_options = false;
+        assert_eq!(
+            reloader.diff(&new),
+            vec![Change::RestartRequired(String::from(
+                "config of plugin 'example': option 'poll_interval' has changed"
+            ))]
+        );
+    }
+
+    #[test]
+    fn enable_disable_plugins() {
+        let running = config("", &[("rapl", true, ""), ("procfs", false, ""), ("csv", true, "")]);
+        let mut reloader = Reloader::new(running);
+
+        let new = config("", &[("rapl", false, ""), ("procfs", true, ""), ("csv", true, "")]);
+        assert_eq!(
+            reloader.diff(&new),
+            vec![
+                Change::DisablePlugin(String::from("rapl")),
+                Change::StartPlugin(String::from("procfs")),
+            ]
+        );
+
+        // once disabled, the plugin is enabled again, not started
+        reloader.update_running(&Change::DisablePlugin(String::from("rapl")), &new);
+        let new = config("", &[("rapl", true, ""), ("procfs", false, ""), ("csv", true, "")]);
+        assert_eq!(reloader.diff(&new), vec![Change::EnablePlugin(String::from("rapl"))]);
+    }
+
+    #[test]
+    fn general_options_require_restart() {
+        let running = config("max_update_interval = '500ms'", &[]);
+        let reloader = Reloader::new(running);
+        let new = config("max_update_interval = '1s'\nsource_channel_size = 10", &[]);
+        assert_eq!(
+            reloader.diff(&new),
+            vec![
+                Change::RestartRequired(String::from("option 'max_update_interval' has changed")),
+                Change::RestartRequired(String::from("option 'source_channel_size' has changed")),
+            ]
+        );
+    }
+
+    #[test]
+    fn applied_trigger_is_remembered() {
+        let running = config("", &[("rapl", true, "poll_interval = '1s'")]);
+        let mut reloader = Reloader::new(running);
+        let new = config("", &[("rapl", true, "poll_interval = '2s'")]);
+        let changes = reloader.diff(&new);
+        assert_eq!(changes.len(), 1);
+        reloader.update_running(&changes[0], &new);
+        assert_eq!(reloader.diff(&new), Vec::new());
+    }
+}
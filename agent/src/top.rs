@@ -0,0 +1,654 @@
+//! Live view of the latest measurements, for the `top` command.
+//!
+//! A dedicated output keeps the latest value of each time series (metric, resource, consumer and attributes)
+//! in a [`LiveTable`], which is periodically rendered as a table by [`run`]. For the counter metrics,
+//! such as energy measured since the previous measurement, the table also shows the rate per second.
+//!
+//! The view is a plain ANSI terminal: it does not need a terminal library, and it can be disabled
+//! in favor of a simple periodic print (batch mode), which is useful when the output is not a terminal.
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex, mpsc},
+    time::{Duration, Instant},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    metrics::Metric,
+    pipeline::{
+        self, MeasurementPipeline, Output,
+        elements::{error::WriteError, output::OutputContext, output::builder::OutputBuilder},
+        naming::PluginName,
+    },
+    units::Unit,
+};
+use regex::Regex;
+
+/// Name of the output that updates the live table.
+const OUTPUT_NAME: &str = "top";
+
+/// Size of the terminal, used when it cannot be obtained.
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+/// Latest measurement of each time series.
+///
+/// Register the table with [`LiveTable::install`] before the pipeline is built.
+#[derive(Clone, Default)]
+pub struct LiveTable {
+    rows: Arc<Mutex<BTreeMap<SeriesKey, Series>>>,
+}
+
+/// Identifies a time series: the measurements of the same metric, about the same
+/// resource and consumer, and with the same attributes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SeriesKey {
+    pub metric: String,
+    pub resource: String,
+    pub consumer: String,
+    pub attributes: String,
+}
+
+/// Latest measurement of a time series.
+#[derive(Clone)]
+pub struct Series {
+    pub value: f64,
+    /// Unit of the metric, as registered in the `MetricRegistry`.
+    pub unit: String,
+    pub timestamp: Timestamp,
+    /// Increase per second, computed for the counter metrics only.
+    pub rate: Option<f64>,
+}
+
+/// Column used to sort the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortColumn {
+    Metric,
+    Resource,
+    Consumer,
+    Value,
+    Rate,
+}
+
+/// How the table is displayed: sorting and filtering.
+pub struct View {
+    pub sort: SortColumn,
+    pub descending: bool,
+    /// Only the metrics whose name matches this regex are displayed.
+    pub filter: Option<Regex>,
+    /// Filter being typed by the user, after the `/` key.
+    input: Option<String>,
+    /// Message to display in the status line, for instance an invalid filter.
+    message: Option<String>,
+}
+
+/// Settings of the `top` command.
+pub struct TopSettings {
+    /// Time between two refreshes of the table.
+    pub refresh: Duration,
+    /// If true, print the table at each refresh instead of redrawing the screen, and ignore the keyboard.
+    pub batch: bool,
+    /// Number of refreshes after which the agent is stopped. If `None`, run until the agent is stopped.
+    pub iterations: Option<usize>,
+}
+
+/// Output that updates the live table.
+struct TopOutput {
+    rows: Arc<Mutex<BTreeMap<SeriesKey, Series>>>,
+}
+
+/// Something that happened while the table was waiting for the next refresh.
+enum Event {
+    Key(u8),
+    Shutdown,
+}
+
+impl LiveTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the output that updates the table to the pipeline.
+    pub fn install(&self, pipeline: &mut pipeline::Builder) -> anyhow::Result<()> {
+        let rows = self.rows.clone();
+        pipeline
+            .add_output_builder(
+                PluginName(String::from(env!("CARGO_PKG_NAME"))),
+                OUTPUT_NAME,
+                OutputBuilder::Blocking(Box::new(move |_| Ok(Box::new(TopOutput { rows })))),
+            )
+            .map_err(|e| anyhow::anyhow!("could not add the output of the top command: {e}"))?;
+        Ok(())
+    }
+
+    /// Returns a copy of the current content of the table.
+    pub fn snapshot(&self) -> BTreeMap<SeriesKey, Series> {
+        self.rows.lock().unwrap().clone()
+    }
+
+    /// Updates the table with a new measurement.
+    fn update(&self, m: &MeasurementPoint, metric: &Metric) {
+        let key = SeriesKey {
+            metric: metric.name.clone(),
+            resource: describe(m.resource.kind(), m.resource.id_string()),
+            consumer: describe(m.consumer.kind(), m.consumer.id_string()),
+            attributes: m
+                .attributes()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        let value = m.value.as_f64();
+        let mut rows = self.rows.lock().unwrap();
+        let rate = if is_counter(metric) {
+            // The rate cannot be computed from the first measurement, or if the time has not increased.
+            match rows.get(&key) {
+                Some(previous) => match m.timestamp.duration_since(previous.timestamp) {
+                    Ok(elapsed) if !elapsed.is_zero() => Some(value / elapsed.as_secs_f64()),
+                    _ => previous.rate,
+                },
+                None => None,
+            }
+        } else {
+            None
+        };
+        rows.insert(
+            key,
+            Series {
+                value,
+                unit: metric.unit.display_name(),
+                timestamp: m.timestamp,
+                rate,
+            },
+        );
+    }
+}
+
+impl Output for TopOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let table = LiveTable {
+            rows: self.rows.clone(),
+        };
+        for m in measurements {
+            if let Some(metric) = ctx.metrics.by_id(&m.metric) {
+                table.update(m, metric);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if the measurements of the metric are increments (for instance,
+/// the energy consumed since the previous measurement), which can be turned into rates.
+fn is_counter(metric: &Metric) -> bool {
+    matches!(metric.unit.base_unit, Unit::Joule | Unit::WattHour)
+        || metric.name.ends_with("_delta")
+        || metric.description.contains("since the previous measurement")
+}
+
+fn describe(kind: &str, id: Option<String>) -> String {
+    match id {
+        Some(id) if !id.is_empty() => format!("{kind} {id}"),
+        _ => kind.to_owned(),
+    }
+}
+
+impl SortColumn {
+    /// Returns the next column, in the order of the table.
+    fn next(self) -> Self {
+        match self {
+            SortColumn::Metric => SortColumn::Resource,
+            SortColumn::Resource => SortColumn::Consumer,
+            SortColumn::Consumer => SortColumn::Value,
+            SortColumn::Value => SortColumn::Rate,
+            SortColumn::Rate => SortColumn::Metric,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortColumn::Metric => "metric",
+            SortColumn::Resource => "resource",
+            SortColumn::Consumer => "consumer",
+            SortColumn::Value => "value",
+            SortColumn::Rate => "rate",
+        }
+    }
+
+    fn compare(self, a: (&SeriesKey, &Series), b: (&SeriesKey, &Series)) -> Ordering {
+        let (ka, sa) = a;
+        let (kb, sb) = b;
+        match self {
+            SortColumn::Metric => ka.metric.cmp(&kb.metric),
+            SortColumn::Resource => ka.resource.cmp(&kb.resource),
+            SortColumn::Consumer => ka.consumer.cmp(&kb.consumer),
+            SortColumn::Value => sa.value.total_cmp(&sb.value),
+            // the series without rate come first
+            SortColumn::Rate => match (sa.rate, sb.rate) {
+                (Some(ra), Some(rb)) => ra.total_cmp(&rb),
+                (ra, rb) => ra.is_some().cmp(&rb.is_some()),
+            },
+        }
+        // keep the order stable when the values are equal
+        .then_with(|| ka.cmp(kb))
+    }
+}
+
+impl View {
+    pub fn new(sort: SortColumn, descending: bool, filter: Option<Regex>) -> Self {
+        Self {
+            sort,
+            descending,
+            filter,
+            input: None,
+            message: None,
+        }
+    }
+
+    /// Handles a key pressed by the user.
+    ///
+    /// Returns `false` if the user wants to quit.
+    pub fn handle_key(&mut self, key: u8) -> bool {
+        if let Some(input) = &mut self.input {
+            match key {
+                b'\r' | b'\n' => {
+                    let input = self.input.take().unwrap();
+                    if input.is_empty() {
+                        self.filter = None;
+                    } else {
+                        match Regex::new(&input) {
+                            Ok(regex) => self.filter = Some(regex),
+                            Err(e) => self.message = Some(format!("invalid filter: {e}")),
+                        }
+                    }
+                }
+                // Escape
+                0x1b => self.input = None,
+                // Backspace or Delete
+                0x08 | 0x7f => {
+                    input.pop();
+                }
+                c if c.is_ascii_graphic() || c == b' ' => input.push(c as char),
+                _ => (),
+            }
+            return true;
+        }
+
+        self.message = None;
+        match key {
+            b'q' => return false,
+            b's' => self.sort = self.sort.next(),
+            b'r' => self.descending = !self.descending,
+            b'/' => self.input = Some(String::new()),
+            b'c' => self.filter = None,
+            _ => (),
+        }
+        true
+    }
+
+    /// Renders the table in a text of at most `height` lines of `width` characters.
+    pub fn render(&self, rows: &BTreeMap<SeriesKey, Series>, width: usize, height: usize) -> String {
+        let mut visible: Vec<(&SeriesKey, &Series)> = rows
+            .iter()
+            .filter(|(k, _)| self.filter.as_ref().is_none_or(|f| f.is_match(&k.metric)))
+            .collect();
+        visible.sort_by(|a, b| {
+            let ord = self.sort.compare(*a, *b);
+            if self.descending { ord.reverse() } else { ord }
+        });
+
+        const HEADERS: [&str; 7] = ["METRIC", "RESOURCE", "CONSUMER", "ATTRIBUTES", "VALUE", "UNIT", "RATE"];
+        let cells: Vec<[String; 7]> = visible
+            .iter()
+            .map(|(k, s)| {
+                [
+                    k.metric.clone(),
+                    k.resource.clone(),
+                    k.consumer.clone(),
+                    k.attributes.clone(),
+                    format_number(s.value),
+                    s.unit.clone(),
+                    match s.rate {
+                        Some(rate) if s.unit.is_empty() => format!("{}/s", format_number(rate)),
+                        Some(rate) => format!("{} {}/s", format_number(rate), s.unit),
+                        None => String::new(),
+                    },
+                ]
+            })
+            .collect();
+        let mut widths = HEADERS.map(str::len);
+        for row in &cells {
+            for (w, cell) in widths.iter_mut().zip(row) {
+                *w = (*w).max(cell.chars().count());
+            }
+        }
+
+        let mut lines = Vec::with_capacity(cells.len() + 3);
+        let mut status = format!(
+            "Alumet top - {} series, sorted by {} ({})",
+            visible.len(),
+            self.sort.name(),
+            if self.descending { "descending" } else { "ascending" }
+        );
+        if let Some(filter) = &self.filter {
+            let _ = write!(status, ", filter: {filter}");
+        }
+        lines.push(status);
+        lines.push(format_row(&HEADERS.map(String::from), &widths));
+        // keep one line for the prompt
+        let max_rows = height.saturating_sub(3);
+        for row in cells.iter().take(max_rows) {
+            lines.push(format_row(row, &widths));
+        }
+        lines.push(match (&self.input, &self.message) {
+            (Some(input), _) => format!("filter (regex on the metric name): {input}"),
+            (None, Some(message)) => message.clone(),
+            (None, None) => String::from("[s] sort  [r] reverse  [/] filter  [c] clear filter  [q] quit"),
+        });
+
+        let mut res = String::new();
+        for line in lines {
+            res.extend(line.chars().take(width));
+            res.push('\n');
+        }
+        res
+    }
+}
+
+/// Aligns the cells of a row: text to the left, numbers to the right.
+fn format_row(cells: &[String; 7], widths: &[usize; 7]) -> String {
+    let mut res = String::new();
+    for (i, (cell, w)) in cells.iter().zip(widths).enumerate() {
+        if i > 0 {
+            res.push_str("  ");
+        }
+        if i == 4 {
+            let _ = write!(res, "{cell:>w$}");
+        } else {
+            let _ = write!(res, "{cell:<w$}");
+        }
+    }
+    res.trim_end().to_owned()
+}
+
+fn format_number(x: f64) -> String {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        format!("{x}")
+    } else {
+        format!("{x:.3}")
+    }
+}
+
+/// Displays the live table until the agent is stopped, the user quits or the number of iterations is reached.
+///
+/// If the user quits or the number of iterations is reached, the shutdown of the pipeline is requested.
+pub fn run(
+    pipeline: &MeasurementPipeline,
+    table: &LiveTable,
+    mut view: View,
+    settings: &TopSettings,
+) -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    // stop when the pipeline shuts down (e.g. on Ctrl+C)
+    let control = pipeline.control_handle();
+    let shutdown_tx = tx.clone();
+    pipeline.async_runtime().spawn(async move {
+        control.shutdown_requested().await;
+        let _ = shutdown_tx.send(Event::Shutdown);
+    });
+
+    let terminal = if settings.batch {
+        None
+    } else {
+        let terminal = Terminal::enter()?;
+        // The thread is blocked on stdin and will be stopped with the process.
+        std::thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                let Ok(byte) = byte else { break };
+                if tx.send(Event::Key(byte)).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(terminal)
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut refreshes = 0;
+    loop {
+        let rows = table.snapshot();
+        match &terminal {
+            Some(terminal) => {
+                let (width, height) = terminal.size();
+                let frame = view.render(&rows, width, height);
+                write!(stdout, "{}{frame}", ansi::CLEAR)?;
+            }
+            None => {
+                let frame = view.render(&rows, usize::MAX, usize::MAX);
+                writeln!(stdout, "{frame}")?;
+            }
+        }
+        stdout.flush()?;
+
+        refreshes += 1;
+        if settings.iterations.is_some_and(|n| refreshes >= n) {
+            break;
+        }
+
+        // Wait for the next refresh. A key press redraws the table immediately.
+        let deadline = Instant::now() + settings.refresh;
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(Event::Key(key)) => {
+                if !view.handle_key(key) {
+                    break;
+                }
+            }
+            Ok(Event::Shutdown) | Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+        }
+    }
+    pipeline.control_handle().shutdown();
+    Ok(())
+}
+
+mod ansi {
+    pub const ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+    pub const MAIN_SCREEN: &str = "\x1b[?1049l";
+    pub const HIDE_CURSOR: &str = "\x1b[?25l";
+    pub const SHOW_CURSOR: &str = "\x1b[?25h";
+    /// Moves the cursor to the top-left corner and clears the screen.
+    pub const CLEAR: &str = "\x1b[H\x1b[2J";
+}
+
+/// Interactive terminal: alternate screen, with the keys being read one by one.
+///
+/// The previous state of the terminal is restored on drop.
+struct Terminal {
+    original: Option<libc::termios>,
+}
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        // SAFETY: termios is a plain C struct, which is filled by tcgetattr.
+        let original = unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 1 {
+                let mut original: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Disable the line buffering and the echo, but keep the signals (Ctrl+C).
+                let mut raw = original;
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Some(original)
+            } else {
+                None
+            }
+        };
+        let mut stdout = io::stdout();
+        write!(stdout, "{}{}", ansi::ALTERNATE_SCREEN, ansi::HIDE_CURSOR)?;
+        stdout.flush()?;
+        Ok(Self { original })
+    }
+
+    /// Returns the width and height of the terminal.
+    fn size(&self) -> (usize, usize) {
+        // SAFETY: winsize is a plain C struct, which is filled by ioctl.
+        unsafe {
+            let mut size: libc::winsize = std::mem::zeroed();
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 && size.ws_row > 0
+            {
+                (size.ws_col as usize, size.ws_row as usize)
+            } else {
+                DEFAULT_SIZE
+            }
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}{}", ansi::SHOW_CURSOR, ansi::MAIN_SCREEN);
+        let _ = stdout.flush();
+        if let Some(original) = &self.original {
+            // SAFETY: original has been obtained by tcgetattr.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+        metrics::{Metric, RawMetricId},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+    use regex::Regex;
+
+    use super::{LiveTable, SortColumn, View};
+
+    fn metric(name: &str, unit: Unit) -> Metric {
+        Metric {
+            name: name.to_owned(),
+            description: String::new(),
+            value_type: WrappedMeasurementType::F64,
+            unit: PrefixedUnit::from(unit),
+        }
+    }
+
+    fn point(secs: u64, resource: Resource, value: f64) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::from(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            RawMetricId::from_u64(0),
+            resource,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::F64(value),
+        )
+    }
+
+    fn table() -> LiveTable {
+        let table = LiveTable::new();
+        let energy = metric("rapl_consumed_energy", Unit::Joule);
+        let power = metric("power", Unit::Watt);
+        for (secs, value) in [(10, 30.0), (12, 50.0)] {
+            table.update(&point(secs, Resource::CpuPackage { id: 0 }, value), &energy);
+            table.update(&point(secs, Resource::LocalMachine, value * 2.0), &power);
+        }
+        table.update(
+            &point(12, Resource::Dram { pkg_id: 0 }, 4.0).with_attr("domain", "dram"),
+            &energy,
+        );
+        table
+    }
+
+    #[test]
+    fn latest_values_and_rates() {
+        let rows = table().snapshot();
+        assert_eq!(rows.len(), 3);
+
+        let pkg = rows.iter().find(|(k, _)| k.resource == "cpu_package 0").unwrap().1;
+        assert_eq!(pkg.value, 50.0);
+        assert_eq!(pkg.unit, "J");
+        assert_eq!(pkg.rate, Some(25.0));
+
+        let dram = rows.iter().find(|(k, _)| k.resource == "dram 0").unwrap();
+        assert_eq!(dram.0.attributes, "domain=dram");
+        assert_eq!(dram.1.rate, None, "no rate for the first measurement");
+
+        let power = rows.iter().find(|(k, _)| k.metric == "power").unwrap();
+        assert_eq!(power.0.resource, "local_machine");
+        assert_eq!(power.1.value, 100.0);
+        assert_eq!(power.1.rate, None, "power is not a counter");
+    }
+
+    #[test]
+    fn render_sorted_and_filtered() {
+        let rows = table().snapshot();
+        let view = View::new(SortColumn::Value, true, None);
+        let text = view.render(&rows, 200, 50);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 6, "{text}");
+        assert!(lines[0].contains("3 series, sorted by value (descending)"));
+        assert!(lines[1].starts_with("METRIC"));
+        assert!(lines[2].starts_with("power"));
+        assert!(
+            lines[3].contains("cpu_package 0") && lines[3].ends_with("25 J/s"),
+            "{text}"
+        );
+        assert!(lines[4].contains("dram 0"));
+
+        let view = View::new(SortColumn::Metric, false, Some(Regex::new("^rapl").unwrap()));
+        let text = view.render(&rows, 200, 50);
+        assert!(text.contains("2 series") && text.contains("filter: ^rapl"));
+        assert!(!text.contains("power"));
+
+        // the table is cut to fit in the terminal
+        let text = view.render(&rows, 10, 4);
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.lines().all(|l| l.chars().count() <= 10));
+    }
+
+    #[test]
+    fn keys() {
+        let mut view = View::new(SortColumn::Metric, false, None);
+        assert!(view.handle_key(b's'));
+        assert_eq!(view.sort, SortColumn::Resource);
+        assert!(view.handle_key(b'r'));
+        assert!(view.descending);
+
+        for key in b"/pow\x7fw\r" {
+            assert!(view.handle_key(*key));
+        }
+        assert_eq!(view.filter.as_ref().map(|f| f.as_str()), Some("pow"));
+        // q is part of the filter, not a request to quit
+        for key in b"/q\x1b" {
+            assert!(view.handle_key(*key));
+        }
+        assert_eq!(view.filter.as_ref().map(|f| f.as_str()), Some("pow"));
+        assert!(view.handle_key(b'c'));
+        assert!(view.filter.is_none());
+
+        for key in b"/(\r" {
+            assert!(view.handle_key(*key));
+        }
+        assert!(view.filter.is_none());
+        assert!(view.render(&Default::default(), 200, 50).contains("invalid filter"));
+
+        assert!(!view.handle_key(b'q'));
+    }
+}
@@ -5,7 +5,7 @@ use anyhow::Context;
 
 use common::{
     empty_temp_dir,
-    run::{run_agent, run_agent_tee},
+    run::{ChildGuard, command_run_agent, run_agent, run_agent_tee},
     tests,
 };
 use indoc::indoc;
@@ -109,6 +109,212 @@ fn args_output_exec() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn exec_report() -> anyhow::Result<()> {
+    let tmp = empty_temp_dir()?;
+    let tmp_dir = tmp.0.path();
+    let tmp_file_conf = tmp_dir.join("agent-config.toml");
+    let tmp_file_report = tmp_dir.join("report.json");
+
+    let command_out = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--config",
+            tmp_file_conf.to_str().unwrap(),
+            "--plugins",
+            "procfs",
+            "exec",
+            "--report",
+            "--report-json",
+            tmp_file_report.to_str().unwrap(),
+            "sleep",
+            "1",
+        ],
+        tmp_dir,
+    )?;
+    assert!(command_out.status.success(), "alumet-agent exec --report should work");
+    let stdout = String::from_utf8(command_out.stdout)?;
+    assert!(stdout.contains("Summary of `sleep 1`"), "the report should be printed");
+
+    let report = std::fs::read_to_string(&tmp_file_report).context("failed to read the report")?;
+    let report: serde_json::Value = serde_json::from_str(&report)?;
+    assert_eq!(report["command"], serde_json::json!(["sleep", "1"]));
+    assert_eq!(report["exit_code"], 0);
+    assert!(report["pid"].is_u64());
+    let wall_time = report["wall_time_seconds"].as_f64().expect("wall time should be set");
+    assert!(wall_time >= 1.0, "wall time should be at least 1s, got {wall_time}");
+    Ok(())
+}
+
+#[test]
+fn top_batch() -> anyhow::Result<()> {
+    let tmp = empty_temp_dir()?;
+    let tmp_dir = tmp.0.path();
+    let tmp_file_conf = tmp_dir.join("agent-config.toml");
+
+    let command_out = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--config",
+            tmp_file_conf.to_str().unwrap(),
+            "--plugins",
+            "procfs",
+            "--config-override",
+            "plugins.procfs.kernel.poll_interval='100ms'",
+            "top",
+            "--batch",
+            "--refresh",
+            "1s",
+            "-n",
+            "3",
+            "--filter",
+            "^kernel_",
+        ],
+        tmp_dir,
+    )?;
+    assert!(
+        command_out.status.success(),
+        "alumet-agent top should stop after 3 refreshes"
+    );
+    let stdout = String::from_utf8(command_out.stdout)?;
+    assert_eq!(
+        stdout.matches("Alumet top - ").count(),
+        3,
+        "the table should be printed 3 times"
+    );
+    assert!(stdout.contains("filter: ^kernel_"));
+    let last = stdout.rsplit("Alumet top - ").next().unwrap();
+    assert!(last.contains("METRIC") && last.contains("kernel_"), "{last}");
+    assert!(!last.contains("memory_"), "the filter should apply: {last}");
+    Ok(())
+}
+
+#[test]
+fn health_endpoint() -> anyhow::Result<()> {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        process::Stdio,
+        time::Duration,
+    };
+
+    let tmp = empty_temp_dir()?;
+    let tmp_dir = tmp.0.path();
+    let tmp_file_conf = tmp_dir.join("agent-config.toml");
+
+    // find a free port
+    let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?.to_string();
+
+    let mut cmd = command_run_agent(
+        AGENT_BIN,
+        &[
+            "--config",
+            tmp_file_conf.to_str().unwrap(),
+            "--plugins",
+            "procfs",
+            "--health",
+            &addr,
+            "exec",
+            "sleep",
+            "3",
+        ],
+    )?;
+    let _child = ChildGuard::new(cmd.current_dir(tmp_dir).stdout(Stdio::null()).spawn()?);
+
+    // wait for the endpoint to be up
+    let get = |path: &str| -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(&addr)?;
+        write!(stream, "GET {path} HTTP/1.0\r\n\r\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    };
+    let mut live = get("/live");
+    for _ in 0..40 {
+        if live.is_ok() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        live = get("/live");
+    }
+    let live = live.context("the health endpoint should be available")?;
+    assert!(live.starts_with("HTTP/1.0 200"), "bad response: {live}");
+
+    let ready = get("/ready")?;
+    assert!(ready.starts_with("HTTP/1.0 200"), "bad response: {ready}");
+    let (_, body) = ready
+        .split_once("\r\n\r\n")
+        .context("the response should have a body")?;
+    let report: serde_json::Value = serde_json::from_str(body)?;
+    assert_eq!(report["ready"], true);
+    let elements = report["elements"].as_array().context("elements should be an array")?;
+    assert!(
+        elements
+            .iter()
+            .any(|e| e["name"].as_str().unwrap().starts_with("sources/procfs/") && e["state"] == "running"),
+        "the procfs sources should be running: {elements:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn exec_benchmark() -> anyhow::Result<()> {
+    let tmp = empty_temp_dir()?;
+    let tmp_dir = tmp.0.path();
+    let tmp_file_conf = tmp_dir.join("agent-config.toml");
+    let tmp_file_out = tmp_dir.join("agent-output.csv");
+    let tmp_file_report = tmp_dir.join("report.json");
+
+    let command_out = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--config",
+            tmp_file_conf.to_str().unwrap(),
+            "--output-file",
+            tmp_file_out.to_str().unwrap(),
+            "--plugins",
+            "procfs,csv",
+            "exec",
+            "--runs",
+            "2",
+            "--warmup",
+            "1",
+            "--idle",
+            "200ms",
+            "--report-json",
+            tmp_file_report.to_str().unwrap(),
+            "sleep",
+            "0.5",
+        ],
+        tmp_dir,
+    )?;
+    assert!(command_out.status.success(), "alumet-agent exec --runs should work");
+
+    let report = std::fs::read_to_string(&tmp_file_report).context("failed to read the report")?;
+    let report: serde_json::Value = serde_json::from_str(&report)?;
+    let run_ids: Vec<&str> = report["phases"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["run_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(run_ids, vec!["idle-before", "warmup-1", "1", "2", "idle-after"]);
+    assert_eq!(report["statistics"]["runs"], 2);
+    let mean_duration = report["statistics"]["duration_seconds"]["mean"].as_f64().unwrap();
+    assert!(
+        mean_duration >= 0.5,
+        "runs should last at least 0.5s, got {mean_duration}"
+    );
+
+    // The measurements must be tagged with the id of the run.
+    let alumet_out = std::fs::read_to_string(&tmp_file_out)?;
+    assert!(
+        alumet_out.contains("run_id"),
+        "measurements should have a run_id attribute"
+    );
+    Ok(())
+}
+
 #[test]
 fn plugin_enabled_with_missing_config_should_use_default() -> anyhow::Result<()> {
     let tmp_dir = tempfile::tempdir()?;
@@ -145,3 +351,163 @@ fn plugin_enabled_with_bad_config_should_fail() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn config_check() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let conf_path_str = conf.to_str().unwrap();
+
+    // the default config is valid
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--plugins", "csv", "--config", conf_path_str, "config", "regen"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "check"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "the default config should be valid");
+
+    // introduce several problems, which must all be reported
+    let default_config = std::fs::read_to_string(&conf)?;
+    let content = format!("source_chanel_size = 10\n[plugins.csvv]\n{default_config}")
+        .replace("force_flush = true", "force_flush = \"yes\"")
+        .replace("use_unit_display_name", "use_unit_display_nam");
+    std::fs::write(&conf, &content)?;
+    let line_of = |pat: &str| content.lines().position(|l| l.starts_with(pat)).unwrap() + 1;
+
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "check"],
+        tmp_dir.path(),
+    )?;
+    assert!(!output.status.success(), "the check should fail");
+    let stdout = String::from_utf8(output.stdout)?;
+    for expected in [
+        String::from(":1:1: unknown key `source_chanel_size`, did you mean `source_channel_size`?"),
+        String::from(":2:10: unknown plugin `plugins.csvv`, did you mean `csv`?"),
+        format!(
+            ":{}:1: [csv] unknown key `plugins.csv.use_unit_display_nam`, did you mean `use_unit_display_name`?",
+            line_of("use_unit_display_nam")
+        ),
+        format!(
+            ":{}:1: [csv] invalid value for `plugins.csv.force_flush`",
+            line_of("force_flush")
+        ),
+        String::from("found 4 problems"),
+    ] {
+        assert!(stdout.contains(&expected), "missing '{expected}' in:\n{stdout}");
+    }
+    Ok(())
+}
+
+#[test]
+fn config_show_merged() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let common = tmp_dir.path().join("common.toml");
+    let drop_in = tmp_dir.path().join("config.d/10-csv.toml");
+    let conf_path_str = conf.to_str().unwrap();
+
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--plugins", "csv", "--config", conf_path_str, "config", "regen"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+
+    // move a general option to an included file, and override a plugin option in a drop-in file
+    let default_config = std::fs::read_to_string(&conf)?;
+    let content: Vec<&str> = default_config
+        .lines()
+        .filter(|l| !l.starts_with("source_channel_size"))
+        .collect();
+    std::fs::write(&conf, format!("include = [\"common.toml\"]\n{}\n", content.join("\n")))?;
+    std::fs::write(&common, "source_channel_size = 42\n")?;
+    std::fs::create_dir(drop_in.parent().unwrap())?;
+    std::fs::write(&drop_in, "[plugins.csv]\nforce_flush = false\n")?;
+
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "show", "--merged"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+    let stdout = String::from_utf8(output.stdout)?;
+    for expected in [
+        format!("source_channel_size = 42 # {}", common.display()),
+        format!("plugins.csv.force_flush = false # {}", drop_in.display()),
+        format!("plugins.csv.output_path = \"alumet-output.csv\" # {}", conf.display()),
+    ] {
+        assert!(stdout.contains(&expected), "missing '{expected}' in:\n{stdout}");
+    }
+    assert!(
+        !stdout.contains("include"),
+        "the include key should be removed:\n{stdout}"
+    );
+
+    // the check follows the includes and the drop-in files
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "check"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "the config should be valid");
+    Ok(())
+}
+
+#[test]
+fn config_schema() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let conf_path_str = conf.to_str().unwrap();
+
+    let output = run_agent_tee(AGENT_BIN, &["config", "schema"], tmp_dir.path())?;
+    assert!(output.status.success(), "command should succeed");
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).context("invalid JSON")?;
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["properties"]["source_channel_size"]["type"], "integer");
+    let csv = &schema["properties"]["plugins"]["properties"]["csv"];
+    assert_eq!(csv["properties"]["enabled"]["type"], "boolean");
+    assert_eq!(csv["properties"]["force_flush"]["type"], "boolean");
+    assert!(
+        csv["required"].as_array().unwrap().contains(&"output_path".into()),
+        "output_path should be required: {csv}"
+    );
+
+    // every key of the default config is described by the schema
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "regen"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+    let default_config: toml::Table = std::fs::read_to_string(&conf)?.parse()?;
+    fn key_schema<'a>(schema: &'a serde_json::Value, key: &str) -> &'a serde_json::Value {
+        // the key is either a known property, the property of a variant (enums), or an arbitrary key of a map
+        let variants = ["oneOf", "anyOf"]
+            .iter()
+            .filter_map(|k| schema[k].as_array())
+            .flatten()
+            .map(|variant| &variant["properties"][key]);
+        std::iter::once(&schema["properties"][key])
+            .chain(variants)
+            .find(|s| s.is_object())
+            .unwrap_or(&schema["additionalProperties"])
+    }
+    fn check_keys(table: &toml::Table, schema: &serde_json::Value, path: &str) {
+        for (key, value) in table {
+            let key_schema = key_schema(schema, key);
+            assert!(key_schema.is_object(), "key {path}{key} is missing from the schema");
+            if let toml::Value::Table(t) = value {
+                check_keys(t, key_schema, &format!("{path}{key}."));
+            }
+        }
+    }
+    check_keys(&default_config, &schema, "");
+    Ok(())
+}
@@ -1,11 +1,20 @@
 //! Builder for Alumet agents.
 
-use std::{collections::HashMap, ops::DerefMut, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::DerefMut,
+    time::Duration,
+};
 
 use anyhow::{Context, anyhow};
 use thiserror::Error;
 
 use crate::agent::plugin::PluginInfo;
+use crate::metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, registry::MetricRegistry};
+use crate::pipeline::control::request::{self, TransformPosition};
+use crate::pipeline::elements::output::builder::{BlockingOutputBuildContext, OutputBuilder};
+use crate::pipeline::elements::source::builder::{ManagedSourceBuildContext, SourceBuilder, SourcePace};
+use crate::pipeline::elements::transform::builder::TransformBuildContext;
 use crate::pipeline::error::PipelineError;
 use crate::plugin::phases::PreStartAction;
 use crate::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable, Plugin};
@@ -137,134 +146,6 @@ impl Builder {
 
     /// Builds and starts the underlying measurement pipeline and the enabled plugins.
     pub fn build_and_start(self) -> anyhow::Result<RunningAgent> {
-        /// Initializes one plugin.
-        ///
-        /// Returns the initialized plugin, or an error.
-        fn init_plugin(p: PluginInfo) -> anyhow::Result<Box<dyn Plugin>> {
-            let name = p.metadata.name;
-            let version = p.metadata.version;
-            let config = match p.config {
-                Some(config) => Some(ConfigTable(config)),
-                None => {
-                    // no config has been provided for this plugin, use its default config
-                    (p.metadata.default_config)()
-                        .with_context(|| format!("failed to generate default config of plugin {name} v{version}"))?
-                }
-            };
-            let config = config.unwrap_or_default();
-            log::debug!("Initializing plugin {name} v{version} with config {config:?}...");
-
-            // call init
-            let initialized = (p.metadata.init)(config)
-                .with_context(|| format!("plugin failed to initialize: {} v{}", name, version))?;
-
-            // check that the plugin corresponds to its metadata
-            if (initialized.name(), initialized.version()) != (&name, &version) {
-                return Err(anyhow!(
-                    "invalid plugin: metadata is '{name}' v{version} but the plugin's methods return '{name}' v{version}"
-                ));
-            }
-            Ok(initialized)
-        }
-
-        /// Starts a plugin, i.e. calls [`Plugin::start`] with the right context.
-        fn start_plugin(
-            p: &mut dyn Plugin,
-            pipeline_builder: &mut pipeline::Builder,
-            pre_start_actions: &mut Vec<(PluginName, Box<dyn PreStartAction>)>,
-            post_start_actions: &mut Vec<(PluginName, Box<dyn PostStartAction>)>,
-        ) -> anyhow::Result<()> {
-            let name = p.name().to_owned();
-            let version = p.version().to_owned();
-            log::debug!("Starting plugin {name} v{version}...");
-
-            let mut ctx = AlumetPluginStart {
-                current_plugin: PluginName(name.clone()),
-                pipeline_builder,
-                pre_start_actions,
-                post_start_actions,
-            };
-            p.start(&mut ctx)
-                .with_context(|| format!("plugin failed to start: {name} v{version}"))
-        }
-
-        /// Executes the pre-pipeline-start phase of a plugin, i.e. calls [`Plugin::pre_pipeline_start`] with the right context.
-        fn pre_pipeline_start(
-            p: &mut dyn Plugin,
-            pipeline_builder: &mut pipeline::Builder,
-            actions: &mut HashMap<PluginName, Vec<Box<dyn PreStartAction>>>,
-        ) -> anyhow::Result<()> {
-            let name = p.name().to_owned();
-            let version = p.version().to_owned();
-            log::debug!("Running pre-pipeline-start hook for plugin {name} v{version}...");
-
-            // Prepare the context.
-            let pname = PluginName(name.clone());
-            let mut ctx = AlumetPreStart {
-                current_plugin: pname.clone(),
-                pipeline_builder,
-            };
-
-            // Call pre_pipeline_start.
-            p.pre_pipeline_start(&mut ctx)
-                .with_context(|| format!("plugin pre_pipeline_start failed: {} v{}", p.name(), p.version()))?;
-
-            // Run the additional actions registered by the plugin, if any.
-            if let Some(actions) = actions.remove(&pname) {
-                for f in actions {
-                    (f)(&mut ctx)
-                        .with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
-                }
-            }
-            Ok(())
-        }
-
-        /// Executes the post-pipeline-start phase of a plugin, i.e. calls [`Plugin::post_pipeline_start`] with the right context.
-        ///
-        /// Plugins can also register post-pipeline-start actions in the form of closures, we run these too.
-        fn post_pipeline_start(
-            p: &mut dyn Plugin,
-            pipeline: &mut pipeline::MeasurementPipeline,
-            actions: &mut HashMap<PluginName, Vec<Box<dyn PostStartAction>>>,
-        ) -> anyhow::Result<()> {
-            let name = p.name().to_owned();
-            let version = p.version().to_owned();
-            log::debug!("Running post-pipeline-start hook for plugin {name} v{version}...");
-
-            // Prepare the context.
-            let pname = PluginName(name.clone());
-            let mut ctx = AlumetPostStart {
-                current_plugin: pname.clone(),
-                pipeline,
-            };
-
-            // Call post_pipeline_start.
-            p.post_pipeline_start(&mut ctx)
-                .with_context(|| format!("plugin post_pipeline_start method failed: {name} v{version}"))?;
-
-            // Run the additional actions registered by the plugin, if any.
-            if let Some(actions) = actions.remove(&pname) {
-                for f in actions {
-                    (f)(&mut ctx)
-                        .with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
-                }
-            }
-            Ok(())
-        }
-
-        /// Groups all pre or post-start actions by plugin.
-        fn group_plugin_actions<BoxedAction>(
-            post_start_actions: Vec<(PluginName, BoxedAction)>,
-            n_plugins: usize,
-        ) -> HashMap<PluginName, Vec<BoxedAction>> {
-            let mut res = HashMap::with_capacity(n_plugins);
-            for (plugin, action) in post_start_actions {
-                let plugin_actions: &mut Vec<_> = res.entry(plugin).or_default();
-                plugin_actions.push(action);
-            }
-            res
-        }
-
         // Find which plugins are enabled.
         log::info!("Initializing the plugins...");
         let (enabled_plugins, disabled_plugins): (Vec<PluginInfo>, Vec<PluginInfo>) = self.plugins.into_partition();
@@ -416,6 +297,306 @@ impl RunningAgent {
             Err(ShutdownError { errors })
         }
     }
+
+    /// Initializes and starts a plugin while the measurement pipeline is running.
+    ///
+    /// The metrics and pipeline elements that the plugin registers during its start-up phase
+    /// are added to the running pipeline, then its post-pipeline-start hooks are executed.
+    /// The plugin will be stopped with the others in [`wait_for_shutdown`](Self::wait_for_shutdown).
+    ///
+    /// # Limitations
+    /// Only managed sources, transforms and blocking outputs can be added to a running pipeline.
+    /// If the plugin registers an autonomous source, an async output or a metric listener, it is stopped
+    /// and an error is returned: the agent must be restarted to enable the plugin.
+    ///
+    /// Transforms and outputs cannot be added to a "simplified" pipeline, see [`pipeline::Builder::allow_simplified_pipeline`]:
+    /// the agent must be restarted to enable a plugin that registers some.
+    ///
+    /// # Blocking
+    /// This is a blocking function, it should not be called from within an async runtime.
+    pub fn start_plugin(&mut self, plugin: PluginInfo) -> anyhow::Result<()> {
+        let mut plugin = init_plugin(plugin)?;
+        let name = plugin.name().to_owned();
+        let version = plugin.version().to_owned();
+
+        let mut post_start_actions = Vec::new();
+        if let Err(e) = self.add_plugin_elements(plugin.deref_mut(), &mut post_start_actions) {
+            // Nothing has been added to the pipeline, we can stop the plugin.
+            if let Err(stop_err) = plugin.stop() {
+                log::error!("Error while stopping plugin {name} v{version}. {stop_err:?}");
+            }
+            return Err(e);
+        }
+
+        // The elements are running: keep the plugin, even if the hooks fail, to stop it on shutdown.
+        let mut post_actions_per_plugin = group_plugin_actions(post_start_actions, 1);
+        let res = post_pipeline_start(plugin.deref_mut(), &mut self.pipeline, &mut post_actions_per_plugin);
+        self.initialized_plugins.push(plugin);
+        res
+    }
+
+    /// Starts a plugin and adds the elements that it registers to the running pipeline.
+    fn add_plugin_elements(
+        &mut self,
+        plugin: &mut dyn Plugin,
+        post_start_actions: &mut Vec<(PluginName, Box<dyn PostStartAction>)>,
+    ) -> anyhow::Result<()> {
+        let plugin_name = PluginName(plugin.name().to_owned());
+        let rt = self.pipeline.async_runtime().clone();
+
+        // Start the plugin with a pipeline builder that knows the metrics of the running pipeline.
+        let metrics_reader = self.pipeline.metrics_reader();
+        let registry = rt.block_on(async { metrics_reader.read().await.clone() });
+        let known_metrics: HashSet<RawMetricId> = registry.iter().map(|(id, _)| *id).collect();
+        let mut pipeline_builder = pipeline::Builder::new();
+        pipeline_builder.metrics = registry;
+
+        let mut pre_start_actions = Vec::new();
+        start_plugin(
+            plugin,
+            &mut pipeline_builder,
+            &mut pre_start_actions,
+            post_start_actions,
+        )?;
+        let mut pre_actions_per_plugin = group_plugin_actions(pre_start_actions, 1);
+        pre_pipeline_start(plugin, &mut pipeline_builder, &mut pre_actions_per_plugin)?;
+
+        // Check that every element can be added to the running pipeline, before building any of them.
+        let elements = pipeline_builder.take_elements();
+        let mut unsupported: Vec<String> = elements
+            .metric_listeners
+            .iter()
+            .map(|l| format!("metric listener {l}"))
+            .collect();
+        for (name, builder) in &elements.sources {
+            if let SourceBuilder::Autonomous(_) = builder {
+                unsupported.push(format!("autonomous source {name}"));
+            }
+        }
+        for (name, builder) in &elements.outputs {
+            if let OutputBuilder::Async(_) = builder {
+                unsupported.push(format!("async output {name}"));
+            }
+        }
+        if self.pipeline.is_simplified() {
+            unsupported.extend(elements.transforms.iter().map(|(name, _)| format!("transform {name}")));
+            unsupported.extend(elements.outputs.iter().map(|(name, _)| format!("output {name}")));
+        }
+        if !unsupported.is_empty() {
+            return Err(anyhow!(
+                "plugin {} registers elements that cannot be added to a running pipeline: {} (restart required)",
+                plugin_name.0,
+                unsupported.join(", ")
+            ));
+        }
+
+        // Register the new metrics. They must get the same ids as in the builder,
+        // because the plugin may have kept them.
+        let registry = &pipeline_builder.metrics;
+        let mut new_metrics: Vec<(RawMetricId, Metric)> = registry
+            .iter()
+            .filter(|(id, _)| !known_metrics.contains(id))
+            .map(|(id, m)| (*id, m.clone()))
+            .collect();
+        new_metrics.sort_by_key(|(id, _)| id.as_u64());
+        if !new_metrics.is_empty() {
+            let (expected_ids, metrics): (Vec<_>, Vec<_>) = new_metrics.into_iter().unzip();
+            let metrics_sender = self.pipeline.metrics_sender();
+            let registered = rt
+                .block_on(metrics_sender.create_metrics(metrics, DuplicateReaction::Error))
+                .map_err(|e| anyhow!("could not register the metrics: {e}"))?;
+            for (expected, res) in expected_ids.into_iter().zip(registered) {
+                let id = res.context("could not register a metric")?;
+                if id != expected {
+                    return Err(anyhow!(
+                        "the metric registry has been modified while plugin {} was starting, please retry",
+                        plugin_name.0
+                    ));
+                }
+            }
+        }
+
+        // Build the elements and send them to the pipeline.
+        let mut ctx = RegistryBuildContext(registry);
+        let mut request = request::create_many();
+        for (name, builder) in elements.outputs {
+            if let OutputBuilder::Blocking(builder) = builder {
+                let output = builder(&mut ctx).with_context(|| format!("error in output creation: {name}"))?;
+                request.add_blocking_output(name.output(), output);
+            }
+        }
+        for (name, builder) in elements.transforms {
+            let transform = builder(&mut ctx).with_context(|| format!("error in transform creation: {name}"))?;
+            request.add_transform(name.transform(), transform, TransformPosition::Last);
+        }
+        for (name, builder) in elements.sources {
+            if let SourceBuilder::Managed(builder, pace) = builder {
+                let source = builder(&mut ctx).with_context(|| format!("error in source creation: {name}"))?;
+                match pace {
+                    SourcePace::Fast => request.add_source_builder(name.source(), move |_| Ok(source)),
+                    SourcePace::Blocking => request.add_blocking_source_builder(name.source(), move |_| Ok(source)),
+                };
+            }
+        }
+        let control_handle = self.pipeline.control_handle().with_plugin(plugin_name);
+        rt.block_on(control_handle.send_wait(request.build(), LATE_START_TIMEOUT))
+            .context("could not add the elements to the pipeline")?;
+        Ok(())
+    }
+}
+
+/// Maximum time to wait for the pipeline to create the elements of a plugin that is started late.
+const LATE_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Provides the metric registry to the builders of the elements of a plugin that is started late.
+struct RegistryBuildContext<'a>(&'a MetricRegistry);
+
+impl ManagedSourceBuildContext for RegistryBuildContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.0.by_name(name)
+    }
+}
+
+impl TransformBuildContext for RegistryBuildContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.0.by_name(name)
+    }
+
+    fn metrics(&self) -> &MetricRegistry {
+        self.0
+    }
+}
+
+impl BlockingOutputBuildContext for RegistryBuildContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.0.by_name(name)
+    }
+}
+
+/// Initializes one plugin.
+///
+/// Returns the initialized plugin, or an error.
+fn init_plugin(p: PluginInfo) -> anyhow::Result<Box<dyn Plugin>> {
+    let name = p.metadata.name;
+    let version = p.metadata.version;
+    let config = match p.config {
+        Some(config) => Some(ConfigTable(config)),
+        None => {
+            // no config has been provided for this plugin, use its default config
+            (p.metadata.default_config)()
+                .with_context(|| format!("failed to generate default config of plugin {name} v{version}"))?
+        }
+    };
+    let config = config.unwrap_or_default();
+    log::debug!("Initializing plugin {name} v{version} with config {config:?}...");
+
+    // call init
+    let initialized =
+        (p.metadata.init)(config).with_context(|| format!("plugin failed to initialize: {} v{}", name, version))?;
+
+    // check that the plugin corresponds to its metadata
+    if (initialized.name(), initialized.version()) != (&name, &version) {
+        return Err(anyhow!(
+            "invalid plugin: metadata is '{name}' v{version} but the plugin's methods return '{name}' v{version}"
+        ));
+    }
+    Ok(initialized)
+}
+
+/// Starts a plugin, i.e. calls [`Plugin::start`] with the right context.
+fn start_plugin(
+    p: &mut dyn Plugin,
+    pipeline_builder: &mut pipeline::Builder,
+    pre_start_actions: &mut Vec<(PluginName, Box<dyn PreStartAction>)>,
+    post_start_actions: &mut Vec<(PluginName, Box<dyn PostStartAction>)>,
+) -> anyhow::Result<()> {
+    let name = p.name().to_owned();
+    let version = p.version().to_owned();
+    log::debug!("Starting plugin {name} v{version}...");
+
+    let mut ctx = AlumetPluginStart {
+        current_plugin: PluginName(name.clone()),
+        pipeline_builder,
+        pre_start_actions,
+        post_start_actions,
+    };
+    p.start(&mut ctx)
+        .with_context(|| format!("plugin failed to start: {name} v{version}"))
+}
+
+/// Executes the pre-pipeline-start phase of a plugin, i.e. calls [`Plugin::pre_pipeline_start`] with the right context.
+fn pre_pipeline_start(
+    p: &mut dyn Plugin,
+    pipeline_builder: &mut pipeline::Builder,
+    actions: &mut HashMap<PluginName, Vec<Box<dyn PreStartAction>>>,
+) -> anyhow::Result<()> {
+    let name = p.name().to_owned();
+    let version = p.version().to_owned();
+    log::debug!("Running pre-pipeline-start hook for plugin {name} v{version}...");
+
+    // Prepare the context.
+    let pname = PluginName(name.clone());
+    let mut ctx = AlumetPreStart {
+        current_plugin: pname.clone(),
+        pipeline_builder,
+    };
+
+    // Call pre_pipeline_start.
+    p.pre_pipeline_start(&mut ctx)
+        .with_context(|| format!("plugin pre_pipeline_start failed: {} v{}", p.name(), p.version()))?;
+
+    // Run the additional actions registered by the plugin, if any.
+    if let Some(actions) = actions.remove(&pname) {
+        for f in actions {
+            (f)(&mut ctx).with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Executes the post-pipeline-start phase of a plugin, i.e. calls [`Plugin::post_pipeline_start`] with the right context.
+///
+/// Plugins can also register post-pipeline-start actions in the form of closures, we run these too.
+fn post_pipeline_start(
+    p: &mut dyn Plugin,
+    pipeline: &mut pipeline::MeasurementPipeline,
+    actions: &mut HashMap<PluginName, Vec<Box<dyn PostStartAction>>>,
+) -> anyhow::Result<()> {
+    let name = p.name().to_owned();
+    let version = p.version().to_owned();
+    log::debug!("Running post-pipeline-start hook for plugin {name} v{version}...");
+
+    // Prepare the context.
+    let pname = PluginName(name.clone());
+    let mut ctx = AlumetPostStart {
+        current_plugin: pname.clone(),
+        pipeline,
+    };
+
+    // Call post_pipeline_start.
+    p.post_pipeline_start(&mut ctx)
+        .with_context(|| format!("plugin post_pipeline_start method failed: {name} v{version}"))?;
+
+    // Run the additional actions registered by the plugin, if any.
+    if let Some(actions) = actions.remove(&pname) {
+        for f in actions {
+            (f)(&mut ctx).with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Groups all pre or post-start actions by plugin.
+fn group_plugin_actions<BoxedAction>(
+    post_start_actions: Vec<(PluginName, BoxedAction)>,
+    n_plugins: usize,
+) -> HashMap<PluginName, Vec<BoxedAction>> {
+    let mut res = HashMap::with_capacity(n_plugins);
+    for (plugin, action) in post_start_actions {
+        let plugin_actions: &mut Vec<_> = res.entry(plugin).or_default();
+        plugin_actions.push(action);
+    }
+    res
 }
 
 /// Prints some statistics after the plugin start-up phase.
@@ -0,0 +1,435 @@
+//! Checking configurations without starting anything.
+//!
+//! Deserializing a configuration with `toml` stops at the first error, and silently ignores the
+//! keys that the configuration structure does not expect. This module provides a deserialization
+//! that records every unknown key and the location of the error, if any, to report them to the user.
+//!
+//! Plugins deserialize their configuration with [`deserialize_config`](crate::plugin::rust::deserialize_config).
+//! Call it through [`collect_issues`] to get the issues found in the configuration of a plugin:
+//!
+//! ```
+//! use alumet::agent::check::{self, IssueKind};
+//! use alumet::plugin::{ConfigTable, rust::deserialize_config};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Config {
+//!     poll_interval: String,
+//! }
+//!
+//! let table = toml::toml! {
+//!     poll_interval = "1s"
+//!     pol_interval = "2s"
+//! };
+//! let (res, issues) = check::collect_issues(|| deserialize_config::<Config>(ConfigTable(table)));
+//! assert!(res.is_ok());
+//! assert_eq!(issues.len(), 1);
+//! assert_eq!(issues[0].path.to_string(), "pol_interval");
+//! assert!(matches!(issues[0].kind, IssueKind::UnknownKey { .. }));
+//! ```
+
+use std::{cell::RefCell, fmt};
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+
+/// A problem found in a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    /// Location of the problem, relative to the deserialized table.
+    pub path: ConfigPath,
+    pub kind: IssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// The key is not expected by the configuration structure, its value is ignored.
+    UnknownKey {
+        /// The keys that the structure accepts.
+        expected: Vec<&'static str>,
+    },
+    /// The value could not be deserialized: wrong type, missing field, invalid value...
+    Invalid { message: String },
+}
+
+/// Path to a value in a TOML document, for instance `groups[0].name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigPath(pub Vec<PathSegment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Vec<ConfigIssue>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` and returns the issues found by the calls to
+/// [`deserialize_config`](crate::plugin::rust::deserialize_config) that it made on the current thread.
+pub fn collect_issues<R>(f: impl FnOnce() -> R) -> (R, Vec<ConfigIssue>) {
+    COLLECTOR.set(Some(Vec::new()));
+    let res = f();
+    let issues = COLLECTOR.take().unwrap_or_default();
+    (res, issues)
+}
+
+/// Returns `true` if [`collect_issues`] is running on the current thread.
+pub(crate) fn is_collecting() -> bool {
+    COLLECTOR.with_borrow(|c| c.is_some())
+}
+
+/// Deserializes the table and adds the issues to the current collector.
+pub(crate) fn deserialize_and_collect<'de, T: de::Deserialize<'de>>(table: toml::Table) -> Result<T, toml::de::Error> {
+    let (res, mut issues) = deserialize(table);
+    COLLECTOR.with_borrow_mut(|c| {
+        if let Some(collected) = c {
+            collected.append(&mut issues);
+        }
+    });
+    res
+}
+
+/// Deserializes a table like [`toml::Value::try_into`] does, and records the issues.
+///
+/// If the deserialization fails, the issues contain the error, with its location.
+pub fn deserialize<'de, T: de::Deserialize<'de>>(table: toml::Table) -> (Result<T, toml::de::Error>, Vec<ConfigIssue>) {
+    let state = RefCell::new(State::default());
+    let root = Tracked {
+        value: toml::Value::Table(table),
+        path: ConfigPath::default(),
+        state: &state,
+    };
+    let res = T::deserialize(root);
+    let mut state = state.into_inner();
+    if let Err(e) = &res {
+        state.issues.push(ConfigIssue {
+            path: state.error_path.unwrap_or_default(),
+            kind: IssueKind::Invalid {
+                message: e.message().to_owned(),
+            },
+        });
+    }
+    (res, state.issues)
+}
+
+#[derive(Default)]
+struct State {
+    issues: Vec<ConfigIssue>,
+    /// Path of the deepest value that failed to deserialize.
+    error_path: Option<ConfigPath>,
+}
+
+impl State {
+    fn failed_at(&mut self, path: ConfigPath) {
+        // Errors propagate from the inner values to the outer ones: keep the first path.
+        if self.error_path.is_none() {
+            self.error_path = Some(path);
+        }
+    }
+}
+
+/// A TOML value that records its path and the unknown keys of its tables.
+///
+/// Behaves like the deserializer of [`toml::Value`].
+struct Tracked<'s> {
+    value: toml::Value,
+    path: ConfigPath,
+    state: &'s RefCell<State>,
+}
+
+impl<'de> de::Deserializer<'de> for Tracked<'_> {
+    type Error = toml::de::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            toml::Value::Array(array) => {
+                let len = array.len();
+                let mut seq = TrackedSeq {
+                    iter: array.into_iter().enumerate(),
+                    path: self.path,
+                    state: self.state,
+                };
+                let res = visitor.visit_seq(&mut seq)?;
+                if seq.iter.len() == 0 {
+                    Ok(res)
+                } else {
+                    Err(de::Error::invalid_length(len, &"fewer elements in array"))
+                }
+            }
+            toml::Value::Table(table) => {
+                let len = table.len();
+                let mut map = TrackedMap {
+                    iter: table.into_iter(),
+                    next_value: None,
+                    path: self.path,
+                    state: self.state,
+                };
+                let res = visitor.visit_map(&mut map)?;
+                if map.iter.len() == 0 {
+                    Ok(res)
+                } else {
+                    Err(de::Error::invalid_length(len, &"fewer elements in map"))
+                }
+            }
+            scalar => scalar.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if let toml::Value::Table(table) = &self.value {
+            let mut state = self.state.borrow_mut();
+            for key in table.keys().filter(|k| !fields.contains(&k.as_str())) {
+                state.issues.push(ConfigIssue {
+                    path: self.path.join(PathSegment::Key(key.to_owned())),
+                    kind: IssueKind::UnknownKey {
+                        expected: fields.to_vec(),
+                    },
+                });
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // TOML has no null value: if the key is present, the option is `Some`.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        // The variants are not tracked.
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
+        bytes byte_buf map unit_struct tuple_struct tuple ignored_any identifier
+    }
+}
+
+struct TrackedSeq<'s> {
+    iter: std::iter::Enumerate<std::vec::IntoIter<toml::Value>>,
+    path: ConfigPath,
+    state: &'s RefCell<State>,
+}
+
+impl<'de> SeqAccess<'de> for TrackedSeq<'_> {
+    type Error = toml::de::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((i, value)) => {
+                let path = self.path.join(PathSegment::Index(i));
+                deserialize_child(seed, value, path, self.state).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct TrackedMap<'s> {
+    iter: toml::map::IntoIter<String, toml::Value>,
+    next_value: Option<(String, toml::Value)>,
+    path: ConfigPath,
+    state: &'s RefCell<State>,
+}
+
+impl<'de> MapAccess<'de> for TrackedMap<'_> {
+    type Error = toml::de::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                let res = seed.deserialize(key.clone().into_deserializer()).map(Some);
+                self.next_value = Some((key, value));
+                res
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .next_value
+            .take()
+            .expect("next_value_seed should be called after next_key_seed");
+        let path = self.path.join(PathSegment::Key(key));
+        deserialize_child(seed, value, path, self.state)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+fn deserialize_child<'de, T: DeserializeSeed<'de>>(
+    seed: T,
+    value: toml::Value,
+    path: ConfigPath,
+    state: &RefCell<State>,
+) -> Result<T::Value, toml::de::Error> {
+    let child = Tracked {
+        value,
+        path: path.clone(),
+        state,
+    };
+    seed.deserialize(child)
+        .inspect_err(|_| state.borrow_mut().failed_at(path))
+}
+
+impl ConfigPath {
+    /// Returns a new path with one more segment.
+    pub fn join(&self, segment: PathSegment) -> ConfigPath {
+        let mut segments = self.0.clone();
+        segments.push(segment);
+        ConfigPath(segments)
+    }
+}
+
+impl fmt::Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => write!(f, "{key}")?,
+                PathSegment::Key(key) => write!(f, ".{key}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::{ConfigIssue, IssueKind, deserialize};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        #[serde(with = "humantime_serde")]
+        poll_interval: Duration,
+        #[serde(default)]
+        groups: Vec<Group>,
+        extra: Option<Extra>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Group {
+        name: String,
+        #[serde(alias = "regex")]
+        pattern: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Extra {
+        Count(u32),
+        Names(Vec<String>),
+    }
+
+    fn paths(issues: &[ConfigIssue]) -> Vec<String> {
+        issues.iter().map(|i| i.path.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_config() {
+        let table = toml::toml! {
+            poll_interval = "1s"
+            extra = ["a", "b"]
+
+            [[groups]]
+            name = "a"
+            regex = ".*"
+        };
+        let (res, issues) = deserialize::<Config>(table.clone());
+        assert_eq!(issues, vec![]);
+        assert_eq!(res.unwrap(), toml::Value::Table(table).try_into::<Config>().unwrap());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let table = toml::toml! {
+            poll_interval = "1s"
+            flush_interval = "1s"
+
+            [[groups]]
+            name = "a"
+
+            [[groups]]
+            name = "b"
+            patern = "b.*"
+        };
+        let (res, issues) = deserialize::<Config>(table);
+        assert!(res.is_ok(), "unknown keys are ignored");
+        assert_eq!(paths(&issues), vec!["flush_interval", "groups[1].patern"]);
+        assert_eq!(
+            issues[1].kind,
+            IssueKind::UnknownKey {
+                expected: vec!["name", "pattern", "regex"]
+            }
+        );
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let table = toml::toml! {
+            poll_interval = "1s"
+
+            [[groups]]
+            name = "a"
+
+            [[groups]]
+            name = 42
+        };
+        let (res, issues) = deserialize::<Config>(table);
+        assert!(res.is_err());
+        assert_eq!(paths(&issues), vec!["groups[1].name"]);
+        let IssueKind::Invalid { message } = &issues[0].kind else {
+            panic!("unexpected issue {issues:?}");
+        };
+        assert!(message.contains("invalid type"), "{message}");
+
+        // missing field: the location is the table that should contain it
+        let table = toml::toml! {
+            poll_interval = "1s"
+            unknown = true
+
+            [[groups]]
+            pattern = "a"
+        };
+        let (res, issues) = deserialize::<Config>(table);
+        assert!(res.is_err());
+        assert_eq!(paths(&issues), vec!["unknown", "groups[0]"]);
+
+        // invalid value in a custom deserializer
+        let table = toml::toml! {
+            poll_interval = "one second"
+        };
+        let (_, issues) = deserialize::<Config>(table);
+        assert_eq!(paths(&issues), vec!["poll_interval"]);
+    }
+}
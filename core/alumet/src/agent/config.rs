@@ -42,16 +42,35 @@
 //!
 //! // TODO use the config
 //! ```
+//!
+//! # Splitting the configuration
+//!
+//! The configuration can be split into several files, which are merged with [`merge_override`]:
+//! - the files listed in the `include` array of a configuration file, for instance
+//!   `include = ["common.toml"]`, are loaded first, in order, and overridden by the file that includes them.
+//!   Relative paths are resolved from the directory of the including file.
+//!   See [`Loader::include_files`].
+//! - the `.toml` files of the drop-in directory, usually `alumet-config.d/`, override the main file,
+//!   in the lexical order of their names. See [`Loader::drop_in_dir`].
+//!
+//! Use [`Loader::load_with_origins`] to know which file each value comes from.
+use std::collections::BTreeMap;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::{borrow::Cow, env::VarError};
+use std::{borrow::Cow, env::VarError, fmt};
 
 use anyhow::anyhow;
 use indexmap::IndexMap;
+#[cfg(feature = "schema")]
+use schemars::json_schema;
 use serde::Serialize;
+#[cfg(feature = "schema")]
+use serde_json::{Value, json};
 
 use super::plugin::{PluginFilter, PluginSet};
+#[cfg(feature = "schema")]
+use super::schema::{self, Schema};
 use crate::plugin::PluginMetadata;
 use error::*;
 
@@ -67,6 +86,28 @@ pub struct Loader<'d> {
     overrides: Option<toml::Table>,
     /// Should environment variable substitution be applied before deserializing?
     substitute_env: bool,
+    /// Should the files listed in `include` be loaded?
+    include_files: bool,
+    /// Directory that contains additional config files.
+    drop_in_dir: Option<PathBuf>,
+}
+
+/// Where each value of a configuration comes from.
+///
+/// The origin of a value is the last layer that set it: the main config file, an included file,
+/// a drop-in file or an override.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOrigins(BTreeMap<Vec<String>, ConfigOrigin>);
+
+/// The origin of a configuration value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    /// The value comes from a file.
+    File(PathBuf),
+    /// The value comes from the default configuration, which has not been saved.
+    Default,
+    /// The value comes from an override, see [`Loader::with_override`].
+    Override,
 }
 
 /// Generates default configurations.
@@ -110,6 +151,8 @@ impl<'d> Loader<'d> {
             save_default: false,
             overrides: None,
             substitute_env: false,
+            include_files: false,
+            drop_in_dir: None,
         }
     }
 
@@ -158,27 +201,115 @@ impl<'d> Loader<'d> {
         self
     }
 
+    /// Enables or disables the `include` key, which lists the files to load before the config file.
+    ///
+    /// Included files can include other files. The `include` key is removed from the loaded config.
+    pub fn include_files(mut self, enabled: bool) -> Self {
+        self.include_files = enabled;
+        self
+    }
+
+    /// Merges the `.toml` files of a directory, in lexical order, after the config file.
+    ///
+    /// If the directory does not exist, it is ignored. See also [`default_drop_in_dir`].
+    pub fn drop_in_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.drop_in_dir = Some(dir.into());
+        self
+    }
+
     /// Loads the configuration with the provided settings.
-    pub fn load(mut self) -> Result<toml::Table, LoadError> {
-        self.load_impl().map_err(|e| LoadError {
-            config_file: self.file,
-            kind: e,
-        })
+    pub fn load(self) -> Result<toml::Table, LoadError> {
+        self.load_with_origins().map(|(config, _)| config)
+    }
+
+    /// Loads the configuration with the provided settings, and returns the origin of each value.
+    pub fn load_with_origins(mut self) -> Result<(toml::Table, ConfigOrigins), LoadError> {
+        let mut origins = ConfigOrigins::default();
+        match self.load_impl(&mut origins) {
+            Ok(config) => Ok((config, origins)),
+            Err(e) => Err(LoadError {
+                config_file: self.file,
+                kind: e,
+            }),
+        }
     }
 
-    fn load_impl(&mut self) -> Result<toml::Table, LoadErrorCause> {
-        let config_content = self.read_config_or_default()?;
-        let config_content = substitute_env(&config_content)?;
-        let mut parsed_config = toml::Table::from_str(&config_content)?;
+    fn load_impl(&mut self, origins: &mut ConfigOrigins) -> Result<toml::Table, LoadErrorCause> {
+        let (config_content, is_default) = self.read_config_or_default()?;
+        let origin = if is_default && !self.save_default {
+            ConfigOrigin::Default
+        } else {
+            ConfigOrigin::File(self.file.clone())
+        };
+        let mut loading = vec![canonical(&self.file)];
+        let file = self.file.clone();
+        let mut parsed_config = self.parse_layer(&config_content, &file, origin, &mut loading, origins)?;
+
+        if let Some(dir) = &self.drop_in_dir {
+            for drop_in in list_drop_in_files(dir).map_err(LoadErrorCause::DropInDir)? {
+                let layer = self.load_file(&drop_in, &mut loading, origins)?;
+                merge_override(&mut parsed_config, layer);
+            }
+        }
         if let Some(overrides) = self.overrides.take() {
+            origins.record(&overrides, &ConfigOrigin::Override);
             merge_override(&mut parsed_config, overrides);
         }
         Ok(parsed_config)
     }
 
-    fn read_config_or_default(&mut self) -> Result<String, LoadErrorCause> {
+    /// Loads an included or drop-in file.
+    fn load_file(
+        &self,
+        file: &Path,
+        loading: &mut Vec<PathBuf>,
+        origins: &mut ConfigOrigins,
+    ) -> Result<toml::Table, LoadErrorCause> {
+        let wrap = |e| LoadErrorCause::File(file.to_owned(), Box::new(e));
+        let canonical_file = canonical(file);
+        if loading.contains(&canonical_file) {
+            return Err(wrap(LoadErrorCause::IncludeCycle));
+        }
+        let content = std::fs::read_to_string(file).map_err(|e| wrap(LoadErrorCause::Read(e)))?;
+        loading.push(canonical_file);
+        let res = self.parse_layer(&content, file, ConfigOrigin::File(file.to_owned()), loading, origins);
+        loading.pop();
+        res.map_err(wrap)
+    }
+
+    /// Parses the content of a config file, and merges it on top of the files that it includes.
+    fn parse_layer(
+        &self,
+        content: &str,
+        file: &Path,
+        origin: ConfigOrigin,
+        loading: &mut Vec<PathBuf>,
+        origins: &mut ConfigOrigins,
+    ) -> Result<toml::Table, LoadErrorCause> {
+        let content = substitute_env(content)?;
+        let mut layer = toml::Table::from_str(&content)?;
+        if !self.include_files {
+            origins.record(&layer, &origin);
+            return Ok(layer);
+        }
+
+        let mut config = toml::Table::new();
+        let base_dir = file.parent().unwrap_or(Path::new(""));
+        for include in take_includes(&mut layer)? {
+            let included = self.load_file(&base_dir.join(include), loading, origins)?;
+            merge_override(&mut config, included);
+        }
+        origins.record(&layer, &origin);
+        merge_override(&mut config, layer);
+        Ok(config)
+    }
+
+    /// Reads the config file, or generates the default config.
+    ///
+    /// Returns the content of the config and `true` if it is the default one.
+    fn read_config_or_default(&mut self) -> Result<(String, bool), LoadErrorCause> {
         match std::fs::read_to_string(&self.file) {
-            Ok(s) => Ok(s),
+            Ok(s) => Ok((s, false)),
             Err(e) if e.kind() == io::ErrorKind::NotFound => {
                 // no config file, try the default
                 if let Some(default_provider) = self.default_provider.take() {
@@ -192,7 +323,7 @@ impl<'d> Loader<'d> {
                         std::fs::write(&self.file, &default_content).map_err(LoadErrorCause::DefaultWrite)?;
                     }
 
-                    Ok(default_content)
+                    Ok((default_content, true))
                 } else {
                     // no default
                     Err(LoadErrorCause::Read(e))
@@ -344,6 +475,143 @@ pub fn merge_override(original: &mut toml::Table, overrider: toml::Table) {
     }
 }
 
+/// Returns the default drop-in directory of a config file: `alumet-config.d` for `alumet-config.toml`.
+pub fn default_drop_in_dir(config_file: &Path) -> PathBuf {
+    config_file.with_extension("d")
+}
+
+/// Lists the `.toml` files of a drop-in directory, sorted by name.
+fn list_drop_in_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "toml") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Removes the `include` key from a config and returns the listed files.
+fn take_includes(config: &mut toml::Table) -> Result<Vec<String>, BadTypeError> {
+    match config.remove("include") {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(files)) => files
+            .into_iter()
+            .map(|f| match f {
+                toml::Value::String(f) => Ok(f),
+                bad => Err(BadTypeError::new(String::from("include"), "string", bad)),
+            })
+            .collect(),
+        Some(bad) => Err(BadTypeError::new(String::from("include"), "array", bad)),
+    }
+}
+
+/// Canonicalizes a path to detect include cycles, or returns it as is if that fails.
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_owned())
+}
+
+impl ConfigOrigins {
+    /// Returns the origin of a value.
+    ///
+    /// If the value is a table, returns the origin of the first value that it contains.
+    pub fn get(&self, path: &[String]) -> Option<&ConfigOrigin> {
+        self.0
+            .range(path.to_vec()..)
+            .next()
+            .filter(|(p, _)| p.starts_with(path))
+            .map(|(_, origin)| origin)
+    }
+
+    /// Sets the origin of every value contained in `layer`, which is merged on top of the previous layers.
+    fn record(&mut self, layer: &toml::Table, origin: &ConfigOrigin) {
+        self.record_at(&mut Vec::new(), layer, origin);
+    }
+
+    fn record_at(&mut self, path: &mut Vec<String>, layer: &toml::Table, origin: &ConfigOrigin) {
+        for (key, value) in layer {
+            path.push(key.to_owned());
+            match value {
+                toml::Value::Table(table) => {
+                    // the table is merged with the previous one, if any, but replaces a previous value
+                    self.0.remove(path);
+                    if table.is_empty() {
+                        self.0.insert(path.clone(), origin.clone());
+                    }
+                    self.record_at(path, table, origin);
+                }
+                _ => {
+                    // the value replaces the previous one, which could have been a table
+                    let nested: Vec<_> = self
+                        .0
+                        .range(path.clone()..)
+                        .map(|(p, _)| p.clone())
+                        .take_while(|p| p.starts_with(path))
+                        .collect();
+                    for p in nested {
+                        self.0.remove(&p);
+                    }
+                    self.0.insert(path.clone(), origin.clone());
+                }
+            }
+            path.pop();
+        }
+    }
+
+    /// Formats the config as TOML, with one line per value, followed by a comment that indicates its origin.
+    ///
+    /// The values are written with dotted keys, for instance `plugins.csv.output_path = "out.csv" # alumet-config.toml`.
+    pub fn annotate(&self, config: &toml::Table) -> String {
+        fn write_values(out: &mut String, path: &mut Vec<String>, table: &toml::Table, origins: &ConfigOrigins) {
+            for (key, value) in table {
+                path.push(key.to_owned());
+                match value {
+                    toml::Value::Table(t) if !t.is_empty() => write_values(out, path, t, origins),
+                    _ => {
+                        let dotted_key: Vec<String> = path.iter().map(|k| format_key(k)).collect();
+                        let origin = origins
+                            .get(path)
+                            .map(|o| o.to_string())
+                            .unwrap_or_else(|| String::from("unknown"));
+                        out.push_str(&format!("{} = {value} # {origin}\n", dotted_key.join(".")));
+                    }
+                }
+                path.pop();
+            }
+        }
+
+        fn format_key(key: &str) -> String {
+            let is_bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if is_bare {
+                key.to_owned()
+            } else {
+                toml::Value::String(key.to_owned()).to_string()
+            }
+        }
+
+        let mut out = String::new();
+        write_values(&mut out, &mut Vec::new(), config, self);
+        out
+    }
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::File(path) => write!(f, "{}", path.display()),
+            ConfigOrigin::Default => write!(f, "default config"),
+            ConfigOrigin::Override => write!(f, "override"),
+        }
+    }
+}
+
 /// For each plugin in `metadata`, extracts the corresponding config subsection and some
 /// standard settings.
 ///
@@ -485,8 +753,54 @@ pub fn generate_plugin_configs<'p, I: IntoIterator<Item = &'p PluginMetadata>>(
     Ok(table)
 }
 
+/// Generates the schema of a configuration file that contains the general options described by `general`,
+/// and a `plugins` table with the configuration of each plugin.
+///
+/// The schema of a plugin config is derived from the type of its default configuration,
+/// see [`schema::record_schema`]. If the plugin does not use [`serialize_config_with_schema`](crate::plugin::rust::serialize_config_with_schema),
+/// the schema is inferred from the default configuration.
+#[cfg(feature = "schema")]
+pub fn generate_config_schema<'p, I: IntoIterator<Item = &'p PluginMetadata>>(
+    mut general: Schema,
+    plugins: I,
+) -> Result<Schema, PluginSchemaError> {
+    let mut plugin_schemas = serde_json::Map::new();
+    for p in plugins {
+        let (default_config, schema) = schema::record_schema(|| (p.default_config)());
+        let default_config = default_config.map_err(|source| PluginSchemaError {
+            plugin_name: p.name.clone(),
+            source,
+        })?;
+        let mut schema = match (schema, default_config) {
+            (Some(mut schema), Some(config)) => {
+                schema::set_defaults(&mut schema, &config.0);
+                schema
+            }
+            (Some(schema), None) => schema,
+            (None, Some(config)) => schema::infer(&config.0),
+            (None, None) => json_schema!({ "type": "object" }),
+        };
+        // every plugin can be enabled or disabled
+        if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+            properties.insert(String::from("enabled"), json!({ "type": "boolean", "default": true }));
+        }
+        plugin_schemas.insert(p.name.clone(), schema.to_value());
+    }
+
+    let plugins = json!({ "type": "object", "properties": plugin_schemas });
+    if let Some(Value::Object(properties)) = general.get_mut("properties") {
+        properties.insert(String::from("plugins"), plugins);
+    } else {
+        general.insert(String::from("properties"), json!({ "plugins": plugins }));
+    }
+    Ok(general)
+}
+
 pub mod error {
-    use std::{io, path::PathBuf};
+    use std::{
+        io,
+        path::{Path, PathBuf},
+    };
     use thiserror::Error;
 
     /// [`Loader::load`](super::Loader::load) failed.
@@ -501,6 +815,20 @@ pub mod error {
         pub(super) kind: LoadErrorCause,
     }
 
+    impl LoadError {
+        /// Returns the file that could not be loaded: the config file, or one of the files that it includes,
+        /// or a drop-in file.
+        pub fn failed_file(&self) -> &Path {
+            let mut file = &self.config_file;
+            let mut cause = &self.kind;
+            while let LoadErrorCause::File(path, inner) = cause {
+                file = path;
+                cause = inner;
+            }
+            file
+        }
+    }
+
     /// What made the configuration loading fail?
     #[derive(Error, Debug)]
     pub(super) enum LoadErrorCause {
@@ -525,6 +853,22 @@ pub mod error {
         /// (after environment variable substitution).
         #[error("invalid TOML config")]
         InvalidToml(#[from] toml::de::Error),
+
+        /// The `include` key of the config file is not an array of strings.
+        #[error("invalid include")]
+        InvalidInclude(#[from] BadTypeError),
+
+        /// The files include each other.
+        #[error("include cycle detected")]
+        IncludeCycle,
+
+        /// The drop-in directory could not be read.
+        #[error("could not read the drop-in directory")]
+        DropInDir(#[source] io::Error),
+
+        /// An included or drop-in file could not be loaded.
+        #[error("could not load '{}'", .0.display())]
+        File(PathBuf, #[source] Box<LoadErrorCause>),
     }
 
     /// Environment variable substitution failed.
@@ -572,6 +916,17 @@ pub mod error {
         #[source]
         pub(super) source: anyhow::Error,
     }
+
+    /// A plugin failed to provide the schema of its configuration.
+    #[cfg(feature = "schema")]
+    #[derive(Error, Debug)]
+    #[error("plugin {plugin_name} failed to provide the schema of its configuration")]
+    pub struct PluginSchemaError {
+        pub plugin_name: String,
+
+        #[source]
+        pub(super) source: anyhow::Error,
+    }
 }
 
 #[cfg(test)]
@@ -716,3 +1071,128 @@ mod tests_substitute_env {
         assert_eq!(substitute_env(input), Err(InvalidSubstitutionError::WrongSyntax));
     }
 }
+
+#[cfg(test)]
+mod tests_includes {
+    use std::path::{Path, PathBuf};
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::{ConfigOrigin, Loader, default_drop_in_dir};
+
+    fn write(dir: &Path, file: &str, content: &str) -> PathBuf {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn path(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn includes_and_drop_ins() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let common = write(
+            dir,
+            "shared/common.toml",
+            indoc! {r#"
+                include = ["base.toml"]
+                a = "common"
+                [plugins.csv]
+                output_path = "common.csv"
+                force_flush = true
+            "#},
+        );
+        let base = write(dir, "shared/base.toml", "a = 'base'\nb = 'base'\n");
+        let main = write(
+            dir,
+            "alumet-config.toml",
+            indoc! {r#"
+                include = ["shared/common.toml"]
+                a = "main"
+                [plugins.csv]
+                output_path = "main.csv"
+            "#},
+        );
+        let drop_in_dir = default_drop_in_dir(&main);
+        assert_eq!(drop_in_dir, dir.join("alumet-config.d"));
+        let drop_in_20 = write(&drop_in_dir, "20-node.toml", "plugins.csv.output_path = 'node.csv'");
+        write(
+            &drop_in_dir,
+            "10-class.toml",
+            "plugins.csv.output_path = 'class.csv'\nc = 1",
+        );
+        write(&drop_in_dir, "README.md", "not a config file");
+
+        let (config, origins) = Loader::parse_file(&main)
+            .include_files(true)
+            .drop_in_dir(&drop_in_dir)
+            .with_override(toml::toml! { c = 2 })
+            .load_with_origins()
+            .unwrap();
+
+        let expected = toml::toml! {
+            a = "main"
+            b = "base"
+            c = 2
+            [plugins.csv]
+            output_path = "node.csv"
+            force_flush = true
+        };
+        assert_eq!(config, expected);
+        assert_eq!(origins.get(&path(&["a"])), Some(&ConfigOrigin::File(main.clone())));
+        assert_eq!(origins.get(&path(&["b"])), Some(&ConfigOrigin::File(base)));
+        assert_eq!(origins.get(&path(&["c"])), Some(&ConfigOrigin::Override));
+        assert_eq!(
+            origins.get(&path(&["plugins", "csv", "output_path"])),
+            Some(&ConfigOrigin::File(drop_in_20.clone()))
+        );
+        assert_eq!(
+            origins.get(&path(&["plugins", "csv", "force_flush"])),
+            Some(&ConfigOrigin::File(common.clone()))
+        );
+
+        let annotated = origins.annotate(&config);
+        let expected = format!(
+            "a = \"main\" # {}\nb = \"base\" # {}\nplugins.csv.output_path = \"node.csv\" # {}\nplugins.csv.force_flush = true # {}\nc = 2 # override\n",
+            main.display(),
+            dir.join("shared/base.toml").display(),
+            drop_in_20.display(),
+            common.display(),
+        );
+        assert_eq!(annotated, expected);
+        // the annotated config is valid TOML
+        assert_eq!(toml::from_str::<toml::Table>(&annotated).unwrap(), config);
+    }
+
+    #[test]
+    fn includes_disabled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let main = write(tmp.path(), "config.toml", "include = ['missing.toml']");
+        let config = Loader::parse_file(&main).load().unwrap();
+        assert_eq!(config, toml::toml! { include = ["missing.toml"] });
+    }
+
+    #[test]
+    fn include_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let main = write(dir, "config.toml", "include = ['a.toml']");
+        write(dir, "a.toml", "include = ['b.toml']");
+        write(dir, "b.toml", "include = ['a.toml']");
+        let err = anyhow::Error::from(Loader::parse_file(&main).include_files(true).load().unwrap_err());
+        assert!(format!("{err:#}").contains("include cycle"), "{err:#}");
+
+        let main = write(dir, "config.toml", "include = ['missing.toml']");
+        let err = anyhow::Error::from(Loader::parse_file(&main).include_files(true).load().unwrap_err());
+        assert!(format!("{err:#}").contains("missing.toml"), "{err:#}");
+
+        let main = write(dir, "config.toml", "include = 'a.toml'");
+        let err = anyhow::Error::from(Loader::parse_file(&main).include_files(true).load().unwrap_err());
+        assert!(format!("{err:#}").contains("expected array"), "{err:#}");
+    }
+}
@@ -2,13 +2,20 @@
 
 use std::{
     process::{Command, ExitStatus},
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
 
 use crate::{
-    pipeline::{MeasurementPipeline, control::request, naming::matching::SourceNamePattern},
+    measurement::{MeasurementBuffer, Timestamp},
+    pipeline::{
+        self, MeasurementPipeline, Transform,
+        control::request,
+        elements::transform::{TransformContext, TransformError},
+        naming::{PluginName, matching::SourceNamePattern, namespace::DuplicateNameError},
+    },
     plugin::event::EndConsumerMeasurement,
     plugin::event::StartConsumerMeasurement,
     resources::ResourceConsumer,
@@ -68,6 +75,198 @@ pub fn exec_process(
     Ok(exit_status)
 }
 
+/// Name of the attribute that [`RunTagger`] adds to the measurements.
+pub const RUN_ID_ATTRIBUTE: &str = "run_id";
+
+/// Time to wait after triggering a measurement at the beginning or at the end of a benchmark phase.
+///
+/// This gives the sources the time to perform the measurement, so that it is tagged with the right `run_id`:
+/// the measurement at the beginning of the phase is not tagged (it covers the time before the phase),
+/// and the measurement at the end is tagged with the phase.
+const PHASE_SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Settings of [`exec_benchmark`].
+#[derive(Debug, Clone)]
+pub struct BenchmarkSettings {
+    /// Number of measured runs.
+    pub runs: usize,
+    /// Number of warm-up runs, executed before the measured runs.
+    pub warmup_runs: usize,
+    /// Duration of the idle periods that are measured before and after the runs, if any.
+    pub idle: Option<Duration>,
+}
+
+/// Kind of benchmark phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseKind {
+    /// The program does not run, the machine is idle.
+    Idle,
+    /// The program runs, but the run is not taken into account in the statistics.
+    Warmup,
+    /// The program runs and the run is measured.
+    Run,
+}
+
+/// Outcome of a benchmark phase.
+#[derive(Debug, Clone)]
+pub struct PhaseOutcome {
+    /// Value of the `run_id` attribute of the measurements taken during this phase.
+    pub run_id: String,
+    pub kind: PhaseKind,
+    /// Exit status of the program, `None` for idle phases.
+    pub exit_status: Option<ExitStatus>,
+    /// Duration of the phase (wall time of the program).
+    pub duration: Duration,
+}
+
+/// Tags the measurements with the `run_id` of the benchmark phase during which they have been taken.
+///
+/// The tagger is a transform that must be added to the pipeline before it starts,
+/// with [`RunTagger::install`].
+#[derive(Clone, Default)]
+pub struct RunTagger {
+    phases: Phases,
+}
+
+/// Beginning of each phase, in chronological order.
+/// A phase lasts until the beginning of the next one. `None` marks the end of a phase.
+type Phases = Arc<Mutex<Vec<(Timestamp, Option<String>)>>>;
+
+struct RunTaggerTransform {
+    phases: Phases,
+}
+
+impl RunTagger {
+    /// Adds the tagger to the pipeline.
+    pub fn install(&self, pipeline: &mut pipeline::Builder) -> Result<(), DuplicateNameError> {
+        let phases = self.phases.clone();
+        pipeline.add_transform_builder(
+            PluginName(String::from("exec")),
+            "run-tagger",
+            Box::new(move |_| Ok(Box::new(RunTaggerTransform { phases }))),
+        )?;
+        Ok(())
+    }
+
+    /// Starts a new phase: the measurements taken from now on are tagged with `run_id`.
+    pub fn begin(&self, run_id: String) {
+        self.phases.lock().unwrap().push((Timestamp::now(), Some(run_id)));
+    }
+
+    /// Ends the current phase: the measurements taken from now on are not tagged.
+    pub fn end(&self) {
+        self.phases.lock().unwrap().push((Timestamp::now(), None));
+    }
+}
+
+impl Transform for RunTaggerTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        let phases = self.phases.lock().unwrap();
+        for m in measurements.iter_mut() {
+            let i = phases.partition_point(|(begin, _)| *begin <= m.timestamp);
+            if let Some((_, Some(run_id))) = i.checked_sub(1).map(|i| &phases[i]) {
+                m.add_attr(RUN_ID_ATTRIBUTE, run_id.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `program args` several times, then stops the measurement agent.
+/// Returns the outcome of every phase of the benchmark, in order.
+///
+/// The phases are: an optional idle period, the warm-up runs, the measured runs, and another optional idle period.
+/// The measurement sources are triggered at the beginning and at the end of each phase, and the measurements
+/// are tagged with the `run_id` of their phase by `tagger`, which must have been installed in the pipeline.
+///
+/// After the last phase, the pipeline must stop within `shutdown_timeout`, or an error is returned.
+pub fn exec_benchmark(
+    agent: RunningAgent,
+    program: String,
+    args: Vec<String>,
+    settings: &BenchmarkSettings,
+    tagger: &RunTagger,
+    shutdown_timeout: Duration,
+) -> Result<Vec<PhaseOutcome>, ExecError> {
+    let pipeline = &agent.pipeline;
+    let mut phases = Vec::with_capacity(settings.warmup_runs + settings.runs + 2);
+
+    let idle = |run_id: &str, duration: Duration| {
+        log::info!("Measuring the idle machine for {duration:?}.");
+        let (_, duration) = measure_phase(pipeline, tagger, run_id, || {
+            std::thread::sleep(duration);
+            Ok(())
+        })?;
+        Ok::<_, ExecError>(PhaseOutcome {
+            run_id: run_id.to_owned(),
+            kind: PhaseKind::Idle,
+            exit_status: None,
+            duration,
+        })
+    };
+    let run = |run_id: String, kind: PhaseKind| {
+        let (exit_status, duration) =
+            measure_phase(pipeline, tagger, &run_id, || exec_child(program.clone(), args.clone()))?;
+        log::info!("Run {run_id} exited with {exit_status} after {duration:?}.");
+        Ok::<_, ExecError>(PhaseOutcome {
+            run_id,
+            kind,
+            exit_status: Some(exit_status),
+            duration,
+        })
+    };
+
+    if let Some(duration) = settings.idle {
+        phases.push(idle("idle-before", duration)?);
+    }
+    for i in 1..=settings.warmup_runs {
+        phases.push(run(format!("warmup-{i}"), PhaseKind::Warmup)?);
+    }
+    for i in 1..=settings.runs {
+        phases.push(run(i.to_string(), PhaseKind::Run)?);
+    }
+    if let Some(duration) = settings.idle {
+        phases.push(idle("idle-after", duration)?);
+    }
+    log::info!("Benchmark finished, Alumet will now stop.");
+
+    log::info!("Publishing EndConsumerMeasurement event");
+    crate::plugin::event::end_consumer_measurement().publish(EndConsumerMeasurement);
+
+    log::debug!("Initiating shutdown.");
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(shutdown_timeout).map_err(ExecError::Shutdown)?;
+    Ok(phases)
+}
+
+/// Runs one phase of a benchmark, with a measurement before and after `f`.
+///
+/// The phase begins after the first measurement, so that the measurements tagged with `run_id`
+/// (e.g. the energy consumed since the previous measurement) do not include the time before the phase.
+///
+/// Returns the result of `f` and its duration.
+fn measure_phase<T>(
+    pipeline: &MeasurementPipeline,
+    tagger: &RunTagger,
+    run_id: &str,
+    f: impl FnOnce() -> Result<T, ExecError>,
+) -> Result<(T, Duration), ExecError> {
+    if let Err(e) = trigger_measurement_now(pipeline) {
+        log::error!("Could not trigger a measurement at the beginning of phase {run_id}: {e}");
+    }
+    std::thread::sleep(PHASE_SETTLE_DELAY);
+    tagger.begin(run_id.to_owned());
+    let start = Instant::now();
+    let res = f();
+    let duration = start.elapsed();
+    if let Err(e) = trigger_measurement_now(pipeline) {
+        log::error!("Could not trigger a measurement at the end of phase {run_id}: {e}");
+    }
+    std::thread::sleep(PHASE_SETTLE_DELAY);
+    tagger.end();
+    Ok((res?, duration))
+}
+
 /// Spawns a child process and waits for it to exit.
 fn exec_child(external_command: String, args: Vec<String>) -> Result<ExitStatus, ExecError> {
     // Spawn the process.
@@ -101,3 +300,58 @@ fn trigger_measurement_now(pipeline: &MeasurementPipeline) -> anyhow::Result<()>
         .block_on(send_task)
         .context("failed to send TriggerMessage")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{RawMetricId, registry::MetricRegistry},
+        pipeline::{Transform, elements::transform::TransformContext},
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::{RUN_ID_ATTRIBUTE, RunTaggerTransform};
+
+    #[test]
+    fn tag_runs() {
+        let t0 = Timestamp::now();
+        let at = |secs: u64| {
+            MeasurementPoint::new_untyped(
+                t0 + Duration::from_secs(secs),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::U64(1),
+            )
+        };
+        let phases = vec![
+            (t0 + Duration::from_secs(10), Some(String::from("1"))),
+            (t0 + Duration::from_secs(20), Some(String::from("2"))),
+            (t0 + Duration::from_secs(30), None),
+        ];
+        let mut transform = RunTaggerTransform {
+            phases: std::sync::Arc::new(std::sync::Mutex::new(phases)),
+        };
+        let mut buf = MeasurementBuffer::from(vec![at(5), at(10), at(15), at(25), at(30), at(35)]);
+        let metrics = MetricRegistry::new();
+        transform
+            .apply(&mut buf, &TransformContext { metrics: &metrics })
+            .unwrap();
+
+        let run_ids: Vec<Option<String>> = buf
+            .iter()
+            .map(|m| {
+                m.attributes()
+                    .find(|(k, _)| *k == RUN_ID_ATTRIBUTE)
+                    .map(|(_, v)| match v {
+                        AttributeValue::String(s) => s.clone(),
+                        v => panic!("unexpected attribute value {v:?}"),
+                    })
+            })
+            .collect();
+        let expected = [None, Some("1"), Some("1"), Some("2"), None, None];
+        assert_eq!(run_ids, expected.map(|id| id.map(String::from)));
+    }
+}
@@ -65,11 +65,16 @@
 //!
 //! Use the [`config`] module to manage a TOML configuration file that contains both
 //! the general agent options and the configuration of each plugin.
+//! The [`check`] module helps to report the problems of a configuration,
+//! and the `schema` module (enabled by the `schema` feature) describes its structure as a JSON Schema.
 
 pub mod builder;
+pub mod check;
 pub mod config;
 pub mod exec;
 pub mod plugin;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod watch;
 
 pub use builder::{Builder, RunningAgent};
@@ -0,0 +1,308 @@
+//! Schemas of configurations, as [JSON Schemas](https://json-schema.org/).
+//!
+//! The schema of a configuration type is derived from its definition with [`JsonSchema`],
+//! which follows the `serde` attributes and turns the doc comments into descriptions.
+//! Durations and other values that are (de)serialized through another type must say so with
+//! the `schemars(with = ...)` attribute:
+//!
+//! ```
+//! use std::time::Duration;
+//! use alumet::agent::schema::{self, JsonSchema};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Default, Serialize, Deserialize, JsonSchema)]
+//! struct Config {
+//!     /// Time between two measurements.
+//!     #[serde(with = "humantime_serde")]
+//!     #[schemars(with = "String")]
+//!     poll_interval: Duration,
+//!     #[serde(default)]
+//!     verbose: bool,
+//! }
+//!
+//! let schema = schema::schema_of::<Config>();
+//! assert_eq!(schema.pointer("/properties/poll_interval/type"), Some(&"string".into()));
+//! assert_eq!(schema.pointer("/required/0"), Some(&"poll_interval".into()));
+//! ```
+//!
+//! Plugins do not need to provide their schema: [`serialize_config_with_schema`](crate::plugin::rust::serialize_config_with_schema)
+//! records the type of the default configuration, see [`record_schema`].
+
+use std::cell::RefCell;
+
+use schemars::{SchemaGenerator, generate::SchemaSettings, json_schema, transform::RecursiveTransform};
+use serde_json::{Map, Value, json};
+
+pub use schemars::{JsonSchema, Schema};
+
+thread_local! {
+    static RECORDER: RefCell<Option<Option<Schema>>> = const { RefCell::new(None) };
+}
+
+/// Returns the schema of the configuration type `T`.
+///
+/// The schemas of the nested types are inlined, so that the schema can be embedded in another one.
+pub fn schema_of<T: JsonSchema>() -> Schema {
+    let settings = SchemaSettings::draft2020_12()
+        .for_deserialize()
+        .with(|s| {
+            s.inline_subschemas = true;
+            s.meta_schema = None;
+        })
+        .with_transform(RecursiveTransform(remove_null));
+    let mut schema = SchemaGenerator::new(settings).into_root_schema_for::<T>();
+    schema.remove("title");
+    schema
+}
+
+/// Infers a schema from a configuration, without knowing its type.
+///
+/// The keys of the tables are not required, and no other key is allowed.
+pub fn infer(config: &toml::Table) -> Schema {
+    let properties: Map<String, Value> = config
+        .iter()
+        .map(|(key, value)| {
+            let mut schema = infer_value(value);
+            schema["default"] = to_json(value);
+            (key.to_owned(), schema)
+        })
+        .collect();
+    json_schema!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// Adds the values of the default configuration to the schema, as the default values of the keys.
+///
+/// The keys that already have a default value are left untouched.
+pub fn set_defaults(schema: &mut Schema, config: &toml::Table) {
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        set_defaults_of(properties, config);
+    }
+}
+
+fn set_defaults_of(properties: &mut Map<String, Value>, config: &toml::Table) {
+    for (key, value) in config {
+        let Some(Value::Object(property)) = properties.get_mut(key) else {
+            continue;
+        };
+        if let (toml::Value::Table(table), Some(Value::Object(nested))) = (value, property.get_mut("properties")) {
+            set_defaults_of(nested, table);
+        }
+        property.entry("default").or_insert_with(|| to_json(value));
+    }
+}
+
+/// Runs `f` and returns the schema of the last configuration type that it has serialized
+/// with [`serialize_config_with_schema`](crate::plugin::rust::serialize_config_with_schema) on the current thread.
+///
+/// This gives the schema of a plugin configuration, from its default configuration.
+pub fn record_schema<R>(f: impl FnOnce() -> R) -> (R, Option<Schema>) {
+    RECORDER.set(Some(None));
+    let res = f();
+    let schema = RECORDER.take().flatten();
+    (res, schema)
+}
+
+/// Records the schema of `T`, if [`record_schema`] is running on the current thread.
+pub(crate) fn record<T: JsonSchema>() {
+    let recording = RECORDER.with_borrow(|r| r.is_some());
+    if recording {
+        let schema = schema_of::<T>();
+        RECORDER.set(Some(Some(schema)));
+    }
+}
+
+fn infer_value(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(_) | toml::Value::Datetime(_) => json!({ "type": "string" }),
+        toml::Value::Integer(_) => json!({ "type": "integer" }),
+        toml::Value::Float(_) => json!({ "type": "number" }),
+        toml::Value::Boolean(_) => json!({ "type": "boolean" }),
+        toml::Value::Array(values) => {
+            let mut items = values.iter().map(infer_value);
+            match items.next() {
+                // the schema of the items is known if they all have the same
+                Some(first) if items.all(|s| s == first) => json!({ "type": "array", "items": first }),
+                _ => json!({ "type": "array" }),
+            }
+        }
+        toml::Value::Table(table) => infer(table).to_value(),
+    }
+}
+
+fn to_json(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::from(s.as_str()),
+        toml::Value::Integer(i) => Value::from(*i),
+        toml::Value::Float(f) => Value::from(*f),
+        toml::Value::Boolean(b) => Value::from(*b),
+        toml::Value::Datetime(d) => Value::from(d.to_string()),
+        toml::Value::Array(values) => values.iter().map(to_json).collect(),
+        toml::Value::Table(table) => table.iter().map(|(k, v)| (k.to_owned(), to_json(v))).collect(),
+    }
+}
+
+/// Removes the `null` type and values, which do not exist in TOML: an optional key is omitted instead.
+fn remove_null(schema: &mut Schema) {
+    let Some(obj) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Array(types)) = obj.get_mut("type") {
+        types.retain(|t| t != "null");
+        if let [single] = &types[..] {
+            let single = single.clone();
+            obj.insert(String::from("type"), single);
+        }
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = obj.get_mut(key) {
+            schemas.retain(|s| s != &json!({ "type": "null" }));
+            if let [Value::Object(single)] = &schemas[..] {
+                let single = single.clone();
+                obj.remove(key);
+                for (k, v) in single {
+                    obj.entry(k).or_insert(v);
+                }
+            }
+        }
+    }
+    for key in ["const", "default"] {
+        if obj.get(key) == Some(&Value::Null) {
+            obj.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use pretty_assertions::assert_eq;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    use super::{infer, record, record_schema, schema_of, set_defaults};
+
+    /// Configuration of the test.
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Config {
+        /// Time between two measurements.
+        #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
+        poll_interval: Duration,
+        #[serde(default)]
+        verbose: bool,
+        #[serde(with = "humantime_serde", default)]
+        #[schemars(with = "Option<String>")]
+        timeout: Option<Duration>,
+        groups: Vec<Group>,
+        mode: Mode,
+        labels: BTreeMap<String, String>,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Group {
+        name: String,
+        #[serde(default)]
+        weight: Option<f64>,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum Mode {
+        Fast,
+        Precise,
+    }
+
+    #[test]
+    fn json_schema() {
+        let schema = schema_of::<Config>();
+        let expected = serde_json::json!({
+            "description": "Configuration of the test.",
+            "type": "object",
+            "properties": {
+                "poll_interval": { "description": "Time between two measurements.", "type": "string" },
+                "verbose": { "type": "boolean", "default": false },
+                "timeout": { "type": "string" },
+                "groups": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "weight": { "type": "number", "format": "double" },
+                        },
+                        "required": ["name"],
+                        "additionalProperties": false,
+                    },
+                },
+                "mode": { "type": "string", "enum": ["fast", "precise"] },
+                "labels": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+            },
+            "required": ["poll_interval", "groups", "mode", "labels"],
+            "additionalProperties": false,
+        });
+        assert_eq!(schema.to_value(), expected);
+    }
+
+    #[test]
+    fn defaults() {
+        let mut schema = schema_of::<Config>();
+        let config = toml::toml! {
+            poll_interval = "1s"
+            verbose = true
+            mode = "fast"
+        };
+        set_defaults(&mut schema, &config);
+        assert_eq!(schema.pointer("/properties/poll_interval/default"), Some(&"1s".into()));
+        assert_eq!(schema.pointer("/properties/mode/default"), Some(&"fast".into()));
+        // the default value of the type is kept
+        assert_eq!(schema.pointer("/properties/verbose/default"), Some(&false.into()));
+    }
+
+    #[test]
+    fn infer_schema() {
+        let config = toml::toml! {
+            path = "out.csv"
+            sizes = [1, 2]
+            [nested]
+            flag = true
+        };
+        let schema = infer(&config);
+        let expected = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "default": "out.csv" },
+                "sizes": { "type": "array", "items": { "type": "integer" }, "default": [1, 2] },
+                "nested": {
+                    "type": "object",
+                    "properties": { "flag": { "type": "boolean", "default": true } },
+                    "additionalProperties": false,
+                    "default": { "flag": true },
+                },
+            },
+            "additionalProperties": false,
+        });
+        assert_eq!(schema.to_value(), expected);
+    }
+
+    #[test]
+    fn record_config_schema() {
+        let (_, schema) = record_schema(record::<Group>);
+        let schema = schema.expect("the schema should be recorded");
+        assert_eq!(schema.pointer("/required/0"), Some(&"name".into()));
+
+        // nothing is recorded outside of record_schema
+        record::<Group>();
+        let (_, schema) = record_schema(|| ());
+        assert!(schema.is_none());
+    }
+}
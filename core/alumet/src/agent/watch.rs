@@ -1,6 +1,11 @@
-//! Watch processes through it's pid
+//! Watch existing processes until they exit.
 use anyhow::Context;
-use std::{fs, io, path::PathBuf, ptr, time::Duration};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    time::Duration,
+};
 use thiserror::Error;
 
 use crate::{
@@ -11,7 +16,7 @@ use crate::{
 
 use super::{RunningAgent, builder::ShutdownError};
 
-/// Error that can occur in [`watch_process`].
+/// Error that can occur in [`watch_process`] and [`watch_processes`].
 #[derive(Error, Debug)]
 pub enum WatchError {
     /// The process could not be spawned.
@@ -23,6 +28,12 @@ pub enum WatchError {
     /// The process has spawned but waiting for it has failed.
     #[error("failed to wait for pid {0}")]
     ProcessWait(i32, #[source] std::io::Error),
+    /// The running processes could not be listed.
+    #[error("failed to list the running processes")]
+    ProcessList(#[source] std::io::Error),
+    /// No running process matches the targets.
+    #[error("no process to watch: {0}")]
+    NoProcess(String),
     /// An error occurred while waiting for the agent to shut down.
     #[error("error in shutdown")]
     Shutdown(#[source] ShutdownError),
@@ -30,29 +41,71 @@ pub enum WatchError {
 
 const TRIGGER_TIMEOUT: Duration = Duration::from_secs(1);
 
+/// Interval between two checks of the watched processes.
+///
+/// A child process that starts and exits between two checks is not detected.
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The processes to watch.
+#[derive(Debug, Clone, Default)]
+pub struct WatchTargets {
+    /// Processes identified by their PID.
+    pub pids: Vec<u32>,
+    /// Processes whose name matches this regular expression.
+    ///
+    /// The name of a process is the one that appears in `/proc/<pid>/comm`, truncated to 15 characters.
+    /// The expression is only applied to the processes that run when the watch begins.
+    pub name: Option<Regex>,
+    /// If `true`, the descendants of the watched processes are also watched,
+    /// including the ones that are created during the watch.
+    pub children: bool,
+}
+
 /// Watch process that runs identified by it's pid until it's end.
 ///
 /// The measurement sources are triggered before the process spawns and after it exits.
 ///
 /// After the process exits, the pipeline must stop within `shutdown_timeout`, or an error is returned.
 pub fn watch_process(agent: RunningAgent, pid: u32, shutdown_timeout: Duration) -> Result<(), WatchError> {
-    // Check if we can convert the pid to i32, from u32 because we don't want a negative value
-    let pid_i32 = if pid <= i32::MAX as u32 {
-        pid as i32
-    } else {
-        return Err(WatchError::PidValue(String::from("Value exceeds i32::MAX")));
+    let targets = WatchTargets {
+        pids: vec![pid],
+        ..Default::default()
     };
+    watch_processes(agent, &targets, shutdown_timeout)
+}
+
+/// Watches a set of processes until they have all exited.
+///
+/// A [`StartConsumerMeasurement`] event is published for each watched process, including the
+/// descendants that are discovered during the watch if [`WatchTargets::children`] is set.
+/// The measurement sources are triggered before the watch begins and after its end.
+///
+/// After the last process exits, the pipeline must stop within `shutdown_timeout`, or an error is returned.
+pub fn watch_processes(
+    agent: RunningAgent,
+    targets: &WatchTargets,
+    shutdown_timeout: Duration,
+) -> Result<(), WatchError> {
+    let mut watched = WatchedProcesses::new(targets)?;
 
     if let Err(e) = trigger_poll_now(&agent.pipeline) {
-        log::error!("Could not trigger a first time poll before the child spawn: {e}");
+        log::error!("Could not trigger a first time poll before the watch: {e}");
     }
-    log::info!("Watch process spawned with pid {pid_i32}.");
-    crate::plugin::event::start_consumer_measurement()
-        .publish(StartConsumerMeasurement(vec![ResourceConsumer::Process { pid }]));
+    watched.refresh(targets.children);
+    let pids = watched.pids();
+    log::info!("Watching {} process(es): {pids:?}", pids.len());
+    publish_start(pids);
 
-    // Spawn the process and wait for it to exit.
-    wait_child(pid_i32)?;
-    log::info!("Watched process exited, Alumet will now stop.");
+    // Wait for the processes to exit.
+    while !watched.is_empty() {
+        std::thread::sleep(SCAN_INTERVAL);
+        let new_pids = watched.refresh(targets.children);
+        if !new_pids.is_empty() {
+            log::info!("Watching {} new child process(es): {new_pids:?}", new_pids.len());
+            publish_start(new_pids);
+        }
+    }
+    log::info!("Watched processes exited, Alumet will now stop.");
 
     // One last measurement.
     if let Err(e) = trigger_poll_now(&agent.pipeline) {
@@ -68,65 +121,151 @@ pub fn watch_process(agent: RunningAgent, pid: u32, shutdown_timeout: Duration)
     agent.wait_for_shutdown(shutdown_timeout).map_err(WatchError::Shutdown)
 }
 
-/// Spawns a child process and waits for it to exit.
-fn wait_child(pid: i32) -> Result<(), WatchError> {
-    match unsafe { nc::pidfd_open(pid, 0) } {
-        Ok(pidfd) => wait_child_syscall(pid, pidfd),
-        Err(e) => {
-            log::warn!(
-                "PIDFD_OPEN returned an error: {}, syscall may not be supported on this system: - use of another way",
-                e
-            );
-            wait_child_sysfs(pid)
-        }
-    }
+fn publish_start(pids: Vec<u32>) {
+    let consumers = pids.into_iter().map(|pid| ResourceConsumer::Process { pid }).collect();
+    crate::plugin::event::start_consumer_measurement().publish(StartConsumerMeasurement(consumers));
+}
+
+/// Information about a process, read from `/proc/<pid>/stat`.
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessStat {
+    ppid: u32,
+    /// Start time of the process, which allows to detect the reuse of PIDs.
+    start_time: u64,
+    zombie: bool,
 }
 
-// Wait for process exit using sysfs file
-fn wait_child_sysfs(pid: i32) -> Result<(), WatchError> {
-    let path = PathBuf::from(format!("/proc/{pid}/"));
-    loop {
-        if !fs::metadata(&path).is_ok() {
-            break;
+/// The processes that are being watched, with their start time.
+#[derive(Debug, Default)]
+struct WatchedProcesses(HashMap<u32, u64>);
+
+impl WatchedProcesses {
+    /// Finds the processes that match the targets (except for the descendants).
+    fn new(targets: &WatchTargets) -> Result<Self, WatchError> {
+        let mut watched = HashMap::new();
+        for &pid in &targets.pids {
+            let pid_i32 =
+                i32::try_from(pid).map_err(|_| WatchError::PidValue(String::from("Value exceeds i32::MAX")))?;
+            match read_stat(pid) {
+                Ok(stat) if !stat.zombie => watched.insert(pid, stat.start_time),
+                Ok(_) => return Err(WatchError::NoProcess(format!("process {pid} has already exited"))),
+                Err(e) => return Err(WatchError::ProcessWait(pid_i32, e)),
+            };
         }
-        // Wait 5s to avoid too much load
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        if let Some(name) = &targets.name {
+            let myself = std::process::id();
+            let mut found = false;
+            for pid in list_pids().map_err(WatchError::ProcessList)? {
+                if pid != myself
+                    && process_matches(pid, name)
+                    && let Ok(stat) = read_stat(pid)
+                    && !stat.zombie
+                {
+                    watched.insert(pid, stat.start_time);
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(WatchError::NoProcess(format!("no process matches '{name}'")));
+            }
+        }
+        if watched.is_empty() {
+            return Err(WatchError::NoProcess(String::from("no target has been specified")));
+        }
+        Ok(Self(watched))
     }
-    Ok(())
-}
 
-// Wait for process exit using a syscall
-fn wait_child_syscall(pid: i32, pidfd: i32) -> Result<(), WatchError> {
-    loop {
-        // Attempt to read from the file descriptor
-        let mut timeout = libc::timeval {
-            tv_sec: Duration::from_secs(5).as_secs() as i64,
-            tv_usec: 0,
-        };
-        let mut read_fds = unsafe { std::mem::zeroed::<libc::fd_set>() }; // Initialize fd_set
-        unsafe { libc::FD_SET(pidfd, &mut read_fds) }; // Add the pidfd to the read set
-        let result = unsafe { libc::select(pidfd + 1, &mut read_fds, ptr::null_mut(), ptr::null_mut(), &mut timeout) };
-
-        if result < 0 {
-            // An error occurred
-            log::error!("Error occurred in select: {}", io::Error::last_os_error());
-            break;
-        } else if result == 0 {
-            // Timeout occurred - It means the process is still running
-            continue;
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn pids(&self) -> Vec<u32> {
+        let mut pids: Vec<u32> = self.0.keys().copied().collect();
+        pids.sort_unstable();
+        pids
+    }
+
+    /// Removes the processes that have exited and, if `children` is true, adds the new descendants.
+    ///
+    /// Returns the PIDs of the processes that have been added.
+    fn refresh(&mut self, children: bool) -> Vec<u32> {
+        let mut added = Vec::new();
+        if children {
+            let processes: HashMap<u32, ProcessStat> = list_pids()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|pid| read_stat(pid).ok().map(|stat| (pid, stat)))
+                .collect();
+            added = self.add_descendants(&processes);
+            self.0.retain(|pid, start_time| {
+                processes
+                    .get(pid)
+                    .is_some_and(|stat| !stat.zombie && stat.start_time == *start_time)
+            });
         } else {
-            // At least one file descriptor is ready
-            if unsafe { libc::FD_ISSET(pidfd, &read_fds) } {
-                // The process has terminated
-                log::info!("Process {} has terminated", pid);
+            self.0.retain(|pid, start_time| {
+                read_stat(*pid).is_ok_and(|stat| !stat.zombie && stat.start_time == *start_time)
+            });
+        }
+        added
+    }
+
+    /// Adds the descendants of the watched processes, and returns their PIDs.
+    fn add_descendants(&mut self, processes: &HashMap<u32, ProcessStat>) -> Vec<u32> {
+        let mut added = Vec::new();
+        let mut parents: HashSet<u32> = self.0.keys().copied().collect();
+        loop {
+            let new_children: Vec<(u32, u64)> = processes
+                .iter()
+                .filter(|(pid, stat)| !stat.zombie && parents.contains(&stat.ppid) && !self.0.contains_key(*pid))
+                .map(|(pid, stat)| (*pid, stat.start_time))
+                .collect();
+            if new_children.is_empty() {
                 break;
             }
+            parents.clear();
+            for (pid, start_time) in new_children {
+                self.0.insert(pid, start_time);
+                parents.insert(pid);
+                added.push(pid);
+            }
         }
-        // Reset the read file descriptor set for the next select call
-        unsafe { libc::FD_SET(pidfd, &mut read_fds) };
+        added.sort_unstable();
+        added
     }
-    unsafe { libc::close(pidfd) };
-    Ok(())
+}
+
+/// Lists the PIDs of the running processes.
+fn list_pids() -> io::Result<Vec<u32>> {
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        if let Some(pid) = entry?.file_name().to_str().and_then(|name| name.parse().ok()) {
+            pids.push(pid);
+        }
+    }
+    Ok(pids)
+}
+
+fn read_stat(pid: u32) -> io::Result<ProcessStat> {
+    let content = fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    parse_stat(&content).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid /proc/<pid>/stat"))
+}
+
+/// Parses the content of `/proc/<pid>/stat`.
+fn parse_stat(content: &str) -> Option<ProcessStat> {
+    // The name of the process is between parentheses and can contain spaces or parentheses.
+    let (_, fields) = content.rsplit_once(')')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    Some(ProcessStat {
+        zombie: *fields.first()? == "Z",
+        ppid: fields.get(1)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+/// Checks whether the name of the process matches the regex.
+fn process_matches(pid: u32, name: &Regex) -> bool {
+    fs::read_to_string(format!("/proc/{pid}/comm")).is_ok_and(|comm| name.is_match(comm.trim_end()))
 }
 
 // Triggers one poll (on all sources that support manual trigger).
@@ -141,3 +280,70 @@ fn trigger_poll_now(pipeline: &MeasurementPipeline) -> anyhow::Result<()> {
         .block_on(send_task)
         .context("failed to send TriggerMessage")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process::Command,
+        time::{Duration, Instant},
+    };
+
+    use super::{ProcessStat, WatchTargets, WatchedProcesses, parse_stat};
+
+    #[test]
+    fn parse_proc_stat() {
+        let stat = "1234 (my (weird) name) S 1 1234 1234 0 -1 4194560 120 0 0 0 1 2 0 0 20 0 1 0 987654 8192 200";
+        assert_eq!(
+            parse_stat(stat),
+            Some(ProcessStat {
+                ppid: 1,
+                start_time: 987654,
+                zombie: false
+            })
+        );
+        let zombie = "42 (defunct) Z 41 0 0 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 123 0 0";
+        assert!(parse_stat(zombie).unwrap().zombie);
+        assert_eq!(parse_stat("42 (truncated) S"), None);
+    }
+
+    #[test]
+    fn watch_tree() {
+        let mut parent = Command::new("sh").args(["-c", "sleep 2 & wait"]).spawn().unwrap();
+        let parent_pid = parent.id();
+
+        let targets = WatchTargets {
+            pids: vec![parent_pid],
+            children: true,
+            ..Default::default()
+        };
+        let mut watched = WatchedProcesses::new(&targets).unwrap();
+
+        // the shell forks at some point: wait for its child to be detected
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut children = watched.refresh(true);
+        while children.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            children = watched.refresh(true);
+        }
+        assert_eq!(children.len(), 1, "the child 'sleep' should be detected");
+        assert_eq!(watched.pids().len(), 2);
+
+        parent.wait().unwrap();
+        watched.refresh(true);
+        assert!(
+            watched.is_empty(),
+            "all the processes have exited: {:?}",
+            watched.pids()
+        );
+    }
+
+    #[test]
+    fn no_process() {
+        let targets = WatchTargets {
+            name: Some(regex::Regex::new("^this process does not exist$").unwrap()),
+            ..Default::default()
+        };
+        WatchedProcesses::new(&targets).expect_err("no process should match");
+        WatchedProcesses::new(&WatchTargets::default()).expect_err("there is no target");
+    }
+}
@@ -140,6 +140,39 @@ impl MeasurementPoint {
         self.attributes.push((key.into(), value.into()));
     }
 
+    /// Removes the attributes with the given key from this measurement point.
+    ///
+    /// Returns the value of the removed attribute, if any.
+    pub fn remove_attr(&mut self, key: &str) -> Option<AttributeValue> {
+        let mut removed = None;
+        self.attributes.retain(|(k, v)| {
+            if k == key {
+                removed = Some(v.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// Renames the attributes with the given key.
+    /// If an attribute with the new key already exists, it is replaced.
+    ///
+    /// Returns `true` if at least one attribute has been renamed.
+    pub fn rename_attr<K: Into<Cow<'static, str>>>(&mut self, key: &str, new_key: K) -> bool {
+        let new_key = new_key.into();
+        if key != new_key && self.attributes.iter().any(|(k, _)| k == key) {
+            self.attributes.retain(|(k, _)| *k != new_key);
+        }
+        let mut renamed = false;
+        for (k, _) in self.attributes.iter_mut().filter(|(k, _)| k == key) {
+            *k = new_key.clone();
+            renamed = true;
+        }
+        renamed
+    }
+
     /// Sets an attribute on this measurement point, and returns self to allow for method chaining.
     /// If an attribute with the same key already exists, its value is replaced.
     pub fn with_attr<K: Into<Cow<'static, str>>, V: Into<AttributeValue>>(mut self, key: K, value: V) -> Self {
@@ -614,5 +647,46 @@ mod tests {
             assert_ne!(a, c);
             assert_eq!(c, c_different_order);
         }
+
+        #[test]
+        fn remove_and_rename_attr() {
+            let mut a = MeasurementPoint::new_untyped(
+                UNIX_EPOCH.into(),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::U64(123),
+            )
+            .with_attr("key", "value")
+            .with_attr("other", 123);
+
+            assert!(a.rename_attr("key", "renamed"));
+            assert!(!a.rename_attr("missing", "renamed"));
+            assert_eq!(a.remove_attr("other"), Some(AttributeValue::U64(123)));
+            assert_eq!(a.remove_attr("other"), None);
+            assert_eq!(
+                a.attributes().collect::<Vec<_>>(),
+                vec![("renamed", &AttributeValue::Str("value"))]
+            );
+        }
+
+        #[test]
+        fn rename_attr_replaces_existing_key() {
+            let mut a = MeasurementPoint::new_untyped(
+                UNIX_EPOCH.into(),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::U64(123),
+            )
+            .with_attr("key", "value")
+            .with_attr("renamed", 123);
+
+            assert!(a.rename_attr("key", "renamed"));
+            assert_eq!(
+                a.attributes().collect::<Vec<_>>(),
+                vec![("renamed", &AttributeValue::Str("value"))]
+            );
+        }
     }
 }
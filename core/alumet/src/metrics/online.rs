@@ -33,7 +33,15 @@ pub enum ControlMessage {
         on_duplicate: DuplicateReaction,
         reply_to: Option<oneshot::Sender<Vec<Result<RawMetricId, MetricCreationError>>>>,
     },
-    /// Adds a new listener that will be notified on new metric registration.
+    /// Retires some metrics, which are removed from the registry.
+    ///
+    /// The reply contains the metrics that have actually been retired.
+    /// Unknown ids are ignored.
+    RetireMetrics {
+        metrics: Vec<RawMetricId>,
+        reply_to: Option<oneshot::Sender<Vec<(RawMetricId, Metric)>>>,
+    },
+    /// Adds a new listener that will be notified on new metric registration and retirement.
     Subscribe(ListenerName, Box<dyn listener::MetricListenerBuilder + Send>),
 }
 
@@ -46,9 +54,34 @@ pub(crate) struct MetricRegistryControl {
 pub mod listener {
     use crate::metrics::def::{Metric, RawMetricId};
 
-    /// A callback that gets notified of new metrics.
-    pub trait MetricListener: FnMut(Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> + Send {}
-    impl<F> MetricListener for F where F: FnMut(Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> + Send {}
+    /// A callback that gets notified of new metrics, and of retired metrics.
+    ///
+    /// Any closure `FnMut(Vec<(RawMetricId, Metric)>) -> anyhow::Result<()>` is a `MetricListener`
+    /// that ignores the retired metrics.
+    pub trait MetricListener: Send {
+        /// Called when new metrics are registered.
+        fn on_metrics_registered(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()>;
+
+        /// Called when some metrics are retired, i.e. removed from the registry.
+        ///
+        /// Implement this method to drop the state associated with these metrics.
+        ///
+        /// # Default implementation
+        /// The default implementation does nothing.
+        #[allow(unused_variables)]
+        fn on_metrics_retired(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<F> MetricListener for F
+    where
+        F: FnMut(Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> + Send,
+    {
+        fn on_metrics_registered(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+            self(metrics)
+        }
+    }
 
     pub(super) struct BuildContext<'a> {
         pub(super) rt: &'a tokio::runtime::Handle,
@@ -87,7 +120,7 @@ pub mod listener {
 impl MetricRegistryControl {
     /// Creates a new `MetricRegistryControl` and registers some listeners.
     ///
-    /// The listeners will be notified of the metric registrations that occur after
+    /// The listeners will be notified of the metric registrations (and retirements) that occur after
     /// [`start`](Self::start) is called. They do _not_ get notified of the metrics
     /// that are initially present in the registry.
     pub fn new(registry: MetricRegistry) -> Self {
@@ -134,13 +167,26 @@ impl MetricRegistryControl {
     async fn handle_message(&mut self, msg: ControlMessage) {
         fn call_listener(name: &ListenerName, listener: &mut dyn MetricListener, metrics: Vec<(RawMetricId, Metric)>) {
             let n = metrics.len();
-            if let Err(e) = listener(metrics) {
+            if let Err(e) = listener.on_metrics_registered(metrics) {
                 let plugin = &name.plugin;
                 let listener = &name.name;
                 log::error!("Error in metric listener {plugin}/{listener} (called on {n} metrics): {e}",);
             }
         }
 
+        fn call_listener_retired(
+            name: &ListenerName,
+            listener: &mut dyn MetricListener,
+            metrics: Vec<(RawMetricId, Metric)>,
+        ) {
+            let n = metrics.len();
+            if let Err(e) = listener.on_metrics_retired(metrics) {
+                let plugin = &name.plugin;
+                let listener = &name.name;
+                log::error!("Error in metric listener {plugin}/{listener} (called on {n} retired metrics): {e}",);
+            }
+        }
+
         match msg {
             ControlMessage::RegisterMetrics {
                 metrics,
@@ -183,7 +229,7 @@ impl MetricRegistryControl {
 
                 // call listeners with the new metric definitions
                 for (name, listener) in &mut self.listeners {
-                    call_listener(name, listener, new_metrics.clone());
+                    call_listener(name, listener.as_mut(), new_metrics.clone());
                 }
 
                 // send reply
@@ -193,6 +239,30 @@ impl MetricRegistryControl {
                     }
                 }
             }
+            ControlMessage::RetireMetrics { metrics, reply_to } => {
+                // Same RCU scheme as above.
+                let mut copy = (*self.registry.read().await).clone();
+                let retired: Vec<(RawMetricId, Metric)> = metrics
+                    .into_iter()
+                    .filter_map(|id| copy.retire(id).map(|m| (id, m)))
+                    .collect();
+                *self.registry.write().await = copy;
+                log::debug!("{} metrics have been retired", retired.len());
+
+                // call listeners with the retired metrics
+                if !retired.is_empty() {
+                    for (name, listener) in &mut self.listeners {
+                        call_listener_retired(name, listener.as_mut(), retired.clone());
+                    }
+                }
+
+                // send reply
+                if let Some(tx) = reply_to {
+                    if let Err(e) = tx.send(retired) {
+                        log::error!("Failed to send reply to metric retirement message: {e:?}");
+                    }
+                }
+            }
             ControlMessage::Subscribe(name, listener) => {
                 let rt = tokio::runtime::Handle::current();
                 // TODO avoid creating a full namespace hierarchy for this
@@ -272,6 +342,7 @@ impl Debug for SendError {
             Self::ChannelFull(msg) => {
                 let msg_short: &dyn Debug = &match msg {
                     ControlMessage::RegisterMetrics { .. } => "ControlMessage::RegisterMetrics(...)",
+                    ControlMessage::RetireMetrics { .. } => "ControlMessage::RetireMetrics(...)",
                     ControlMessage::Subscribe(_, _) => "ControlMessage::Subscribe(...)",
                 };
                 f.debug_tuple("ChannelFull").field(msg_short).finish()
@@ -364,6 +435,30 @@ impl MetricSender {
         Ok(result)
     }
 
+    /// Retires some metrics: they are removed from the registry and the metric listeners are notified.
+    ///
+    /// Returns the metrics that have actually been retired (unknown ids are ignored).
+    ///
+    /// # Retired metrics
+    /// The id of a retired metric is never reused. You should stop producing measurement points
+    /// for this metric _before_ retiring it, because the outputs will not be able to get its definition anymore.
+    ///
+    /// # Errors
+    /// `retire_metrics` can fail if the control message cannot be sent, or if the reply cannot be received.
+    pub async fn retire_metrics(
+        &self,
+        metrics: Vec<RawMetricId>,
+    ) -> Result<Vec<(RawMetricId, Metric)>, SendWithReplyError> {
+        let (tx, rx) = oneshot::channel();
+        let message = ControlMessage::RetireMetrics {
+            metrics,
+            reply_to: Some(tx),
+        };
+        self.send(message).await.map_err(SendWithReplyError::Send)?;
+        let result = rx.await.map_err(SendWithReplyError::Recv)?;
+        Ok(result)
+    }
+
     /// Attempts to add a new metric listener immediately.
     pub fn try_subscribe<F: MetricListenerBuilder + Send + 'static>(
         &self,
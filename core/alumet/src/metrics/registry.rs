@@ -11,10 +11,15 @@ use super::{
 /// A registry of metrics.
 ///
 /// New metrics are created by the plugins during their initialization.
+/// Metrics can also be created and retired while the pipeline is running,
+/// see [`MetricSender`](super::online::MetricSender).
 #[derive(Clone)]
 pub struct MetricRegistry {
     pub(crate) metrics_by_id: HashMap<RawMetricId, Metric>,
     pub(crate) metrics_by_name: HashMap<String, RawMetricId>,
+    /// The id of the next metric to register.
+    /// Ids are never reused, even when metrics are retired.
+    next_id: usize,
 }
 
 impl MetricRegistry {
@@ -23,6 +28,7 @@ impl MetricRegistry {
         MetricRegistry {
             metrics_by_id: HashMap::new(),
             metrics_by_name: HashMap::new(),
+            next_id: 0,
         }
     }
 
@@ -59,7 +65,8 @@ impl MetricRegistry {
     ///
     /// NOTE: the caller must ensure that the name of the metric is unique.
     fn register_new(&mut self, m: Metric) -> RawMetricId {
-        let id = RawMetricId(self.metrics_by_name.len());
+        let id = RawMetricId(self.next_id);
+        self.next_id += 1;
 
         let prev = self.metrics_by_name.insert(m.name.clone(), id);
         debug_assert!(prev.is_none(), "duplicate metric name {}", m.name);
@@ -70,6 +77,16 @@ impl MetricRegistry {
         id
     }
 
+    /// Removes a metric from this registry.
+    ///
+    /// Returns the definition of the metric, or `None` if there was no metric with this id.
+    /// The id of a retired metric is never given to another metric.
+    pub(crate) fn retire(&mut self, id: RawMetricId) -> Option<Metric> {
+        let metric = self.metrics_by_id.remove(&id)?;
+        self.metrics_by_name.remove(&metric.name);
+        Some(metric)
+    }
+
     /// Registers a new metric in this registry.
     ///
     /// A new id is generated and returned.
@@ -352,6 +369,40 @@ mod tests {
         assert_eq!(vec!["metric", "metric2", "metric2_dedup"], names);
     }
 
+    #[test]
+    fn retire() {
+        let mut metrics = MetricRegistry::new();
+        let new_metric = |name: &str| Metric {
+            name: name.to_owned(),
+            description: "".to_owned(),
+            value_type: WrappedMeasurementType::U64,
+            unit: Unit::Watt.into(),
+        };
+        let id1 = metrics
+            .register(new_metric("a"), DuplicateCriteria::Strict, DuplicateReaction::Error)
+            .unwrap();
+        let id2 = metrics
+            .register(new_metric("b"), DuplicateCriteria::Strict, DuplicateReaction::Error)
+            .unwrap();
+        assert_eq!(metrics.len(), 2);
+
+        let retired = metrics.retire(id1).expect("metric should exist");
+        assert_eq!(retired.name, "a");
+        assert_eq!(metrics.len(), 1);
+        assert!(metrics.by_id(&id1).is_none());
+        assert!(metrics.by_name("a").is_none());
+        assert!(metrics.retire(id1).is_none(), "metric should be retired only once");
+
+        // the id of a retired metric must not be reused
+        let id3 = metrics
+            .register(new_metric("a"), DuplicateCriteria::Strict, DuplicateReaction::Error)
+            .unwrap();
+        assert_ne!(id3, id1);
+        assert_ne!(id3, id2);
+        assert_eq!(metrics.by_name("a").unwrap().0, id3);
+        assert_eq!(metrics.by_name("b").unwrap().0, id2);
+    }
+
     #[test]
     fn register_infallible() {
         {
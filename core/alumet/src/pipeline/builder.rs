@@ -22,17 +22,19 @@ use crate::pipeline::elements::transform::control::TransformControl;
 use crate::pipeline::util::channel;
 
 use super::elements::output::builder::OutputBuilder;
-use super::elements::source::builder::SourceBuilder;
-use super::elements::source::trigger::TriggerConstraints;
+use super::elements::source::builder::{ManagedSource, SourceBuilder, SourcePace};
+use super::elements::source::control::TaskState;
+use super::elements::source::trigger::{TriggerConstraints, TriggerSpec};
 use super::elements::transform::builder::TransformBuilder;
 use super::error::PipelineError;
+use super::monitoring::{self, MonitoringMetrics, MonitoringSource, PipelineMonitor};
 use super::naming::{
     OutputName, PluginName, SourceName, TransformName,
     namespace::{DuplicateNameError, Namespace2},
 };
 use super::{
     control::key::{OutputKey, SourceKey, TransformKey},
-    control::{AnonymousControlHandle, PipelineControl},
+    control::{AnonymousControlHandle, PipelineControl, status::StatusBoard},
     util,
 };
 
@@ -44,6 +46,8 @@ pub struct MeasurementPipeline {
     metrics: (MetricSender, MetricReader),
     pipeline_control_task: JoinHandle<Result<(), PipelineError>>,
     metrics_control_task: JoinHandle<()>,
+    /// `true` if the sources are directly connected to the only output (no transform step).
+    simplified: bool,
 }
 
 /// A Builder for [`MeasurementPipeline`].
@@ -71,6 +75,9 @@ pub struct Builder {
     /// Set this to `false` if you plan to add more outputs at runtime, while there is only one output at the beginning.
     allow_simplified_pipeline: bool,
 
+    /// Interval between two reports of the self-monitoring statistics, `None` if self-monitoring is disabled.
+    self_monitoring: Option<Duration>,
+
     /// Metrics
     pub(crate) metrics: MetricRegistry,
     metric_listeners: Namespace2<Box<dyn MetricListenerBuilder>>,
@@ -80,6 +87,16 @@ pub struct Builder {
     threads_high_priority: Option<usize>,
 }
 
+/// Pipeline elements that have been registered in a [`Builder`], but not built yet.
+pub(crate) struct PendingElements {
+    pub sources: Vec<(SourceName, SourceBuilder)>,
+    /// The transforms, in the order in which they have been added.
+    pub transforms: Vec<(TransformName, Box<dyn TransformBuilder>)>,
+    pub outputs: Vec<(OutputName, OutputBuilder)>,
+    /// Names of the metric listeners.
+    pub metric_listeners: Vec<String>,
+}
+
 /// Allows to inspect the content of a pipeline builder.
 pub struct BuilderInspector<'a> {
     inner: &'a Builder,
@@ -98,6 +115,7 @@ impl Builder {
             trigger_constraints: TriggerConstraints::default(),
             source_channel_size: DEFAULT_CHAN_BUF_SIZE,
             allow_simplified_pipeline: true,
+            self_monitoring: None,
             metrics: MetricRegistry::new(),
             metric_listeners: Namespace2::new(),
             threads_normal: None, // default to the number of cores
@@ -124,6 +142,15 @@ impl Builder {
         &mut self.allow_simplified_pipeline
     }
 
+    /// Enables the self-monitoring of the pipeline.
+    ///
+    /// Every `interval`, the statistics of the pipeline elements (latencies, number of measurements,
+    /// errors, dropped buffers, channel occupancy) are injected in the pipeline as measurement points.
+    /// See the [`monitoring`](super::monitoring) module.
+    pub fn enable_self_monitoring(&mut self, interval: Duration) {
+        self.self_monitoring = Some(interval);
+    }
+
     /// Registers a listener that will be notified of the metrics that are created while the pipeline is running,
     /// with a dedicated builder.
     pub fn add_metric_listener_builder(
@@ -229,6 +256,36 @@ impl Builder {
         });
     }
 
+    /// Takes the elements that have been registered in this builder, in order to add them to a running pipeline.
+    pub(crate) fn take_elements(&mut self) -> PendingElements {
+        let sources = std::mem::replace(&mut self.sources, Namespace2::new())
+            .into_iter()
+            .map(|((plugin, source), builder)| (SourceName::new(plugin, source), builder))
+            .collect();
+        let mut transforms = std::mem::replace(&mut self.transforms, Namespace2::new());
+        let transforms = std::mem::take(&mut self.default_transforms_order)
+            .into_iter()
+            .filter_map(|name| {
+                let builder = transforms.remove(name.plugin(), name.transform())?;
+                Some((name, builder))
+            })
+            .collect();
+        let outputs = std::mem::replace(&mut self.outputs, Namespace2::new())
+            .into_iter()
+            .map(|((plugin, output), builder)| (OutputName::new(plugin, output), builder))
+            .collect();
+        let metric_listeners = std::mem::replace(&mut self.metric_listeners, Namespace2::new())
+            .into_iter()
+            .map(|((plugin, listener), _)| format!("{plugin}/{listener}"))
+            .collect();
+        PendingElements {
+            sources,
+            transforms,
+            outputs,
+            metric_listeners,
+        }
+    }
+
     /// Builds the measurement pipeline.
     ///
     /// The new pipeline is immediately started.
@@ -303,6 +360,32 @@ impl Builder {
         // Token to shutdown the remaining parts of the pipeline, after the elements have been stopped.
         let pipeline_shutdown_finalize = CancellationToken::new();
 
+        // --- Self-monitoring (optional) ---
+        // The metrics and the source must be registered before the registry is moved and the sources are built.
+        let monitor = match self.self_monitoring {
+            Some(interval) => {
+                let monitor = PipelineMonitor::enabled();
+                let metrics = MonitoringMetrics::register(&mut self.metrics)
+                    .context("could not register the metrics of the self-monitoring")?;
+                let source = MonitoringSource::new(monitor.clone(), metrics);
+                let builder = SourceBuilder::Managed(
+                    Box::new(move |_| {
+                        Ok(ManagedSource {
+                            trigger_spec: TriggerSpec::at_interval(interval),
+                            initial_state: TaskState::Run,
+                            source: Box::new(source),
+                        })
+                    }),
+                    SourcePace::Fast,
+                );
+                self.sources
+                    .add(String::from("alumet"), String::from(monitoring::SOURCE_NAME), builder)
+                    .context("could not add the self-monitoring source")?;
+                monitor
+            }
+            None => PipelineMonitor::default(),
+        };
+
         // --- Metric registry (one for the entire pipeline) ---
         // Note: We can modify it without sending a message thanks to MetricAccess::write().
         let mut registry_control = MetricRegistryControl::new(self.metrics);
@@ -322,6 +405,7 @@ impl Builder {
 
         // Channel: sources -> transforms (or sources -> output in case of optimization).
         let (in_tx, in_rx) = mpsc::channel::<MeasurementBuffer>(self.source_channel_size);
+        monitor.watch_source_channel(&in_tx);
 
         let mut output_control;
         let transform_control;
@@ -333,14 +417,16 @@ impl Builder {
             add_dummy_output(&mut self.outputs);
         }
 
-        if self.outputs.total_count() == 1 && self.transforms.is_empty() && self.allow_simplified_pipeline {
+        let simplified =
+            self.outputs.total_count() == 1 && self.transforms.is_empty() && self.allow_simplified_pipeline;
+        if simplified {
             // OPTIMIZATION: there is only one output and no transform,
             // we can connect the inputs directly to the output.
             log::info!("Only one output and no transform, using a simplified and optimized measurement pipeline.");
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(in_rx);
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone(), monitor.clone());
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
@@ -350,10 +436,11 @@ impl Builder {
         } else {
             // Broadcast queue: transforms -> outputs
             let out_tx = broadcast::Sender::<MeasurementBuffer>::new(self.source_channel_size);
+            monitor.watch_output_channel(&out_tx, self.source_channel_size);
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(out_tx.clone());
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone(), monitor.clone());
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
@@ -361,10 +448,19 @@ impl Builder {
             // Transforms
             let order = self.transforms_order.unwrap_or(self.default_transforms_order);
             let transforms = take_transforms_in_order(self.transforms, order)?;
-            transform_control =
-                TransformControl::with_transforms(transforms, metrics_r.clone(), in_rx, out_tx, rt_handle)?;
+            transform_control = TransformControl::with_transforms(
+                transforms,
+                metrics_r.clone(),
+                in_rx,
+                out_tx,
+                rt_handle,
+                monitor.clone(),
+            )?;
         };
 
+        // Status of the elements, shared by the sources and the pipeline controller.
+        let status = StatusBoard::default();
+
         // Sources, last in order not to loose any measurement if they start measuring right away.
         let mut source_control = SourceControl::new(
             self.trigger_constraints,
@@ -373,13 +469,15 @@ impl Builder {
             rt_handle.clone(),
             rt_priority.as_ref().unwrap_or(&rt_normal).handle().clone(),
             (metrics_r.clone(), metrics_tx.clone()),
+            status.clone(),
+            monitor,
         );
         source_control
             .blocking_create_sources(self.sources)
             .context("source creation failed")?;
 
         // Pipeline control
-        let control = PipelineControl::new(source_control, transform_control, output_control);
+        let control = PipelineControl::new(source_control, transform_control, output_control, status);
         let (control_handle, control_join) = control.start(pipeline_shutdown, pipeline_shutdown_finalize, rt_handle);
 
         // Done!
@@ -390,6 +488,7 @@ impl Builder {
             metrics: (metrics_tx, metrics_r),
             pipeline_control_task: control_join,
             metrics_control_task: metrics_join,
+            simplified,
         })
     }
 
@@ -560,6 +659,12 @@ impl MeasurementPipeline {
         self.rt_normal.handle()
     }
 
+    /// Returns `true` if the pipeline has been simplified, in which case no transform or output
+    /// can be added to it while it is running (see [`Builder::allow_simplified_pipeline`]).
+    pub fn is_simplified(&self) -> bool {
+        self.simplified
+    }
+
     /// Wait for the pipeline to be shut down (via its [`control_handle()`](Self::control_handle) or by `Ctrl+C`).
     ///
     /// # Blocking
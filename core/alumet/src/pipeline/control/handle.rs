@@ -74,6 +74,17 @@ impl AnonymousControlHandle {
         self.shutdown_token.cancel();
     }
 
+    /// Waits until the shutdown of the pipeline begins.
+    ///
+    /// Completes when the shutdown is requested, by [`shutdown`](Self::shutdown) or by `Ctrl+C`,
+    /// or when the pipeline controller has stopped.
+    pub async fn shutdown_requested(&self) {
+        tokio::select! {
+            _ = self.shutdown_token.cancelled() => (),
+            _ = self.tx.closed() => (),
+        }
+    }
+
     /// Sends a control request to the pipeline, without waiting for a response.
     ///
     /// # Errors
@@ -11,6 +11,7 @@ use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
 use super::messages::SpecificBody;
+use super::status::StatusBoard;
 use super::{AnonymousControlHandle, messages};
 
 /// Encapsulates sources, transforms and outputs control.
@@ -18,6 +19,8 @@ pub(crate) struct PipelineControl {
     sources: source::control::SourceControl,
     transforms: transform::control::TransformControl,
     outputs: output::control::OutputControl,
+    /// Status reported by the elements.
+    status: StatusBoard,
 }
 
 impl PipelineControl {
@@ -25,11 +28,13 @@ impl PipelineControl {
         sources: source::control::SourceControl,
         transforms: transform::control::TransformControl,
         outputs: output::control::OutputControl,
+        status: StatusBoard,
     ) -> Self {
         Self {
             sources,
             transforms,
             outputs,
+            status,
         }
     }
 
@@ -52,7 +57,7 @@ impl PipelineControl {
     async fn handle_specific_msg(&mut self, body: SpecificBody) -> anyhow::Result<()> {
         match body {
             messages::SpecificBody::Source(msg) => self.sources.handle_message(msg).await,
-            messages::SpecificBody::Transform(msg) => self.transforms.handle_message(msg),
+            messages::SpecificBody::Transform(msg) => self.transforms.handle_message(msg).await,
             messages::SpecificBody::Output(msg) => self.outputs.handle_message(msg).await,
         }
     }
@@ -102,6 +107,16 @@ impl PipelineControl {
                 };
                 send_response(result, response_tx)
             }
+            messages::ControlRequest::Status(RequestMessage { response_tx, body }) => {
+                send_response(Ok(self.status.list(&body)), response_tx)
+            }
+            messages::ControlRequest::State(RequestMessage { response_tx, body }) => {
+                let mut buf = Vec::new();
+                self.sources.list_states(&mut buf, &body);
+                self.transforms.list_states(&mut buf, &body);
+                self.outputs.list_states(&mut buf, &body);
+                send_response(Ok(buf), response_tx)
+            }
         }
     }
 
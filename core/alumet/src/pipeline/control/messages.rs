@@ -1,6 +1,7 @@
 use tokio::sync::{mpsc, oneshot};
 
 use crate::pipeline::{
+    control::status::{ElementState, ElementStatus},
     elements::{output, source, transform},
     error::PipelineError,
     matching::ElementNamePattern,
@@ -14,6 +15,8 @@ pub type Sender = mpsc::Sender<ControlRequest>;
 pub enum ControlRequest {
     NoResult(RequestMessage<EmptyResponseBody, ()>),
     Introspect(RequestMessage<IntrospectionBody, IntrospectionResponse>),
+    Status(RequestMessage<ElementNamePattern, StatusResponse>),
+    State(RequestMessage<ElementNamePattern, StateResponse>),
 }
 
 pub type ResponseSender<R> = oneshot::Sender<Result<R, PipelineError>>;
@@ -43,3 +46,7 @@ pub enum IntrospectionBody {
 }
 
 pub type IntrospectionResponse = Vec<ElementName>;
+
+pub type StatusResponse = Vec<(ElementName, ElementStatus)>;
+
+pub type StateResponse = Vec<(ElementName, ElementState)>;
@@ -5,6 +5,7 @@ mod main_loop;
 pub mod matching;
 mod messages;
 pub mod request;
+pub mod status;
 
 pub use handle::{AnonymousControlHandle, PluginControlHandle};
 pub(crate) use main_loop::PipelineControl;
@@ -25,8 +25,13 @@ mod output;
 pub mod source;
 mod transform;
 
-pub use create::{CreationRequest, MultiCreationRequestBuilder, SingleCreationRequestBuilder, create_many, create_one};
-pub use introspect::{ElementListFilter, IntrospectionRequest, list_elements};
+pub use create::{
+    CreationRequest, MultiCreationRequestBuilder, SingleCreationRequestBuilder, TransformPosition, create_many,
+    create_one,
+};
+pub use introspect::{
+    ElementListFilter, IntrospectionRequest, StateRequest, StatusRequest, element_states, element_status, list_elements,
+};
 pub use output::{OutputRequest, OutputRequestBuilder, RemainingDataStrategy, output};
 pub use source::{SourceRequest, SourceRequestBuilder, source};
 use tokio::sync::oneshot;
@@ -8,7 +8,10 @@ use crate::pipeline::{
 
 use super::{
     AnonymousControlRequest, CreationRequest, DirectResponseReceiver, PluginControlRequest, ResponseReceiver, create,
-    introspect::IntrospectionRequest, output::OutputRequest, source::SourceRequest, transform::TransformRequest,
+    introspect::{IntrospectionRequest, StateRequest, StatusRequest},
+    output::OutputRequest,
+    source::SourceRequest,
+    transform::TransformRequest,
 };
 
 #[derive(Debug)]
@@ -22,6 +25,8 @@ enum ControlRequestImpl {
     Source(SourceRequest),
     Transform(TransformRequest),
     Introspect(IntrospectionRequest),
+    Status(StatusRequest),
+    State(StateRequest),
 }
 
 #[derive(Debug)]
@@ -40,6 +45,8 @@ pub struct ResponseDiscarder(ResponseDiscarderImpl);
 enum ResponseDiscarderImpl {
     NoResult(DirectResponseReceiver<()>),
     Introspect(DirectResponseReceiver<messages::IntrospectionResponse>),
+    Status(DirectResponseReceiver<messages::StatusResponse>),
+    State(DirectResponseReceiver<messages::StateResponse>),
 }
 
 impl From<DirectResponseReceiver<()>> for ResponseDiscarder {
@@ -54,6 +61,18 @@ impl From<DirectResponseReceiver<messages::IntrospectionResponse>> for ResponseD
     }
 }
 
+impl From<DirectResponseReceiver<messages::StatusResponse>> for ResponseDiscarder {
+    fn from(value: DirectResponseReceiver<messages::StatusResponse>) -> Self {
+        Self(ResponseDiscarderImpl::Status(value))
+    }
+}
+
+impl From<DirectResponseReceiver<messages::StateResponse>> for ResponseDiscarder {
+    fn from(value: DirectResponseReceiver<messages::StateResponse>) -> Self {
+        Self(ResponseDiscarderImpl::State(value))
+    }
+}
+
 impl ResponseReceiver for ResponseDiscarder {
     type Ok = ();
 
@@ -71,6 +90,8 @@ impl ResponseReceiver for ResponseDiscarder {
         match self.0 {
             ResponseDiscarderImpl::NoResult(r) => discard_success(r.recv().await),
             ResponseDiscarderImpl::Introspect(r) => discard_success(r.recv().await),
+            ResponseDiscarderImpl::Status(r) => discard_success(r.recv().await),
+            ResponseDiscarderImpl::State(r) => discard_success(r.recv().await),
         }
     }
 }
@@ -86,6 +107,8 @@ impl AnonymousControlRequest for AnyAnonymousControlRequest {
             ControlRequestImpl::Source(req) => AnonymousControlRequest::serialize(req),
             ControlRequestImpl::Transform(req) => AnonymousControlRequest::serialize(req),
             ControlRequestImpl::Introspect(req) => AnonymousControlRequest::serialize(req),
+            ControlRequestImpl::Status(req) => AnonymousControlRequest::serialize(req),
+            ControlRequestImpl::State(req) => AnonymousControlRequest::serialize(req),
         }
     }
 
@@ -107,6 +130,14 @@ impl AnonymousControlRequest for AnyAnonymousControlRequest {
                 let (req, rx) = AnonymousControlRequest::serialize_with_response(req);
                 (req, ResponseDiscarder::from(rx))
             }
+            ControlRequestImpl::Status(req) => {
+                let (req, rx) = AnonymousControlRequest::serialize_with_response(req);
+                (req, ResponseDiscarder::from(rx))
+            }
+            ControlRequestImpl::State(req) => {
+                let (req, rx) = AnonymousControlRequest::serialize_with_response(req);
+                (req, ResponseDiscarder::from(rx))
+            }
         }
     }
 }
@@ -157,6 +188,17 @@ impl From<IntrospectionRequest> for AnyAnonymousControlRequest {
         Self(ControlRequestImpl::Introspect(value))
     }
 }
+impl From<StatusRequest> for AnyAnonymousControlRequest {
+    fn from(value: StatusRequest) -> Self {
+        Self(ControlRequestImpl::Status(value))
+    }
+}
+
+impl From<StateRequest> for AnyAnonymousControlRequest {
+    fn from(value: StateRequest) -> Self {
+        Self(ControlRequestImpl::State(value))
+    }
+}
 
 impl From<AnyAnonymousControlRequest> for AnyPluginControlRequest {
     fn from(value: AnyAnonymousControlRequest) -> Self {
@@ -1,12 +1,12 @@
 use tokio::sync::oneshot;
 
 use crate::pipeline::{
-    Output, Source,
+    Output, Source, Transform,
     control::messages,
     elements::{
         output::{
             self,
-            builder::{BlockingOutputBuilder, SendOutputBuilder},
+            builder::{AsyncOutputBuilder, BlockingOutputBuilder, SendOutputBuilder},
         },
         source::{
             self,
@@ -14,8 +14,12 @@ use crate::pipeline::{
             control::TaskState,
             trigger::TriggerSpec,
         },
+        transform::{
+            self,
+            builder::{SendTransformBuilder, TransformBuilder},
+        },
     },
-    naming::{OutputName, PluginName, SourceName},
+    naming::{OutputName, PluginName, SourceName, TransformName},
 };
 
 use super::DirectResponseReceiver;
@@ -23,11 +27,25 @@ use super::DirectResponseReceiver;
 #[derive(Default, Debug)]
 pub struct MultiCreationRequestBuilder {
     sources: Vec<(String, SendSourceBuilder)>,
-    // TODO transforms when it becomes possible to add them at runtime
-    // transforms: Vec<(String, Box<dyn TransformBuilder + Send>)>,
+    transforms: Vec<(String, TransformPosition, SendTransformBuilder)>,
     outputs: Vec<(String, SendOutputBuilder)>,
 }
 
+/// Where to insert a new transform in the chain of transforms.
+///
+/// Transforms are applied in order, from the first to the last one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformPosition {
+    /// Before all the existing transforms.
+    First,
+    /// After all the existing transforms.
+    Last,
+    /// Immediately before the given transform.
+    Before(TransformName),
+    /// Immediately after the given transform.
+    After(TransformName),
+}
+
 pub struct SingleCreationRequestBuilder {
     inner: MultiCreationRequestBuilder,
 }
@@ -88,6 +106,33 @@ impl SingleCreationRequestBuilder {
         self.inner.build()
     }
 
+    /// Requests the creation of a transform, inserted at the given `position` in the chain of transforms.
+    ///
+    /// # Simplified pipeline
+    /// Transforms cannot be created if the pipeline has been built without any transform step,
+    /// which happens when the "simplified pipeline" optimization is enabled (see [`allow_simplified_pipeline`](crate::pipeline::Builder::allow_simplified_pipeline)).
+    pub fn add_transform(
+        mut self,
+        name: &str,
+        transform: Box<dyn Transform>,
+        position: TransformPosition,
+    ) -> CreationRequest {
+        self.inner.add_transform(name, transform, position);
+        self.inner.build()
+    }
+
+    /// Requests the creation of a transform, built by `builder` and inserted at the given `position`
+    /// in the chain of transforms.
+    ///
+    /// See [`add_transform`](Self::add_transform).
+    pub fn add_transform_builder<F>(mut self, name: &str, position: TransformPosition, builder: F) -> CreationRequest
+    where
+        F: TransformBuilder + Send + 'static,
+    {
+        self.inner.add_transform_builder(name, position, builder);
+        self.inner.build()
+    }
+
     /// Requests the creation of a blocking output.
     pub fn add_blocking_output(mut self, name: &str, output: Box<dyn Output>) -> CreationRequest {
         self.inner.add_blocking_output(name, output);
@@ -102,6 +147,16 @@ impl SingleCreationRequestBuilder {
         self.inner.add_blocking_output_builder(name, builder);
         self.inner.build()
     }
+
+    /// Requests the creation of an async output.
+    pub fn add_async_output_builder<F: AsyncOutputBuilder + Send + 'static>(
+        mut self,
+        name: &str,
+        builder: F,
+    ) -> CreationRequest {
+        self.inner.add_async_output_builder(name, builder);
+        self.inner.build()
+    }
 }
 
 impl MultiCreationRequestBuilder {
@@ -142,6 +197,16 @@ impl MultiCreationRequestBuilder {
         self
     }
 
+    /// Adds the builder of a managed and **blocking** source to the request.
+    pub fn add_blocking_source_builder<F>(&mut self, name: &str, builder: F) -> &mut Self
+    where
+        F: ManagedSourceBuilder + Send + 'static,
+    {
+        let builder = SendSourceBuilder::Managed(Box::new(builder), SourcePace::Blocking);
+        self.sources.push((name.to_string(), builder));
+        self
+    }
+
     pub fn add_autonomous_source_builder<F>(&mut self, name: &str, builder: F) -> &mut Self
     where
         F: AutonomousSourceBuilder + Send + 'static,
@@ -151,6 +216,28 @@ impl MultiCreationRequestBuilder {
         self
     }
 
+    pub fn add_transform(
+        &mut self,
+        name: &str,
+        transform: Box<dyn Transform>,
+        position: TransformPosition,
+    ) -> &mut Self {
+        self.add_transform_builder(name, position, |_| Ok(transform))
+    }
+
+    /// Adds the builder of a transform to the request.
+    ///
+    /// The transforms of the request are inserted in the order in which they have been added.
+    /// Therefore, a [`TransformPosition`] can refer to a transform that is part of the same request.
+    pub fn add_transform_builder<F>(&mut self, name: &str, position: TransformPosition, builder: F) -> &mut Self
+    where
+        F: TransformBuilder + Send + 'static,
+    {
+        let builder = SendTransformBuilder(Box::new(builder));
+        self.transforms.push((name.to_string(), position, builder));
+        self
+    }
+
     pub fn add_blocking_output(&mut self, name: &str, output: Box<dyn Output>) {
         self.add_blocking_output_builder(name, |_| Ok(output));
     }
@@ -159,16 +246,19 @@ impl MultiCreationRequestBuilder {
         let builder = SendOutputBuilder::Blocking(Box::new(builder));
         self.outputs.push((name.to_string(), builder));
     }
+
+    pub fn add_async_output_builder<F: AsyncOutputBuilder + Send + 'static>(&mut self, name: &str, builder: F) {
+        let builder = SendOutputBuilder::Async(Box::new(builder));
+        self.outputs.push((name.to_string(), builder));
+    }
 }
 
 impl CreationRequest {
     fn into_body(self, plugin: &PluginName) -> messages::EmptyResponseBody {
         let builders = self.builders;
-        let has_sources = !builders.sources.is_empty();
-        let has_outputs = !builders.outputs.is_empty();
 
         // add the plugin name to every builder
-        let source_builders = builders
+        let source_builders: Vec<_> = builders
             .sources
             .into_iter()
             .map(|(source_name, builder)| {
@@ -176,7 +266,15 @@ impl CreationRequest {
                 (full_name, builder)
             })
             .collect();
-        let output_builders = builders
+        let transform_builders: Vec<_> = builders
+            .transforms
+            .into_iter()
+            .map(|(transform_name, position, builder)| {
+                let full_name = TransformName::new(plugin.to_owned().0, transform_name);
+                (full_name, position, builder)
+            })
+            .collect();
+        let output_builders: Vec<_> = builders
             .outputs
             .into_iter()
             .map(|(output_name, builder)| {
@@ -185,23 +283,34 @@ impl CreationRequest {
             })
             .collect();
 
-        // create the message
-        let source_msg = messages::SpecificBody::Source(source::control::ControlMessage::CreateMany(
-            source::control::CreateManyMessage {
-                builders: source_builders,
-            },
-        ));
-        let out_msg = messages::SpecificBody::Output(output::control::ControlMessage::CreateMany(
-            output::control::CreateManyMessage {
-                builders: output_builders,
-            },
-        ));
-        if !has_outputs {
-            messages::EmptyResponseBody::Single(source_msg)
-        } else if !has_sources {
-            messages::EmptyResponseBody::Single(out_msg)
+        // Create the messages.
+        // Outputs and transforms are created before the sources, in order not to lose any measurement.
+        let mut bodies = Vec::with_capacity(3);
+        if !output_builders.is_empty() {
+            bodies.push(messages::SpecificBody::Output(
+                output::control::ControlMessage::CreateMany(output::control::CreateManyMessage {
+                    builders: output_builders,
+                }),
+            ));
+        }
+        if !transform_builders.is_empty() {
+            bodies.push(messages::SpecificBody::Transform(
+                transform::control::ControlMessage::CreateMany(transform::control::CreateManyMessage {
+                    builders: transform_builders,
+                }),
+            ));
+        }
+        if !source_builders.is_empty() || bodies.is_empty() {
+            bodies.push(messages::SpecificBody::Source(
+                source::control::ControlMessage::CreateMany(source::control::CreateManyMessage {
+                    builders: source_builders,
+                }),
+            ));
+        }
+        if bodies.len() == 1 {
+            messages::EmptyResponseBody::Single(bodies.pop().unwrap())
         } else {
-            messages::EmptyResponseBody::Mixed(vec![source_msg, out_msg])
+            messages::EmptyResponseBody::Mixed(bodies)
         }
     }
 }
@@ -13,11 +13,37 @@ pub fn list_elements(filter: ElementListFilter) -> IntrospectionRequest {
     IntrospectionRequest { list_filter: filter }
 }
 
+/// Creates a request that returns the status reported by the elements that match the given filter.
+///
+/// Only the elements that have reported a status are included in the response.
+/// See [`status`](crate::pipeline::control::status).
+pub fn element_status(filter: ElementListFilter) -> StatusRequest {
+    StatusRequest { filter }
+}
+
+/// Creates a request that returns the state of the elements that match the given filter.
+///
+/// Unlike [`element_status`], every element is included in the response, and the state is
+/// determined by the pipeline controller. See [`ElementState`](crate::pipeline::control::status::ElementState).
+pub fn element_states(filter: ElementListFilter) -> StateRequest {
+    StateRequest { filter }
+}
+
 #[derive(Debug)]
 pub struct IntrospectionRequest {
     list_filter: ElementListFilter,
 }
 
+#[derive(Debug)]
+pub struct StatusRequest {
+    filter: ElementListFilter,
+}
+
+#[derive(Debug)]
+pub struct StateRequest {
+    filter: ElementListFilter,
+}
+
 #[derive(Debug)]
 pub struct ElementListFilter {
     pub(crate) pattern: ElementNamePattern,
@@ -89,3 +115,45 @@ impl AnonymousControlRequest for IntrospectionRequest {
         (req, DirectResponseReceiver(rx))
     }
 }
+
+impl AnonymousControlRequest for StatusRequest {
+    type OkResponse = messages::StatusResponse;
+    type Receiver = DirectResponseReceiver<Self::OkResponse>;
+
+    fn serialize(self) -> messages::ControlRequest {
+        messages::ControlRequest::Status(messages::RequestMessage {
+            response_tx: None,
+            body: self.filter.pattern,
+        })
+    }
+
+    fn serialize_with_response(self) -> (messages::ControlRequest, Self::Receiver) {
+        let (tx, rx) = oneshot::channel();
+        let req = messages::ControlRequest::Status(messages::RequestMessage {
+            response_tx: Some(tx),
+            body: self.filter.pattern,
+        });
+        (req, DirectResponseReceiver(rx))
+    }
+}
+
+impl AnonymousControlRequest for StateRequest {
+    type OkResponse = messages::StateResponse;
+    type Receiver = DirectResponseReceiver<Self::OkResponse>;
+
+    fn serialize(self) -> messages::ControlRequest {
+        messages::ControlRequest::State(messages::RequestMessage {
+            response_tx: None,
+            body: self.filter.pattern,
+        })
+    }
+
+    fn serialize_with_response(self) -> (messages::ControlRequest, Self::Receiver) {
+        let (tx, rx) = oneshot::channel();
+        let req = messages::ControlRequest::State(messages::RequestMessage {
+            response_tx: Some(tx),
+            body: self.filter.pattern,
+        });
+        (req, DirectResponseReceiver(rx))
+    }
+}
@@ -2,7 +2,7 @@ use tokio::sync::oneshot;
 
 use crate::pipeline::{
     control::{matching::TransformMatcher, messages},
-    elements::transform::control::{ControlMessage, TaskState},
+    elements::transform::control::{ConfigureMessage, ControlMessage, RemoveMessage, TaskState},
 };
 
 use super::DirectResponseReceiver;
@@ -26,19 +26,29 @@ pub fn transform(matcher: impl Into<TransformMatcher>) -> TransformRequestBuilde
 impl TransformRequestBuilder {
     pub fn disable(self) -> TransformRequest {
         TransformRequest {
-            msg: ControlMessage {
+            msg: ControlMessage::Configure(ConfigureMessage {
                 matcher: self.matcher,
                 new_state: TaskState::Disabled,
-            },
+            }),
         }
     }
 
     pub fn enable(self) -> TransformRequest {
         TransformRequest {
-            msg: ControlMessage {
+            msg: ControlMessage::Configure(ConfigureMessage {
                 matcher: self.matcher,
                 new_state: TaskState::Enabled,
-            },
+            }),
+        }
+    }
+
+    /// Removes the transform(s) from the pipeline.
+    ///
+    /// The removed transforms are finished (see [`Transform::finish`](crate::pipeline::Transform::finish))
+    /// as soon as the request is handled, even if no measurements go through the chain of transforms.
+    pub fn remove(self) -> TransformRequest {
+        TransformRequest {
+            msg: ControlMessage::Remove(RemoveMessage { matcher: self.matcher }),
         }
     }
 }
@@ -0,0 +1,208 @@
+//! Status of the pipeline elements, for introspection.
+//!
+//! Some elements have an internal state that is useful to inspect while the pipeline is running,
+//! for instance the remote clients that are connected to a source.
+//! Such elements report their status with a [`StatusReporter`], and the status can then be
+//! obtained with the control request [`element_status`](super::request::element_status).
+//!
+//! Independently of what the elements report, the pipeline controller keeps track of the
+//! [`ElementState`] of every element, which can be obtained with the control request
+//! [`element_states`](super::request::element_states).
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+use tokio::task::{self, JoinError};
+
+use crate::pipeline::{error::PipelineError, matching::ElementNamePattern, naming::ElementName};
+
+/// Status of a pipeline element, as reported by the element itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElementStatus {
+    /// Element-specific details.
+    pub details: toml::Table,
+}
+
+/// State of a pipeline element, as seen by the pipeline controller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElementState {
+    /// The element is running.
+    Running,
+    /// The element has been paused (or disabled, for transforms).
+    Paused,
+    /// The element is running, but it has failed several times in a row.
+    Degraded {
+        /// The last error of the element.
+        last_error: String,
+    },
+    /// The element has stopped without error.
+    Stopped,
+    /// The element has stopped because of an error.
+    Failed {
+        /// The error that stopped the element.
+        error: String,
+    },
+}
+
+/// Allows an element to report its status.
+///
+/// The status is removed when the reporter is dropped.
+pub struct StatusReporter {
+    name: ElementName,
+    board: StatusBoard,
+}
+
+/// Stores the last status of each element.
+#[derive(Clone, Default)]
+pub(crate) struct StatusBoard(Arc<Mutex<IndexMap<ElementName, ElementStatus>>>);
+
+impl StatusReporter {
+    /// Returns the name of the element that this reporter is attached to.
+    pub fn element_name(&self) -> &ElementName {
+        &self.name
+    }
+
+    /// Replaces the status of the element.
+    pub fn report(&self, status: ElementStatus) {
+        self.board.0.lock().unwrap().insert(self.name.clone(), status);
+    }
+}
+
+impl Drop for StatusReporter {
+    fn drop(&mut self) {
+        if let Ok(mut board) = self.board.0.lock() {
+            board.shift_remove(&self.name);
+        }
+    }
+}
+
+/// Keeps track of the state of elements that run in their own task.
+pub(crate) struct StateTracker<N> {
+    states: IndexMap<N, ElementState>,
+    tasks: HashMap<task::Id, N>,
+}
+
+impl ElementState {
+    /// Returns `true` if the element has stopped, with or without error.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ElementState::Stopped | ElementState::Failed { .. })
+    }
+
+    /// Returns the state of an element whose task has finished with the given result.
+    pub(crate) fn after_task(res: &Result<Result<(), PipelineError>, JoinError>) -> Self {
+        match res {
+            Ok(Ok(())) => ElementState::Stopped,
+            Ok(Err(e)) => ElementState::Failed {
+                error: format!("{e:#}"),
+            },
+            Err(e) => ElementState::Failed { error: e.to_string() },
+        }
+    }
+}
+
+impl Display for ElementState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElementState::Running => f.write_str("running"),
+            ElementState::Paused => f.write_str("paused"),
+            ElementState::Degraded { .. } => f.write_str("degraded"),
+            ElementState::Stopped => f.write_str("stopped"),
+            ElementState::Failed { .. } => f.write_str("failed"),
+        }
+    }
+}
+
+impl<N: Clone + Eq + Hash + Into<ElementName>> StateTracker<N> {
+    pub fn new() -> Self {
+        Self {
+            states: IndexMap::new(),
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// Registers a new element, which runs in the task `id`.
+    pub fn spawned(&mut self, name: N, id: task::Id, state: ElementState) {
+        self.tasks.insert(id, name.clone());
+        self.states.insert(name, state);
+    }
+
+    /// Changes the state of an element, unless it has already finished.
+    pub fn set(&mut self, name: &N, state: ElementState) {
+        if let Some(current) = self.states.get_mut(name)
+            && !current.is_finished()
+        {
+            *current = state;
+        }
+    }
+
+    /// Updates the state of the element whose task has finished, and returns the result of the task.
+    pub fn task_finished(
+        &mut self,
+        res: Result<(task::Id, Result<(), PipelineError>), JoinError>,
+    ) -> Result<Result<(), PipelineError>, JoinError> {
+        let (id, res) = match res {
+            Ok((id, res)) => (id, Ok(res)),
+            Err(e) => (e.id(), Err(e)),
+        };
+        if let Some(name) = self.tasks.remove(&id) {
+            self.states.insert(name, ElementState::after_task(&res));
+        }
+        res
+    }
+
+    /// Appends the states of the elements that match the pattern to `buf`.
+    pub fn list(&self, buf: &mut Vec<(ElementName, ElementState)>, pat: &ElementNamePattern) {
+        buf.extend(self.states.iter().filter_map(|(name, state)| {
+            let name: ElementName = name.clone().into();
+            pat.matches(&name).then(|| (name, state.clone()))
+        }));
+    }
+}
+
+impl StatusBoard {
+    pub fn reporter(&self, name: ElementName) -> StatusReporter {
+        StatusReporter {
+            name,
+            board: self.clone(),
+        }
+    }
+
+    /// Returns the status of the elements that match the pattern.
+    pub fn list(&self, pat: &ElementNamePattern) -> Vec<(ElementName, ElementStatus)> {
+        let board = self.0.lock().unwrap();
+        board
+            .iter()
+            .filter(|(name, _)| pat.matches(*name))
+            .map(|(name, status)| (name.clone(), status.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{
+        matching::ElementNamePattern,
+        naming::{ElementKind, ElementName},
+    };
+
+    use super::{ElementStatus, StatusBoard};
+
+    #[test]
+    fn report_and_drop() {
+        let board = StatusBoard::default();
+        let name = ElementName::from_str(ElementKind::Source, "plugin", "source");
+        let reporter = board.reporter(name.clone());
+        assert!(board.list(&ElementNamePattern::wildcard()).is_empty());
+
+        let mut status = ElementStatus::default();
+        status.details.insert(String::from("clients"), toml::Value::Integer(2));
+        reporter.report(status.clone());
+        assert_eq!(board.list(&ElementNamePattern::wildcard()), vec![(name, status)]);
+
+        drop(reporter);
+        assert!(board.list(&ElementNamePattern::wildcard()).is_empty());
+    }
+}
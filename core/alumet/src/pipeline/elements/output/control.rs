@@ -10,8 +10,10 @@ use tokio::{
     task::{JoinError, JoinSet},
 };
 
+use crate::pipeline::control::status::{ElementState, StateTracker};
 use crate::pipeline::elements::output::{AsyncOutputStream, run::run_async_output};
 use crate::pipeline::matching::OutputNamePattern;
+use crate::pipeline::monitoring::PipelineMonitor;
 use crate::pipeline::naming::{OutputName, namespace::Namespace2};
 use crate::pipeline::util::{
     channel,
@@ -66,6 +68,10 @@ pub enum SingleOutputController {
 pub struct SharedOutputConfig {
     pub change_notifier: Notify,
     pub atomic_state: AtomicU8,
+    /// Receiver to use after [`TaskState::RunDiscard`], subscribed when the state has been requested.
+    ///
+    /// The task can be notified after some measurements have been sent: they must not be discarded.
+    pub discard_rx: Mutex<Option<channel::ReceiverEnum>>,
 }
 
 impl SharedOutputConfig {
@@ -73,10 +79,14 @@ impl SharedOutputConfig {
         Self {
             change_notifier: Notify::new(),
             atomic_state: AtomicU8::new(TaskState::Run as u8),
+            discard_rx: Mutex::new(None),
         }
     }
 
     pub fn set_state(&self, state: TaskState) {
+        if state != TaskState::RunDiscard {
+            self.discard_rx.lock().unwrap().take();
+        }
         self.atomic_state.store(state as u8, Ordering::Relaxed);
         self.change_notifier.notify_one();
     }
@@ -100,6 +110,7 @@ pub(crate) struct OutputControl {
 struct TaskManager {
     spawned_tasks: JoinSet<Result<(), PipelineError>>,
     controllers: Vec<(OutputName, SingleOutputController)>,
+    states: StateTracker<OutputName>,
 
     rx_provider: channel::ReceiverProvider,
 
@@ -107,17 +118,27 @@ struct TaskManager {
     rt_normal: runtime::Handle,
 
     metrics: MetricReader,
+
+    /// Collects the statistics of the outputs.
+    monitor: PipelineMonitor,
 }
 
 impl OutputControl {
-    pub fn new(rx_provider: channel::ReceiverProvider, rt_normal: runtime::Handle, metrics: MetricReader) -> Self {
+    pub fn new(
+        rx_provider: channel::ReceiverProvider,
+        rt_normal: runtime::Handle,
+        metrics: MetricReader,
+        monitor: PipelineMonitor,
+    ) -> Self {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
                 controllers: Vec::new(),
+                states: StateTracker::new(),
                 rx_provider,
                 rt_normal,
                 metrics: metrics.clone(),
+                monitor,
             },
             metrics,
         }
@@ -179,8 +200,8 @@ impl OutputControl {
     }
 
     pub async fn join_next_task(&mut self) -> Result<Result<(), PipelineError>, JoinError> {
-        match self.tasks.spawned_tasks.join_next().await {
-            Some(res) => res,
+        match self.tasks.spawned_tasks.join_next_with_id().await {
+            Some(res) => self.tasks.states.task_finished(res),
             None => unreachable!("join_next_task must be guarded by has_task to prevent an infinite loop"),
         }
     }
@@ -208,6 +229,10 @@ impl OutputControl {
         self.tasks.shutdown(handle_task_result).await;
     }
 
+    pub fn list_states(&self, buf: &mut Vec<(ElementName, ElementState)>, pat: &ElementNamePattern) {
+        self.tasks.states.list(buf, pat);
+    }
+
     pub fn list_elements(&self, buf: &mut Vec<ElementName>, pat: &ElementNamePattern) {
         if pat.kind == None || pat.kind == Some(ElementKind::Output) {
             buf.extend(self.tasks.controllers.iter().filter_map(|(name, _)| {
@@ -246,6 +271,7 @@ impl TaskManager {
         // Create the necessary context.
         let rx = self.rx_provider.get(); // to receive measurements
         let metrics = self.metrics.clone(); // to read metric definitions
+        let probe = self.monitor.probe(name.clone()); // to record statistics
 
         // Create and store the task controller.
         let config = Arc::new(SharedOutputConfig::new());
@@ -257,17 +283,19 @@ impl TaskManager {
         let guarded_output = Arc::new(Mutex::new(output));
 
         // Spawn the task on the runtime.
-        match rx {
+        let task_name = name.clone();
+        let task = match rx {
             // Specialize on the kind of receiver at compile-time (for performance).
             channel::ReceiverEnum::Broadcast(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
-                self.spawned_tasks.spawn_on(task, &self.rt_normal);
+                let task = run_blocking_output(task_name, guarded_output, rx, metrics, shared_config, probe);
+                self.spawned_tasks.spawn_on(task, &self.rt_normal)
             }
             channel::ReceiverEnum::Single(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
-                self.spawned_tasks.spawn_on(task, &self.rt_normal);
+                let task = run_blocking_output(task_name, guarded_output, rx, metrics, shared_config, probe);
+                self.spawned_tasks.spawn_on(task, &self.rt_normal)
             }
-        }
+        };
+        self.states.spawned(name, task.id(), ElementState::Running);
 
         Ok(())
     }
@@ -308,15 +336,30 @@ impl TaskManager {
         self.controllers.push((name.clone(), control));
 
         // Spawn the output
-        let task = run_async_output(name, output);
-        self.spawned_tasks.spawn_on(task, &self.rt_normal);
+        let task = run_async_output(name.clone(), output);
+        let task = self.spawned_tasks.spawn_on(task, &self.rt_normal);
+        self.states.spawned(name, task.id(), ElementState::Running);
         Ok(())
     }
 
     fn reconfigure(&mut self, msg: ConfigureMessage) {
+        let new_state = match msg.new_state {
+            TaskState::Run | TaskState::RunDiscard => Some(ElementState::Running),
+            TaskState::Pause => Some(ElementState::Paused),
+            // the state changes when the task finishes
+            TaskState::StopFinish | TaskState::StopNow => None,
+        };
         for (name, output_config) in &mut self.controllers {
             if msg.matcher.matches(name) {
+                if let (TaskState::RunDiscard, SingleOutputController::Blocking(shared)) =
+                    (msg.new_state, &output_config)
+                {
+                    *shared.discard_rx.lock().unwrap() = self.rx_provider.subscribe();
+                }
                 output_config.set_state(msg.new_state);
+                if let Some(state) = &new_state {
+                    self.states.set(name, state.clone());
+                }
             }
         }
     }
@@ -8,6 +8,7 @@ use crate::{
     metrics::online::MetricReader,
     pipeline::{
         error::PipelineError,
+        monitoring::ElementProbe,
         naming::OutputName,
         util::channel::{self, RecvError},
     },
@@ -22,12 +23,13 @@ pub async fn run_async_output(name: OutputName, output: BoxedAsyncOutput) -> Res
     })
 }
 
-pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
+pub(crate) async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
     name: OutputName,
     guarded_output: Arc<Mutex<Box<dyn Output>>>,
     mut rx: Rx,
     metrics_reader: MetricReader,
     config: Arc<control::SharedOutputConfig>,
+    probe: ElementProbe,
 ) -> Result<(), PipelineError> {
     /// If `measurements` is an `Ok`, build an [`OutputContext`] and call `output.write(&measurements, &ctx)`.
     /// Otherwise, handle the error.
@@ -35,16 +37,24 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
         name: &OutputName,
         output: Arc<Mutex<Box<dyn Output>>>,
         metrics_r: MetricReader,
+        probe: &ElementProbe,
         maybe_measurements: Result<MeasurementBuffer, channel::RecvError>,
     ) -> anyhow::Result<ControlFlow<()>> {
         match maybe_measurements {
             Ok(measurements) => {
                 log::trace!("writing {} measurements to {name}", measurements.len());
+                let probe = probe.clone();
                 let res = tokio::task::spawn_blocking(move || {
                     let ctx = OutputContext {
                         metrics: &metrics_r.blocking_read(),
                     };
-                    output.lock().unwrap().write(&measurements, &ctx)
+                    let t0 = probe.start();
+                    let res = output.lock().unwrap().write(&measurements, &ctx);
+                    probe.record(t0, measurements.len());
+                    if res.is_err() {
+                        probe.record_error();
+                    }
+                    res
                 })
                 .await?;
                 match res {
@@ -61,6 +71,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
             }
             Err(channel::RecvError::Lagged(n)) => {
                 log::warn!("Output {name} is too slow, it lost the oldest {n} messages.");
+                probe.record_dropped(n);
                 Ok(ControlFlow::Continue(()))
             }
             Err(channel::RecvError::Closed) => {
@@ -83,8 +94,13 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                     }
                     control::TaskState::RunDiscard => {
                         // Resume the output but discard the data that is in the buffer.
-                        // The output will only see the measurements that are sent after this point.
-                        rx = rx.discard_pending();
+                        // The output will only see the measurements that are sent after the request,
+                        // thanks to the receiver subscribed by the request (if the channel supports it).
+                        let subscribed = config.discard_rx.lock().unwrap().take().and_then(Rx::from_enum);
+                        rx = match subscribed {
+                            Some(subscribed) => subscribed,
+                            None => rx.discard_pending(),
+                        };
                         receive = true;
                     }
                     control::TaskState::Pause => {
@@ -100,7 +116,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                 }
             },
             measurements = rx.recv(), if receive => {
-                let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), measurements)
+                let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), &probe, measurements)
                     .await
                     .map_err(|e| PipelineError::for_element(name.clone(), e))?;
                 if res.is_break() {
@@ -125,7 +141,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                     Err(RecvError::Lagged(n)) => format!("Err(Lagged({n}))"),
                 }
             );
-            let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), received)
+            let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), &probe, received)
                 .await
                 .map_err(|e| PipelineError::for_element(name.clone(), e))?;
             if res.is_break() {
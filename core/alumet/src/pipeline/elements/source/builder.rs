@@ -8,6 +8,10 @@ use crate::{
         online::{MetricReader, MetricSender},
         registry::MetricRegistry,
     },
+    pipeline::{
+        control::status::{StatusBoard, StatusReporter},
+        naming::SourceName,
+    },
 };
 
 use super::control::TaskState;
@@ -157,6 +161,9 @@ pub(super) struct BuildContext<'a> {
     pub(super) metrics: &'a MetricRegistry,
     pub(super) metrics_r: &'a MetricReader,
     pub(super) metrics_tx: &'a MetricSender,
+    pub(super) status: &'a StatusBoard,
+    /// Name of the source that is being built.
+    pub(super) name: Option<SourceName>,
 }
 
 /// Context accessible when building a managed source.
@@ -173,6 +180,8 @@ pub trait AutonomousSourceBuildContext {
     fn metrics_reader(&self) -> MetricReader;
     /// Returns a `MetricSender`, which allows to register new metrics while the pipeline is running.
     fn metrics_sender(&self) -> MetricSender;
+    /// Returns a `StatusReporter`, which allows the source to report its status for introspection.
+    fn status_reporter(&self) -> StatusReporter;
 }
 
 impl ManagedSourceBuildContext for BuildContext<'_> {
@@ -193,4 +202,12 @@ impl AutonomousSourceBuildContext for BuildContext<'_> {
     fn metrics_sender(&self) -> MetricSender {
         self.metrics_tx.clone()
     }
+
+    fn status_reporter(&self) -> StatusReporter {
+        let name = self
+            .name
+            .clone()
+            .expect("the name of the source should be set before building it");
+        self.status.reporter(name.into())
+    }
 }
@@ -12,10 +12,12 @@ use tokio_util::sync::CancellationToken;
 use crate::measurement::MeasurementBuffer;
 use crate::metrics::online::{MetricReader, MetricSender};
 use crate::pipeline::control::matching::SourceMatcher;
+use crate::pipeline::control::status::{ElementState, StateTracker, StatusBoard};
 use crate::pipeline::elements::source::builder::SourcePace;
 use crate::pipeline::elements::source::run::{run_autonomous, run_managed};
 use crate::pipeline::error::PipelineError;
 use crate::pipeline::matching::{ElementNamePattern, SourceNamePattern};
+use crate::pipeline::monitoring::PipelineMonitor;
 use crate::pipeline::naming::{ElementKind, ElementName};
 use crate::pipeline::naming::{SourceName, namespace::Namespace2};
 
@@ -99,6 +101,8 @@ pub(crate) struct SourceControl {
     tasks: TaskManager,
     /// Read-only and write-only access to the metrics.
     metrics: (MetricReader, MetricSender),
+    /// Status reported by the sources.
+    status: StatusBoard,
 }
 
 struct TaskManager {
@@ -108,6 +112,9 @@ struct TaskManager {
     /// Controllers for each source, by name.
     controllers: Vec<(SourceName, super::task_controller::SingleSourceController)>,
 
+    /// State of each source.
+    states: StateTracker<SourceName>,
+
     /// Cancelled when the pipeline shuts down.
     ///
     /// This token is the parent of the tokens of the autonomous sources.
@@ -127,9 +134,13 @@ struct TaskManager {
 
     /// Handle of the "priority" async runtime. Used for creating new sources.
     rt_priority: runtime::Handle,
+
+    /// Collects the statistics of the managed sources.
+    monitor: PipelineMonitor,
 }
 
 impl SourceControl {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         trigger_constraints: TriggerConstraints,
         shutdown_token: CancellationToken,
@@ -137,18 +148,23 @@ impl SourceControl {
         rt_normal: runtime::Handle,
         rt_priority: runtime::Handle,
         metrics: (MetricReader, MetricSender),
+        status: StatusBoard,
+        monitor: PipelineMonitor,
     ) -> Self {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
                 controllers: Vec::new(),
+                states: StateTracker::new(),
                 shutdown_token,
                 trigger_constraints,
                 in_tx,
                 rt_normal,
                 rt_priority,
+                monitor,
             },
             metrics,
+            status,
         }
     }
 
@@ -159,6 +175,8 @@ impl SourceControl {
                 metrics: &metrics,
                 metrics_r: &self.metrics.0,
                 metrics_tx: &self.metrics.1,
+                status: &self.status,
+                name: None,
             };
             let full_name = SourceName::new(plugin.clone(), name);
             self.tasks
@@ -181,6 +199,8 @@ impl SourceControl {
             metrics: &metrics,
             metrics_r: &self.metrics.0,
             metrics_tx: &self.metrics.1,
+            status: &self.status,
+            name: None,
         };
         let n_sources = builders.len();
         log::debug!("Creating {n_sources} sources...");
@@ -217,8 +237,8 @@ impl SourceControl {
     }
 
     pub async fn join_next_task(&mut self) -> Result<Result<(), PipelineError>, JoinError> {
-        match self.tasks.spawned_tasks.join_next().await {
-            Some(res) => res,
+        match self.tasks.spawned_tasks.join_next_with_id().await {
+            Some(res) => self.tasks.states.task_finished(res),
             None => unreachable!("join_next_task must be guarded by has_task to prevent an infinite loop"),
         }
     }
@@ -227,6 +247,25 @@ impl SourceControl {
         !self.tasks.spawned_tasks.is_empty()
     }
 
+    pub fn list_states(&self, buf: &mut Vec<(ElementName, ElementState)>, pat: &ElementNamePattern) {
+        let start = buf.len();
+        self.tasks.states.list(buf, pat);
+        // A running source that keeps failing is degraded.
+        for (name, state) in &mut buf[start..] {
+            if *state != ElementState::Running {
+                continue;
+            }
+            let controller = self
+                .tasks
+                .controllers
+                .iter()
+                .find(|(n, _)| &ElementName::from(n.clone()) == name);
+            if let Some(last_error) = controller.and_then(|(_, c)| c.last_error()) {
+                *state = ElementState::Degraded { last_error };
+            }
+        }
+    }
+
     pub fn list_elements(&self, buf: &mut Vec<ElementName>, pat: &ElementNamePattern) {
         if pat.kind == None || pat.kind == Some(ElementKind::Source) {
             buf.extend(self.tasks.controllers.iter().filter_map(|(name, _)| {
@@ -274,17 +313,17 @@ impl TaskManager {
         name: SourceName,
         builder: builder::SourceBuilder,
     ) -> anyhow::Result<()> {
-        /// Spawns a task on a JoinSet.
+        /// Spawns a task on a JoinSet and returns its id.
         /// When built with tokio unstable features, give a name to the task.
         fn spawn_task<R: Send + 'static>(
             set: &mut JoinSet<R>,
             source_task: impl Future<Output = R> + Send + 'static,
             runtime: &tokio::runtime::Handle,
             _name: SourceName,
-        ) {
+        ) -> tokio::task::Id {
             #[cfg(not(tokio_unstable))]
             {
-                set.spawn_on(source_task, runtime);
+                set.spawn_on(source_task, runtime).id()
             }
             #[cfg(tokio_unstable)]
             {
@@ -292,10 +331,13 @@ impl TaskManager {
                 // For now, this is an unstable API of tokio.
                 set.build_task()
                     .name(_name.to_string().as_str())
-                    .spawn_on(source_task, runtime);
+                    .spawn_on(source_task, runtime)
+                    .expect("the source task should spawn")
+                    .id()
             }
         }
 
+        ctx.name = Some(name.clone());
         match builder {
             builder::SourceBuilder::Managed(build, pace) => {
                 // Build the source
@@ -349,18 +391,24 @@ impl TaskManager {
                 log::trace!("new trigger created from the spec: {trigger:?}");
 
                 // Create a controller to control the async task.
+                let initial_state = match source.initial_state {
+                    TaskState::Run | TaskState::RunFlush => ElementState::Running,
+                    TaskState::Pause => ElementState::Paused,
+                    TaskState::Stop => ElementState::Stopped,
+                };
                 let (controller, config) = super::task_controller::new_managed(trigger, source.initial_state);
                 self.controllers.push((name.clone(), controller));
                 log::trace!("new controller initialized");
 
                 // Create the future (async task).
-                let source_task = run_managed(name.clone(), source.source, self.in_tx.clone(), config);
+                let probe = self.monitor.probe(name.clone());
+                let source_task = run_managed(name.clone(), source.source, self.in_tx.clone(), config, probe);
                 log::trace!("source task created: {name}");
 
-                match pace {
+                let task_id = match pace {
                     builder::SourcePace::Fast => {
                         // Spawn the future (execute the async task on the thread pool)
-                        spawn_task(&mut self.spawned_tasks, source_task, runtime, name.clone());
+                        spawn_task(&mut self.spawned_tasks, source_task, runtime, name.clone())
                     }
                     builder::SourcePace::Blocking => {
                         // Spawn a dedicated thread for this future.
@@ -385,9 +433,10 @@ impl TaskManager {
                             let res = result_rx.await.expect("sender dropped, did the thread panic?");
                             res // propagate the result to alumet control
                         };
-                        spawn_task(&mut self.spawned_tasks, thread_waiter, runtime, name.clone());
+                        spawn_task(&mut self.spawned_tasks, thread_waiter, runtime, name.clone())
                     }
-                }
+                };
+                self.states.spawned(name.clone(), task_id, initial_state);
             }
             builder::SourceBuilder::Autonomous(build) => {
                 let token = self.shutdown_token.child_token();
@@ -400,7 +449,8 @@ impl TaskManager {
                 self.controllers.push((name.clone(), controller));
                 log::trace!("new controller initialized");
 
-                spawn_task(&mut self.spawned_tasks, source_task, &self.rt_normal, name.clone());
+                let task_id = spawn_task(&mut self.spawned_tasks, source_task, &self.rt_normal, name.clone());
+                self.states.spawned(name.clone(), task_id, ElementState::Running);
             }
         };
         log::trace!("source task spawned on the runtime: {name}");
@@ -420,9 +470,18 @@ impl TaskManager {
             }
         };
 
+        let new_state = match command {
+            Reconfiguration::SetState(TaskState::Run) => Some(ElementState::Running),
+            Reconfiguration::SetState(TaskState::Pause) => Some(ElementState::Paused),
+            // the state changes when the task finishes
+            _ => None,
+        };
         for (name, source_controller) in &mut self.controllers {
             if msg.matcher.matches(name) {
                 source_controller.reconfigure(&command);
+                if let Some(state) = &new_state {
+                    self.states.set(name, state.clone());
+                }
             }
         }
     }
@@ -6,6 +6,7 @@ use tokio::sync::mpsc::error::TrySendError;
 
 use crate::measurement::{MeasurementBuffer, Timestamp};
 use crate::pipeline::error::PipelineError;
+use crate::pipeline::monitoring::ElementProbe;
 use crate::pipeline::naming::SourceName;
 use crate::pipeline::util::coop::TriggerCoop;
 
@@ -14,11 +15,15 @@ use super::error::PollError;
 use super::interface::{AutonomousSource, Source};
 use super::trigger::TriggerReason;
 
+/// Number of consecutive poll errors after which a source is considered to be degraded.
+const DEGRADED_AFTER_ERRORS: usize = 3;
+
 pub(crate) async fn run_managed(
     source_name: SourceName,
     mut source: Box<dyn Source>,
     tx: mpsc::Sender<MeasurementBuffer>,
     config: Arc<super::task_controller::SharedSourceConfig>,
+    probe: ElementProbe,
 ) -> Result<(), PipelineError> {
     /// Flushes the measurement and returns a new buffer.
     async fn flush(
@@ -118,6 +123,7 @@ pub(crate) async fn run_managed(
 
     // main loop
     let mut i = 1usize;
+    let mut consecutive_errors = 0usize;
     'run: loop {
         // Wait for the trigger. It can return for two reasons:
         // - "normal case": the underlying mechanism (e.g. timer) triggers <- this is the most likely case
@@ -135,16 +141,31 @@ pub(crate) async fn run_managed(
             TriggerReason::Triggered => {
                 // poll the source
                 let timestamp = Timestamp::now();
-                match source.poll(&mut buffer.as_accumulator(), timestamp) {
-                    Ok(()) => (),
+                let t0 = probe.start();
+                let prev_length = buffer.len();
+                let res = source.poll(&mut buffer.as_accumulator(), timestamp);
+                probe.record(t0, buffer.len().saturating_sub(prev_length));
+                match res {
+                    Ok(()) => {
+                        if consecutive_errors >= DEGRADED_AFTER_ERRORS {
+                            config.set_last_error(None);
+                        }
+                        consecutive_errors = 0;
+                    }
                     Err(PollError::NormalStop) => {
                         log::info!("Source {source_name} stopped itself.");
                         break 'run; // stop polling
                     }
                     Err(PollError::CanRetry(e)) => {
+                        probe.record_error();
                         log::error!("Non-fatal error when polling {source_name} (will retry): {e:#}");
+                        consecutive_errors += 1;
+                        if consecutive_errors >= DEGRADED_AFTER_ERRORS {
+                            config.set_last_error(Some(format!("{e:#}")));
+                        }
                     }
                     Err(PollError::Fatal(e)) => {
+                        probe.record_error();
                         log::error!("Fatal error when polling {source_name} (will stop running): {e:?}");
                         return Err(PipelineError::for_element(source_name, e));
                     }
@@ -30,6 +30,8 @@ pub struct SharedSourceConfig {
     pub atomic_state: AtomicU8,
     pub new_trigger: Mutex<Option<Trigger>>,
     pub manual_trigger: Option<ManualTrigger>,
+    /// The last poll error, set when the source has failed several times in a row.
+    pub last_error: Mutex<Option<String>>,
 }
 
 pub fn new_managed(
@@ -42,6 +44,7 @@ pub fn new_managed(
         atomic_state: AtomicU8::new(initial_state as u8),
         new_trigger: Mutex::new(Some(initial_trigger)),
         manual_trigger,
+        last_error: Mutex::new(None),
     });
     (SingleSourceController::Managed(config.clone()), config)
 }
@@ -54,6 +57,10 @@ impl SharedSourceConfig {
     pub fn take_new_trigger(&self) -> Option<Trigger> {
         self.new_trigger.lock().unwrap().take()
     }
+
+    pub fn set_last_error(&self, error: Option<String>) {
+        *self.last_error.lock().unwrap() = error;
+    }
 }
 
 impl SingleSourceController {
@@ -84,6 +91,14 @@ impl SingleSourceController {
         }
     }
 
+    /// Returns the last error of a source that keeps failing, if any.
+    pub fn last_error(&self) -> Option<String> {
+        match self {
+            SingleSourceController::Managed(shared) => shared.last_error.lock().unwrap().clone(),
+            SingleSourceController::Autonomous(_) => None,
+        }
+    }
+
     pub fn trigger_now(&mut self) {
         match self {
             SingleSourceController::Managed(shared) => {
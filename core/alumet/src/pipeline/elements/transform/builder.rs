@@ -24,6 +24,17 @@ use super::Transform;
 pub trait TransformBuilder: FnOnce(&mut dyn TransformBuildContext) -> anyhow::Result<Box<dyn Transform>> {}
 impl<F> TransformBuilder for F where F: FnOnce(&mut dyn TransformBuildContext) -> anyhow::Result<Box<dyn Transform>> {}
 
+/// Like [`TransformBuilder`] but with a [`Send`] bound on the builder.
+///
+/// Use this type in the pipeline control loop.
+pub struct SendTransformBuilder(pub Box<dyn TransformBuilder + Send>);
+
+impl std::fmt::Debug for SendTransformBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SendTransformBuilder").field(&"Box<dyn _>").finish()
+    }
+}
+
 pub(super) struct BuildContext<'a> {
     pub(super) metrics: &'a MetricRegistry,
 }
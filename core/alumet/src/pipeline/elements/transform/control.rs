@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use tokio::task::{JoinError, JoinSet};
 use tokio::{
     runtime,
@@ -12,27 +13,38 @@ use tokio::{
 use crate::measurement::MeasurementBuffer;
 use crate::metrics::online::MetricReader;
 use crate::pipeline::control::matching::TransformMatcher;
+use crate::pipeline::control::request::TransformPosition;
+use crate::pipeline::control::status::ElementState;
 use crate::pipeline::error::PipelineError;
 use crate::pipeline::matching::ElementNamePattern;
+use crate::pipeline::monitoring::PipelineMonitor;
 use crate::pipeline::naming::{ElementKind, ElementName, TransformName};
 
 use super::Transform;
-use super::builder::{BuildContext, TransformBuilder};
-use super::run::run_all_in_order;
+use super::builder::{BuildContext, SendTransformBuilder, TransformBuilder};
+use super::run::{ChainUpdate, ChainedTransform, run_all_in_order};
 
 /// Controls the transforms of a measurement pipeline.
-///
-/// There can be a maximum of 64 transforms for the moment.
 pub(crate) struct TransformControl {
     tasks: TaskManager,
+    /// Read-only access to the metrics, used to build new transforms.
+    /// `None` if the pipeline has no transform step.
+    metrics: Option<MetricReader>,
 }
 
 struct TaskManager {
     // Even though there is only one task, we don't use its JoinHandle directly,
     // because awaiting it consumes the task.
     spawned_tasks: JoinSet<Result<(), PipelineError>>,
-    active_bitset: Arc<AtomicU64>,
-    names_by_bitset_position: Vec<TransformName>,
+    /// Names of the transforms, in the order of execution, with their "enabled" flag.
+    chain: Vec<(TransformName, Arc<AtomicBool>)>,
+    /// State of the transforms after the end of the task that runs them.
+    final_states: HashMap<TransformName, ElementState>,
+    /// Sends modifications of the chain to the thread that runs the transforms.
+    /// `None` if the pipeline has no transform step.
+    chain_tx: Option<mpsc::UnboundedSender<ChainUpdate>>,
+    /// Collects the statistics of the transforms.
+    monitor: PipelineMonitor,
 }
 
 impl TransformControl {
@@ -40,9 +52,12 @@ impl TransformControl {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
-                active_bitset: Arc::new(AtomicU64::new(0)),
-                names_by_bitset_position: Vec::new(),
+                chain: Vec::new(),
+                final_states: HashMap::new(),
+                chain_tx: None,
+                monitor: PipelineMonitor::default(),
             },
+            metrics: None,
         }
     }
 
@@ -52,6 +67,7 @@ impl TransformControl {
         rx: mpsc::Receiver<MeasurementBuffer>,
         tx: broadcast::Sender<MeasurementBuffer>,
         rt_normal: &runtime::Handle,
+        monitor: PipelineMonitor,
     ) -> anyhow::Result<Self> {
         let metrics_r = metrics.blocking_read();
         let mut built = Vec::with_capacity(transforms.len());
@@ -62,18 +78,59 @@ impl TransformControl {
                 .inspect_err(|e| log::error!("Failed to build transform {full_name}: {e:#}"))?;
             built.push((full_name, transform));
         }
-        let tasks = TaskManager::spawn(built, metrics.clone(), rx, tx, rt_normal);
-        Ok(Self { tasks })
+        drop(metrics_r);
+        let tasks = TaskManager::spawn(built, metrics.clone(), rx, tx, rt_normal, monitor);
+        Ok(Self {
+            tasks,
+            metrics: Some(metrics),
+        })
     }
 
-    pub fn handle_message(&mut self, msg: ControlMessage) -> anyhow::Result<()> {
-        self.tasks.reconfigure(msg);
+    pub async fn create_transforms(
+        &mut self,
+        builders: Vec<(TransformName, TransformPosition, SendTransformBuilder)>,
+    ) -> anyhow::Result<()> {
+        let Some(metrics) = &self.metrics else {
+            return Err(anyhow!(
+                "cannot create transforms: the pipeline has been built without a transform step (see Builder::allow_simplified_pipeline)"
+            ));
+        };
+        let metrics = metrics.read().await;
+        let n = builders.len();
+        log::debug!("Creating {n} transforms...");
+        let mut n_errors = 0;
+        for (name, position, SendTransformBuilder(builder)) in builders {
+            let mut ctx = BuildContext { metrics: &metrics };
+            let _ = self
+                .tasks
+                .create_transform(&mut ctx, name.clone(), position, builder)
+                .inspect_err(|e| {
+                    log::error!("Error while creating transform '{name}': {e:?}");
+                    n_errors += 1;
+                });
+        }
+        if n_errors == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("failed to create {n_errors}/{n} transforms (see logs above)"))
+        }
+    }
+
+    pub async fn handle_message(&mut self, msg: ControlMessage) -> anyhow::Result<()> {
+        match msg {
+            ControlMessage::Configure(msg) => self.tasks.reconfigure(msg),
+            ControlMessage::CreateMany(msg) => self.create_transforms(msg.builders).await?,
+            ControlMessage::Remove(msg) => self.tasks.remove(msg)?,
+        }
         Ok(())
     }
 
     pub async fn join_next_task(&mut self) -> Result<Result<(), PipelineError>, JoinError> {
         match self.tasks.spawned_tasks.join_next().await {
-            Some(res) => res,
+            Some(res) => {
+                self.tasks.task_finished(&res);
+                res
+            }
             None => unreachable!("join_next_task must be guarded by has_task to prevent an infinite loop"),
         }
     }
@@ -95,9 +152,21 @@ impl TransformControl {
         }
     }
 
+    pub fn list_states(&self, buf: &mut Vec<(ElementName, ElementState)>, pat: &ElementNamePattern) {
+        buf.extend(self.tasks.chain.iter().filter_map(|(name, enabled)| {
+            let state = match self.tasks.final_states.get(name) {
+                Some(state) => state.clone(),
+                None if enabled.load(Ordering::Relaxed) => ElementState::Running,
+                None => ElementState::Paused,
+            };
+            let name: ElementName = name.to_owned().into();
+            pat.matches(&name).then_some((name, state))
+        }))
+    }
+
     pub fn list_elements(&self, buf: &mut Vec<ElementName>, pat: &ElementNamePattern) {
         if pat.kind == None || pat.kind == Some(ElementKind::Transform) {
-            buf.extend(self.tasks.names_by_bitset_position.iter().filter_map(|name| {
+            buf.extend(self.tasks.chain.iter().filter_map(|(name, _)| {
                 if pat.matches(name) {
                     Some(name.to_owned().into())
                 } else {
@@ -115,24 +184,31 @@ impl TaskManager {
         rx: mpsc::Receiver<MeasurementBuffer>,
         tx: broadcast::Sender<MeasurementBuffer>,
         rt_normal: &runtime::Handle,
+        monitor: PipelineMonitor,
     ) -> Self {
-        // Prepare the active bitset.
-        let mut active_bitset: u64 = 0;
-        let mut names_by_bitset_position = Vec::with_capacity(transforms.len());
-
-        for (i, (name, _)) in transforms.iter().enumerate() {
-            active_bitset |= 1 << i;
-            names_by_bitset_position.push(name.clone());
-        }
+        // Prepare the "enabled" flags.
+        let mut chain = Vec::with_capacity(transforms.len());
+        let transforms = transforms
+            .into_iter()
+            .map(|(name, transform)| {
+                let enabled = Arc::new(AtomicBool::new(true));
+                chain.push((name.clone(), enabled.clone()));
+                ChainedTransform {
+                    probe: monitor.probe(name.clone()),
+                    name,
+                    transform,
+                    enabled,
+                }
+            })
+            .collect();
 
         // Start the transforms thread.
         // Transforms functions can be CPU intensive, which is why they run on their own thread, isolated from the tokio runtime.
-        let active_bitset = Arc::new(AtomicU64::new(active_bitset));
-        let active_bitset_2 = Arc::clone(&active_bitset);
+        let (chain_tx, chain_rx) = mpsc::unbounded_channel();
         let (res_tx, res_rx) = tokio::sync::oneshot::channel();
         std::thread::spawn(move || {
             let res = match std::panic::catch_unwind(AssertUnwindSafe(move || {
-                run_all_in_order(transforms, rx, tx, active_bitset_2, metrics_r)
+                run_all_in_order(transforms, rx, tx, chain_rx, metrics_r)
             })) {
                 Ok(res) => res,
                 Err(panic) => Err(PipelineError::internal(anyhow::anyhow!(
@@ -151,39 +227,137 @@ impl TaskManager {
         set.spawn_on(thread_waiter, rt_normal);
         Self {
             spawned_tasks: set,
-            active_bitset,
-            names_by_bitset_position,
+            chain,
+            final_states: HashMap::new(),
+            chain_tx: Some(chain_tx),
+            monitor,
         }
     }
 
-    fn reconfigure(&mut self, msg: ControlMessage) {
-        let mut bitset = self.active_bitset.load(Ordering::Relaxed);
-        for (i, name) in self.names_by_bitset_position.iter().enumerate() {
+    fn create_transform(
+        &mut self,
+        ctx: &mut BuildContext,
+        name: TransformName,
+        position: TransformPosition,
+        builder: Box<dyn TransformBuilder + Send>,
+    ) -> anyhow::Result<()> {
+        let chain_tx = self
+            .chain_tx
+            .as_ref()
+            .context("the pipeline has been built without a transform step")?;
+        if self.chain.iter().any(|(n, _)| n == &name) {
+            return Err(anyhow!("a transform named {name} already exists"));
+        }
+
+        // Find where to insert the transform before building it, to fail early.
+        let index = match &position {
+            TransformPosition::First => 0,
+            TransformPosition::Last => self.chain.len(),
+            TransformPosition::Before(other) => self.position_of(other)?,
+            TransformPosition::After(other) => self.position_of(other)? + 1,
+        };
+
+        // Build the transform.
+        let transform = builder(ctx).context("transform creation failed")?;
+
+        // Send it to the transforms thread, which will insert it before processing any other measurements.
+        let enabled = Arc::new(AtomicBool::new(true));
+        let element = ChainedTransform {
+            name: name.clone(),
+            transform,
+            enabled: enabled.clone(),
+            probe: self.monitor.probe(name.clone()),
+        };
+        chain_tx
+            .send(ChainUpdate::Insert { index, element })
+            .map_err(|_| anyhow!("the transforms thread has stopped"))?;
+        self.chain.insert(index, (name, enabled));
+        Ok(())
+    }
+
+    /// Updates the state of the transforms after the end of their task.
+    ///
+    /// Since all the transforms run in the same task, they all stop at the same time.
+    /// If the task has failed because of a transform, the other ones are considered to be stopped.
+    fn task_finished(&mut self, res: &Result<Result<(), PipelineError>, JoinError>) {
+        let culprit = match res {
+            Ok(Err(e)) => e.element().cloned(),
+            _ => None,
+        };
+        let state = ElementState::after_task(res);
+        for (name, _) in &self.chain {
+            let state = match &culprit {
+                Some(culprit) if culprit != &ElementName::from(name.clone()) => ElementState::Stopped,
+                _ => state.clone(),
+            };
+            self.final_states.insert(name.clone(), state);
+        }
+    }
+
+    fn position_of(&self, name: &TransformName) -> anyhow::Result<usize> {
+        self.chain
+            .iter()
+            .position(|(n, _)| n == name)
+            .with_context(|| format!("transform not found: {name}"))
+    }
+
+    fn remove(&mut self, msg: RemoveMessage) -> anyhow::Result<()> {
+        let Some(chain_tx) = &self.chain_tx else {
+            return Ok(()); // no transform to remove
+        };
+        let mut res = Ok(());
+        self.chain.retain(|(name, _)| {
             if msg.matcher.matches(name) {
-                match msg.new_state {
-                    TaskState::Enabled => {
-                        bitset |= 1 << i;
-                    }
-                    TaskState::Disabled => {
-                        bitset &= !(1 << i);
-                    }
+                if chain_tx.send(ChainUpdate::Remove { name: name.clone() }).is_err() {
+                    res = Err(anyhow!("the transforms thread has stopped"));
                 }
+                false
+            } else {
+                true
+            }
+        });
+        res
+    }
+
+    fn reconfigure(&mut self, msg: ConfigureMessage) {
+        for (name, enabled) in &self.chain {
+            if msg.matcher.matches(name) {
+                let enable = msg.new_state == TaskState::Enabled;
+                enabled.store(enable, Ordering::Relaxed);
+                log::trace!("transform {name} enabled: {enable}");
             }
         }
-        self.active_bitset.store(bitset, Ordering::Relaxed);
-        log::trace!("new 'enabled' bitset: {bitset}");
     }
 }
 
 /// A control message for transforms.
 #[derive(Debug)]
-pub struct ControlMessage {
+pub enum ControlMessage {
+    Configure(ConfigureMessage),
+    CreateMany(CreateManyMessage),
+    Remove(RemoveMessage),
+}
+
+#[derive(Debug)]
+pub struct ConfigureMessage {
     /// Which transform(s) to reconfigure.
     pub matcher: TransformMatcher,
     /// The new state to apply to the selected transform(s).
     pub new_state: TaskState,
 }
 
+#[derive(Debug)]
+pub struct CreateManyMessage {
+    /// The transforms to create, in order.
+    pub builders: Vec<(TransformName, TransformPosition, SendTransformBuilder)>,
+}
+
+#[derive(Debug)]
+pub struct RemoveMessage {
+    /// Which transform(s) to remove.
+    pub matcher: TransformMatcher,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum TaskState {
     Enabled,
@@ -2,7 +2,7 @@
 
 use std::sync::{
     Arc,
-    atomic::{AtomicU64, Ordering},
+    atomic::{AtomicBool, Ordering},
 };
 
 use anyhow::Context;
@@ -11,32 +11,69 @@ use tokio::sync::{broadcast, mpsc};
 use crate::{
     measurement::MeasurementBuffer,
     metrics::online::MetricReader,
-    pipeline::{error::PipelineError, naming::TransformName},
+    pipeline::{error::PipelineError, monitoring::ElementProbe, naming::TransformName},
 };
 
 use super::{Transform, TransformContext, error::TransformError};
 
-pub fn run_all_in_order(
-    mut transforms: Vec<(TransformName, Box<dyn Transform>)>,
+/// A transform that is part of the chain of transforms.
+pub(crate) struct ChainedTransform {
+    pub name: TransformName,
+    pub transform: Box<dyn Transform>,
+    /// Set to `false` to skip the transform without removing it from the chain.
+    pub enabled: Arc<AtomicBool>,
+    /// Records the statistics of the transform (if self-monitoring is enabled).
+    pub probe: ElementProbe,
+}
+
+/// A modification of the chain of transforms, sent by the control loop while the pipeline is running.
+pub(crate) enum ChainUpdate {
+    /// Inserts a new transform at the given index.
+    Insert { index: usize, element: ChainedTransform },
+    /// Removes the transform with the given name from the chain.
+    Remove { name: TransformName },
+}
+
+/// What the transforms thread has received.
+enum Received {
+    Measurements(Option<MeasurementBuffer>),
+    Update(Option<ChainUpdate>),
+}
+
+pub(crate) fn run_all_in_order(
+    mut transforms: Vec<ChainedTransform>,
     mut rx: mpsc::Receiver<MeasurementBuffer>,
     tx: broadcast::Sender<MeasurementBuffer>,
-    active_flags: Arc<AtomicU64>,
+    mut updates: mpsc::UnboundedReceiver<ChainUpdate>,
     metrics_reader: MetricReader,
 ) -> Result<(), PipelineError> {
-    log::trace!(
-        "Running transforms: {}",
-        transforms
-            .iter()
-            .map(|(name, _)| name.to_string())
-            .collect::<Vec<_>>()
-            .join(", ")
-    );
+    log::trace!("Running transforms: {}", chain_to_string(&transforms));
+    let mut updates_open = true;
     loop {
-        if let Some(mut measurements) = rx.blocking_recv() {
-            // Update the list of active transforms.
-            let current_flags = active_flags.load(Ordering::Relaxed);
-            log::trace!("current 'enabled' bitset: {current_flags}");
-
+        // Wait for the next measurements, or for a modification of the chain, whichever comes first.
+        // Modifications are not delayed until the next measurements: an idle pipeline must finish
+        // the removed transforms too.
+        let received = futures::executor::block_on(async {
+            tokio::select! {
+                biased;
+                update = updates.recv(), if updates_open => Received::Update(update),
+                measurements = rx.recv() => Received::Measurements(measurements),
+            }
+        });
+        let measurements = match received {
+            Received::Update(None) => {
+                updates_open = false;
+                continue;
+            }
+            Received::Update(Some(update)) => {
+                let metrics = &metrics_reader.blocking_read();
+                let ctx = TransformContext { metrics };
+                apply_updates(&mut transforms, Some(update), &mut updates, &ctx);
+                continue;
+            }
+            Received::Measurements(measurements) => measurements,
+        };
+        if let Some(mut measurements) = measurements {
             // Build the transform context.
             // This will block the publication of any modification to the MetricRegistry until the context is dropped.
             // TODO this need to change: if transforms take a "long" time to execute, the registry will be blocked for a long time,
@@ -46,13 +83,26 @@ pub fn run_all_in_order(
             let metrics = &metrics_reader.blocking_read();
             let ctx = TransformContext { metrics };
 
+            // Apply the modifications of the chain that have been requested since the last buffer.
+            apply_updates(&mut transforms, None, &mut updates, &ctx);
+
             // Run the enabled transforms. If one of them fails, the ability to continue running depends on the error type.
-            for (i, (name, t)) in &mut transforms.iter_mut().enumerate() {
-                let t_flag = 1 << i;
-                if current_flags & t_flag != 0 {
-                    match t.apply(&mut measurements, &ctx) {
+            for ChainedTransform {
+                name,
+                transform: t,
+                enabled,
+                probe,
+            } in transforms.iter_mut()
+            {
+                if enabled.load(Ordering::Relaxed) {
+                    let t0 = probe.start();
+                    let n_measurements = measurements.len();
+                    let res = t.apply(&mut measurements, &ctx);
+                    probe.record(t0, n_measurements);
+                    match res {
                         Ok(()) => (),
                         Err(TransformError::UnexpectedInput(e)) => {
+                            probe.record_error();
                             log::error!("Transform {name} received unexpected measurements: {e:#}");
                             // TODO should we really continue here? Transforms are not necessarily independent…
                         }
@@ -76,18 +126,71 @@ pub fn run_all_in_order(
     // the channel has been closed, which means that the pipeline is shutting down
     let metrics = &metrics_reader.blocking_read();
     let ctx = TransformContext { metrics };
+    apply_updates(&mut transforms, None, &mut updates, &ctx);
     let mut err = Ok(());
-    for (name, trans) in transforms.iter_mut() {
-        match trans.finish(&ctx) {
-            Ok(()) => (),
-            Err(TransformError::UnexpectedInput(e)) => {
-                log::error!("Transform {name} received unexpected measurements during finish: {e:#}");
+    for ChainedTransform { name, transform, .. } in transforms.iter_mut() {
+        if let Err(e) = finish_transform(name, transform.as_mut(), &ctx) {
+            err = Err(e);
+        }
+    }
+    err
+}
+
+/// Applies `first` (if any) and the pending modifications of the chain, in the order in which they have been sent.
+///
+/// Removed transforms are finished with [`Transform::finish`].
+fn apply_updates(
+    transforms: &mut Vec<ChainedTransform>,
+    first: Option<ChainUpdate>,
+    updates: &mut mpsc::UnboundedReceiver<ChainUpdate>,
+    ctx: &TransformContext,
+) {
+    let mut modified = false;
+    let pending = std::iter::from_fn(|| updates.try_recv().ok());
+    for update in first.into_iter().chain(pending) {
+        modified = true;
+        match update {
+            ChainUpdate::Insert { index, element } => {
+                log::debug!("Inserting transform {} at position {index}", element.name);
+                transforms.insert(index.min(transforms.len()), element);
             }
-            Err(TransformError::Fatal(e)) => {
-                log::error!("Fatal error in transform {name} during finish: {e:?}");
-                err = Err(PipelineError::for_element(name.to_owned(), e));
+            ChainUpdate::Remove { name } => {
+                if let Some(i) = transforms.iter().position(|t| t.name == name) {
+                    log::debug!("Removing transform {name}");
+                    let mut removed = transforms.remove(i);
+                    // the transform task continues, even if the removed transform fails to finish
+                    let _ = finish_transform(&removed.name, removed.transform.as_mut(), ctx);
+                }
             }
         }
     }
-    err
+    if modified {
+        log::trace!("New chain of transforms: {}", chain_to_string(transforms));
+    }
+}
+
+fn finish_transform(
+    name: &TransformName,
+    transform: &mut dyn Transform,
+    ctx: &TransformContext,
+) -> Result<(), PipelineError> {
+    match transform.finish(ctx) {
+        Ok(()) => Ok(()),
+        Err(TransformError::UnexpectedInput(e)) => {
+            log::error!("Transform {name} received unexpected measurements during finish: {e:#}");
+            Ok(())
+        }
+        Err(TransformError::Fatal(e)) => {
+            log::error!("Fatal error in transform {name} during finish: {e:?}");
+            Err(PipelineError::for_element(name.to_owned(), e))
+        }
+    }
+}
+
+fn chain_to_string(transforms: &[ChainedTransform]) -> String {
+    transforms
+        .iter()
+        .map(|t| t.name.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
@@ -0,0 +1,432 @@
+//! Self-monitoring of the measurement pipeline.
+//!
+//! When enabled with [`Builder::enable_self_monitoring`](super::Builder::enable_self_monitoring),
+//! the pipeline keeps statistics about its own elements:
+//! - the latency of [`Source::poll`], [`Transform::apply`](super::Transform::apply) and
+//!   [`Output::write`](super::Output::write),
+//! - the number of measurements that go through each element,
+//! - the number of errors and of buffers that have been dropped by slow outputs,
+//! - the occupancy of the channels that connect the steps of the pipeline.
+//!
+//! These statistics are periodically injected in the pipeline as regular measurement points,
+//! by the source `alumet/self-monitoring`. Therefore, any output can store them.
+//! The element is identified by the attribute `element`, and the channel by the attribute `channel`.
+//!
+//! Only the managed sources and the blocking outputs are monitored: autonomous sources and
+//! async outputs handle the measurements on their own.
+
+use std::sync::{
+    Arc, Mutex, Weak,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::measurement::{
+    MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType,
+    WrappedMeasurementValue,
+};
+use crate::metrics::def::{Metric, RawMetricId};
+use crate::metrics::duplicate::{DuplicateCriteria, DuplicateReaction};
+use crate::metrics::error::MetricCreationError;
+use crate::metrics::registry::MetricRegistry;
+use crate::resources::{Resource, ResourceConsumer};
+use crate::units::{PrefixedUnit, Unit};
+
+use super::Source;
+use super::elements::source::error::PollError;
+use super::naming::ElementName;
+
+/// Name of the source that produces the self-monitoring measurements, in the plugin `alumet`.
+pub const SOURCE_NAME: &str = "self-monitoring";
+
+/// Collects the statistics of the pipeline elements.
+///
+/// A disabled monitor (the default) hands out probes that do nothing.
+#[derive(Clone, Default)]
+pub(crate) struct PipelineMonitor(Option<Arc<MonitorState>>);
+
+#[derive(Default)]
+struct MonitorState {
+    elements: Mutex<Vec<(ElementName, Weak<ElementStats>)>>,
+    channels: Mutex<Vec<ChannelProbe>>,
+}
+
+/// Allows a pipeline element to record its activity.
+///
+/// The statistics of the element are no longer reported once its probe has been dropped.
+#[derive(Clone, Default)]
+pub(crate) struct ElementProbe(Option<Arc<ElementStats>>);
+
+/// Statistics of one element, since the last report.
+#[derive(Default)]
+struct ElementStats {
+    calls: AtomicU64,
+    latency_total_ns: AtomicU64,
+    latency_max_ns: AtomicU64,
+    measurements: AtomicU64,
+    errors: AtomicU64,
+    dropped_buffers: AtomicU64,
+}
+
+/// Snapshot of [`ElementStats`], taken when the statistics are reported.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ElementReport {
+    calls: u64,
+    latency_total: Duration,
+    latency_max: Duration,
+    measurements: u64,
+    errors: u64,
+    dropped_buffers: u64,
+}
+
+/// A channel of the pipeline, whose occupancy is reported.
+///
+/// Weak senders are used in order not to keep the channel open during the shutdown.
+struct ChannelProbe {
+    name: &'static str,
+    sender: WeakSender,
+}
+
+enum WeakSender {
+    Single(mpsc::WeakSender<MeasurementBuffer>),
+    Broadcast(broadcast::WeakSender<MeasurementBuffer>, usize),
+}
+
+impl PipelineMonitor {
+    /// Creates a monitor that collects statistics.
+    pub fn enabled() -> Self {
+        Self(Some(Arc::new(MonitorState::default())))
+    }
+
+    /// Returns a probe for the given element.
+    pub fn probe(&self, name: impl Into<ElementName>) -> ElementProbe {
+        match &self.0 {
+            Some(state) => {
+                let stats = Arc::new(ElementStats::default());
+                let mut elements = state.elements.lock().unwrap();
+                elements.push((name.into(), Arc::downgrade(&stats)));
+                ElementProbe(Some(stats))
+            }
+            None => ElementProbe(None),
+        }
+    }
+
+    /// Reports the occupancy of the channel `sources -> transforms` (or `sources -> output`).
+    pub fn watch_source_channel(&self, tx: &mpsc::Sender<MeasurementBuffer>) {
+        self.watch_channel("sources", WeakSender::Single(tx.downgrade()));
+    }
+
+    /// Reports the occupancy of the channel `transforms -> outputs`.
+    pub fn watch_output_channel(&self, tx: &broadcast::Sender<MeasurementBuffer>, capacity: usize) {
+        self.watch_channel("outputs", WeakSender::Broadcast(tx.downgrade(), capacity));
+    }
+
+    fn watch_channel(&self, name: &'static str, sender: WeakSender) {
+        if let Some(state) = &self.0 {
+            state.channels.lock().unwrap().push(ChannelProbe { name, sender });
+        }
+    }
+
+    /// Takes the statistics of the elements that are still alive and resets them.
+    fn take_reports(&self) -> Vec<(ElementName, ElementReport)> {
+        let Some(state) = &self.0 else {
+            return Vec::new();
+        };
+        let mut elements = state.elements.lock().unwrap();
+        elements.retain(|(_, stats)| stats.strong_count() > 0);
+        elements
+            .iter()
+            .filter_map(|(name, stats)| Some((name.clone(), stats.upgrade()?.take())))
+            .collect()
+    }
+
+    /// Returns the number of buffers in each channel, and the capacity of the channel.
+    fn channel_occupancy(&self) -> Vec<(&'static str, usize, usize)> {
+        let Some(state) = &self.0 else {
+            return Vec::new();
+        };
+        let channels = state.channels.lock().unwrap();
+        channels
+            .iter()
+            .filter_map(|c| {
+                let (len, capacity) = match &c.sender {
+                    WeakSender::Single(tx) => {
+                        let tx = tx.upgrade()?;
+                        (tx.max_capacity() - tx.capacity(), tx.max_capacity())
+                    }
+                    WeakSender::Broadcast(tx, capacity) => (tx.upgrade()?.len(), *capacity),
+                };
+                Some((c.name, len, capacity))
+            })
+            .collect()
+    }
+}
+
+impl ElementProbe {
+    /// Starts measuring the latency of an operation.
+    ///
+    /// Returns `None` if the monitoring is disabled, in order to avoid the cost of reading the clock.
+    pub fn start(&self) -> Option<Instant> {
+        self.0.as_ref().map(|_| Instant::now())
+    }
+
+    /// Records an operation that has been started with [`start`](Self::start) and has
+    /// processed `measurements` measurements.
+    pub fn record(&self, start: Option<Instant>, measurements: usize) {
+        if let (Some(stats), Some(t0)) = (&self.0, start) {
+            let latency = u64::try_from(t0.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            stats.calls.fetch_add(1, Ordering::Relaxed);
+            stats.latency_total_ns.fetch_add(latency, Ordering::Relaxed);
+            stats.latency_max_ns.fetch_max(latency, Ordering::Relaxed);
+            stats.measurements.fetch_add(measurements as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Records an error returned by the element.
+    pub fn record_error(&self) {
+        if let Some(stats) = &self.0 {
+            stats.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records buffers that have been lost before reaching the element.
+    pub fn record_dropped(&self, n_buffers: u64) {
+        if let Some(stats) = &self.0 {
+            stats.dropped_buffers.fetch_add(n_buffers, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ElementStats {
+    fn take(&self) -> ElementReport {
+        ElementReport {
+            calls: self.calls.swap(0, Ordering::Relaxed),
+            latency_total: Duration::from_nanos(self.latency_total_ns.swap(0, Ordering::Relaxed)),
+            latency_max: Duration::from_nanos(self.latency_max_ns.swap(0, Ordering::Relaxed)),
+            measurements: self.measurements.swap(0, Ordering::Relaxed),
+            errors: self.errors.swap(0, Ordering::Relaxed),
+            dropped_buffers: self.dropped_buffers.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Metrics of the self-monitoring measurements.
+#[derive(Clone)]
+pub(crate) struct MonitoringMetrics {
+    calls: RawMetricId,
+    latency_mean: RawMetricId,
+    latency_max: RawMetricId,
+    measurements: RawMetricId,
+    errors: RawMetricId,
+    dropped_buffers: RawMetricId,
+    channel_occupancy: RawMetricId,
+}
+
+impl MonitoringMetrics {
+    /// Registers the self-monitoring metrics.
+    pub fn register(registry: &mut MetricRegistry) -> Result<Self, MetricCreationError> {
+        let mut register = |name: &str, value_type, unit: Unit, description: &str| {
+            let m = Metric {
+                name: name.to_owned(),
+                description: description.to_owned(),
+                value_type,
+                unit: PrefixedUnit::from(unit),
+            };
+            registry.register(m, DuplicateCriteria::Incompatible, DuplicateReaction::Error)
+        };
+        Ok(Self {
+            calls: register(
+                "alumet_element_calls",
+                WrappedMeasurementType::U64,
+                Unit::Unity,
+                "number of polls (sources), applications (transforms) or writes (outputs) since the last report",
+            )?,
+            latency_mean: register(
+                "alumet_element_latency_mean",
+                WrappedMeasurementType::F64,
+                Unit::Second,
+                "mean duration of a poll, application or write since the last report",
+            )?,
+            latency_max: register(
+                "alumet_element_latency_max",
+                WrappedMeasurementType::F64,
+                Unit::Second,
+                "maximum duration of a poll, application or write since the last report",
+            )?,
+            measurements: register(
+                "alumet_element_measurements",
+                WrappedMeasurementType::U64,
+                Unit::Unity,
+                "number of measurements produced (sources) or received (transforms, outputs) since the last report",
+            )?,
+            errors: register(
+                "alumet_element_errors",
+                WrappedMeasurementType::U64,
+                Unit::Unity,
+                "number of errors returned by the element since the last report",
+            )?,
+            dropped_buffers: register(
+                "alumet_element_dropped_buffers",
+                WrappedMeasurementType::U64,
+                Unit::Unity,
+                "number of measurement buffers lost by an output that is too slow, since the last report",
+            )?,
+            channel_occupancy: register(
+                "alumet_channel_occupancy",
+                WrappedMeasurementType::U64,
+                Unit::Unity,
+                "number of measurement buffers waiting in a channel of the pipeline",
+            )?,
+        })
+    }
+}
+
+/// Source that injects the statistics of the pipeline in the pipeline itself.
+pub(crate) struct MonitoringSource {
+    monitor: PipelineMonitor,
+    metrics: MonitoringMetrics,
+}
+
+impl MonitoringSource {
+    pub fn new(monitor: PipelineMonitor, metrics: MonitoringMetrics) -> Self {
+        Self { monitor, metrics }
+    }
+}
+
+impl Source for MonitoringSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let m = &self.metrics;
+        let point = |metric: RawMetricId, value: WrappedMeasurementValue| {
+            MeasurementPoint::new_untyped(
+                timestamp,
+                metric,
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                value,
+            )
+        };
+
+        for (name, report) in self.monitor.take_reports() {
+            let element = name.to_string();
+            let mut push =
+                |metric, value| measurements.push(point(metric, value).with_attr("element", element.clone()));
+            push(m.calls, WrappedMeasurementValue::U64(report.calls));
+            push(m.measurements, WrappedMeasurementValue::U64(report.measurements));
+            push(m.errors, WrappedMeasurementValue::U64(report.errors));
+            push(m.dropped_buffers, WrappedMeasurementValue::U64(report.dropped_buffers));
+            if report.calls > 0 {
+                let mean = report.latency_total.as_secs_f64() / report.calls as f64;
+                push(m.latency_mean, WrappedMeasurementValue::F64(mean));
+                push(
+                    m.latency_max,
+                    WrappedMeasurementValue::F64(report.latency_max.as_secs_f64()),
+                );
+            }
+        }
+
+        for (channel, len, capacity) in self.monitor.channel_occupancy() {
+            measurements.push(
+                point(m.channel_occupancy, WrappedMeasurementValue::U64(len as u64))
+                    .with_attr("channel", channel)
+                    .with_attr("capacity", capacity as u64),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+
+    use crate::measurement::{MeasurementBuffer, Timestamp, WrappedMeasurementValue};
+    use crate::metrics::registry::MetricRegistry;
+    use crate::pipeline::Source;
+    use crate::pipeline::naming::{ElementKind, ElementName};
+
+    use super::{MonitoringMetrics, MonitoringSource, PipelineMonitor};
+
+    #[test]
+    fn disabled() {
+        let monitor = PipelineMonitor::default();
+        let probe = monitor.probe(ElementName::from_str(ElementKind::Source, "plugin", "source"));
+        assert!(probe.start().is_none());
+        probe.record(None, 12);
+        probe.record_error();
+        assert!(monitor.take_reports().is_empty());
+    }
+
+    #[test]
+    fn report_and_reset() {
+        let monitor = PipelineMonitor::enabled();
+        let name = ElementName::from_str(ElementKind::Transform, "plugin", "transform");
+        let probe = monitor.probe(name.clone());
+
+        let t0 = probe.start();
+        assert!(t0.is_some());
+        std::thread::sleep(Duration::from_millis(2));
+        probe.record(t0, 10);
+        probe.record(probe.start(), 5);
+        probe.record_error();
+        probe.record_dropped(3);
+
+        let reports = monitor.take_reports();
+        assert_eq!(reports.len(), 1);
+        let (report_name, report) = &reports[0];
+        assert_eq!(report_name, &name);
+        assert_eq!(report.calls, 2);
+        assert_eq!(report.measurements, 15);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.dropped_buffers, 3);
+        assert!(report.latency_max >= Duration::from_millis(2));
+        assert!(report.latency_total >= report.latency_max);
+
+        // the statistics are reset after each report
+        let (_, report) = &monitor.take_reports()[0];
+        assert_eq!(report.calls, 0);
+        assert_eq!(report.latency_max, Duration::ZERO);
+
+        // the element is forgotten when its probe is dropped
+        drop(probe);
+        assert!(monitor.take_reports().is_empty());
+    }
+
+    #[test]
+    fn source_produces_points() {
+        let mut registry = MetricRegistry::new();
+        let metrics = MonitoringMetrics::register(&mut registry).unwrap();
+        let monitor = PipelineMonitor::enabled();
+        let probe = monitor.probe(ElementName::from_str(ElementKind::Output, "plugin", "output"));
+        probe.record(probe.start(), 4);
+
+        let (tx, _rx) = mpsc::channel(8);
+        tx.try_send(MeasurementBuffer::new()).unwrap();
+        monitor.watch_source_channel(&tx);
+
+        let mut source = MonitoringSource::new(monitor, metrics.clone());
+        let mut buf = MeasurementBuffer::new();
+        source.poll(&mut buf.as_accumulator(), Timestamp::now()).unwrap();
+
+        // 6 points for the output (calls, measurements, errors, dropped, latency mean and max) + 1 for the channel
+        assert_eq!(buf.len(), 7);
+        let occupancy = buf.iter().find(|p| p.metric == metrics.channel_occupancy).unwrap();
+        assert_eq!(occupancy.value, WrappedMeasurementValue::U64(1));
+        let calls = buf.iter().find(|p| p.metric == metrics.calls).unwrap();
+        assert_eq!(calls.value, WrappedMeasurementValue::U64(1));
+        assert!(
+            calls
+                .attributes()
+                .any(|(k, v)| k == "element" && v.to_string() == "outputs/plugin/output")
+        );
+
+        // the channel is no longer reported once it is closed
+        drop(tx);
+        let mut buf = MeasurementBuffer::new();
+        source.poll(&mut buf.as_accumulator(), Timestamp::now()).unwrap();
+        assert!(buf.iter().all(|p| p.metric != metrics.channel_occupancy));
+    }
+}
@@ -3,7 +3,7 @@ use thiserror::Error;
 
 use crate::pipeline::naming::ElementKind;
 
-use super::matching::StringPattern;
+use super::matching::{ElementNamePattern, StringPattern};
 
 /// Parses a string to an `ElementKind`.
 ///
@@ -31,6 +31,41 @@ pub enum NamePatternParseError {
     Empty,
 }
 
+#[derive(Debug, Error)]
+pub enum ElementPatternParseError {
+    #[error("bad kind: '{0}'")]
+    Kind(String, #[source] KindParseError),
+    #[error("bad pattern: '{0}'")]
+    Name(String, #[source] NamePatternParseError),
+    #[error("bad pattern, expected kind/plugin/element but got '{0}'")]
+    Format(String),
+}
+
+impl FromStr for ElementNamePattern {
+    type Err = ElementPatternParseError;
+
+    /// Parses an `ElementNamePattern` of the form `kind/plugin/element` or `kind`.
+    ///
+    /// The kind is parsed with [`parse_kind`], the plugin and element with [`StringPattern::from_str`].
+    /// For instance, `sources/rapl/*` matches every source of the `rapl` plugin.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_name =
+            |pat: &str| StringPattern::from_str(pat).map_err(|e| ElementPatternParseError::Name(pat.to_owned(), e));
+        let parts: Vec<_> = s.splitn(3, '/').collect();
+        let (kind, names) = match parts[..] {
+            [kind, plugin, element] => (kind, Some((plugin, element))),
+            [kind] => (kind, None),
+            _ => return Err(ElementPatternParseError::Format(s.to_owned())),
+        };
+        let kind = parse_kind(kind).map_err(|e| ElementPatternParseError::Kind(kind.to_owned(), e))?;
+        let (plugin, element) = match names {
+            Some((plugin, element)) => (parse_name(plugin)?, parse_name(element)?),
+            None => (StringPattern::Any, StringPattern::Any),
+        };
+        Ok(ElementNamePattern { kind, plugin, element })
+    }
+}
+
 impl FromStr for StringPattern {
     type Err = NamePatternParseError;
 
@@ -68,6 +103,7 @@ impl FromStr for StringPattern {
 #[cfg(test)]
 mod tests {
     use super::{NamePatternParseError, StringPattern};
+    use crate::pipeline::{matching::ElementNamePattern, naming::ElementKind};
     use std::str::FromStr;
 
     #[test]
@@ -90,4 +126,37 @@ mod tests {
         assert_eq!(StringPattern::from_str(""), Err(NamePatternParseError::Empty));
         Ok(())
     }
+
+    #[test]
+    fn parse_element_pattern() -> anyhow::Result<()> {
+        assert_eq!(
+            ElementNamePattern::from_str("sources/rapl/*")?,
+            ElementNamePattern {
+                kind: Some(ElementKind::Source),
+                plugin: StringPattern::Exact(String::from("rapl")),
+                element: StringPattern::Any,
+            }
+        );
+        assert_eq!(
+            ElementNamePattern::from_str("out")?,
+            ElementNamePattern {
+                kind: Some(ElementKind::Output),
+                plugin: StringPattern::Any,
+                element: StringPattern::Any,
+            }
+        );
+        assert_eq!(
+            ElementNamePattern::from_str("source/without-element")
+                .unwrap_err()
+                .to_string(),
+            "bad pattern, expected kind/plugin/element but got 'source/without-element'"
+        );
+        assert_eq!(
+            ElementNamePattern::from_str("bad/plugin/element")
+                .unwrap_err()
+                .to_string(),
+            "bad kind: 'bad'"
+        );
+        Ok(())
+    }
 }
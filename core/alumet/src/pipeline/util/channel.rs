@@ -11,6 +11,10 @@ use crate::measurement::MeasurementBuffer;
 pub trait MeasurementReceiver {
     fn recv(&mut self) -> impl Future<Output = Result<MeasurementBuffer, RecvError>> + Send;
     fn discard_pending(self) -> Self;
+    /// Returns the inner receiver if it is of type `Self`.
+    fn from_enum(rx: ReceiverEnum) -> Option<Self>
+    where
+        Self: Sized;
     fn into_stream(self) -> impl Stream<Item = Result<MeasurementBuffer, StreamRecvError>>;
 }
 
@@ -53,6 +57,13 @@ impl MeasurementReceiver for broadcast::Receiver<MeasurementBuffer> {
         self.resubscribe()
     }
 
+    fn from_enum(rx: ReceiverEnum) -> Option<Self> {
+        match rx {
+            ReceiverEnum::Broadcast(rx) => Some(rx),
+            ReceiverEnum::Single(_) => None,
+        }
+    }
+
     fn into_stream(self) -> impl Stream<Item = Result<MeasurementBuffer, StreamRecvError>> {
         use tokio_stream::StreamExt;
         use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
@@ -80,6 +91,13 @@ impl MeasurementReceiver for mpsc::Receiver<MeasurementBuffer> {
         self
     }
 
+    fn from_enum(rx: ReceiverEnum) -> Option<Self> {
+        match rx {
+            ReceiverEnum::Single(rx) => Some(rx),
+            ReceiverEnum::Broadcast(_) => None,
+        }
+    }
+
     fn into_stream(self) -> impl Stream<Item = Result<MeasurementBuffer, StreamRecvError>> {
         use tokio_stream::{StreamExt, wrappers::ReceiverStream};
         ReceiverStream::new(self).map(Ok)
@@ -89,6 +107,15 @@ impl MeasurementReceiver for mpsc::Receiver<MeasurementBuffer> {
 // providers
 
 impl ReceiverProvider {
+    /// Returns a new receiver, which only receives the measurements sent from now on,
+    /// or `None` if the channel has a single receiver.
+    pub fn subscribe(&self) -> Option<ReceiverEnum> {
+        match &self.0 {
+            ProviderEnum::Broadcast(tx) => Some(ReceiverEnum::Broadcast(tx.subscribe())),
+            ProviderEnum::Single(_) => None,
+        }
+    }
+
     pub fn get(&mut self) -> ReceiverEnum {
         match &mut self.0 {
             ProviderEnum::Broadcast(tx) => ReceiverEnum::Broadcast(tx.subscribe()),
@@ -422,7 +422,7 @@ impl<'a> AlumetPreStart<'a> {
         &self.pipeline_builder.metrics
     }
 
-    /// Registers a metric listener, which will be notified of all the new registered metrics.
+    /// Registers a metric listener, which will be notified of all the new registered metrics (and of the retired ones).
     pub fn add_metric_listener<F: MetricListener + Send + 'static>(
         &mut self,
         name: &str,
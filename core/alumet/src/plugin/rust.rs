@@ -4,7 +4,12 @@
 
 use anyhow::{Context, anyhow};
 
-use crate::plugin::{AlumetPluginStart, Plugin};
+#[cfg(feature = "schema")]
+use crate::agent::schema::{self, JsonSchema};
+use crate::{
+    agent::check,
+    plugin::{AlumetPluginStart, Plugin},
+};
 
 use super::{AlumetPostStart, ConfigTable, phases::AlumetPreStart};
 
@@ -28,6 +33,10 @@ pub trait AlumetPlugin {
 
     /// Returns the default configuration of the plugin.
     ///
+    /// With the `schema` feature, the schema of the configuration, which tools can use to validate or complete it,
+    /// is derived from the type that is given to `serialize_config_with_schema`.
+    /// Without it, the schema is inferred from the default values.
+    ///
     /// # Example
     /// ```ignore
     /// use serde::{Deserialize, Serialize}
@@ -114,9 +123,13 @@ impl<P: AlumetPlugin> Plugin for P {
 }
 
 pub fn deserialize_config<'de, T: serde::de::Deserialize<'de>>(config: ConfigTable) -> anyhow::Result<T> {
-    toml::Value::Table(config.0)
-        .try_into::<T>()
-        .with_context(|| format!("error when deserializing ConfigTable to {}", std::any::type_name::<T>()))
+    let res = if check::is_collecting() {
+        // the config is being checked, record all the issues
+        check::deserialize_and_collect(config.0)
+    } else {
+        toml::Value::Table(config.0).try_into::<T>()
+    };
+    res.with_context(|| format!("error when deserializing ConfigTable to {}", std::any::type_name::<T>()))
         .context(InvalidConfig)
 }
 
@@ -136,6 +149,13 @@ pub fn serialize_config<T: serde::ser::Serialize>(config: T) -> anyhow::Result<C
     res.context(InvalidConfig)
 }
 
+/// Like [`serialize_config`], but also records the schema of `T` for [`schema::record_schema`].
+#[cfg(feature = "schema")]
+pub fn serialize_config_with_schema<T: serde::ser::Serialize + JsonSchema>(config: T) -> anyhow::Result<ConfigTable> {
+    schema::record::<T>();
+    serialize_config(config)
+}
+
 /// Signals an invalid configuration.
 ///
 /// Use this singleton with [`anyhow::Context`] to signal that
@@ -3,13 +3,15 @@ use std::{collections::HashSet, time::Duration};
 use alumet::{
     agent::{self, plugin::PluginSet},
     pipeline::{
-        Output, Source, Transform,
+        self, Output, Source, Transform,
         control::{
             handle::SendWaitError,
-            request::{self, ElementListFilter},
+            request::{self, ElementListFilter, TransformPosition},
+            status::{ElementState, ElementStatus},
         },
-        elements::source::trigger::TriggerSpec,
-        naming::{ElementKind, ElementName, PluginName, SourceName},
+        elements::{output::AsyncOutputStream, source::trigger::TriggerSpec},
+        matching::SourceNamePattern,
+        naming::{ElementKind, ElementName, PluginName, SourceName, TransformName},
     },
     plugin::{PluginMetadata, rust::AlumetPlugin},
     static_plugins,
@@ -84,6 +86,311 @@ fn create_source_error_in_builder() {
     assert_eq!(list, Vec::new());
 }
 
+#[test]
+fn element_status() {
+    let no_plugins = PluginSet::new();
+    let agent = agent::Builder::new(no_plugins).build_and_start().unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // create an autonomous source that reports its status, then stops when the pipeline shuts down
+    let request = request::create_one().add_autonomous_source_builder("status_source", |ctx, cancel_token, _tx| {
+        let reporter = ctx.status_reporter();
+        let mut status = ElementStatus::default();
+        status.details.insert(String::from("answer"), toml::Value::Integer(42));
+        reporter.report(status);
+        Ok(Box::pin(async move {
+            cancel_token.cancelled().await;
+            drop(reporter);
+            Ok(())
+        }))
+    });
+    rt.block_on(handle.send_wait(request, TIMEOUT))
+        .expect("creation request failed");
+
+    let request = request::element_status(ElementListFilter::kind(ElementKind::Source));
+    let status = rt
+        .block_on(handle.send_wait(request, TIMEOUT))
+        .expect("status request failed");
+    assert_eq!(status.len(), 1);
+    let (name, status) = &status[0];
+    assert_eq!(
+        name,
+        &ElementName::from_str(ElementKind::Source, "test", "status_source")
+    );
+    assert_eq!(status.details.get("answer"), Some(&toml::Value::Integer(42)));
+
+    // elements that do not report anything are not listed
+    let request = request::element_status(ElementListFilter::kind(ElementKind::Output));
+    let status = rt
+        .block_on(handle.send_wait(request, TIMEOUT))
+        .expect("status request failed");
+    assert!(status.is_empty());
+}
+
+#[test]
+fn element_states() {
+    let no_plugins = PluginSet::new();
+    let agent = agent::Builder::new(no_plugins).build_and_start().unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // one source that runs, one that is paused, one that fails, one that keeps failing
+    let trigger = TriggerSpec::at_interval(Duration::from_millis(10));
+    let request = request::create_many()
+        .add_source("running", Box::new(DummySource), trigger.clone())
+        .add_source("retrying", Box::new(RetryingSource), trigger.clone())
+        .add_source("paused", Box::new(DummySource), trigger)
+        .add_autonomous_source_builder("failing", |_, _, _| {
+            Ok(Box::pin(async { Err(anyhow!("it failed, on purpose")) }))
+        })
+        .build();
+    rt.block_on(handle.send_wait(request, TIMEOUT))
+        .expect("creation request failed");
+    let paused = SourceNamePattern::exact("test", "paused");
+    rt.block_on(handle.send_wait(request::source(paused).disable(), TIMEOUT))
+        .expect("disable request failed");
+
+    // leave some time for the failing source to stop
+    std::thread::sleep(Duration::from_millis(100));
+
+    let request = request::element_states(ElementListFilter::kind(ElementKind::Source));
+    let mut states = rt
+        .block_on(handle.send_wait(request, TIMEOUT))
+        .expect("state request failed");
+    states.sort_by(|(a, _), (b, _)| a.element.cmp(&b.element));
+    let source = |name: &str| ElementName::from_str(ElementKind::Source, "test", name);
+    assert_eq!(states.len(), 4, "unexpected states: {states:?}");
+    assert_eq!(states[1], (source("paused"), ElementState::Paused));
+    assert_eq!(
+        states[2],
+        (
+            source("retrying"),
+            ElementState::Degraded {
+                last_error: String::from("it failed again")
+            }
+        )
+    );
+    assert_eq!(states[3], (source("running"), ElementState::Running));
+    let (name, state) = &states[0];
+    assert_eq!(name, &source("failing"));
+    match state {
+        ElementState::Failed { error } => assert!(error.contains("on purpose"), "bad error: {error}"),
+        bad => panic!("the source should have failed, got {bad:?}"),
+    }
+}
+
+#[test]
+fn create_and_remove_transforms() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    // disable the simplified pipeline, otherwise there is no transform step
+    let mut pipeline_builder = pipeline::Builder::new();
+    *pipeline_builder.allow_simplified_pipeline() = false;
+    let agent = agent::Builder::from_pipeline(PluginSet::new(), pipeline_builder)
+        .build_and_start()
+        .unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // create a source, then two transforms
+    let trigger = TriggerSpec::at_interval(Duration::from_millis(10));
+    let request = request::create_one().add_source("src", Box::new(DummySource), trigger);
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+
+    let n_applied = Arc::new(AtomicUsize::new(0));
+    let counting = CountingTransform(n_applied.clone());
+    let request = request::create_many()
+        .add_transform("tr_a", Box::new(counting), TransformPosition::Last)
+        .add_transform("tr_b", Box::new(DummyTransform), TransformPosition::First)
+        .add_transform(
+            "tr_c",
+            Box::new(DummyTransform),
+            TransformPosition::After(TransformName::from_str("test", "tr_b")),
+        )
+        .build();
+    rt.block_on(handle.send_wait(request, TIMEOUT))
+        .expect("creation request failed");
+
+    // the transforms must be listed in the order of execution
+    let list_transforms = |rt: &tokio::runtime::Runtime| {
+        let request = request::list_elements(ElementListFilter::kind(ElementKind::Transform));
+        rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap()
+    };
+    assert_eq!(
+        list_transforms(&rt),
+        vec![
+            ElementName::from_str(ElementKind::Transform, "test", "tr_b"),
+            ElementName::from_str(ElementKind::Transform, "test", "tr_c"),
+            ElementName::from_str(ElementKind::Transform, "test", "tr_a"),
+        ]
+    );
+
+    // the new transform must receive the measurements
+    std::thread::sleep(Duration::from_millis(100));
+    assert_ne!(n_applied.load(Ordering::Relaxed), 0);
+
+    // a transform cannot be inserted relatively to a transform that does not exist
+    let request = request::create_one().add_transform(
+        "tr_d",
+        Box::new(DummyTransform),
+        TransformPosition::Before(TransformName::from_str("test", "unknown")),
+    );
+    let res = rt.block_on(handle.send_wait(request, TIMEOUT));
+    assert!(matches!(res, Err(SendWaitError::Operation(_))));
+
+    // remove a transform, it must not be applied anymore
+    let request = request::transform(TransformName::from_str("test", "tr_a")).remove();
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    assert_eq!(
+        list_transforms(&rt),
+        vec![
+            ElementName::from_str(ElementKind::Transform, "test", "tr_b"),
+            ElementName::from_str(ElementKind::Transform, "test", "tr_c"),
+        ]
+    );
+    std::thread::sleep(Duration::from_millis(50));
+    let n_after_removal = n_applied.load(Ordering::Relaxed);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(n_applied.load(Ordering::Relaxed), n_after_removal);
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn remove_transform_idle_pipeline() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    let mut pipeline_builder = pipeline::Builder::new();
+    *pipeline_builder.allow_simplified_pipeline() = false;
+    let agent = agent::Builder::from_pipeline(PluginSet::new(), pipeline_builder)
+        .build_and_start()
+        .unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // no source: no measurement goes through the transforms
+    let finished = Arc::new(AtomicBool::new(false));
+    let transform = FinishingTransform(finished.clone());
+    let request = request::create_one().add_transform("tr", Box::new(transform), TransformPosition::Last);
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+
+    // the removed transform must be finished anyway
+    let request = request::transform(TransformName::from_str("test", "tr")).remove();
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    let deadline = std::time::Instant::now() + TIMEOUT;
+    while !finished.load(Ordering::Relaxed) {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "the removed transform has not been finished"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn create_transform_simplified_pipeline() {
+    // no transform and one output: the pipeline is simplified, it has no transform step
+    let agent = agent::Builder::new(PluginSet::new()).build_and_start().unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+
+    let rt = current_thread_runtime();
+    let request = request::create_one().add_transform("tr", Box::new(DummyTransform), TransformPosition::Last);
+    let res = rt.block_on(handle.send_wait(request, TIMEOUT));
+    assert!(
+        matches!(res, Err(SendWaitError::Operation(_))),
+        "transform creation should fail in a simplified pipeline"
+    );
+}
+
+#[test]
+fn create_async_output() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    let mut pipeline_builder = pipeline::Builder::new();
+    *pipeline_builder.allow_simplified_pipeline() = false;
+    let agent = agent::Builder::from_pipeline(PluginSet::new(), pipeline_builder)
+        .build_and_start()
+        .unwrap();
+    let handle = agent
+        .pipeline
+        .control_handle()
+        .with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    let n_received = Arc::new(AtomicUsize::new(0));
+    let n_received_out = n_received.clone();
+    let request = request::create_one().add_async_output_builder("async_out", move |_ctx, input| {
+        Ok(Box::pin(counting_async_output(input, n_received_out)))
+    });
+    rt.block_on(handle.send_wait(request, TIMEOUT))
+        .expect("creation request failed");
+
+    let trigger = TriggerSpec::at_interval(Duration::from_millis(10));
+    let request = request::create_one().add_source("src", Box::new(DummySource), trigger);
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+
+    // check that the output has been created and receives the measurements
+    let request = request::list_elements(ElementListFilter::kind(ElementKind::Output).name("async_out"));
+    let list = rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    assert_eq!(
+        list,
+        vec![ElementName::from_str(ElementKind::Output, "test", "async_out")]
+    );
+    std::thread::sleep(Duration::from_millis(100));
+    assert_ne!(n_received.load(Ordering::Relaxed), 0);
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+async fn counting_async_output(
+    mut input: AsyncOutputStream,
+    counter: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) -> anyhow::Result<()> {
+    use futures::StreamExt;
+
+    while let Some(buf) = input.0.next().await {
+        if buf.is_err() {
+            anyhow::bail!("the output has lagged behind");
+        }
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+    Ok(())
+}
+
 #[test]
 fn list_filter() {
     let _ = env_logger::try_init_from_env(env_logger::Env::default());
@@ -297,7 +604,10 @@ fn current_thread_runtime() -> tokio::runtime::Runtime {
 }
 
 struct DummySource;
+struct RetryingSource;
 struct DummyTransform;
+struct CountingTransform(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+struct FinishingTransform(std::sync::Arc<std::sync::atomic::AtomicBool>);
 struct DummyOutput;
 struct TestPlugin;
 
@@ -311,6 +621,18 @@ impl Source for DummySource {
     }
 }
 
+impl Source for RetryingSource {
+    fn poll(
+        &mut self,
+        _measurements: &mut alumet::measurement::MeasurementAccumulator,
+        _timestamp: alumet::measurement::Timestamp,
+    ) -> Result<(), alumet::pipeline::elements::error::PollError> {
+        Err(alumet::pipeline::elements::error::PollError::CanRetry(anyhow!(
+            "it failed again"
+        )))
+    }
+}
+
 impl Transform for DummyTransform {
     fn apply(
         &mut self,
@@ -321,6 +643,35 @@ impl Transform for DummyTransform {
     }
 }
 
+impl Transform for CountingTransform {
+    fn apply(
+        &mut self,
+        _measurements: &mut alumet::measurement::MeasurementBuffer,
+        _ctx: &alumet::pipeline::elements::transform::TransformContext,
+    ) -> Result<(), alumet::pipeline::elements::error::TransformError> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Transform for FinishingTransform {
+    fn apply(
+        &mut self,
+        _measurements: &mut alumet::measurement::MeasurementBuffer,
+        _ctx: &alumet::pipeline::elements::transform::TransformContext,
+    ) -> Result<(), alumet::pipeline::elements::error::TransformError> {
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        _ctx: &alumet::pipeline::elements::transform::TransformContext,
+    ) -> Result<(), alumet::pipeline::elements::error::TransformError> {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+}
+
 impl Output for DummyOutput {
     fn write(
         &mut self,
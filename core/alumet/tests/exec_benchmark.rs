@@ -0,0 +1,151 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use alumet::{
+    agent::{
+        self,
+        exec::{BenchmarkSettings, RUN_ID_ATTRIBUTE, RunTagger, exec_benchmark},
+        plugin::PluginSet,
+    },
+    measurement::{
+        AttributeValue, MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue,
+    },
+    metrics::TypedMetricId,
+    pipeline::{
+        self, Output, Source,
+        elements::{
+            error::{PollError, WriteError},
+            output::OutputContext,
+            source::trigger,
+        },
+    },
+    plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin},
+    resources::{Resource, ResourceConsumer},
+    static_plugins,
+    units::{PrefixedUnit, Unit},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time between the start of the pipeline and the start of the benchmark.
+const PRE_RUN_DELAY: Duration = Duration::from_secs(1);
+
+/// Energy measured during the run, in "milli-joules" (the test source consumes 1 J/s).
+static RUN_ENERGY: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+struct TestPlugin;
+
+/// Measures the energy consumed since the previous measurement, like a RAPL or NVML source.
+struct EnergySource {
+    metric: TypedMetricId<u64>,
+    last: Instant,
+}
+
+struct RecordingOutput;
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        "test"
+    }
+
+    fn version() -> &'static str {
+        "0"
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric::<u64>("energy", PrefixedUnit::milli(Unit::Joule), "energy consumed")?;
+        let source = EnergySource {
+            metric,
+            last: Instant::now(),
+        };
+        alumet.add_source("energy", Box::new(source), trigger::builder::manual().build()?)?;
+        alumet.add_blocking_output("recorder", Box::new(RecordingOutput))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl Source for EnergySource {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        let now = Instant::now();
+        let energy = (now - self.last).as_millis() as u64;
+        self.last = now;
+        acc.push(MeasurementPoint::new(
+            t,
+            self.metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            energy,
+        ));
+        Ok(())
+    }
+}
+
+impl Output for RecordingOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut run_energy = RUN_ENERGY.lock().unwrap();
+        for m in measurements {
+            let tagged = m
+                .attributes()
+                .any(|(k, v)| k == RUN_ID_ATTRIBUTE && *v == AttributeValue::String(String::from("1")));
+            if let (true, WrappedMeasurementValue::U64(energy)) = (tagged, &m.value) {
+                run_energy.push(*energy);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn first_run_excludes_pre_run_energy() {
+    let plugins = PluginSet::from(static_plugins![TestPlugin]);
+    let mut pipeline = pipeline::Builder::new();
+    pipeline.trigger_constraints_mut().allow_manual_trigger = true;
+    let tagger = RunTagger::default();
+    tagger.install(&mut pipeline).unwrap();
+
+    let agent = agent::Builder::from_pipeline(plugins, pipeline)
+        .build_and_start()
+        .expect("agent should start fine");
+    thread::sleep(PRE_RUN_DELAY);
+
+    let settings = BenchmarkSettings {
+        runs: 1,
+        warmup_runs: 0,
+        idle: None,
+    };
+    let phases = exec_benchmark(
+        agent,
+        String::from("sleep"),
+        vec![String::from("0.1")],
+        &settings,
+        &tagger,
+        TIMEOUT,
+    )
+    .expect("the benchmark should run");
+    assert_eq!(phases.len(), 1);
+
+    // Only the measurement at the end of the run is tagged, it covers the run but not the time before.
+    let run_energy = RUN_ENERGY.lock().unwrap();
+    assert_eq!(run_energy.len(), 1, "unexpected measurements: {run_energy:?}");
+    let energy = Duration::from_millis(run_energy[0]);
+    assert!(energy >= phases[0].duration, "{energy:?} < {:?}", phases[0].duration);
+    assert!(
+        energy < PRE_RUN_DELAY,
+        "the energy of the run includes the pre-run interval: {energy:?}"
+    );
+}
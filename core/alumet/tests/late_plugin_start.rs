@@ -0,0 +1,158 @@
+use std::{
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    pipeline::{self, elements::source::trigger},
+    plugin::{AlumetPluginStart, PluginMetadata, rust::AlumetPlugin},
+};
+
+mod common;
+use common::test_plugin::{AtomicState, MeasurementCounters, State, TestPlugin};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn test_plugin(name: &'static str, state: Arc<AtomicState>, counters: MeasurementCounters) -> PluginInfo {
+    let source_trigger = trigger::builder::time_interval(Duration::from_millis(20))
+        .build()
+        .unwrap();
+    PluginInfo {
+        metadata: PluginMetadata {
+            name: name.to_owned(),
+            version: "0.0.1".to_owned(),
+            init: Box::new(move |_| Ok(TestPlugin::init(name, 1, state, counters, source_trigger))),
+            default_config: Box::new(|| Ok(None)),
+        },
+        enabled: true,
+        config: None,
+    }
+}
+
+fn build_pipeline() -> pipeline::Builder {
+    let mut pipeline = pipeline::Builder::new();
+    // the late plugin adds a transform
+    *pipeline.allow_simplified_pipeline() = false;
+    pipeline
+}
+
+#[test]
+fn start_plugin_after_pipeline() {
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    let state1 = Arc::new(AtomicState::new(State::PreInit));
+    let state2 = Arc::new(AtomicState::new(State::PreInit));
+    let counters1 = MeasurementCounters::default();
+    let counters2 = MeasurementCounters::default();
+
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(test_plugin("plugin1", state1.clone(), counters1.clone()));
+    let mut agent = agent::Builder::from_pipeline(plugins, build_pipeline())
+        .build_and_start()
+        .unwrap();
+
+    agent
+        .start_plugin(test_plugin("plugin2", state2.clone(), counters2.clone()))
+        .expect("plugin2 should start");
+    assert_eq!(state2.get(), State::PostPipelineStart);
+    assert!(agent.initialized_plugins.iter().any(|p| p.name() == "plugin2"));
+
+    // the source, transform and output of plugin2 should run
+    std::thread::sleep(Duration::from_millis(300));
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+    assert_eq!(state1.get(), State::Stopped);
+    assert_eq!(state2.get(), State::Stopped);
+
+    assert!(counters2.n_polled.load(Ordering::Relaxed) > 0);
+    assert!(counters2.n_transform_in.load(Ordering::Relaxed) > 0);
+    assert!(counters2.n_written.load(Ordering::Relaxed) > 0);
+    // the output of plugin1 receives the measurements of plugin2
+    assert!(counters1.n_written.load(Ordering::Relaxed) > counters2.n_polled.load(Ordering::Relaxed));
+}
+
+#[test]
+fn start_plugin_with_autonomous_source() {
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    let mut agent = agent::Builder::from_pipeline(PluginSet::new(), build_pipeline())
+        .build_and_start()
+        .unwrap();
+
+    let plugin = PluginInfo {
+        metadata: PluginMetadata::from_static::<AutonomousPlugin>(),
+        enabled: true,
+        config: None,
+    };
+    let err = agent
+        .start_plugin(plugin)
+        .expect_err("autonomous sources cannot be added late");
+    assert!(format!("{err:?}").contains("autonomous"), "unexpected error: {err:?}");
+    assert!(agent.initialized_plugins.is_empty());
+
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn start_plugin_in_simplified_pipeline() {
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    // only one (dummy) output and no transform: the pipeline is simplified
+    let mut agent = agent::Builder::from_pipeline(PluginSet::new(), pipeline::Builder::new())
+        .build_and_start()
+        .unwrap();
+    assert!(agent.pipeline.is_simplified());
+
+    let state = Arc::new(AtomicState::new(State::PreInit));
+    let err = agent
+        .start_plugin(test_plugin("plugin2", state.clone(), MeasurementCounters::default()))
+        .expect_err("transforms and outputs cannot be added to a simplified pipeline");
+    assert!(
+        format!("{err:?}").contains("restart required"),
+        "unexpected error: {err:?}"
+    );
+    assert!(agent.initialized_plugins.is_empty());
+    assert_eq!(state.get(), State::Stopped);
+
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+struct AutonomousPlugin;
+
+impl AlumetPlugin for AutonomousPlugin {
+    fn name() -> &'static str {
+        "autonomous"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(AutonomousPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.add_autonomous_source_builder("src", |_, cancel, _| {
+            Ok(Box::pin(async move {
+                cancel.cancelled().await;
+                Ok(())
+            }))
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::{sync::Mutex, time::Duration};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    metrics::{Metric, RawMetricId, def::MetricId, online::listener::MetricListener},
+    plugin::rust::AlumetPlugin,
+    static_plugins,
+    units::Unit,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+// Names of the metrics seen by the listener.
+static REGISTERED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RETIRED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct RecordingListener;
+
+impl MetricListener for RecordingListener {
+    fn on_metrics_registered(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+        let mut registered = REGISTERED.lock().unwrap();
+        registered.extend(metrics.into_iter().map(|(_, m)| m.name));
+        Ok(())
+    }
+
+    fn on_metrics_retired(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+        let mut retired = RETIRED.lock().unwrap();
+        retired.extend(metrics.into_iter().map(|(_, m)| m.name));
+        Ok(())
+    }
+}
+
+struct TestPlugin {
+    m1: Option<RawMetricId>,
+}
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        "test"
+    }
+
+    fn version() -> &'static str {
+        "0"
+    }
+
+    fn init(_config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self { m1: None }))
+    }
+
+    fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
+        Ok(None)
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let m1 = alumet.create_metric::<u64>("m1", Unit::Second, "test metric 1")?;
+        alumet.create_metric::<u64>("m2", Unit::Second, "test metric 2")?;
+        self.m1 = Some(m1.untyped_id());
+        Ok(())
+    }
+
+    fn pre_pipeline_start(&mut self, alumet: &mut alumet::plugin::AlumetPreStart) -> anyhow::Result<()> {
+        alumet.add_metric_listener("recorder", RecordingListener)?;
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut alumet::plugin::AlumetPostStart) -> anyhow::Result<()> {
+        let m1 = self.m1.unwrap();
+        let unknown = RawMetricId::from_u64(1234);
+
+        // Retire m1, the unknown id should be ignored.
+        let retired = alumet
+            .block_on(alumet.metrics_sender().retire_metrics(vec![m1, unknown]))
+            .expect("retire_metrics should work");
+        assert_eq!(retired.len(), 1);
+        assert_eq!(retired[0].0, m1);
+        assert_eq!(retired[0].1.name, "m1");
+
+        // m1 is no longer in the registry, m2 is still there
+        let reader = alumet.metrics_reader();
+        let registry = alumet.block_on(reader.read());
+        assert!(registry.by_id(&m1).is_none());
+        assert!(registry.by_name("m1").is_none());
+        assert!(registry.by_name("m2").is_some());
+        drop(registry);
+
+        // Retiring m1 again is a no-op.
+        let retired = alumet
+            .block_on(alumet.metrics_sender().retire_metrics(vec![m1]))
+            .expect("retire_metrics should work");
+        assert!(retired.is_empty());
+
+        // A new metric gets a new id, even if its name is the same as the retired metric.
+        let m1_bis = Metric {
+            name: "m1".to_owned(),
+            description: "".to_owned(),
+            value_type: alumet::measurement::WrappedMeasurementType::U64,
+            unit: Unit::Second.into(),
+        };
+        let res = alumet
+            .block_on(
+                alumet
+                    .metrics_sender()
+                    .create_metrics(vec![m1_bis], alumet::metrics::duplicate::DuplicateReaction::Error),
+            )
+            .expect("create_metrics should work");
+        let new_id = res[0].as_ref().expect("m1 should be registered again").to_owned();
+        assert_ne!(new_id, m1);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn retire_metrics_at_runtime() {
+    let plugins = PluginSet::from(static_plugins![TestPlugin]);
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("agent should build");
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(TIMEOUT).expect("error while running");
+
+    // The listener has been notified of the metric retirement, and of the new registration.
+    assert_eq!(*RETIRED.lock().unwrap(), vec!["m1"]);
+    assert_eq!(*REGISTERED.lock().unwrap(), vec!["m1"]);
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementBuffer, Timestamp},
+    pipeline::{
+        self, Output, Source, Transform,
+        elements::{
+            error::{PollError, TransformError, WriteError},
+            output::{OutputContext, builder::OutputBuilder},
+            source::{
+                builder::{ManagedSource, SourceBuilder, SourcePace},
+                control::TaskState,
+                trigger::TriggerSpec,
+            },
+            transform::TransformContext,
+        },
+        naming::PluginName,
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// (metric name, value of the attribute `element` or `channel`)
+type Seen = Arc<Mutex<HashSet<(String, String)>>>;
+
+#[test]
+fn self_monitoring_points_reach_the_outputs() {
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+
+    let plugin = || PluginName(String::from("test"));
+    let seen = Seen::default();
+    let seen_out = seen.clone();
+
+    let mut pipeline = pipeline::Builder::new();
+    pipeline.enable_self_monitoring(Duration::from_millis(20));
+    pipeline
+        .add_source_builder(
+            plugin(),
+            "src",
+            SourceBuilder::Managed(
+                Box::new(|_| {
+                    Ok(ManagedSource {
+                        trigger_spec: TriggerSpec::at_interval(Duration::from_millis(10)),
+                        initial_state: TaskState::Run,
+                        source: Box::new(DummySource),
+                    })
+                }),
+                SourcePace::Fast,
+            ),
+        )
+        .unwrap();
+    pipeline
+        .add_transform_builder(plugin(), "tr", Box::new(|_| Ok(Box::new(DummyTransform))))
+        .unwrap();
+    pipeline
+        .add_output_builder(
+            plugin(),
+            "out",
+            OutputBuilder::Blocking(Box::new(move |_| Ok(Box::new(RecordingOutput(seen_out))))),
+        )
+        .unwrap();
+
+    let pipeline = pipeline.build().unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    pipeline.control_handle().shutdown();
+    assert!(pipeline.wait_for_shutdown(Some(TIMEOUT)).is_ok());
+
+    let seen = seen.lock().unwrap();
+    for element in ["sources/test/src", "transforms/test/tr", "outputs/test/out"] {
+        for metric in [
+            "alumet_element_calls",
+            "alumet_element_latency_mean",
+            "alumet_element_measurements",
+        ] {
+            assert!(
+                seen.contains(&(metric.to_owned(), element.to_owned())),
+                "missing {metric} for {element} in {seen:?}"
+            );
+        }
+    }
+    for channel in ["sources", "outputs"] {
+        assert!(seen.contains(&(String::from("alumet_channel_occupancy"), channel.to_owned())));
+    }
+}
+
+struct DummySource;
+struct DummyTransform;
+struct RecordingOutput(Seen);
+
+impl Source for DummySource {
+    fn poll(&mut self, _measurements: &mut MeasurementAccumulator, _timestamp: Timestamp) -> Result<(), PollError> {
+        Ok(())
+    }
+}
+
+impl Transform for DummyTransform {
+    fn apply(&mut self, _measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        Ok(())
+    }
+}
+
+impl Output for RecordingOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut seen = self.0.lock().unwrap();
+        for m in measurements {
+            let metric = &ctx.metrics.by_id(&m.metric).unwrap().name;
+            for (key, value) in m.attributes() {
+                if key == "element" || key == "channel" {
+                    seen.insert((metric.to_owned(), value.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,27 @@
-use alumet::measurement::{MeasurementPoint, WrappedMeasurementValue};
+use std::time::Duration;
+
+use alumet::{
+    agent::schema::JsonSchema,
+    measurement::{MeasurementPoint, WrappedMeasurementType, WrappedMeasurementValue},
+    units::{PrefixedUnit, Unit},
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, Copy)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
 pub(crate) enum Function {
     Sum,
     Mean,
+    Min,
+    Max,
+    Count,
+    Last,
+    StdDev,
+    P50,
+    P95,
+    P99,
+    /// Rate of the value per second, for metrics whose points are deltas (such as the energy
+    /// or the `*_delta` metrics): sum of the values divided by the duration of the window.
+    Rate,
 }
 
 impl Function {
@@ -12,13 +29,64 @@ impl Function {
         match self {
             Function::Sum => "sum".to_string(),
             Function::Mean => "mean".to_string(),
+            Function::Min => "min".to_string(),
+            Function::Max => "max".to_string(),
+            Function::Count => "count".to_string(),
+            Function::Last => "last".to_string(),
+            Function::StdDev => "stddev".to_string(),
+            Function::P50 => "p50".to_string(),
+            Function::P95 => "p95".to_string(),
+            Function::P99 => "p99".to_string(),
+            Function::Rate => "rate".to_string(),
+        }
+    }
+
+    /// Applies the function to the points of a window of duration `window`.
+    pub(crate) fn apply(self, sub_vec: Vec<MeasurementPoint>, window: Duration) -> Option<WrappedMeasurementValue> {
+        match self {
+            Function::Sum => sum(sub_vec),
+            Function::Mean => mean(sub_vec),
+            Function::Min => min(sub_vec),
+            Function::Max => max(sub_vec),
+            Function::Count => count(sub_vec),
+            Function::Last => last(sub_vec),
+            Function::StdDev => std_dev(sub_vec),
+            Function::P50 => p50(sub_vec),
+            Function::P95 => p95(sub_vec),
+            Function::P99 => p99(sub_vec),
+            Function::Rate => rate(sub_vec, window),
+        }
+    }
+
+    /// Returns the type of the values produced by the function, when applied to values of type `input`.
+    pub(crate) fn output_type(self, input: &WrappedMeasurementType) -> WrappedMeasurementType {
+        match self {
+            Function::Count => WrappedMeasurementType::U64,
+            Function::StdDev | Function::Rate => WrappedMeasurementType::F64,
+            _ => input.clone(),
         }
     }
 
-    pub(crate) fn function(self) -> fn(Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    /// Returns the unit of the values produced by the function, when applied to values of unit `input`.
+    pub(crate) fn output_unit(self, input: &PrefixedUnit) -> PrefixedUnit {
         match self {
-            Function::Sum => sum,
-            Function::Mean => mean,
+            Function::Count => Unit::Unity.into(),
+            Function::Rate => match input.base_unit {
+                Unit::Joule => PrefixedUnit {
+                    base_unit: Unit::Watt,
+                    prefix: input.prefix.clone(),
+                },
+                Unit::Unity => PrefixedUnit {
+                    base_unit: Unit::Hertz,
+                    prefix: input.prefix.clone(),
+                },
+                _ => Unit::Custom {
+                    unique_name: format!("{}/s", input.unique_name()),
+                    display_name: format!("{}/s", input.display_name()),
+                }
+                .into(),
+            },
+            _ => input.clone(),
         }
     }
 }
@@ -48,6 +116,97 @@ pub(crate) fn mean(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementV
     })
 }
 
+/// Returns the minimum value of the given vec.
+pub(crate) fn min(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    sub_vec.into_iter().map(|x| x.value).reduce(|x, y| match (x, y) {
+        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => {
+            WrappedMeasurementValue::F64(fx.min(fy))
+        }
+        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => {
+            WrappedMeasurementValue::U64(ux.min(uy))
+        }
+        (_, _) => unreachable!("should not receive mixed U64 and F64 values"),
+    })
+}
+
+/// Returns the maximum value of the given vec.
+pub(crate) fn max(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    sub_vec.into_iter().map(|x| x.value).reduce(|x, y| match (x, y) {
+        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => {
+            WrappedMeasurementValue::F64(fx.max(fy))
+        }
+        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => {
+            WrappedMeasurementValue::U64(ux.max(uy))
+        }
+        (_, _) => unreachable!("should not receive mixed U64 and F64 values"),
+    })
+}
+
+/// Returns the number of points in the given vec.
+pub(crate) fn count(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    if sub_vec.is_empty() {
+        return None;
+    }
+    Some(WrappedMeasurementValue::U64(sub_vec.len() as u64))
+}
+
+/// Returns the value of the last point of the given vec.
+pub(crate) fn last(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    sub_vec.into_iter().max_by_key(|x| x.timestamp).map(|x| x.value)
+}
+
+/// Returns the (population) standard deviation of the given vec.
+pub(crate) fn std_dev(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    if sub_vec.is_empty() {
+        return None;
+    }
+    let n = sub_vec.len() as f64;
+    let mean = sub_vec.iter().map(|x| x.value.as_f64()).sum::<f64>() / n;
+    let variance = sub_vec.iter().map(|x| (x.value.as_f64() - mean).powi(2)).sum::<f64>() / n;
+    Some(WrappedMeasurementValue::F64(variance.sqrt()))
+}
+
+/// Returns the p-th percentile of the given vec, with the nearest-rank method.
+///
+/// `p` must be in `]0, 100]`.
+fn percentile(sub_vec: Vec<MeasurementPoint>, p: f64) -> Option<WrappedMeasurementValue> {
+    if sub_vec.is_empty() {
+        return None;
+    }
+    let mut values: Vec<WrappedMeasurementValue> = sub_vec.into_iter().map(|x| x.value).collect();
+    values.sort_by(|a, b| a.as_f64().total_cmp(&b.as_f64()));
+    let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+    Some(values.swap_remove(rank.clamp(1, values.len()) - 1))
+}
+
+/// Returns the median of the given vec.
+pub(crate) fn p50(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    percentile(sub_vec, 50.0)
+}
+
+/// Returns the 95th percentile of the given vec.
+pub(crate) fn p95(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    percentile(sub_vec, 95.0)
+}
+
+/// Returns the 99th percentile of the given vec.
+pub(crate) fn p99(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    percentile(sub_vec, 99.0)
+}
+
+/// Returns the rate (per second) of the given vec of deltas, over a window of duration `window`.
+///
+/// Each point is the variation of the value since the previous point of its series, as with the energy
+/// and the `*_delta` metrics: the rate is the sum of the points divided by the duration of the window.
+/// When the group contains several series (see `Grouping`), this is the sum of the rates of the series.
+pub(crate) fn rate(sub_vec: Vec<MeasurementPoint>, window: Duration) -> Option<WrappedMeasurementValue> {
+    if sub_vec.is_empty() || window.is_zero() {
+        return None;
+    }
+    let total: f64 = sub_vec.iter().map(|x| x.value.as_f64()).sum();
+    Some(WrappedMeasurementValue::F64(total / window.as_secs_f64()))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::aggregations::Function;
@@ -56,9 +215,42 @@ mod tests {
     fn test_function_get_string() {
         assert_eq!(Function::Mean.name(), "mean");
         assert_eq!(Function::Sum.name(), "sum");
+        assert_eq!(Function::StdDev.name(), "stddev");
+        assert_eq!(Function::P95.name(), "p95");
+        assert_eq!(Function::Rate.name(), "rate");
+    }
+
+    #[test]
+    fn test_function_output_type_and_unit() {
+        use alumet::{
+            measurement::WrappedMeasurementType,
+            units::{PrefixedUnit, Unit},
+        };
+
+        let u64 = WrappedMeasurementType::U64;
+        assert_eq!(Function::Max.output_type(&u64), WrappedMeasurementType::U64);
+        assert_eq!(Function::StdDev.output_type(&u64), WrappedMeasurementType::F64);
+        assert_eq!(
+            Function::Count.output_type(&WrappedMeasurementType::F64),
+            WrappedMeasurementType::U64
+        );
+
+        let joules = PrefixedUnit::milli(Unit::Joule);
+        assert_eq!(Function::Sum.output_unit(&joules), joules);
+        assert_eq!(Function::Count.output_unit(&joules), Unit::Unity.into());
+        assert_eq!(Function::Rate.output_unit(&joules), PrefixedUnit::milli(Unit::Watt));
+        assert_eq!(
+            Function::Rate.output_unit(&Unit::Byte.into()),
+            PrefixedUnit::from(Unit::Custom {
+                unique_name: "By/s".to_string(),
+                display_name: "B/s".to_string()
+            })
+        );
     }
 
     mod sum {
+        use std::time::Duration;
+
         use alumet::measurement::WrappedMeasurementValue;
 
         use crate::{
@@ -111,11 +303,13 @@ mod tests {
                 new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(1.5), 0),
             ];
 
-            Function::Sum.function()(sub_vec);
+            Function::Sum.apply(sub_vec, Duration::from_secs(1));
         }
     }
 
     mod mean {
+        use std::time::Duration;
+
         use alumet::measurement::WrappedMeasurementValue;
 
         use crate::{
@@ -168,7 +362,108 @@ mod tests {
                 new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(1.5), 0),
             ];
 
-            Function::Mean.function()(sub_vec);
+            Function::Mean.apply(sub_vec, Duration::from_secs(1));
+        }
+    }
+
+    mod others {
+        use std::time::Duration;
+
+        use alumet::measurement::WrappedMeasurementValue;
+
+        use crate::{
+            aggregations::{count, last, max, min, p50, p95, p99, rate, std_dev},
+            transform::tests::new_point,
+        };
+
+        fn u64_sub_vec() -> Vec<alumet::measurement::MeasurementPoint> {
+            vec![
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(7), 0),
+                new_point("2025-02-10T13:19:01Z", WrappedMeasurementValue::U64(1), 0),
+                new_point("2025-02-10T13:19:02Z", WrappedMeasurementValue::U64(56), 0),
+                new_point("2025-02-10T13:19:04Z", WrappedMeasurementValue::U64(3), 0),
+            ]
+        }
+
+        #[test]
+        fn empty_vec() {
+            for f in [count, last, max, min, p50, p95, p99, std_dev] {
+                assert_eq!(f(vec![]), None);
+            }
+            assert_eq!(rate(vec![], Duration::from_secs(1)), None);
+        }
+
+        #[test]
+        fn min_max() {
+            assert_eq!(min(u64_sub_vec()), Some(WrappedMeasurementValue::U64(1)));
+            assert_eq!(max(u64_sub_vec()), Some(WrappedMeasurementValue::U64(56)));
+
+            let sub_vec = vec![
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(1.5), 0),
+                new_point("2025-02-10T13:19:01Z", WrappedMeasurementValue::F64(-3.6), 0),
+            ];
+            assert_eq!(min(sub_vec.clone()), Some(WrappedMeasurementValue::F64(-3.6)));
+            assert_eq!(max(sub_vec), Some(WrappedMeasurementValue::F64(1.5)));
+        }
+
+        #[test]
+        fn count_last() {
+            assert_eq!(count(u64_sub_vec()), Some(WrappedMeasurementValue::U64(4)));
+            assert_eq!(last(u64_sub_vec()), Some(WrappedMeasurementValue::U64(3)));
+        }
+
+        #[test]
+        fn std_dev_f64() {
+            let sub_vec = vec![
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(2.0), 0),
+                new_point("2025-02-10T13:19:01Z", WrappedMeasurementValue::F64(4.0), 0),
+                new_point("2025-02-10T13:19:02Z", WrappedMeasurementValue::F64(4.0), 0),
+                new_point("2025-02-10T13:19:03Z", WrappedMeasurementValue::F64(4.0), 0),
+                new_point("2025-02-10T13:19:04Z", WrappedMeasurementValue::F64(5.0), 0),
+                new_point("2025-02-10T13:19:05Z", WrappedMeasurementValue::F64(5.0), 0),
+                new_point("2025-02-10T13:19:06Z", WrappedMeasurementValue::F64(7.0), 0),
+                new_point("2025-02-10T13:19:07Z", WrappedMeasurementValue::F64(9.0), 0),
+            ];
+            assert_eq!(std_dev(sub_vec), Some(WrappedMeasurementValue::F64(2.0)));
+        }
+
+        #[test]
+        fn percentiles() {
+            let sub_vec: Vec<_> = (1..=100)
+                .rev()
+                .map(|i| new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(i), 0))
+                .collect();
+            assert_eq!(p50(sub_vec.clone()), Some(WrappedMeasurementValue::U64(50)));
+            assert_eq!(p95(sub_vec.clone()), Some(WrappedMeasurementValue::U64(95)));
+            assert_eq!(p99(sub_vec), Some(WrappedMeasurementValue::U64(99)));
+
+            assert_eq!(p50(u64_sub_vec()), Some(WrappedMeasurementValue::U64(3)));
+            assert_eq!(p99(u64_sub_vec()), Some(WrappedMeasurementValue::U64(56)));
+        }
+
+        #[test]
+        fn rate_of_deltas() {
+            // energy consumed in each second, in joules, during a window of 5 seconds
+            let sub_vec: Vec<_> = [10.0, 12.0, 8.0, 11.0, 9.0]
+                .into_iter()
+                .enumerate()
+                .map(|(i, joules)| {
+                    let t = format!("2025-02-10T13:19:0{i}Z");
+                    new_point(&t, WrappedMeasurementValue::F64(joules), 0)
+                })
+                .collect();
+            // 50 joules in 5 seconds: 10 watts
+            assert_eq!(
+                rate(sub_vec, Duration::from_secs(5)),
+                Some(WrappedMeasurementValue::F64(10.0))
+            );
+
+            // one point is enough: it is the delta since the previous window
+            let sub_vec = vec![new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(7), 0)];
+            assert_eq!(
+                rate(sub_vec, Duration::from_secs(2)),
+                Some(WrappedMeasurementValue::F64(3.5))
+            );
         }
     }
 }
@@ -2,32 +2,37 @@ mod aggregations;
 mod transform;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
     time::Duration,
 };
 
+use aggregations::Function;
+
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, online::MetricSender},
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 
 use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
-use transform::AggregationTransform;
+use transform::{Aggregation, AggregationTransform, CorrespondenceTable, Grouping};
 
 pub struct AggregationPlugin {
     config: Config,
 
     /// Store the correspondence table between aggregated metrics and the original ones.
-    /// The key is the original metric's id and the value is the id of the aggregated metric.
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+    /// The key is the original metric's id and the value describes the aggregated metrics.
+    metric_correspondence_table: CorrespondenceTable,
 
+    /// The aggregated metrics to register, one per (original metric, function).
     metrics_list: Vec<Metric>,
-    old_ids: Vec<RawMetricId>,
+    /// The aggregations to apply, without the ids of the aggregated metrics (they are not registered yet).
+    old_ids: Vec<(RawMetricId, Grouping, Vec<Function>)>,
 }
 
 impl AlumetPlugin for AggregationPlugin {
@@ -40,23 +45,23 @@ impl AlumetPlugin for AggregationPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
-        let config = deserialize_config(config)?;
+        let config: Config = deserialize_config(config)?;
+        config.validate()?;
         Ok(Box::new(AggregationPlugin {
             config,
-            metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::new())),
+            metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, Aggregation>::new())),
             metrics_list: Vec::<Metric>::new(),
-            old_ids: Vec::<RawMetricId>::new(),
+            old_ids: Vec::new(),
         }))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let transform = Box::new(AggregationTransform::new(
             self.config.interval,
-            self.config.function,
             self.metric_correspondence_table.clone(),
         ));
         alumet.add_transform("plugin-aggregation", transform)?;
@@ -68,22 +73,30 @@ impl AlumetPlugin for AggregationPlugin {
     fn pre_pipeline_start(&mut self, alumet: &mut alumet::plugin::AlumetPreStart) -> anyhow::Result<()> {
         let metrics = alumet.metrics();
 
-        for metric_name in self.config.metrics.iter() {
+        for aggregation in self.config.aggregations() {
+            let metric_name = &aggregation.metric;
             let (raw_metric_id, metric) = metrics
                 .by_name(metric_name)
                 .with_context(|| format!("metric \"{}\" not found", &metric_name))?;
-            self.old_ids.push(raw_metric_id);
-            let new_metric = Metric {
-                name: format!("{metric_name}_{}", self.config.function.name()),
-                unit: metric.unit.clone(),
-                description: metric.description.clone(),
-                value_type: metric.value_type.clone(),
-            };
-
-            self.metrics_list.push(new_metric);
+            if aggregation.functions.is_empty() {
+                return Err(anyhow!("no aggregation function for metric \"{metric_name}\""));
+            }
+
+            for function in aggregation.functions.iter() {
+                let new_metric = Metric {
+                    name: format!("{metric_name}_{}", function.name()),
+                    unit: function.output_unit(&metric.unit),
+                    description: metric.description.clone(),
+                    value_type: function.output_type(&metric.value_type),
+                };
+                self.metrics_list.push(new_metric);
+            }
+            self.old_ids
+                .push((raw_metric_id, aggregation.group_by, aggregation.functions));
         }
 
-        if self.metrics_list.len() != self.old_ids.len() {
+        let n_functions: usize = self.old_ids.iter().map(|(_, _, functions)| functions.len()).sum();
+        if self.metrics_list.len() != n_functions {
             return Err(anyhow!(
                 "could not pre register one aggregated metric for each requested metrics"
             ));
@@ -113,31 +126,41 @@ impl AlumetPlugin for AggregationPlugin {
 async fn register_new_metrics(
     metric_sender: &mut MetricSender,
     new_metrics: Vec<Metric>,
-    old_ids: Vec<RawMetricId>,
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+    old_ids: Vec<(RawMetricId, Grouping, Vec<Function>)>,
+    metric_correspondence_table: CorrespondenceTable,
 ) -> anyhow::Result<()> {
     let result = metric_sender
         .create_metrics(new_metrics, DuplicateReaction::Error)
         .await
         .map_err(|a| anyhow!("{a}"))?;
 
-    for (before, after) in std::iter::zip(old_ids, result) {
-        let new_id = after?;
+    // The new metrics have been created in order: for each original metric, one per function.
+    let mut new_ids = result.into_iter();
+    for (before, grouping, functions) in old_ids {
+        let mut aggregation = Aggregation {
+            grouping,
+            functions: Vec::with_capacity(functions.len()),
+        };
+        for function in functions {
+            let new_id = new_ids.next().context("missing result for an aggregated metric")??;
+            aggregation.functions.push((function, new_id));
+        }
 
         let metric_correspondence_table_clone = &metric_correspondence_table.clone();
         let mut metric_correspondence_table_write = metric_correspondence_table_clone
             .write()
             .expect("metric_correspondence_table lock poisoned");
 
-        metric_correspondence_table_write.insert(before, new_id);
+        metric_correspondence_table_write.insert(before, aggregation);
     }
     Ok(())
 }
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, JsonSchema)]
 struct Config {
     /// Interval for the aggregation.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     interval: Duration,
 
     // TODO: add boolean about moving aggregation window. P3
@@ -145,12 +168,56 @@ struct Config {
     // TODO: add boolean to drop or not the received metric point. P2
 
     // TODO: add possibility to choose if the generated timestamp is at the left, center or right of the interval. P3
+    /// Function applied to the metrics of the `metrics` list.
     function: aggregations::Function,
 
     // List of metrics where to apply function.
     // Leave empty to apply function to every metrics. NO
     // TODO: manage all/* metrics P3
     metrics: Vec<String>,
+
+    /// Finer-grained aggregations: several functions per metric, and custom grouping.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aggregations: Vec<MetricAggregation>,
+}
+
+/// Aggregations to apply to one metric.
+#[derive(Deserialize, Serialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct MetricAggregation {
+    /// Name of the metric to aggregate.
+    metric: String,
+    /// Functions to apply. Each function produces a new metric `{metric}_{function}`.
+    functions: Vec<aggregations::Function>,
+    /// How to group the points before aggregating them.
+    #[serde(default)]
+    group_by: Grouping,
+}
+
+impl Config {
+    /// Returns all the aggregations to apply, including the ones defined by `function` and `metrics`.
+    fn aggregations(&self) -> Vec<MetricAggregation> {
+        let simple = self.metrics.iter().map(|metric| MetricAggregation {
+            metric: metric.clone(),
+            functions: vec![self.function],
+            group_by: Grouping::default(),
+        });
+        simple.chain(self.aggregations.iter().cloned()).collect()
+    }
+
+    /// Checks that each metric is aggregated only once, in `metrics` or in `aggregations`.
+    fn validate(&self) -> anyhow::Result<()> {
+        let mut seen = HashSet::new();
+        for aggregation in self.aggregations() {
+            if !seen.insert(aggregation.metric.clone()) {
+                return Err(anyhow!(
+                    "metric \"{}\" is aggregated more than once, list it only once in `metrics` or `aggregations`",
+                    aggregation.metric
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for Config {
@@ -159,6 +226,7 @@ impl Default for Config {
             interval: Duration::from_secs(60),
             function: aggregations::Function::Sum,
             metrics: Vec::<String>::new(),
+            aggregations: Vec::new(),
         }
     }
 }
@@ -167,7 +235,7 @@ impl Default for Config {
 mod tests {
     use alumet::plugin::rust::AlumetPlugin;
 
-    use crate::AggregationPlugin;
+    use crate::{AggregationPlugin, Config, aggregations::Function, transform::Grouping};
 
     #[test]
     fn test_name() {
@@ -183,4 +251,69 @@ mod tests {
     fn test_init() {
         let _ = AggregationPlugin::init(AggregationPlugin::default_config().unwrap().unwrap()).unwrap();
     }
+
+    #[test]
+    fn test_config_aggregations() {
+        let config: Config = toml::from_str(
+            r#"
+            interval = "10s"
+            function = "Mean"
+            metrics = ["a"]
+
+            [[aggregations]]
+            metric = "b"
+            functions = ["Max", "P95", "Rate"]
+            group_by = { consumer = false, attributes = ["domain"] }
+            "#,
+        )
+        .unwrap();
+
+        let aggregations = config.aggregations();
+        assert_eq!(aggregations.len(), 2);
+        assert_eq!(aggregations[0].metric, "a");
+        assert_eq!(aggregations[0].functions, vec![Function::Mean]);
+        assert_eq!(aggregations[0].group_by, Grouping::default());
+        assert_eq!(aggregations[1].metric, "b");
+        assert_eq!(
+            aggregations[1].functions,
+            vec![Function::Max, Function::P95, Function::Rate]
+        );
+        assert_eq!(
+            aggregations[1].group_by,
+            Grouping {
+                resource: true,
+                consumer: false,
+                attributes: Some(vec!["domain".to_string()]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_duplicate_metric() {
+        let config: Config = toml::from_str(
+            r#"
+            interval = "10s"
+            function = "Mean"
+            metrics = ["a", "b"]
+
+            [[aggregations]]
+            metric = "a"
+            functions = ["Max"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = Config {
+            metrics: vec!["a".to_string(), "a".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = Config {
+            metrics: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
 }
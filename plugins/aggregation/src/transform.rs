@@ -5,9 +5,11 @@ use std::{
 };
 
 use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
 
 use alumet::{
-    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    agent::schema::JsonSchema,
+    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp},
     metrics::RawMetricId,
     pipeline::{
         Transform,
@@ -16,36 +18,96 @@ use alumet::{
     resources::{Resource, ResourceConsumer},
 };
 
-use crate::aggregations::{self};
+use crate::aggregations::Function;
+
+/// Key of the internal buffer: the points that have the same key are aggregated together.
+type SeriesKey = (RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>);
+
+/// How to group the measurement points of a metric before aggregating them.
+///
+/// By default, the points are grouped by resource, consumer and attributes, that is,
+/// each time series is aggregated separately.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Grouping {
+    /// Group by resource. If false, the points of every resource are aggregated together.
+    pub resource: bool,
+    /// Group by consumer. If false, the points of every consumer are aggregated together.
+    pub consumer: bool,
+    /// Group by these attribute keys only, and drop the other attributes.
+    /// If unset, group by every attribute.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<String>>,
+}
+
+impl Default for Grouping {
+    fn default() -> Self {
+        Self {
+            resource: true,
+            consumer: true,
+            attributes: None,
+        }
+    }
+}
+
+impl Grouping {
+    /// Returns the key of the group that the measurement point belongs to.
+    ///
+    /// When the points are not grouped by resource (resp. consumer), the aggregated points
+    /// are attached to the [`Resource::LocalMachine`] (resp. [`ResourceConsumer::LocalMachine`]).
+    fn key(&self, point: &MeasurementPoint) -> SeriesKey {
+        let consumer = if self.consumer {
+            point.consumer.clone()
+        } else {
+            ResourceConsumer::LocalMachine
+        };
+        let resource = if self.resource {
+            point.resource.clone()
+        } else {
+            Resource::LocalMachine
+        };
+        let attributes = point
+            .attributes()
+            .filter(|(key, _)| match &self.attributes {
+                Some(keys) => keys.iter().any(|k| k == key),
+                None => true,
+            })
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<Vec<(String, AttributeValue)>>();
+        (point.metric, consumer, resource, attributes)
+    }
+}
+
+/// The aggregations to apply to one metric.
+#[derive(Clone, Debug)]
+pub(crate) struct Aggregation {
+    pub grouping: Grouping,
+    /// The functions to apply, with the id of the corresponding aggregated metric.
+    pub functions: Vec<(Function, RawMetricId)>,
+}
+
+/// Store the correspondence table between aggregated metrics and the original ones.
+/// The key is the original metric's id and the value describes the aggregated metrics.
+pub(crate) type CorrespondenceTable = Arc<RwLock<HashMap<RawMetricId, Aggregation>>>;
 
 pub struct AggregationTransform {
     /// Interval used to compute the aggregation.
     interval: Duration,
 
     /// Buffer used to store every measurement point affected by the aggregation.
-    internal_buffer:
-        HashMap<(RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>), Vec<MeasurementPoint>>, // TODO: improve the attribute key parts P2.
+    internal_buffer: HashMap<SeriesKey, Vec<MeasurementPoint>>,
 
     /// Store the correspondence table between aggregated metrics and the original ones.
-    /// The key is the original metric's id and the value is the id of the aggregated metric.
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
-
-    /// Aggregation function.
-    function: fn(Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue>,
+    metric_correspondence_table: CorrespondenceTable,
 }
 
 impl AggregationTransform {
     /// Instantiates a new instance of the aggregation transform plugin.
-    pub fn new(
-        interval: Duration,
-        function: aggregations::Function,
-        metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
-    ) -> Self {
+    pub(crate) fn new(interval: Duration, metric_correspondence_table: CorrespondenceTable) -> Self {
         Self {
             interval,
             internal_buffer: HashMap::new(),
             metric_correspondence_table,
-            function: function.function(),
         }
     }
 
@@ -64,6 +126,18 @@ impl AggregationTransform {
 
         for (key, values) in &mut self.internal_buffer {
             // TODO: Clean the internal_buffer by deleting the empty values/key P2.
+            let (metric, consumer, resource, attributes) = key;
+            let aggregation = metric_correspondence_table_read
+                .get(metric)
+                .ok_or(TransformError::UnexpectedInput(anyhow!(
+                    "the metric ID {} is not known by the correspondence table",
+                    metric.as_u64()
+                )))?;
+
+            // The points of a group can come from several series (see `Grouping`), or arrive late:
+            // sort them so that the first point is the oldest one.
+            values.sort_by_key(|point| point.timestamp);
+
             loop {
                 let min_timestamp = compute_min_timestamp(values[0].timestamp, self.interval);
 
@@ -74,36 +148,29 @@ impl AggregationTransform {
                 let (i, j) = get_ids(self.interval, values, min_timestamp)?;
 
                 let sub_vec: Vec<MeasurementPoint> = values.drain(i..=j).collect();
-
-                // Compute the value of the aggregated point.
-                let Some(value) = (self.function)(sub_vec.clone()) else {
-                    return Err(TransformError::UnexpectedInput(anyhow!(
-                        "could not compute the aggregation for the sub_vec of {key:?}"
-                    )));
-                };
-
-                // Init the new point.
-                let new_point = MeasurementPoint::new_untyped(
-                    compute_min_timestamp(sub_vec[0].timestamp, self.interval),
-                    *metric_correspondence_table_read
-                        .get(&key.clone().0)
-                        .ok_or(TransformError::UnexpectedInput(anyhow!(
-                            "the metric ID {} is not known by the correspondence table",
-                            &key.clone().0.as_u64()
-                        )))?,
-                    key.clone().2,
-                    key.clone().1,
-                    value,
-                )
-                .with_attr_vec(
-                    sub_vec[0]
-                        .attributes()
-                        .map(|(key, value)| (key.to_owned(), value.clone()))
-                        .collect(),
-                );
-
-                // Push the new point to the result buffer.
-                aggregated_points.push(new_point);
+                let timestamp = compute_min_timestamp(sub_vec[0].timestamp, self.interval);
+
+                for (function, aggregated_metric) in &aggregation.functions {
+                    // Compute the value of the aggregated point.
+                    let Some(value) = function.apply(sub_vec.clone(), self.interval) else {
+                        log::debug!(
+                            "not enough points to compute the {} of {key:?} at {timestamp:?}",
+                            function.name()
+                        );
+                        continue;
+                    };
+
+                    // Init the new point and push it to the result buffer.
+                    let new_point = MeasurementPoint::new_untyped(
+                        timestamp,
+                        *aggregated_metric,
+                        resource.clone(),
+                        consumer.clone(),
+                        value,
+                    )
+                    .with_attr_vec(attributes.clone());
+                    aggregated_points.push(new_point);
+                }
             }
         }
 
@@ -125,20 +192,12 @@ impl Transform for AggregationTransform {
         // Store the measurementBuffer needed metrics to the internal_buffer.
         for measurement in measurements.iter() {
             // If metric id not needed, then skip it.
-            if !metric_correspondence_table_read.contains_key(&measurement.metric) {
+            let Some(aggregation) = metric_correspondence_table_read.get(&measurement.metric) else {
                 not_needed_measurement_point.push(measurement.clone());
                 continue;
-            }
+            };
 
-            let id = (
-                measurement.metric,
-                measurement.consumer.clone(),
-                measurement.resource.clone(),
-                measurement
-                    .attributes()
-                    .map(|attribute| (attribute.0.to_string(), attribute.1.clone()))
-                    .collect::<Vec<(String, AttributeValue)>>(),
-            );
+            let id = aggregation.grouping.key(measurement);
 
             // Add the measurement point to the internal buffer.
             match self.internal_buffer.get_mut(&id) {
@@ -146,7 +205,7 @@ impl Transform for AggregationTransform {
                     vec_points.push(measurement.clone());
                 }
                 None => {
-                    self.internal_buffer.insert(id.clone(), vec![measurement.clone()]);
+                    self.internal_buffer.insert(id, vec![measurement.clone()]);
                 }
             }
         }
@@ -157,6 +216,8 @@ impl Transform for AggregationTransform {
         // Fill it again with the not needed points.
         measurements.merge(&mut not_needed_measurement_point);
 
+        // Release the lock before bouncing the buffer, which reads the table again.
+        drop(metric_correspondence_table_read);
         self.buffer_bouncer(measurements)
     }
 }
@@ -213,7 +274,10 @@ fn compute_min_timestamp(reference_timestamp: Timestamp, interval: Duration) ->
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use std::time::{Duration, SystemTime};
+    use std::{
+        sync::{Arc, RwLock},
+        time::{Duration, SystemTime},
+    };
     use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 
     use alumet::{
@@ -222,7 +286,10 @@ pub(crate) mod tests {
         resources::{Resource, ResourceConsumer},
     };
 
-    use crate::transform::{compute_min_timestamp, contains_enough_data};
+    use crate::{
+        aggregations::Function,
+        transform::{Aggregation, CorrespondenceTable, Grouping, compute_min_timestamp, contains_enough_data},
+    };
 
     use super::get_ids;
 
@@ -241,6 +308,21 @@ pub(crate) mod tests {
         )
     }
 
+    /// Returns a correspondence table that applies one function to each metric, with the default grouping.
+    pub(crate) fn correspondence_table(function: Function, ids: &[(u64, u64)]) -> CorrespondenceTable {
+        let table = ids
+            .iter()
+            .map(|(original, aggregated)| {
+                let aggregation = Aggregation {
+                    grouping: Grouping::default(),
+                    functions: vec![(function, RawMetricId::from_u64(*aggregated))],
+                };
+                (RawMetricId::from_u64(*original), aggregation)
+            })
+            .collect();
+        Arc::new(RwLock::new(table))
+    }
+
     fn measurement_buffer_to_comparable_vec(
         measurement_buffer: MeasurementBuffer,
     ) -> Vec<(Timestamp, WrappedMeasurementValue, u64)> {
@@ -373,27 +455,26 @@ pub(crate) mod tests {
         use anyhow::anyhow;
 
         use alumet::{
-            measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+            measurement::{AttributeValue, MeasurementBuffer, WrappedMeasurementValue},
             metrics::RawMetricId,
             pipeline::elements::error::TransformError,
             resources::{Resource, ResourceConsumer},
         };
 
         use crate::{
-            aggregations,
+            aggregations::Function,
             transform::{
-                AggregationTransform,
-                tests::{measurement_buffer_to_comparable_vec, new_point, timestamp_from_rfc3339},
+                Aggregation, AggregationTransform, Grouping,
+                tests::{
+                    correspondence_table, measurement_buffer_to_comparable_vec, new_point, timestamp_from_rfc3339,
+                },
             },
         };
 
         #[test]
         fn empty_buffer() {
-            let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::new())),
-            );
+            let mut transform_plugin =
+                AggregationTransform::new(Duration::from_secs(10), correspondence_table(Function::Mean, &[]));
 
             let mut measurement_buffer = MeasurementBuffer::new();
 
@@ -406,11 +487,7 @@ pub(crate) mod tests {
         fn buffer_with_data() {
             let mut transform_plugin = AggregationTransform::new(
                 Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
+                correspondence_table(Function::Mean, &[(1, 4), (2, 7)]),
             );
 
             let mut measurement_buffer = MeasurementBuffer::new();
@@ -490,25 +567,11 @@ pub(crate) mod tests {
         }
 
         #[test]
-        fn error_in_aggregation_calculation() {
-            fn aggregation_returns_none(_: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
-                None
-            }
-
-            let mut transform_plugin = AggregationTransform {
-                interval: Duration::from_secs(10),
-                internal_buffer: HashMap::<
-                    (RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>),
-                    Vec<MeasurementPoint>,
-                >::new(),
-                metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
-                function: aggregation_returns_none,
-            };
+        fn unknown_metric_in_correspondence_table() {
+            let mut transform_plugin =
+                AggregationTransform::new(Duration::from_secs(10), correspondence_table(Function::Mean, &[(2, 7)]));
 
-            // Add first list of measurement points.
+            // Add a list of measurement points for a metric that is not in the correspondence table.
             let key = (
                 RawMetricId::from_u64(1),
                 ResourceConsumer::LocalMachine,
@@ -519,11 +582,7 @@ pub(crate) mod tests {
                 key.clone(),
                 vec![
                     new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 1),
-                    new_point("2025-02-10T13:19:01Z", WrappedMeasurementValue::U64(1), 1),
-                    new_point("2025-02-10T13:19:05Z", WrappedMeasurementValue::U64(18), 1),
                     new_point("2025-02-10T13:19:10Z", WrappedMeasurementValue::U64(3), 1),
-                    new_point("2025-02-10T13:19:17Z", WrappedMeasurementValue::U64(6), 1),
-                    new_point("2025-02-10T13:19:20Z", WrappedMeasurementValue::U64(0), 1),
                 ],
             );
 
@@ -533,10 +592,79 @@ pub(crate) mod tests {
             assert!(result.is_err());
             assert_eq!(
                 result.unwrap_err().to_string(),
-                TransformError::UnexpectedInput(anyhow!(
-                    "could not compute the aggregation for the sub_vec of {key:?}"
-                ))
-                .to_string()
+                TransformError::UnexpectedInput(anyhow!("the metric ID 1 is not known by the correspondence table"))
+                    .to_string()
+            );
+        }
+
+        #[test]
+        fn several_functions() {
+            let table = HashMap::from([(
+                RawMetricId::from_u64(1),
+                Aggregation {
+                    grouping: Grouping::default(),
+                    functions: vec![
+                        (Function::Max, RawMetricId::from_u64(10)),
+                        (Function::Count, RawMetricId::from_u64(11)),
+                        (Function::Rate, RawMetricId::from_u64(12)),
+                    ],
+                },
+            )]);
+            let mut transform_plugin = AggregationTransform::new(Duration::from_secs(10), Arc::new(RwLock::new(table)));
+
+            let key = (
+                RawMetricId::from_u64(1),
+                ResourceConsumer::LocalMachine,
+                Resource::LocalMachine,
+                Vec::<(String, AttributeValue)>::new(),
+            );
+            transform_plugin.internal_buffer.insert(
+                key.clone(),
+                vec![
+                    new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 1),
+                    new_point("2025-02-10T13:19:04Z", WrappedMeasurementValue::U64(8), 1),
+                    new_point("2025-02-10T13:19:12Z", WrappedMeasurementValue::U64(3), 1),
+                    new_point("2025-02-10T13:19:25Z", WrappedMeasurementValue::U64(6), 1),
+                ],
+            );
+
+            let mut measurement_buffer = MeasurementBuffer::new();
+            transform_plugin.buffer_bouncer(&mut measurement_buffer).unwrap();
+
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer),
+                vec![
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::U64(8),
+                        10
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::U64(2),
+                        11
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::F64(0.8),
+                        12
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:10Z"),
+                        WrappedMeasurementValue::U64(3),
+                        10
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:10Z"),
+                        WrappedMeasurementValue::U64(1),
+                        11
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:10Z"),
+                        WrappedMeasurementValue::F64(0.3),
+                        12
+                    ),
+                ]
             );
         }
 
@@ -544,11 +672,7 @@ pub(crate) mod tests {
         fn metric_correspondence_table_lock_poisoned() {
             let mut transform_plugin = AggregationTransform::new(
                 Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
+                correspondence_table(Function::Mean, &[(1, 4), (2, 7)]),
             );
 
             let mut measurement_buffer = MeasurementBuffer::new();
@@ -589,10 +713,10 @@ pub(crate) mod tests {
         };
 
         use crate::{
-            aggregations,
+            aggregations::Function,
             transform::{
-                AggregationTransform,
-                tests::{measurement_buffer_to_comparable_vec, timestamp_from_rfc3339},
+                Aggregation, AggregationTransform, Grouping,
+                tests::{correspondence_table, measurement_buffer_to_comparable_vec, timestamp_from_rfc3339},
             },
         };
 
@@ -608,11 +732,7 @@ pub(crate) mod tests {
 
             let mut transform_plugin = AggregationTransform::new(
                 Duration::from_secs(10),
-                aggregations::Function::Sum,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(0), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
+                correspondence_table(Function::Sum, &[(0, 4), (2, 7)]),
             );
 
             let mut measurement_buffer = MeasurementBuffer::new();
@@ -705,16 +825,175 @@ pub(crate) mod tests {
             );
         }
 
+        #[test]
+        fn test_apply_with_grouping() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            // Aggregate across consumers, only keep the "domain" attribute.
+            let grouping = Grouping {
+                resource: true,
+                consumer: false,
+                attributes: Some(vec!["domain".to_string()]),
+            };
+            let table = HashMap::from([(
+                RawMetricId::from_u64(0),
+                Aggregation {
+                    grouping,
+                    functions: vec![(Function::Sum, RawMetricId::from_u64(4))],
+                },
+            )]);
+            let mut transform_plugin = AggregationTransform::new(Duration::from_secs(10), Arc::new(RwLock::new(table)));
+
+            let mut measurement_buffer = MeasurementBuffer::new();
+            for (pid, t, value) in [(1, "00", 1), (2, "01", 2), (1, "05", 3), (2, "10", 4)] {
+                let mut point = new_point(
+                    &format!("2025-02-10T13:19:{t}Z"),
+                    WrappedMeasurementValue::U64(value),
+                    0,
+                );
+                point.consumer = ResourceConsumer::Process { pid };
+                point.add_attr("domain", "package");
+                point.add_attr("pid", pid as u64);
+                measurement_buffer.push(point);
+            }
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer.clone()),
+                vec![(
+                    timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                    WrappedMeasurementValue::U64(6),
+                    4
+                )]
+            );
+            let point = measurement_buffer.iter().next().unwrap();
+            assert_eq!(point.consumer, ResourceConsumer::LocalMachine);
+            assert_eq!(
+                point.attributes().collect::<Vec<_>>(),
+                vec![("domain", &AttributeValue::Str("package"))]
+            );
+            assert_eq!(transform_plugin.internal_buffer.len(), 1);
+        }
+
+        #[test]
+        fn test_apply_with_interleaved_series() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            // Aggregate across consumers: the series of both processes are merged into one group.
+            let grouping = Grouping {
+                resource: true,
+                consumer: false,
+                attributes: Some(Vec::new()),
+            };
+            let table = HashMap::from([(
+                RawMetricId::from_u64(0),
+                Aggregation {
+                    grouping,
+                    functions: vec![(Function::Sum, RawMetricId::from_u64(4))],
+                },
+            )]);
+            let mut transform_plugin = AggregationTransform::new(Duration::from_secs(10), Arc::new(RwLock::new(table)));
+
+            // The points of process 2 come first, but process 1 has an older point.
+            let mut measurement_buffer = MeasurementBuffer::new();
+            for (pid, t, value) in [(2, "10", 1), (2, "21", 2), (1, "09", 3), (1, "20", 4)] {
+                let mut point = new_point(
+                    &format!("2025-02-10T13:19:{t}Z"),
+                    WrappedMeasurementValue::U64(value),
+                    0,
+                );
+                point.consumer = ResourceConsumer::Process { pid };
+                measurement_buffer.push(point);
+            }
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer.clone()),
+                vec![
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::U64(3),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:10Z"),
+                        WrappedMeasurementValue::U64(1),
+                        4
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_rate_with_grouping() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            // Aggregate the energy of two processes: the rates of the two series are added.
+            let grouping = Grouping {
+                resource: true,
+                consumer: false,
+                attributes: Some(Vec::new()),
+            };
+            let table = HashMap::from([(
+                RawMetricId::from_u64(0),
+                Aggregation {
+                    grouping,
+                    functions: vec![(Function::Rate, RawMetricId::from_u64(4))],
+                },
+            )]);
+            let mut transform_plugin = AggregationTransform::new(Duration::from_secs(10), Arc::new(RwLock::new(table)));
+
+            // Energy consumed by each process every 5 seconds, in joules.
+            let mut measurement_buffer = MeasurementBuffer::new();
+            for (pid, t, value) in [
+                (1, "00", 10.0),
+                (2, "00", 40.0),
+                (1, "05", 20.0),
+                (2, "05", 30.0),
+                (1, "10", 0.0),
+            ] {
+                let mut point = new_point(
+                    &format!("2025-02-10T13:19:{t}Z"),
+                    WrappedMeasurementValue::F64(value),
+                    0,
+                );
+                point.consumer = ResourceConsumer::Process { pid };
+                measurement_buffer.push(point);
+            }
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            // 30 J in 10 s for process 1, 70 J in 10 s for process 2.
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer.clone()),
+                vec![(
+                    timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                    WrappedMeasurementValue::F64(10.0),
+                    4
+                )]
+            );
+        }
+
         #[test]
         #[should_panic]
         fn metric_correspondence_table_lock_poisoned() {
             let mut transform_plugin = AggregationTransform::new(
                 Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
+                correspondence_table(Function::Mean, &[(1, 4), (2, 7)]),
             );
 
             let mut measurement_buffer = MeasurementBuffer::new();
@@ -1,9 +1,10 @@
 use crate::amd::utils::PLUGIN_NAME;
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         AlumetPluginStart, ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 use amd::{device::AmdGpuDevices, metrics::Metrics, probe::AmdGpuSource};
@@ -20,14 +21,16 @@ use amd_smi_wrapper::MockAmdInterface;
 mod amd;
 mod tests;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Initial interval between two AMD GPU measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
     /// Initial interval between two flushing of AMD GPU measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub flush_interval: Duration,
     /// On startup, the plugin inspects the GPU devices and detect their features.
     /// If `skip_failed_devices = true`, inspection failures will be logged and the plugin will continue.
@@ -81,7 +84,7 @@ impl<P: AmdInterfaceProvider + 'static> AlumetPlugin for AmdGpuPlugin<P> {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -162,6 +165,8 @@ impl AmdGpuPlugin<MockProvider> {
 mod test {
     use std::error::Error;
 
+    use alumet::plugin::rust::serialize_config;
+
     use super::*;
 
     // Test `default_config` function
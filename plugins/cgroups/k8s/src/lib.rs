@@ -1,14 +1,15 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger::TriggerSpec,
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    pods::{ApiClient, AutoNodePodRegistry},
+    pods::{ApiClient, AutoNodePodRegistry, PodAttributeSettings, attribute_key},
     token::{Token, TokenRetrievalConfig},
 };
 use source::SourceSetup;
@@ -18,11 +19,13 @@ use util_cgroups_plugins::{
         CachedCgroupHierarchy, JobAnnotationTransform, OptionalSharedHierarchy, SharedCgroupHierarchy,
     },
     metrics::Metrics,
+    v2::OptionalMetricsConfig,
 };
 
 mod pods;
 mod source;
 mod token;
+mod watch;
 
 pub struct K8sPlugin {
     config: Config,
@@ -40,7 +43,8 @@ impl AlumetPlugin for K8sPlugin {
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
-        let config = deserialize_config(config)?;
+        let config: Config = deserialize_config(config)?;
+        config.validate()?;
         Ok(Box::new(Self {
             config,
             starting_state: None,
@@ -49,12 +53,15 @@ impl AlumetPlugin for K8sPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let metrics = Metrics::create(alumet)?;
-        let reactor_config = ReactorConfig::default();
+        let reactor_config = ReactorConfig {
+            v2_optional_metrics: self.config.optional_metrics,
+            ..Default::default()
+        };
         let mut shared_hierarchy = OptionalSharedHierarchy::default();
         let annotate_containers = self.config.annotate_containers;
 
@@ -63,11 +70,23 @@ impl AlumetPlugin for K8sPlugin {
         let api_token = Token::new(self.config.token_retrieval.clone().into());
         let api_client = ApiClient::new(&self.config.k8s_api_url, api_token)
             .context("failed to create http client for communicating with the K8S API")?;
-        let mut pod_registry = AutoNodePodRegistry::new(node, api_client, annotate_containers);
-        pod_registry
-            .refresh()
-            .context("failed to list pods with the K8S API, are the url and token correct?")?;
-        log::info!("List of pods refreshed.");
+        let attributes = PodAttributeSettings {
+            labels: self.config.labels.clone(),
+            annotations: self.config.annotations.clone(),
+            resolve_owners: self.config.resolve_owners,
+        };
+        let mut pod_registry = AutoNodePodRegistry::new(node, api_client, annotate_containers, attributes);
+        if self.config.watch_pods {
+            pod_registry
+                .start_watch()
+                .context("failed to list pods with the K8S API, are the url and token correct?")?;
+            log::info!("List of pods refreshed, watching the changes.");
+        } else {
+            pod_registry
+                .refresh()
+                .context("failed to list pods with the K8S API, are the url and token correct?")?;
+            log::info!("List of pods refreshed.");
+        }
 
         // If enabled, create the annotation transform.
         if self.config.annotate_foreign_measurements {
@@ -131,7 +150,7 @@ struct StartingState {
     opt_shared_hierarchy: OptionalSharedHierarchy,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// Name of the curent K8S node, defaults to the hostname.
     pub k8s_node: Option<String>,
@@ -141,6 +160,7 @@ pub struct Config {
     pub token_retrieval: TokenRetrievalConfig,
 
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
 
     /// If `true`, adds attributes like `uid`, `name`, `namespace`, `node` to the cgroup measurements produced by other plugins.
@@ -150,6 +170,29 @@ pub struct Config {
     /// A `false` value will only annotate pod cgroups.
     /// Note that `annotate_foreign_measurements` needs to be true.
     pub annotate_containers: bool,
+
+    /// Pod labels to add to the measurements, as attributes `label_<key>`.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Pod annotations to add to the measurements, as attributes `annotation_<key>`.
+    #[serde(default)]
+    pub annotations: Vec<String>,
+    /// If `true`, follows the owners of the pods up to the top-level workload (e.g. ReplicaSet → Deployment).
+    /// This requires the permission to get ReplicaSets and Jobs.
+    /// The default value is `false`.
+    #[serde(default)]
+    pub resolve_owners: bool,
+
+    /// If `true`, keeps the list of pods up to date with a watch on the K8S API.
+    /// This requires the permission to watch pods.
+    /// If `false`, lists the pods of the node again whenever an unknown pod is found.
+    /// The default value is `false`.
+    #[serde(default)]
+    pub watch_pods: bool,
+
+    /// Optional metrics of cgroup v2, disabled by default.
+    #[serde(default)]
+    pub optional_metrics: OptionalMetricsConfig,
 }
 
 fn default_k8s_api_url() -> String {
@@ -165,11 +208,32 @@ impl Default for Config {
             poll_interval: Duration::from_secs(5),
             annotate_foreign_measurements: false,
             annotate_containers: false,
+            labels: Vec::new(),
+            annotations: Vec::new(),
+            resolve_owners: false,
+            watch_pods: false,
+            optional_metrics: OptionalMetricsConfig::default(),
         }
     }
 }
 
 impl Config {
+    /// Checks that the labels and annotations give distinct attribute keys.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (prefix, keys) in [("label", &self.labels), ("annotation", &self.annotations)] {
+            let mut seen = HashMap::new();
+            for key in keys {
+                let attribute = attribute_key(prefix, key);
+                if let Some(other) = seen.insert(attribute.clone(), key) {
+                    return Err(anyhow!(
+                        "{prefix}s \"{other}\" and \"{key}\" both give the attribute \"{attribute}\", list only one of them"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn k8s_node_name(&self) -> String {
         match &self.k8s_node {
             Some(node) => node.clone(),
@@ -194,4 +258,30 @@ mod tests {
 
         assert_eq!(config.k8s_node_name(), "test-node");
     }
+
+    #[test]
+    fn test_attribute_key_collision() {
+        let config = Config {
+            labels: vec![String::from("app.kubernetes.io/name"), String::from("team")],
+            annotations: vec![String::from("team")],
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        let config = Config {
+            labels: vec![
+                String::from("app.kubernetes.io/name"),
+                String::from("app_kubernetes_io_name"),
+            ],
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("label_app_kubernetes_io_name"), "unexpected error: {err}");
+
+        let config = Config {
+            annotations: vec![String::from("team"), String::from("team")],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
 }
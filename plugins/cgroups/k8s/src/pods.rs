@@ -1,18 +1,29 @@
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
 
 use alumet::measurement::AttributeValue;
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use rustc_hash::FxHashMap;
 use util_cgroups_plugins::job_annotation_transform::JobTagger;
 
 use super::token::Token;
-use api::PodList;
+use crate::watch::PodWatcher;
+use api::{OwnedObject, PodList};
+
+/// How long to wait for the watch to report a new pod, see [`AutoNodePodRegistry::get_new`].
+const NEW_POD_DELAY: Duration = Duration::from_secs(1);
 
 /// Kubernetes API client (limited capabilities, just what we need).
 #[derive(Clone)]
 pub struct ApiClient {
     client: reqwest::blocking::Client,
     auth_token: Token,
+    k8s_api_url: String,
     k8s_api_pods_route: String,
 }
 
@@ -23,28 +34,125 @@ pub struct PodInfos {
     pub name: String,
     pub namespace: String,
     pub node: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+    /// Chain of owners of the pod, from its direct owner (e.g. a ReplicaSet) to the top-level workload (e.g. a Deployment).
+    pub owners: Vec<Owner>,
+}
+
+/// Owner of a K8S object, such as a ReplicaSet, Deployment, StatefulSet, Job or DaemonSet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Owner {
+    pub kind: String,
+    pub name: String,
+}
+
+/// Which information about the pods become measurement attributes.
+#[derive(Debug, Default, Clone)]
+pub struct PodAttributeSettings {
+    /// Labels to add as attributes `label_<key>`.
+    pub labels: Vec<String>,
+    /// Annotations to add as attributes `annotation_<key>`.
+    pub annotations: Vec<String>,
+    /// If `true`, follows the owner chain up to the top-level workload (e.g. ReplicaSet → Deployment).
+    pub resolve_owners: bool,
 }
 
 /// Automatically-refreshed pod registry: keep track of the pods on a given node.
+///
+/// The pods are either listed when an unknown pod is found, or kept up to date by a [`PodWatcher`]
+/// (see [`AutoNodePodRegistry::start_watch`]). The clones of the registry share the same pods.
 #[derive(Clone)]
 pub struct AutoNodePodRegistry {
     client: ApiClient,
     node: String,
-    // TODO use uuid instead of string to reduce memory consumption
-    pods: FxHashMap<String, PodInfos>,
+    pods: PodStore,
     annotate_containers: bool,
+    attributes: PodAttributeSettings,
+    preparer: PodPreparer,
+    /// Watch that updates `pods`, stopped when the last clone of the registry is dropped.
+    watcher: Option<Arc<PodWatcher>>,
+}
+
+/// Pods of a node, shared between threads.
+#[derive(Debug, Clone, Default)]
+pub struct PodStore {
+    // TODO use uuid instead of string to reduce memory consumption
+    shared: Arc<(Mutex<FxHashMap<String, PodInfos>>, Condvar)>,
+}
+
+/// Completes the pods returned by the API before they are stored.
+#[derive(Clone)]
+pub struct PodPreparer {
+    client: ApiClient,
+    attributes: PodAttributeSettings,
+    /// Owners of the intermediate objects (ReplicaSets and Jobs), by namespace and object.
+    owner_cache: FxHashMap<(String, Owner), Option<Owner>>,
+    /// Cache of the previous listing, see [`PodPreparer::start_listing`].
+    old_owner_cache: FxHashMap<(String, Owner), Option<Owner>>,
+}
+
+/// Change notified by a watch on the pods.
+#[derive(Debug)]
+pub enum PodEvent {
+    /// The pod has been added or modified.
+    Applied { pod: PodInfos, resource_version: String },
+    /// The pod has been deleted.
+    Deleted { pod: PodInfos, resource_version: String },
+    /// Nothing changed, but the resource version has progressed.
+    Bookmark { resource_version: String },
+    /// The resource version is too old: the pods must be listed again.
+    Expired,
+}
+
+/// Stream of events returned by [`ApiClient::watch_pods`].
+pub struct PodEventStream {
+    reader: BufReader<reqwest::blocking::Response>,
+    expired: bool,
 }
 
 /// Encoding/decoding of the K8S API responses.
 /// Fields that we don't need are not included, serde will skip them.
 mod api {
     use serde::Deserialize;
+    use std::collections::BTreeMap;
 
     #[derive(Deserialize)]
     pub struct PodList {
+        #[serde(default)]
+        pub metadata: ListMeta,
         pub items: Vec<Pod>,
     }
 
+    #[derive(Deserialize, Default)]
+    pub struct ListMeta {
+        #[serde(default, rename = "resourceVersion")]
+        pub resource_version: String,
+    }
+
+    /// Line of the response to a watch request.
+    #[derive(Deserialize)]
+    pub struct WatchEvent {
+        #[serde(rename = "type")]
+        pub event_type: String,
+        pub object: serde_json::Value,
+    }
+
+    /// Object of a BOOKMARK event.
+    #[derive(Deserialize)]
+    pub struct Bookmark {
+        pub metadata: ListMeta,
+    }
+
+    /// Object of an ERROR event.
+    #[derive(Deserialize)]
+    pub struct Status {
+        #[serde(default)]
+        pub code: u16,
+        #[serde(default)]
+        pub message: String,
+    }
+
     #[derive(Deserialize)]
     pub struct Pod {
         pub metadata: ObjectMeta,
@@ -56,6 +164,45 @@ mod api {
         pub name: String,
         pub namespace: String,
         pub uid: String,
+        #[serde(default, rename = "resourceVersion")]
+        pub resource_version: String,
+        #[serde(default)]
+        pub labels: BTreeMap<String, String>,
+        #[serde(default)]
+        pub annotations: BTreeMap<String, String>,
+        #[serde(default, rename = "ownerReferences")]
+        pub owner_references: Vec<OwnerReference>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct OwnerReference {
+        pub kind: String,
+        pub name: String,
+        #[serde(default)]
+        pub controller: bool,
+    }
+
+    /// Any object that can have owners, such as a ReplicaSet or a Job.
+    #[derive(Deserialize)]
+    pub struct OwnedObject {
+        pub metadata: OwnedObjectMeta,
+    }
+
+    #[derive(Deserialize)]
+    pub struct OwnedObjectMeta {
+        #[serde(default, rename = "ownerReferences")]
+        pub owner_references: Vec<OwnerReference>,
+    }
+
+    /// Returns the managing controller among the owners, or the first owner if there is no controller.
+    pub fn controller_of(owners: Vec<OwnerReference>) -> Option<OwnerReference> {
+        let mut owners = owners.into_iter();
+        let first = owners.next()?;
+        if first.controller {
+            Some(first)
+        } else {
+            Some(owners.find(|o| o.controller).unwrap_or(first))
+        }
     }
 
     #[derive(Deserialize)]
@@ -72,10 +219,26 @@ impl From<api::Pod> for PodInfos {
             name: pod.metadata.name,
             namespace: pod.metadata.namespace,
             node: pod.spec.node_name,
+            labels: pod.metadata.labels,
+            annotations: pod.metadata.annotations,
+            owners: api::controller_of(pod.metadata.owner_references)
+                .map(|o| Owner {
+                    kind: o.kind,
+                    name: o.name,
+                })
+                .into_iter()
+                .collect(),
         }
     }
 }
 
+impl PodInfos {
+    /// Returns the top-level owner of the pod, if any.
+    pub fn workload(&self) -> Option<&Owner> {
+        self.owners.last()
+    }
+}
+
 impl ApiClient {
     pub fn new(k8s_api_url: &str, auth_token: Token) -> anyhow::Result<Self> {
         let client = reqwest::blocking::Client::builder()
@@ -88,11 +251,13 @@ impl ApiClient {
         Ok(Self {
             auth_token,
             client,
+            k8s_api_url: k8s_api_url.to_owned(),
             k8s_api_pods_route,
         })
     }
 
-    pub fn list_pods(&self, node: Option<&str>) -> anyhow::Result<impl Iterator<Item = PodInfos>> {
+    /// Lists the pods, and returns the resource version of the list, from which a watch can start.
+    pub fn list_pods(&self, node: Option<&str>) -> anyhow::Result<(impl Iterator<Item = PodInfos>, String)> {
         // get the auth token, refreshed if needed
         let token = self.auth_token.get_value().context("failed to get auth token")?;
 
@@ -108,46 +273,371 @@ impl ApiClient {
         let pods: PodList = response.json().context("failed to parse json response")?;
 
         // turn the response into the format we want
+        let version = pods.metadata.resource_version;
         let pods = pods.items.into_iter().map(PodInfos::from);
-        Ok(pods)
+        Ok((pods, version))
+    }
+
+    /// Watches the changes to the pods of a node, starting from the given resource version.
+    ///
+    /// The server closes the stream after `timeout`, the watch must then be started again.
+    pub fn watch_pods(&self, node: &str, resource_version: &str, timeout: Duration) -> anyhow::Result<PodEventStream> {
+        let token = self.auth_token.get_value().context("failed to get auth token")?;
+        let response = self
+            .client
+            .get(&self.k8s_api_pods_route)
+            .bearer_auth(token)
+            .query(&[
+                ("fieldSelector", format!("spec.nodeName={node}")),
+                ("watch", String::from("true")),
+                ("resourceVersion", resource_version.to_owned()),
+                ("allowWatchBookmarks", String::from("true")),
+                ("timeoutSeconds", timeout.as_secs().to_string()),
+            ])
+            // the default timeout of the client would interrupt the stream
+            .timeout(timeout + Duration::from_secs(30))
+            .send()
+            .context("failed to send http request")?;
+        log::trace!("response: {response:?}");
+
+        // the resource version can be rejected immediately, or later with an ERROR event
+        let expired = response.status() == reqwest::StatusCode::GONE;
+        let response = if expired {
+            response
+        } else {
+            response.error_for_status().context("bad http status")?
+        };
+        Ok(PodEventStream {
+            reader: BufReader::new(response),
+            expired,
+        })
+    }
+
+    /// Returns the owner of an object, if it has one.
+    ///
+    /// Only ReplicaSets and Jobs are looked up, because they are the only workloads that are
+    /// usually managed by another one (a Deployment or a CronJob). The other kinds have no owner.
+    pub fn get_owner(&self, namespace: &str, object: &Owner) -> anyhow::Result<Option<Owner>> {
+        let route = match object.kind.as_str() {
+            "ReplicaSet" => format!(
+                "{}/apis/apps/v1/namespaces/{namespace}/replicasets/{}",
+                self.k8s_api_url, object.name
+            ),
+            "Job" => format!(
+                "{}/apis/batch/v1/namespaces/{namespace}/jobs/{}",
+                self.k8s_api_url, object.name
+            ),
+            _ => return Ok(None),
+        };
+        let token = self.auth_token.get_value().context("failed to get auth token")?;
+        let response = self
+            .client
+            .get(&route)
+            .bearer_auth(token)
+            .send()
+            .context("failed to send http request")?;
+        let status = response.status();
+        if status == reqwest::StatusCode::FORBIDDEN || status == reqwest::StatusCode::NOT_FOUND {
+            // Cached as "no owner": asking again would give the same answer.
+            log::warn!(
+                "cannot get the owner of K8S {} {namespace}/{}: {status}",
+                object.kind,
+                object.name
+            );
+            return Ok(None);
+        }
+        let response = response.error_for_status().context("bad http status")?;
+        let object: OwnedObject = response.json().context("failed to parse json response")?;
+        let owner = api::controller_of(object.metadata.owner_references).map(|o| Owner {
+            kind: o.kind,
+            name: o.name,
+        });
+        Ok(owner)
     }
 }
 
 impl AutoNodePodRegistry {
-    pub fn new(node: String, k8s_api_client: ApiClient, annotate_containers: bool) -> Self {
+    pub fn new(
+        node: String,
+        k8s_api_client: ApiClient,
+        annotate_containers: bool,
+        attributes: PodAttributeSettings,
+    ) -> Self {
         Self {
+            preparer: PodPreparer::new(k8s_api_client.clone(), attributes.clone()),
             client: k8s_api_client,
             node,
-            pods: Default::default(),
+            pods: PodStore::default(),
             annotate_containers,
+            attributes,
+            watcher: None,
         }
     }
 
     pub fn refresh(&mut self) -> anyhow::Result<()> {
-        let all_pods = self
-            .client
-            .list_pods(Some(&self.node))
-            .with_context(|| format!("failed to list K8S pods on node {}", self.node))?;
-        self.pods = all_pods
-            .filter(|p| p.node == self.node)
-            .map(|p| (p.uid.clone(), p))
-            .collect();
+        list_into_store(&self.client, &self.node, &mut self.preparer, &self.pods)?;
+        Ok(())
+    }
+
+    /// Lists the pods, then keeps them up to date with a watch on the K8S API, in the background.
+    ///
+    /// Once the watch is started, unknown pods no longer trigger a new listing, unless the watch is failing.
+    pub fn start_watch(&mut self) -> anyhow::Result<()> {
+        let version = list_into_store(&self.client, &self.node, &mut self.preparer, &self.pods)?;
+        let watcher = PodWatcher::spawn(
+            self.client.clone(),
+            self.node.clone(),
+            self.preparer.clone(),
+            self.pods.clone(),
+            version,
+        )?;
+        self.watcher = Some(Arc::new(watcher));
         Ok(())
     }
 
+    /// Returns the infos of the pod.
+    ///
+    /// If the watch is working, this never waits: a pod that the watch has not reported yet is unknown,
+    /// until a later call.
     pub fn get(&mut self, pod_uid: &str) -> anyhow::Result<Option<PodInfos>> {
         if let Some(infos) = self.pods.get(pod_uid) {
-            return Ok(Some(infos.to_owned()));
+            return Ok(Some(infos));
         }
 
-        // We have no info about this pod, ask the K8S API.
+        if let Some(watcher) = &self.watcher
+            && watcher.is_healthy()
+        {
+            return Ok(None);
+        }
+
+        // We have no info about this pod and no working watch, ask the K8S API.
         self.refresh()?;
 
         // Is the pod here? If not, it must have been deleted in the meantime => return None.
-        match self.pods.get(pod_uid) {
-            Some(infos) => Ok(Some(infos.to_owned())),
-            None => Ok(None),
+        Ok(self.pods.get(pod_uid))
+    }
+
+    /// Returns the infos of a pod that has just been created.
+    ///
+    /// Unlike [`get`](Self::get), this waits a little for the watch to report the pod.
+    /// It must not be called from the measurement pipeline.
+    pub fn get_new(&mut self, pod_uid: &str) -> anyhow::Result<Option<PodInfos>> {
+        if let Some(watcher) = &self.watcher
+            && watcher.is_healthy()
+        {
+            return Ok(self.pods.wait_for(pod_uid, NEW_POD_DELAY));
         }
+        self.get(pod_uid)
+    }
+
+    /// Returns the attributes of the pod that the cgroup belongs to, see [`get_new`](Self::get_new).
+    ///
+    /// Used when a new cgroup is detected, the measurement pipeline uses [`JobTagger::attributes_for_cgroup`].
+    pub fn attributes_for_new_cgroup(&mut self, cgroup: &util_cgroups::Cgroup) -> Vec<(String, AttributeValue)> {
+        self.pod_attributes(cgroup, Self::get_new)
+    }
+
+    fn pod_attributes(
+        &mut self,
+        cgroup: &util_cgroups::Cgroup,
+        get: fn(&mut Self, &str) -> anyhow::Result<Option<PodInfos>>,
+    ) -> Vec<(String, AttributeValue)> {
+        let Some(pod_uid) = extract_pod_uid_from_cgroup(cgroup.fs_path(), self.annotate_containers) else {
+            return Vec::new();
+        };
+        get(self, &pod_uid)
+            .inspect_err(|e| log::error!("failed to get K8S pod infos for pod {pod_uid}: {e:#}"))
+            .ok()
+            .flatten()
+            .map(|pod_infos| self.attributes.attributes(pod_infos))
+            .unwrap_or_default()
+    }
+}
+
+/// Lists the pods of the node and replaces the content of the store.
+///
+/// Returns the resource version of the list.
+pub fn list_into_store(
+    client: &ApiClient,
+    node: &str,
+    preparer: &mut PodPreparer,
+    store: &PodStore,
+) -> anyhow::Result<String> {
+    let (node_pods, version) = client
+        .list_pods(Some(node))
+        .with_context(|| format!("failed to list K8S pods on node {node}"))?;
+    let node_pods: Vec<PodInfos> = node_pods.filter(|p| p.node == node).collect();
+
+    preparer.start_listing();
+    let mut pods = FxHashMap::default();
+    for mut pod in node_pods {
+        preparer.prepare(&mut pod);
+        pods.insert(pod.uid.clone(), pod);
+    }
+    store.replace(pods);
+    Ok(version)
+}
+
+impl PodStore {
+    pub fn get(&self, pod_uid: &str) -> Option<PodInfos> {
+        self.shared.0.lock().unwrap().get(pod_uid).cloned()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.shared.0.lock().unwrap().is_empty()
+    }
+
+    pub fn replace(&self, pods: FxHashMap<String, PodInfos>) {
+        self.update(|current| *current = pods);
+    }
+
+    pub fn insert(&self, pod: PodInfos) {
+        self.update(|pods| {
+            pods.insert(pod.uid.clone(), pod);
+        });
+    }
+
+    pub fn remove(&self, pod_uid: &str) {
+        self.update(|pods| {
+            pods.remove(pod_uid);
+        });
+    }
+
+    /// Waits until the pod is in the store, or until the timeout expires.
+    pub fn wait_for(&self, pod_uid: &str, timeout: Duration) -> Option<PodInfos> {
+        let (pods, changed) = &*self.shared;
+        let pods = pods.lock().unwrap();
+        let (pods, _) = changed
+            .wait_timeout_while(pods, timeout, |pods| !pods.contains_key(pod_uid))
+            .unwrap();
+        pods.get(pod_uid).cloned()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut FxHashMap<String, PodInfos>)) {
+        let (pods, changed) = &*self.shared;
+        f(&mut pods.lock().unwrap());
+        changed.notify_all();
+    }
+}
+
+impl PodPreparer {
+    pub fn new(client: ApiClient, attributes: PodAttributeSettings) -> Self {
+        Self {
+            client,
+            attributes,
+            owner_cache: FxHashMap::default(),
+            old_owner_cache: FxHashMap::default(),
+        }
+    }
+
+    /// Starts a new listing of all the pods.
+    ///
+    /// Only the owners of the pods that are prepared from now on are kept in the cache.
+    pub fn start_listing(&mut self) {
+        self.old_owner_cache = std::mem::take(&mut self.owner_cache);
+    }
+
+    pub fn prepare(&mut self, pod: &mut PodInfos) {
+        // Don't keep the annotations that we don't need, some of them can be quite big.
+        pod.annotations.retain(|k, _| self.attributes.annotations.contains(k));
+        if self.attributes.resolve_owners {
+            self.resolve_owners(pod);
+        }
+    }
+
+    /// Completes the owner chain of the pod, up to its top-level workload.
+    fn resolve_owners(&mut self, pod: &mut PodInfos) {
+        // A Deployment owns a ReplicaSet, which owns the pod: the chain is short.
+        const MAX_DEPTH: usize = 4;
+        while let Some(object) = pod.owners.last()
+            && pod.owners.len() < MAX_DEPTH
+        {
+            let key = (pod.namespace.clone(), object.clone());
+            let owner = match self.owner_cache.get(&key) {
+                Some(owner) => owner.clone(),
+                None => {
+                    let owner = match self.old_owner_cache.remove(&key) {
+                        Some(owner) => owner,
+                        None => match self.client.get_owner(&pod.namespace, object) {
+                            Ok(owner) => owner,
+                            Err(e) => {
+                                // don't cache the error, try again on the next refresh
+                                log::warn!(
+                                    "failed to get the owner of K8S {} {}/{}: {e:#}",
+                                    object.kind,
+                                    pod.namespace,
+                                    object.name
+                                );
+                                return;
+                            }
+                        },
+                    };
+                    self.owner_cache.insert(key, owner.clone());
+                    owner
+                }
+            };
+            match owner {
+                Some(owner) => pod.owners.push(owner),
+                None => return,
+            }
+        }
+    }
+}
+
+impl Iterator for PodEventStream {
+    type Item = anyhow::Result<PodEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.expired {
+            self.expired = false;
+            return Some(Ok(PodEvent::Expired));
+        }
+        // one JSON event per line
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => return Some(parse_event(&line)),
+                Err(e) => return Some(Err(anyhow!(e).context("failed to read the watch stream"))),
+            }
+        }
+    }
+}
+
+fn parse_event(line: &str) -> anyhow::Result<PodEvent> {
+    let event: api::WatchEvent = serde_json::from_str(line).context("failed to parse watch event")?;
+    let pod = |object| -> anyhow::Result<(PodInfos, String)> {
+        let pod: api::Pod = serde_json::from_value(object).context("failed to parse pod")?;
+        let resource_version = pod.metadata.resource_version.clone();
+        Ok((PodInfos::from(pod), resource_version))
+    };
+    match event.event_type.as_str() {
+        "ADDED" | "MODIFIED" => {
+            let (pod, resource_version) = pod(event.object)?;
+            Ok(PodEvent::Applied { pod, resource_version })
+        }
+        "DELETED" => {
+            let (pod, resource_version) = pod(event.object)?;
+            Ok(PodEvent::Deleted { pod, resource_version })
+        }
+        "BOOKMARK" => {
+            let bookmark: api::Bookmark = serde_json::from_value(event.object).context("failed to parse bookmark")?;
+            Ok(PodEvent::Bookmark {
+                resource_version: bookmark.metadata.resource_version,
+            })
+        }
+        "ERROR" => {
+            let status: api::Status = serde_json::from_value(event.object).context("failed to parse error")?;
+            if status.code == 410 {
+                Ok(PodEvent::Expired)
+            } else {
+                Err(anyhow!("watch error {}: {}", status.code, status.message))
+            }
+        }
+        other => Err(anyhow!("unknown watch event type {other}")),
     }
 }
 
@@ -187,26 +677,47 @@ pub fn extract_pod_uid_from_cgroup(cgroup_fs_path: &Path, annotate_containers: b
     Some(uid.to_owned())
 }
 
+/// Turns a label or annotation key into an attribute key, such as `label_app_kubernetes_io_name`.
+pub(crate) fn attribute_key(prefix: &str, key: &str) -> String {
+    let key = key.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    format!("{prefix}_{key}")
+}
+
+impl PodAttributeSettings {
+    /// Returns the attributes of the pod.
+    fn attributes(&self, pod_infos: PodInfos) -> Vec<(String, AttributeValue)> {
+        let mut extra = Vec::with_capacity(2 + self.labels.len() + self.annotations.len());
+        if let Some(workload) = pod_infos.workload() {
+            extra.push(("owner_kind".into(), AttributeValue::String(workload.kind.clone())));
+            extra.push(("owner_name".into(), AttributeValue::String(workload.name.clone())));
+        }
+        for key in &self.labels {
+            if let Some(value) = pod_infos.labels.get(key) {
+                extra.push((attribute_key("label", key), AttributeValue::String(value.clone())));
+            }
+        }
+        for key in &self.annotations {
+            if let Some(value) = pod_infos.annotations.get(key) {
+                extra.push((attribute_key("annotation", key), AttributeValue::String(value.clone())));
+            }
+        }
+
+        let mut attrs = vec![
+            ("uid".into(), AttributeValue::String(pod_infos.uid)),
+            ("name".into(), AttributeValue::String(pod_infos.name)),
+            ("namespace".into(), AttributeValue::String(pod_infos.namespace)),
+            ("node".into(), AttributeValue::String(pod_infos.node)),
+        ];
+        attrs.extend(extra);
+        attrs
+    }
+}
+
 impl JobTagger for AutoNodePodRegistry {
+    /// Never waits for the pods that the watch has not reported yet: their measurements
+    /// are not annotated until the pod is known.
     fn attributes_for_cgroup(&mut self, cgroup: &util_cgroups::Cgroup) -> Vec<(String, AttributeValue)> {
-        let Some(pod_uid) = extract_pod_uid_from_cgroup(cgroup.fs_path(), self.annotate_containers) else {
-            return Vec::new();
-        };
-        let attrs = self
-            .get(&pod_uid)
-            .inspect_err(|e| log::error!("failed to get K8S pod infos for pod {pod_uid}: {e:#}"))
-            .ok()
-            .flatten()
-            .map(|pod_infos| {
-                vec![
-                    ("uid".into(), AttributeValue::String(pod_uid)),
-                    ("name".into(), AttributeValue::String(pod_infos.name)),
-                    ("namespace".into(), AttributeValue::String(pod_infos.namespace)),
-                    ("node".into(), AttributeValue::String(pod_infos.node)),
-                ]
-            })
-            .unwrap_or_default();
-        attrs
+        self.pod_attributes(cgroup, Self::get)
     }
 }
 
@@ -269,7 +780,7 @@ mod tests {
     ) -> anyhow::Result<FxHashMap<String, PodInfos>> {
         let k8s_api_url = server.url();
         let client = ApiClient::new(&k8s_api_url, auth_token)?;
-        let result: FxHashMap<_, _> = client.list_pods(Some(node))?.map(|p| (p.uid.clone(), p)).collect();
+        let result: FxHashMap<_, _> = client.list_pods(Some(node))?.0.map(|p| (p.uid.clone(), p)).collect();
         Ok(result)
     }
 
@@ -393,7 +904,7 @@ mod tests {
         let auth_token = Token::with_file(path.to_str().unwrap().to_owned());
         let k8s_api_url = server.url();
         let k8s_api_client = ApiClient::new(&k8s_api_url, auth_token).unwrap();
-        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false);
+        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false, Default::default());
         assert!(registry.pods.is_empty());
 
         // This is the only request we've got
@@ -458,7 +969,7 @@ mod tests {
         let auth_token = Token::with_file(path.to_str().unwrap().to_owned());
         let k8s_api_url = server.url();
         let k8s_api_client = ApiClient::new(&k8s_api_url, auth_token).unwrap();
-        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false);
+        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false, Default::default());
         assert!(registry.pods.is_empty());
 
         println!("refreshed: {:?}", registry.pods);
@@ -476,6 +987,214 @@ mod tests {
         mock.assert();
     }
 
+    #[test]
+    fn test_registry_with_labels_and_owners() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("token");
+        std::fs::write(&path, TOKEN_CONTENT).unwrap();
+
+        let node = "node1";
+        let url = format!("/api/v1/pods?fieldSelector=spec.nodeName%3D{}", node);
+        let mut server = Server::new();
+        let pods_mock = server
+            .mock("GET", url.as_str())
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(
+                json!({
+                    "items": [
+                        {
+                            "metadata": {
+                                "name": "web-7d4b9c-x2x4z",
+                                "namespace": "shop",
+                                "uid": "5f32d849-6210-4886-a48d-e0d90e1d0206",
+                                "labels": {
+                                    "app.kubernetes.io/name": "web",
+                                    "pod-template-hash": "7d4b9c"
+                                },
+                                "annotations": {
+                                    "team": "frontend",
+                                    "kubectl.kubernetes.io/last-applied-configuration": "{}"
+                                },
+                                "ownerReferences": [
+                                    {"kind": "ReplicaSet", "name": "web-7d4b9c", "controller": true}
+                                ]
+                            },
+                            "spec": {
+                                "nodeName": "node1"
+                            }
+                        },
+                        {
+                            "metadata": {
+                                "name": "db-0",
+                                "namespace": "shop",
+                                "uid": "5fffd849-6210-4886-aaaa-e0d90e1d0206",
+                                "ownerReferences": [
+                                    {"kind": "StatefulSet", "name": "db", "controller": true}
+                                ]
+                            },
+                            "spec": {
+                                "nodeName": "node1"
+                            }
+                        }
+                    ]
+                })
+                .to_string(),
+            )
+            .expect(2)
+            .create();
+        let replicaset_mock = server
+            .mock("GET", "/apis/apps/v1/namespaces/shop/replicasets/web-7d4b9c")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(
+                json!({
+                    "metadata": {
+                        "name": "web-7d4b9c",
+                        "ownerReferences": [
+                            {"kind": "Deployment", "name": "web", "controller": true}
+                        ]
+                    }
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create();
+
+        let auth_token = Token::with_file(path.to_str().unwrap().to_owned());
+        let k8s_api_client = ApiClient::new(&server.url(), auth_token).unwrap();
+        let settings = PodAttributeSettings {
+            labels: vec![String::from("app.kubernetes.io/name"), String::from("missing")],
+            annotations: vec![String::from("team")],
+            resolve_owners: true,
+        };
+        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false, settings.clone());
+
+        // the owner of the ReplicaSet is cached: the second refresh does not query it again
+        registry.refresh().unwrap();
+        registry.refresh().unwrap();
+        pods_mock.assert();
+        replicaset_mock.assert();
+
+        let web = registry.get("5f32d849-6210-4886-a48d-e0d90e1d0206").unwrap().unwrap();
+        assert_eq!(
+            web.owners,
+            vec![
+                Owner {
+                    kind: String::from("ReplicaSet"),
+                    name: String::from("web-7d4b9c")
+                },
+                Owner {
+                    kind: String::from("Deployment"),
+                    name: String::from("web")
+                },
+            ]
+        );
+        assert_eq!(web.annotations.len(), 1, "unused annotations should be dropped");
+        assert_eq!(
+            settings.attributes(web),
+            vec![
+                (
+                    String::from("uid"),
+                    AttributeValue::String(String::from("5f32d849-6210-4886-a48d-e0d90e1d0206"))
+                ),
+                (
+                    String::from("name"),
+                    AttributeValue::String(String::from("web-7d4b9c-x2x4z"))
+                ),
+                (String::from("namespace"), AttributeValue::String(String::from("shop"))),
+                (String::from("node"), AttributeValue::String(String::from("node1"))),
+                (
+                    String::from("owner_kind"),
+                    AttributeValue::String(String::from("Deployment"))
+                ),
+                (String::from("owner_name"), AttributeValue::String(String::from("web"))),
+                (
+                    String::from("label_app_kubernetes_io_name"),
+                    AttributeValue::String(String::from("web"))
+                ),
+                (
+                    String::from("annotation_team"),
+                    AttributeValue::String(String::from("frontend"))
+                ),
+            ]
+        );
+
+        // StatefulSets have no owner, there is no need to query the API
+        let db = registry.get("5fffd849-6210-4886-aaaa-e0d90e1d0206").unwrap().unwrap();
+        assert_eq!(
+            db.workload(),
+            Some(&Owner {
+                kind: String::from("StatefulSet"),
+                name: String::from("db")
+            })
+        );
+    }
+
+    #[test]
+    fn test_registry_owner_forbidden() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("token");
+        std::fs::write(&path, TOKEN_CONTENT).unwrap();
+
+        let node = "node1";
+        let url = format!("/api/v1/pods?fieldSelector=spec.nodeName%3D{}", node);
+        let mut server = Server::new();
+        let _pods_mock = server
+            .mock("GET", url.as_str())
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(
+                json!({
+                    "items": [
+                        {
+                            "metadata": {
+                                "name": "web-7d4b9c-x2x4z",
+                                "namespace": "shop",
+                                "uid": "5f32d849-6210-4886-a48d-e0d90e1d0206",
+                                "ownerReferences": [
+                                    {"kind": "ReplicaSet", "name": "web-7d4b9c", "controller": true}
+                                ]
+                            },
+                            "spec": {
+                                "nodeName": "node1"
+                            }
+                        }
+                    ]
+                })
+                .to_string(),
+            )
+            .create();
+        let replicaset_mock = server
+            .mock("GET", "/apis/apps/v1/namespaces/shop/replicasets/web-7d4b9c")
+            .with_status(403)
+            .expect(1)
+            .create();
+
+        let auth_token = Token::with_file(path.to_str().unwrap().to_owned());
+        let k8s_api_client = ApiClient::new(&server.url(), auth_token).unwrap();
+        let settings = PodAttributeSettings {
+            labels: Vec::new(),
+            annotations: Vec::new(),
+            resolve_owners: true,
+        };
+        let mut registry = AutoNodePodRegistry::new(node.to_owned(), k8s_api_client, false, settings);
+
+        // the missing permission is cached as "no owner": the ReplicaSet is not queried again
+        registry.refresh().unwrap();
+        registry.refresh().unwrap();
+        replicaset_mock.assert();
+
+        let web = registry.get("5f32d849-6210-4886-a48d-e0d90e1d0206").unwrap().unwrap();
+        assert_eq!(
+            web.workload(),
+            Some(&Owner {
+                kind: String::from("ReplicaSet"),
+                name: String::from("web-7d4b9c")
+            })
+        );
+    }
+
     //// Test `get_node_pods_infos` with JSON send in fake server to a specific token,
     //// with some of them missing in the JSON
     #[test]
@@ -2,7 +2,6 @@ use alumet::pipeline::elements::source::trigger::TriggerSpec;
 
 use util_cgroups_plugins::{
     cgroup_events::{CgroupSetupCallback, ProbeSetup, SourceSettings},
-    job_annotation_transform::JobTagger,
     metrics::{AugmentedMetrics, Metrics},
 };
 
@@ -19,7 +18,7 @@ impl CgroupSetupCallback for SourceSetup {
         metrics: &Metrics,
     ) -> Option<util_cgroups_plugins::cgroup_events::ProbeSetup> {
         // Retrieves associated attributes
-        let attrs = self.k8s_pods.attributes_for_cgroup(cgroup);
+        let attrs = self.k8s_pods.attributes_for_new_cgroup(cgroup);
 
         if attrs.is_empty() {
             // If empty, this is NOT a pod
@@ -39,6 +38,7 @@ impl CgroupSetupCallback for SourceSetup {
         Some(ProbeSetup {
             metrics,
             source_settings,
+            summaries: None,
         })
     }
 }
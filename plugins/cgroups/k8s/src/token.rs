@@ -6,6 +6,7 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use alumet::agent::schema::JsonSchema;
 use anyhow::Context;
 use base64::{DecodeError, Engine};
 use serde::{Deserialize, Serialize};
@@ -34,7 +35,7 @@ const DEFAULT_NAMESPACE: &str = "alumet";
 ///     namespace = "alumet-ns"
 /// }
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum TokenRetrievalConfig {
     Kubectl {
@@ -48,7 +49,7 @@ pub enum TokenRetrievalConfig {
     Simple(SimpleRetrievalMethod),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SimpleRetrievalMethod {
     Kubectl,
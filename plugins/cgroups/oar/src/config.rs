@@ -1,11 +1,14 @@
 use std::time::Duration;
 
+use alumet::agent::schema::JsonSchema;
 use serde::{Deserialize, Serialize};
+use util_cgroups_plugins::{job_summary::JobSummaryConfig, v2::OptionalMetricsConfig};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     pub(crate) oar_version: OarVersion,
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub(crate) poll_interval: Duration,
     pub(crate) jobs_only: bool,
     /// If `true`, adds attributes like `job_id` to the measurements produced by other plugins.
@@ -14,6 +17,12 @@ pub struct Config {
     /// The measurements must have the `cgroup` resource consumer, and **cgroup v2** must be used on the node.
     #[serde(default)]
     pub annotate_foreign_measurements: bool,
+    /// Summary of each job, produced when the job ends.
+    #[serde(default)]
+    pub(crate) job_summary: JobSummaryConfig,
+    /// Optional metrics of cgroup v2, disabled by default.
+    #[serde(default)]
+    pub(crate) optional_metrics: OptionalMetricsConfig,
 }
 
 impl Default for Config {
@@ -24,11 +33,13 @@ impl Default for Config {
             poll_interval: Duration::from_secs(1),
             jobs_only: true,
             annotate_foreign_measurements: false,
+            job_summary: JobSummaryConfig::default(),
+            optional_metrics: OptionalMetricsConfig::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum OarVersion {
     Oar2,
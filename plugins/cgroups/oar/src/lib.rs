@@ -1,6 +1,7 @@
+use alumet::pipeline::elements::source::trigger::TriggerSpec;
 use alumet::plugin::{
     AlumetPluginStart, AlumetPostStart, ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
 
@@ -15,6 +16,7 @@ use util_cgroups_plugins::{
     job_annotation_transform::{
         CachedCgroupHierarchy, JobAnnotationTransform, OptionalSharedHierarchy, SharedCgroupHierarchy,
     },
+    job_summary::{JobSummaries, JobSummaryMetrics, JobSummarySource, JobSummaryTransform},
     metrics::Metrics,
 };
 
@@ -60,7 +62,7 @@ impl AlumetPlugin for OarPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -82,12 +84,40 @@ impl AlumetPlugin for OarPlugin {
             alumet.add_transform("oar-annotation", Box::new(transform))?;
         }
 
+        let metrics = Metrics::create(alumet)?;
+
+        // If enabled, create the transform that accumulates the measurements of each job,
+        // and the source that produces the summary of each job when it ends.
+        let summaries = if config.job_summary.enabled {
+            let summaries = JobSummaries::default();
+            let summary_metrics = JobSummaryMetrics::create(alumet)?;
+            let energy_metrics = config.job_summary.energy_metrics.clone();
+            let source = JobSummarySource::new(summaries.clone(), &summary_metrics, energy_metrics.clone());
+            alumet.add_source(
+                "oar-job-summary",
+                Box::new(source),
+                TriggerSpec::at_interval(config.poll_interval),
+            )?;
+            let (s, m) = (summaries.clone(), metrics.clone());
+            alumet.add_transform_builder("oar-job-summary", move |ctx| {
+                let transform = JobSummaryTransform::new(s, &m, &energy_metrics, ctx)?;
+                Ok(Box::new(transform))
+            })?;
+            Some(summaries)
+        } else {
+            None
+        };
+
         // Prepare for cgroup detection.
         let starting_state = StartingState {
-            metrics: Metrics::create(alumet)?,
-            reactor_config: ReactorConfig::default(),
+            metrics,
+            reactor_config: ReactorConfig {
+                v2_optional_metrics: config.optional_metrics,
+                ..Default::default()
+            },
             job_cleaner: JobCleaner::with_version(&tracker, config.oar_version)?,
-            source_setup: source::JobSourceSetup::new(config, tracker.clone(), tagger)?,
+            source_setup: source::JobSourceSetup::new(config, tracker.clone(), tagger, summaries.clone())?,
+            summaries,
         };
         self.starting_state = Some(starting_state);
 
@@ -105,7 +135,7 @@ impl AlumetPlugin for OarPlugin {
             s.metrics,
             ReactorCallbacks {
                 probe_setup: s.source_setup,
-                on_removal: s.job_cleaner,
+                on_removal: (s.job_cleaner, s.summaries.unwrap_or_default()),
                 on_fs_mount: NoCallback,
             },
             alumet.pipeline_control(),
@@ -126,4 +156,5 @@ struct StartingState {
     reactor_config: ReactorConfig,
     source_setup: source::JobSourceSetup,
     job_cleaner: JobCleaner,
+    summaries: Option<JobSummaries>,
 }
@@ -12,6 +12,7 @@ use crate::{
 use util_cgroups_plugins::{
     cgroup_events::{CgroupSetupCallback, ProbeSetup, SourceSettings},
     job_annotation_transform::JobTagger,
+    job_summary::JobSummaries,
     metrics::{AugmentedMetrics, Metrics},
 };
 
@@ -22,10 +23,16 @@ pub struct JobSourceSetup {
     trigger: TriggerSpec,
     tracker: JobTracker,
     jobs_only: bool,
+    summaries: Option<JobSummaries>,
 }
 
 impl JobSourceSetup {
-    pub fn new(config: Config, tracker: JobTracker, tagger: OarJobTagger) -> anyhow::Result<Self> {
+    pub fn new(
+        config: Config,
+        tracker: JobTracker,
+        tagger: OarJobTagger,
+        summaries: Option<JobSummaries>,
+    ) -> anyhow::Result<Self> {
         let trigger = TriggerSpec::at_interval(config.poll_interval);
         match config.oar_version {
             OarVersion::Oar2 => Ok(Self {
@@ -34,6 +41,7 @@ impl JobSourceSetup {
                 trigger,
                 tracker,
                 jobs_only: config.jobs_only,
+                summaries,
             }),
             OarVersion::Oar3 => Ok(Self {
                 tagger,
@@ -41,6 +49,7 @@ impl JobSourceSetup {
                 trigger,
                 tracker,
                 jobs_only: config.jobs_only,
+                summaries,
             }),
         }
     }
@@ -100,12 +109,21 @@ impl CgroupSetupCallback for JobSourceSetup {
             username_from_id,
         )?;
 
+        let summaries = match &self.summaries {
+            Some(summaries) if find_jobid_in_attrs(&attrs).is_some() => {
+                summaries.job_started(cgroup, attrs.clone());
+                Some(summaries.clone())
+            }
+            _ => None,
+        };
+
         let trigger = self.trigger.clone();
         let source_settings = SourceSettings { name, trigger };
         let metrics = AugmentedMetrics::with_common_attr_vec(metrics, attrs);
         Some(ProbeSetup {
             metrics,
             source_settings,
+            summaries,
         })
     }
 }
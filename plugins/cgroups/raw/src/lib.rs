@@ -1,8 +1,9 @@
 use std::time::Duration;
 
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger::TriggerSpec,
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,7 @@ use source::SourceSetup;
 use util_cgroups_plugins::{
     cgroup_events::{CgroupReactor, NoCallback, ReactorCallbacks, ReactorConfig},
     metrics::Metrics,
+    v2::OptionalMetricsConfig,
 };
 
 mod source;
@@ -44,12 +46,15 @@ impl AlumetPlugin for RawCgroupPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let metrics = Metrics::create(alumet)?;
-        let reactor_config = ReactorConfig::default();
+        let reactor_config = ReactorConfig {
+            v2_optional_metrics: self.config.optional_metrics,
+            ..Default::default()
+        };
         let starting_state = StartingState {
             metrics,
             reactor_config,
@@ -89,10 +94,15 @@ struct StartingState {
     reactor_config: ReactorConfig,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
+
+    /// Optional metrics of cgroup v2, disabled by default.
+    #[serde(default)]
+    pub optional_metrics: OptionalMetricsConfig,
 }
 
 impl Default for Config {
@@ -100,6 +110,7 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             poll_interval: Duration::from_secs(5),
+            optional_metrics: OptionalMetricsConfig::default(),
         }
     }
 }
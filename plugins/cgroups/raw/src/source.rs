@@ -30,6 +30,7 @@ impl CgroupSetupCallback for SourceSetup {
         Some(ProbeSetup {
             metrics,
             source_settings,
+            summaries: None,
         })
     }
 }
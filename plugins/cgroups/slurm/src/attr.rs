@@ -1,8 +1,12 @@
 use alumet::measurement::AttributeValue;
 use util_cgroups::Cgroup;
+use util_cgroups_plugins::cgroup_events::CgroupRemovalCallback;
 use util_cgroups_plugins::job_annotation_transform::JobTagger;
+use util_cgroups_plugins::job_summary::JobSummaries;
 use util_cgroups_plugins::regex::RegexAttributesExtrator;
 
+use crate::metadata::JobMetadataCache;
+
 pub const JOB_REGEX_SLURM1: &str = "/slurm/uid_(?<user_id__u64>[0-9]+)/job_(?<job_id__u64>[0-9]+)";
 pub const JOB_REGEX_SLURM2: &str = "/slurmstepd.scope/job_(?<job_id__u64>[0-9]+)(?<remaining>(/.*)?)";
 
@@ -65,19 +69,39 @@ fn extract_values(path: &str) -> Option<JobDetails> {
 pub struct SlurmJobTagger {
     extractor_v1: RegexAttributesExtrator,
     extractor_v2: RegexAttributesExtrator,
+    /// If set, the attributes of the jobs are enriched with their metadata.
+    metadata: Option<JobMetadataCache>,
+    /// If set, the metadata of the jobs is added to their summary when they end.
+    summaries: Option<JobSummaries>,
 }
 
 impl SlurmJobTagger {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(metadata: Option<JobMetadataCache>) -> anyhow::Result<Self> {
         Ok(Self {
             extractor_v1: RegexAttributesExtrator::new(JOB_REGEX_SLURM1)?,
             extractor_v2: RegexAttributesExtrator::new(JOB_REGEX_SLURM2)?,
+            metadata,
+            summaries: None,
         })
     }
-}
 
-impl JobTagger for SlurmJobTagger {
-    fn attributes_for_cgroup(&mut self, cgroup: &Cgroup) -> Vec<(String, AttributeValue)> {
+    /// Adds the metadata of the jobs to their summary when they end.
+    pub fn with_summaries(mut self, summaries: Option<JobSummaries>) -> Self {
+        self.summaries = summaries;
+        self
+    }
+
+    /// Starts to fetch the metadata of the job in the background, if the metadata is enabled.
+    ///
+    /// The metadata is added to the attributes once it has been obtained.
+    pub fn fetch_metadata(&self, job_id: u64) {
+        if let Some(metadata) = &self.metadata {
+            metadata.fetch_in_background(job_id);
+        }
+    }
+
+    /// Extracts the attributes that are contained in the path of the cgroup.
+    fn path_attributes(&self, cgroup: &Cgroup) -> Vec<(String, AttributeValue)> {
         // extracts attributes "job_id" and ("user" or "user_id")
         let extractor = match cgroup.hierarchy().version() {
             util_cgroups::CgroupVersion::V1 => &self.extractor_v1,
@@ -112,6 +136,42 @@ impl JobTagger for SlurmJobTagger {
     }
 }
 
+impl JobTagger for SlurmJobTagger {
+    fn attributes_for_cgroup(&mut self, cgroup: &Cgroup) -> Vec<(String, AttributeValue)> {
+        let mut attrs = self.path_attributes(cgroup);
+        if let Some(metadata) = &self.metadata
+            && let Some(job_id) = find_jobid_in_attrs(&attrs)
+            && let Some(job) = metadata.get(job_id)
+        {
+            attrs.extend(job.attributes());
+        }
+        attrs
+    }
+}
+
+/// Forgets the metadata of the jobs whose cgroup has been deleted, after adding it to their summary.
+impl CgroupRemovalCallback for SlurmJobTagger {
+    fn on_cgroups_removed(&mut self, cgroups: Vec<Cgroup>) -> anyhow::Result<()> {
+        if let Some(metadata) = &self.metadata {
+            for cgroup in cgroups {
+                let attrs = self.path_attributes(&cgroup);
+                // the job has ended when the cgroup of the job (not of a step) is deleted
+                if let Some(job_id) = find_jobid_in_attrs(&attrs)
+                    && find_key_in_attrs("step", &attrs).is_none()
+                {
+                    if let Some(summaries) = &self.summaries
+                        && let Some(job) = metadata.get(job_id)
+                    {
+                        summaries.add_attributes(&cgroup, job.attributes());
+                    }
+                    metadata.forget(job_id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::attr::*;
@@ -1,23 +1,32 @@
 use std::time::Duration;
 
+use alumet::agent::schema::JsonSchema;
+use alumet::pipeline::elements::source::trigger::TriggerSpec;
 use alumet::plugin::{
     AlumetPluginStart, AlumetPostStart, ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
 use util_cgroups_plugins::{
-    cgroup_events::{CgroupReactor, NoCallback, ReactorCallbacks, ReactorConfig},
+    cgroup_events::{CgroupReactor, ReactorCallbacks, ReactorConfig},
     job_annotation_transform::{
         CachedCgroupHierarchy, JobAnnotationTransform, OptionalSharedHierarchy, SharedCgroupHierarchy,
     },
+    job_summary::{JobSummaries, JobSummaryConfig, JobSummaryMetrics, JobSummarySource, JobSummaryTransform},
     metrics::Metrics,
+    v2::OptionalMetricsConfig,
 };
 
-use crate::attr::SlurmJobTagger;
+use crate::{
+    attr::SlurmJobTagger,
+    metadata::{JobMetadataCache, JobMetadataTransform},
+};
+pub use metadata::{JobMetadataConfig, MetadataSource};
 
 mod attr;
+mod metadata;
 mod source;
 
 /// Gathers metrics for slurm jobs.
@@ -56,14 +65,18 @@ impl AlumetPlugin for SlurmPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
         let config = self.config.take().unwrap();
 
-        let tagger = SlurmJobTagger::new()?;
+        let metadata = config
+            .job_metadata
+            .enabled
+            .then(|| JobMetadataCache::new(&config.job_metadata));
+        let tagger = SlurmJobTagger::new(metadata.clone())?;
         let mut shared_hierarchy = OptionalSharedHierarchy::default();
 
         // If enabled, create the annotation transform.
@@ -78,15 +91,48 @@ impl AlumetPlugin for SlurmPlugin {
             alumet.add_transform("slurm/annotation", Box::new(transform))?;
         }
 
+        // If enabled, create the transform that adds the metadata of the jobs, once it has been fetched.
+        if let Some(metadata) = metadata {
+            let transform = JobMetadataTransform { metadata };
+            alumet.add_transform("slurm/metadata", Box::new(transform))?;
+        }
+
+        let metrics = Metrics::create(alumet)?;
+
+        // If enabled, create the transform that accumulates the measurements of each job,
+        // and the source that produces the summary of each job when it ends.
+        let summaries = if config.job_summary.enabled {
+            let summaries = JobSummaries::default();
+            let summary_metrics = JobSummaryMetrics::create(alumet)?;
+            let energy_metrics = config.job_summary.energy_metrics.clone();
+            let source = JobSummarySource::new(summaries.clone(), &summary_metrics, energy_metrics.clone());
+            alumet.add_source(
+                "slurm/job-summary",
+                Box::new(source),
+                TriggerSpec::at_interval(config.poll_interval),
+            )?;
+            let (s, m) = (summaries.clone(), metrics.clone());
+            alumet.add_transform_builder("slurm/job-summary", move |ctx| {
+                let transform = JobSummaryTransform::new(s, &m, &energy_metrics, ctx)?;
+                Ok(Box::new(transform))
+            })?;
+            Some(summaries)
+        } else {
+            None
+        };
+
         // Prepare for cgroup detection.
         let starting_state = StartingState {
-            metrics: Metrics::create(alumet)?,
+            metrics,
             reactor_config: ReactorConfig {
                 add_source_in_pause_state: config.add_source_in_pause_state,
+                v2_optional_metrics: config.optional_metrics,
                 ..Default::default()
             },
-            source_setup: source::JobSourceSetup::new(config, tagger)?,
+            source_setup: source::JobSourceSetup::new(config, tagger.clone(), summaries.clone())?,
+            tagger: tagger.with_summaries(summaries.clone()),
             shared_hierarchy,
+            summaries,
         };
         self.starting_state = Some(starting_state);
         Ok(())
@@ -100,7 +146,7 @@ impl AlumetPlugin for SlurmPlugin {
             s.metrics,
             ReactorCallbacks {
                 probe_setup: s.source_setup,
-                on_removal: NoCallback,
+                on_removal: (s.summaries.unwrap_or_default(), s.tagger),
                 on_fs_mount: s.shared_hierarchy,
             },
             alumet.pipeline_control(),
@@ -118,16 +164,18 @@ impl AlumetPlugin for SlurmPlugin {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// Interval between two measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
 
     /// Interval between two scans of the cgroup v1 hierarchies.
     /// Only applies to cgroup v1 hierarchies (cgroupv2 supports inotify).
     #[serde(default)]
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
     pub cgroupv1_refresh_interval: Option<Duration>,
 
     /// Only monitor the cgroups related to slurm jobs.
@@ -150,6 +198,18 @@ pub struct Config {
     /// The measurements must have the `cgroup` resource consumer, and **cgroup v2** must be used on the node.
     #[serde(default)]
     pub annotate_foreign_measurements: bool,
+
+    /// Summary of each job, produced when the job ends.
+    #[serde(default)]
+    pub job_summary: JobSummaryConfig,
+
+    /// Enrichment of the job attributes with metadata obtained from Slurm.
+    #[serde(default)]
+    pub job_metadata: JobMetadataConfig,
+
+    /// Optional metrics of cgroup v2, disabled by default.
+    #[serde(default)]
+    pub optional_metrics: OptionalMetricsConfig,
 }
 
 impl Default for Config {
@@ -162,6 +222,9 @@ impl Default for Config {
             jobs_monitoring_level: JobMonitoringLevel::Job,
             add_source_in_pause_state: false,
             annotate_foreign_measurements: false,
+            job_summary: JobSummaryConfig::default(),
+            job_metadata: JobMetadataConfig::default(),
+            optional_metrics: OptionalMetricsConfig::default(),
         }
     }
 }
@@ -171,9 +234,11 @@ pub struct StartingState {
     reactor_config: ReactorConfig,
     source_setup: source::JobSourceSetup,
     shared_hierarchy: OptionalSharedHierarchy,
+    summaries: Option<JobSummaries>,
+    tagger: SlurmJobTagger,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum JobMonitoringLevel {
     Job,
@@ -0,0 +1,567 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    process::{Child, Command, Output, Stdio},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use alumet::agent::schema::JsonSchema;
+use alumet::measurement::{AttributeValue, MeasurementBuffer};
+use alumet::pipeline::{
+    Transform,
+    elements::{error::TransformError, transform::TransformContext},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Placeholder that is replaced by the id of the job in the command or path of the [`MetadataSource`].
+const JOB_ID_PLACEHOLDER: &str = "{job_id}";
+
+/// Delay before fetching the metadata of a job again, after a first failure.
+/// It doubles on each subsequent failure, up to [`MAX_RETRY_DELAY`].
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+/// Interval between two checks of the metadata command.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Configuration of the enrichment of the job attributes with metadata obtained from Slurm.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JobMetadataConfig {
+    /// If `true`, adds the metadata of the jobs (name, account, partition, …) to the attributes.
+    pub enabled: bool,
+    /// Where to get the metadata from.
+    pub source: MetadataSource,
+    /// Maximum time to wait for the metadata command, after which it is killed.
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub timeout: Duration,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Source of the job metadata, in the JSON format of `scontrol show job --json`.
+///
+/// The placeholder `{job_id}` is replaced by the id of the job.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    /// Runs a command and reads its standard output.
+    Command(Vec<String>),
+    /// Reads a file, for instance a file written by a Slurm prolog script.
+    File(String),
+}
+
+impl Default for JobMetadataConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: MetadataSource::Command(
+                ["scontrol", "show", "job", "--json", JOB_ID_PLACEHOLDER]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+/// Metadata of a Slurm job.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct JobMetadata {
+    pub name: Option<String>,
+    pub account: Option<String>,
+    pub partition: Option<String>,
+    pub user: Option<String>,
+    pub requested_nodes: Option<u64>,
+    pub requested_cpus: Option<u64>,
+    pub requested_gpus: Option<u64>,
+}
+
+impl JobMetadata {
+    /// Turns the metadata into measurement attributes.
+    pub fn attributes(&self) -> Vec<(String, AttributeValue)> {
+        let strings = [
+            ("job_name", &self.name),
+            ("account", &self.account),
+            ("partition", &self.partition),
+            ("user", &self.user),
+        ];
+        let numbers = [
+            ("requested_nodes", self.requested_nodes),
+            ("requested_cpus", self.requested_cpus),
+            ("requested_gpus", self.requested_gpus),
+        ];
+        let strings = strings
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.to_owned(), AttributeValue::String(v.clone()?))));
+        let numbers = numbers
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.to_owned(), AttributeValue::U64(v?))));
+        strings.chain(numbers).collect()
+    }
+}
+
+/// Fetches the metadata of the jobs and keeps it in memory.
+///
+/// `JobMetadataCache` is `Clone`, `Send` and `Sync`: the clones share the same cache.
+#[derive(Clone)]
+pub struct JobMetadataCache {
+    source: Arc<MetadataSource>,
+    timeout: Duration,
+    retry_delay: Duration,
+    /// State of the metadata, by job id.
+    jobs: Arc<Mutex<HashMap<u64, CacheEntry>>>,
+}
+
+enum CacheEntry {
+    /// The metadata has been obtained.
+    Found(Arc<JobMetadata>),
+    /// The metadata is being fetched by a background thread.
+    Fetching,
+    /// The metadata could not be obtained, it will be fetched again after `retry_at`.
+    Failed { failures: u32, retry_at: Instant },
+}
+
+impl JobMetadataCache {
+    pub fn new(config: &JobMetadataConfig) -> Self {
+        Self {
+            source: Arc::new(config.source.clone()),
+            timeout: config.timeout,
+            retry_delay: INITIAL_RETRY_DELAY,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Starts to fetch the metadata of the job in the background, if it is not in the cache yet.
+    ///
+    /// Does nothing if the metadata has already been obtained, if it is being fetched,
+    /// or if the last attempt failed and the retry delay has not elapsed yet.
+    pub fn fetch_in_background(&self, job_id: u64) {
+        let failures = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let failures = match jobs.get(&job_id) {
+                Some(CacheEntry::Found(_)) | Some(CacheEntry::Fetching) => return,
+                Some(CacheEntry::Failed { failures, retry_at }) => {
+                    if Instant::now() < *retry_at {
+                        return;
+                    }
+                    *failures
+                }
+                None => 0,
+            };
+            jobs.insert(job_id, CacheEntry::Fetching);
+            failures
+        };
+
+        // Fetch on another thread, so that the cgroup detection and the transforms are not blocked by a slow command.
+        let cache = self.clone();
+        std::thread::spawn(move || {
+            let result = fetch(&cache.source, job_id, cache.timeout);
+            cache.store(job_id, failures, result);
+        });
+    }
+
+    /// Returns the metadata of the job, if it is in the cache.
+    ///
+    /// This never blocks. Returns `None` if the metadata is not available (yet): while it is being fetched,
+    /// or if the last attempt failed. In the latter case, the metadata is fetched again in the background
+    /// once the retry delay has elapsed.
+    pub fn get(&self, job_id: u64) -> Option<Arc<JobMetadata>> {
+        match self.jobs.lock().unwrap().get(&job_id) {
+            Some(CacheEntry::Found(metadata)) => return Some(metadata.clone()),
+            Some(CacheEntry::Failed { .. }) => (),
+            Some(CacheEntry::Fetching) | None => return None,
+        }
+        self.fetch_in_background(job_id);
+        None
+    }
+
+    fn store(&self, job_id: u64, failures: u32, result: anyhow::Result<JobMetadata>) {
+        let entry = match result {
+            Ok(metadata) => CacheEntry::Found(Arc::new(metadata)),
+            Err(e) => {
+                let failures = failures + 1;
+                let delay = self
+                    .retry_delay
+                    .saturating_mul(1 << (failures - 1).min(16))
+                    .min(MAX_RETRY_DELAY);
+                log::warn!("failed to get the metadata of Slurm job {job_id}, retrying in {delay:?}: {e:#}");
+                let retry_at = Instant::now() + delay;
+                CacheEntry::Failed { failures, retry_at }
+            }
+        };
+        // The job may have been forgotten in the meantime, in which case it must not come back.
+        if let Some(e) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            *e = entry;
+        }
+    }
+
+    /// Removes the job from the cache.
+    pub fn forget(&self, job_id: u64) {
+        self.jobs.lock().unwrap().remove(&job_id);
+    }
+}
+
+/// Adds the metadata of the jobs to the measurements that have a `job_id` attribute.
+///
+/// The metadata is fetched in the background when the job is detected, hence the first
+/// measurements of a job may not have it. The transform never waits for the metadata.
+pub struct JobMetadataTransform {
+    pub metadata: JobMetadataCache,
+}
+
+impl Transform for JobMetadataTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        for m in measurements.iter_mut() {
+            let job_id = m.attributes().find_map(|(k, v)| match v {
+                AttributeValue::U64(id) if k == "job_id" => Some(*id),
+                _ => None,
+            });
+            if let Some(job) = job_id.and_then(|id| self.metadata.get(id)) {
+                for (k, v) in job.attributes() {
+                    if !m.attributes_keys().any(|key| key == k) {
+                        m.add_attr(k, v);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fetch(source: &MetadataSource, job_id: u64, timeout: Duration) -> anyhow::Result<JobMetadata> {
+    let job_id_str = job_id.to_string();
+    let json = match source {
+        MetadataSource::Command(args) => {
+            let (program, args) = args.split_first().context("the metadata command is empty")?;
+            let child = Command::new(program)
+                .args(args.iter().map(|a| a.replace(JOB_ID_PLACEHOLDER, &job_id_str)))
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .with_context(|| format!("failed to run {program}"))?;
+            let output = wait_with_timeout(child, timeout).with_context(|| format!("failed to run {program}"))?;
+            if !output.status.success() {
+                let error_message = String::from_utf8_lossy(&output.stderr).into_owned();
+                return Err(anyhow!("{program} failed with {}", output.status).context(error_message));
+            }
+            String::from_utf8(output.stdout).with_context(|| format!("invalid output of {program}"))?
+        }
+        MetadataSource::File(path) => {
+            let path = path.replace(JOB_ID_PLACEHOLDER, &job_id_str);
+            std::fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?
+        }
+    };
+    parse_job_json(&json, job_id)
+}
+
+/// Waits for the child to exit and collects its output, or kills it if it runs for longer than `timeout`.
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> anyhow::Result<Output> {
+    // Read the pipes in the background, otherwise the child could block on a full pipe.
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("timeout expired after {timeout:?}"));
+        }
+        std::thread::sleep(COMMAND_POLL_INTERVAL);
+    };
+
+    let collect = |reader: Option<JoinHandle<Vec<u8>>>| reader.and_then(|r| r.join().ok()).unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Parses the output of `scontrol show job --json`.
+///
+/// Supports the formats of Slurm before and after 23.02, which wraps the numbers in `{"set": …, "number": …}`.
+fn parse_job_json(json: &str, job_id: u64) -> anyhow::Result<JobMetadata> {
+    let root: Value = serde_json::from_str(json).context("invalid JSON")?;
+    let jobs = root["jobs"].as_array().context("missing array 'jobs'")?;
+    let job = jobs
+        .iter()
+        .find(|j| number(&j["job_id"]) == Some(job_id))
+        .with_context(|| format!("job {job_id} not found"))?;
+
+    let string = |key: &str| job[key].as_str().filter(|s| !s.is_empty()).map(String::from);
+    Ok(JobMetadata {
+        name: string("name"),
+        account: string("account"),
+        partition: string("partition"),
+        user: string("user_name"),
+        requested_nodes: number(&job["node_count"]),
+        requested_cpus: number(&job["cpus"]),
+        requested_gpus: job["tres_req_str"].as_str().and_then(gpus_in_tres),
+    })
+}
+
+/// Reads a number that is either a plain number or an object `{"set": true, "number": 12}`.
+fn number(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::Object(o) if o.get("set").and_then(Value::as_bool) == Some(true) => o.get("number")?.as_u64(),
+        _ => None,
+    }
+}
+
+/// Finds the number of GPUs in a list of trackable resources, such as `cpu=4,node=1,gres/gpu=2`.
+fn gpus_in_tres(tres: &str) -> Option<u64> {
+    let mut total = None;
+    let mut typed = None;
+    for (key, value) in tres.split(',').filter_map(|kv| kv.split_once('=')) {
+        let Ok(value) = value.parse::<u64>() else {
+            continue;
+        };
+        if key == "gres/gpu" {
+            total = Some(value);
+        } else if key.starts_with("gres/gpu:") {
+            // typed GPUs (e.g. gres/gpu:a100), usually listed in addition to the total
+            typed = Some(typed.unwrap_or(0) + value);
+        }
+    }
+    total.or(typed)
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        pipeline::Builder,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::*;
+
+    const SCONTROL_OUTPUT: &str = r#"{
+        "jobs": [
+            {
+                "account": "physics",
+                "cpus": {"set": true, "infinite": false, "number": 16},
+                "job_id": 10707,
+                "name": "simulation",
+                "node_count": {"set": true, "infinite": false, "number": 2},
+                "partition": "gpu",
+                "tres_req_str": "cpu=16,mem=64G,node=2,billing=16,gres/gpu=4,gres/gpu:a100=4",
+                "user_name": "alice"
+            }
+        ]
+    }"#;
+
+    const SCONTROL_OUTPUT_OLD: &str = r#"{
+        "jobs": [
+            {"job_id": 12, "name": "other", "cpus": 1, "node_count": 1},
+            {"job_id": 13, "name": "", "account": "ops", "cpus": 4, "node_count": 1, "tres_req_str": "cpu=4,node=1"}
+        ]
+    }"#;
+
+    #[test]
+    fn parse_scontrol_output() {
+        let metadata = parse_job_json(SCONTROL_OUTPUT, 10707).unwrap();
+        assert_eq!(
+            metadata,
+            JobMetadata {
+                name: Some(String::from("simulation")),
+                account: Some(String::from("physics")),
+                partition: Some(String::from("gpu")),
+                user: Some(String::from("alice")),
+                requested_nodes: Some(2),
+                requested_cpus: Some(16),
+                requested_gpus: Some(4),
+            }
+        );
+        assert!(parse_job_json(SCONTROL_OUTPUT, 1).is_err());
+    }
+
+    #[test]
+    fn parse_scontrol_output_old_format() {
+        let metadata = parse_job_json(SCONTROL_OUTPUT_OLD, 13).unwrap();
+        assert_eq!(
+            metadata,
+            JobMetadata {
+                account: Some(String::from("ops")),
+                requested_nodes: Some(1),
+                requested_cpus: Some(4),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            metadata.attributes(),
+            vec![
+                (String::from("account"), AttributeValue::String(String::from("ops"))),
+                (String::from("requested_nodes"), AttributeValue::U64(1)),
+                (String::from("requested_cpus"), AttributeValue::U64(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn gpus() {
+        assert_eq!(gpus_in_tres("cpu=4,gres/gpu=2,gres/gpu:v100=2"), Some(2));
+        assert_eq!(gpus_in_tres("cpu=4,gres/gpu:v100=2,gres/gpu:a100=1"), Some(3));
+        assert_eq!(gpus_in_tres("cpu=4,mem=1G"), None);
+    }
+
+    #[test]
+    fn cache_from_file_and_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("job_10707.json");
+        std::fs::write(&path, SCONTROL_OUTPUT).unwrap();
+        let template = tmp.path().join("job_{job_id}.json").to_str().unwrap().to_owned();
+
+        let cache = JobMetadataCache::new(&config(MetadataSource::File(template.clone())));
+        assert!(cache.get(10707).is_none(), "the metadata is only fetched on demand");
+        cache.fetch_in_background(10707);
+        cache.fetch_in_background(10708);
+        assert_eq!(wait(&cache, 10707).unwrap().account.as_deref(), Some("physics"));
+        assert!(wait(&cache, 10708).is_none());
+
+        let cache = JobMetadataCache::new(&config(MetadataSource::Command(vec![String::from("cat"), template])));
+        cache.fetch_in_background(10707);
+        assert_eq!(wait(&cache, 10707).unwrap().user.as_deref(), Some("alice"));
+
+        // the metadata is cached until the job is forgotten
+        std::fs::remove_file(&path).unwrap();
+        assert!(cache.get(10707).is_some());
+        cache.forget(10707);
+        assert!(cache.get(10707).is_none());
+    }
+
+    #[test]
+    fn cache_retries_after_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("job_10707.json");
+        let template = tmp.path().join("job_{job_id}.json").to_str().unwrap().to_owned();
+
+        let mut cache = JobMetadataCache::new(&config(MetadataSource::File(template)));
+        cache.fetch_in_background(10707);
+        assert!(wait(&cache, 10707).is_none());
+        std::fs::write(&path, SCONTROL_OUTPUT).unwrap();
+        // the failure is not retried before the delay
+        assert!(cache.get(10707).is_none());
+        assert!(wait(&cache, 10707).is_none());
+
+        // once the delay has elapsed, get retries in the background
+        cache.retry_delay = Duration::ZERO;
+        cache.fetch_in_background(10708);
+        assert!(wait(&cache, 10708).is_none());
+        std::fs::write(
+            tmp.path().join("job_10708.json"),
+            SCONTROL_OUTPUT.replace("10707", "10708"),
+        )
+        .unwrap();
+        assert!(cache.get(10708).is_none());
+        assert_eq!(wait(&cache, 10708).unwrap().name.as_deref(), Some("simulation"));
+    }
+
+    #[test]
+    fn command_timeout() {
+        let mut config = config(MetadataSource::Command(["sleep", "10"].map(String::from).to_vec()));
+        config.timeout = Duration::from_millis(100);
+        let cache = JobMetadataCache::new(&config);
+        let t0 = Instant::now();
+        cache.fetch_in_background(10707);
+        assert!(cache.get(10707).is_none(), "get should not wait for the command");
+        assert!(wait(&cache, 10707).is_none());
+        assert!(t0.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn transform_adds_metadata_once_available() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("job_10707.json"), SCONTROL_OUTPUT).unwrap();
+        let template = tmp.path().join("job_{job_id}.json").to_str().unwrap().to_owned();
+        let cache = JobMetadataCache::new(&config(MetadataSource::File(template)));
+
+        let builder = Builder::new();
+        let inspector = builder.inspect();
+        let ctx = TransformContext {
+            metrics: inspector.metrics(),
+        };
+        let mut transform = JobMetadataTransform {
+            metadata: cache.clone(),
+        };
+        let measurements = || {
+            let consumer = ResourceConsumer::ControlGroup { path: "/job".into() };
+            let point = MeasurementPoint::new_untyped(
+                Timestamp::now(),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                consumer,
+                WrappedMeasurementValue::U64(1),
+            )
+            .with_attr("job_id", AttributeValue::U64(10707))
+            .with_attr("user", "bob");
+            MeasurementBuffer::from(vec![point])
+        };
+        let attr = |buf: &MeasurementBuffer, key: &str| {
+            buf.iter()
+                .next()
+                .unwrap()
+                .attributes()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        // the metadata has not been obtained yet
+        let mut buf = measurements();
+        transform.apply(&mut buf, &ctx).unwrap();
+        assert_eq!(attr(&buf, "account"), None);
+
+        cache.fetch_in_background(10707);
+        wait(&cache, 10707).unwrap();
+        let mut buf = measurements();
+        transform.apply(&mut buf, &ctx).unwrap();
+        assert_eq!(
+            attr(&buf, "account"),
+            Some(AttributeValue::String(String::from("physics")))
+        );
+        // the existing attributes are kept
+        assert_eq!(attr(&buf, "user"), Some(AttributeValue::Str("bob")));
+    }
+
+    /// Waits for the background fetch of the metadata of the job to finish, and returns the result.
+    fn wait(cache: &JobMetadataCache, job_id: u64) -> Option<Arc<JobMetadata>> {
+        loop {
+            match cache.jobs.lock().unwrap().get(&job_id) {
+                Some(CacheEntry::Fetching) => (),
+                Some(CacheEntry::Found(metadata)) => return Some(metadata.clone()),
+                _ => return None,
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn config(source: MetadataSource) -> JobMetadataConfig {
+        JobMetadataConfig {
+            enabled: true,
+            source,
+            ..Default::default()
+        }
+    }
+}
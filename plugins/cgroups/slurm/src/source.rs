@@ -8,6 +8,7 @@ use crate::{
 use util_cgroups_plugins::{
     cgroup_events::{CgroupSetupCallback, ProbeSetup, SourceSettings},
     job_annotation_transform::JobTagger,
+    job_summary::JobSummaries,
     metrics::{AugmentedMetrics, Metrics},
 };
 
@@ -17,10 +18,11 @@ pub struct JobSourceSetup {
     trigger: TriggerSpec,
     ignore_non_jobs: bool,
     jobs_monitoring_level: JobMonitoringLevel,
+    summaries: Option<JobSummaries>,
 }
 
 impl JobSourceSetup {
-    pub fn new(config: Config, tagger: SlurmJobTagger) -> anyhow::Result<Self> {
+    pub fn new(config: Config, tagger: SlurmJobTagger, summaries: Option<JobSummaries>) -> anyhow::Result<Self> {
         let trigger = TriggerSpec::at_interval(config.poll_interval);
 
         Ok(Self {
@@ -28,6 +30,7 @@ impl JobSourceSetup {
             trigger,
             ignore_non_jobs: config.ignore_non_jobs,
             jobs_monitoring_level: config.jobs_monitoring_level,
+            summaries,
         })
     }
 }
@@ -69,6 +72,10 @@ impl CgroupSetupCallback for JobSourceSetup {
         let attrs = self.tagger.attributes_for_cgroup(cgroup);
 
         let job_id = find_jobid_in_attrs(&attrs);
+        if let Some(job_id) = job_id {
+            // Don't wait for the metadata: the metadata transform adds it to the measurements once it is available.
+            self.tagger.fetch_metadata(job_id);
+        }
         let step_id = find_key_in_attrs("step", &attrs);
         let sub_step = find_key_in_attrs("sub_step", &attrs);
         let task_id = find_key_in_attrs("task", &attrs);
@@ -86,12 +93,22 @@ impl CgroupSetupCallback for JobSourceSetup {
             None => return None,
         };
 
+        // Only the cgroup of the job itself gets a summary, not the cgroups of its steps.
+        let summaries = match &self.summaries {
+            Some(summaries) if job_id.is_some() && step_id.is_none() => {
+                summaries.job_started(cgroup, attrs.clone());
+                Some(summaries.clone())
+            }
+            _ => None,
+        };
+
         let trigger = self.trigger.clone();
         let source_settings = SourceSettings { name, trigger };
         let metrics = AugmentedMetrics::with_common_attr_vec(metrics, attrs);
         Some(ProbeSetup {
             metrics,
             source_settings,
+            summaries,
         })
     }
 }
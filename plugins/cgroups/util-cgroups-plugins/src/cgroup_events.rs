@@ -13,9 +13,10 @@ use anyhow::Context;
 use util_cgroups::{Cgroup, CgroupDetector, CgroupHierarchy, CgroupMountWait, CgroupVersion, detect, mount_wait};
 
 use crate::{
+    job_summary::JobSummaries,
     metrics::{AugmentedMetrics, Metrics},
     v1::CgroupV1Probe,
-    v2::CgroupV2Probe,
+    v2::{CgroupV2Probe, OptionalMetricsConfig},
 };
 
 /// Reacts to the mounting of cgroup virtual filesystems.
@@ -44,6 +45,14 @@ impl CgroupRemovalCallback for NoCallback {
     }
 }
 
+/// Calls two removal callbacks, one after the other.
+impl<A: CgroupRemovalCallback, B: CgroupRemovalCallback> CgroupRemovalCallback for (A, B) {
+    fn on_cgroups_removed(&mut self, cgroups: Vec<Cgroup>) -> anyhow::Result<()> {
+        self.0.on_cgroups_removed(cgroups.clone())?;
+        self.1.on_cgroups_removed(cgroups)
+    }
+}
+
 impl CgroupFsMountCallback for NoCallback {
     fn on_cgroupfs_mounted(&mut self, _cgroupfs: &Vec<CgroupHierarchy>) -> anyhow::Result<()> {
         Ok(())
@@ -54,6 +63,8 @@ impl CgroupFsMountCallback for NoCallback {
 pub struct ProbeSetup {
     pub metrics: AugmentedMetrics,
     pub source_settings: SourceSettings,
+    /// Summaries that the probe reports the CPU usage of the cgroup to, if the cgroup is the cgroup of a job.
+    pub summaries: Option<JobSummaries>,
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +102,9 @@ pub struct ReactorConfig {
     /// For most use cases this should be set to false.
     /// It's essentially needed for advanced Alumet setup with a control plugin that manage the state of sources.
     pub add_source_in_pause_state: bool,
+
+    /// Optional metrics to measure on cgroup v2. The **default value** disables all of them.
+    pub v2_optional_metrics: OptionalMetricsConfig,
 }
 
 impl Default for ReactorConfig {
@@ -99,6 +113,7 @@ impl Default for ReactorConfig {
             v1_coalesce_delay: Some(Duration::from_secs(1)),
             v1_refresh_interval: None,
             add_source_in_pause_state: false,
+            v2_optional_metrics: OptionalMetricsConfig::default(),
         }
     }
 }
@@ -160,6 +175,7 @@ struct CloneableState<M: CgroupFsMountCallback, S: CgroupSetupCallback, R: Cgrou
     callbacks: ReactorCallbacks<M, S, R>,
     alumet_control: PluginControlHandle,
     detector_config: detect::Config,
+    v2_optional_metrics: OptionalMetricsConfig,
 }
 
 impl CgroupReactor {
@@ -179,7 +195,14 @@ impl CgroupReactor {
             detector_config.v1_refresh_interval = refresh_interval;
         }
         detector_config.add_source_in_pause_state = config.add_source_in_pause_state;
-        let callback = WaitCallback::new(metrics, callbacks, alumet_control, detectors.clone(), detector_config);
+        let callback = WaitCallback::new(
+            metrics,
+            callbacks,
+            alumet_control,
+            detectors.clone(),
+            detector_config,
+            config.v2_optional_metrics,
+        );
         let wait = CgroupMountWait::new(config.v1_coalesce_delay, callback)?;
         Ok(Self { wait, detectors })
     }
@@ -197,6 +220,7 @@ where
         alumet_control: PluginControlHandle,
         detectors: AliveDetectors,
         detector_config: detect::Config,
+        v2_optional_metrics: OptionalMetricsConfig,
     ) -> Self {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_time()
@@ -209,6 +233,7 @@ where
                 callbacks,
                 alumet_control,
                 detector_config,
+                v2_optional_metrics,
             },
             rt,
         }
@@ -276,7 +301,7 @@ where
                 Some(s) => {
                     // create the source
                     log::debug!("creating a source for cgroup {}", cgroup.unique_name());
-                    match make_cgroup_source(cgroup, s.metrics) {
+                    match make_cgroup_source(cgroup, s.metrics, s.summaries, &self.state.v2_optional_metrics) {
                         Ok(source) => {
                             sources.push((source, s.source_settings));
                         }
@@ -318,10 +343,20 @@ where
     }
 }
 
-fn make_cgroup_source(cgroup: Cgroup<'_>, metrics: AugmentedMetrics) -> anyhow::Result<Box<dyn Source>> {
+fn make_cgroup_source(
+    cgroup: Cgroup<'_>,
+    metrics: AugmentedMetrics,
+    summaries: Option<JobSummaries>,
+    v2_optional_metrics: &OptionalMetricsConfig,
+) -> anyhow::Result<Box<dyn Source>> {
     match cgroup.hierarchy().version() {
-        CgroupVersion::V1 => Ok(Box::new(CgroupV1Probe::new(cgroup, metrics)?)),
-        CgroupVersion::V2 => Ok(Box::new(CgroupV2Probe::new(cgroup, metrics)?)),
+        CgroupVersion::V1 => Ok(Box::new(CgroupV1Probe::new(cgroup, metrics)?.with_summaries(summaries))),
+        CgroupVersion::V2 => {
+            let settings = v2_optional_metrics.collector_settings();
+            Ok(Box::new(
+                CgroupV2Probe::new(cgroup, metrics, settings)?.with_summaries(summaries),
+            ))
+        }
     }
 }
 
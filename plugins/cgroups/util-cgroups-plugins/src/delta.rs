@@ -1,3 +1,5 @@
+use std::{collections::HashMap, hash::Hash};
+
 use alumet::plugin::util::CounterDiff;
 
 /// CounterDiff to compute the delta when it makes sense.
@@ -25,6 +27,36 @@ impl Default for CpuDeltaCounters {
     }
 }
 
+/// CounterDiff for a variable set of counters, identified by a key.
+///
+/// This is useful for the counters of `io.stat`, which are given for each device.
+pub struct KeyedDeltaCounters<K> {
+    counters: HashMap<K, CounterDiff>,
+}
+
+impl<K: Eq + Hash> KeyedDeltaCounters<K> {
+    /// Updates the counter identified by `key` and returns the difference with its previous value, if any.
+    pub fn update(&mut self, key: K, value: u64) -> Option<u64> {
+        self.counters
+            .entry(key)
+            .or_insert_with(|| CounterDiff::with_max_value(u64::MAX))
+            .update(value)
+            .difference()
+    }
+
+    pub fn reset(&mut self) {
+        self.counters.clear();
+    }
+}
+
+impl<K> Default for KeyedDeltaCounters<K> {
+    fn default() -> Self {
+        Self {
+            counters: HashMap::new(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,4 +80,17 @@ mod tests {
         assert_eq!(cpu_delta_counters.user.update(80), CounterDiffUpdate::FirstTime);
         assert_eq!(cpu_delta_counters.system.update(70), CounterDiffUpdate::FirstTime);
     }
+
+    #[test]
+    fn test_keyed_delta_counters() {
+        let mut counters = KeyedDeltaCounters::default();
+
+        assert_eq!(counters.update(("8:0", "rbytes"), 100), None);
+        assert_eq!(counters.update(("8:16", "rbytes"), 10), None);
+        assert_eq!(counters.update(("8:0", "rbytes"), 150), Some(50));
+        assert_eq!(counters.update(("8:16", "rbytes"), 10), Some(0));
+
+        counters.reset();
+        assert_eq!(counters.update(("8:0", "rbytes"), 200), None);
+    }
 }
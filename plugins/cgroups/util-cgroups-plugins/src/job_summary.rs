@@ -0,0 +1,539 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use alumet::{
+    agent::schema::JsonSchema,
+    measurement::{
+        AttributeValue, MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue,
+    },
+    metrics::{RawMetricId, TypedMetricId, def::MetricId},
+    pipeline::{
+        Source, Transform,
+        elements::{
+            error::{PollError, TransformError},
+            transform::{TransformContext, builder::TransformBuildContext},
+        },
+    },
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use util_cgroups::Cgroup;
+
+use crate::{cgroup_events::CgroupRemovalCallback, metrics::Metrics};
+
+/// Configuration of the job summaries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct JobSummaryConfig {
+    /// If `true`, produces a set of "job total" measurement points when a job ends.
+    /// Disabled by default.
+    pub enabled: bool,
+
+    /// Metrics of attributed energy to sum up in the summary, for instance the formulas
+    /// of the energy-attribution plugin.
+    ///
+    /// The transform of the energy-attribution plugin must run before the summary,
+    /// otherwise the attributed energy is not seen by the summary.
+    #[serde(default)]
+    pub energy_metrics: Vec<String>,
+}
+
+/// Metrics of the job summaries.
+#[derive(Clone)]
+pub struct JobSummaryMetrics {
+    /// Total CPU time of the job.
+    pub cpu_time: TypedMetricId<u64>,
+    /// Maximum memory used by the job.
+    pub memory_peak: TypedMetricId<u64>,
+    /// Duration of the job.
+    pub duration: TypedMetricId<f64>,
+    /// Total energy attributed to the job.
+    pub attributed_energy: TypedMetricId<f64>,
+}
+
+impl JobSummaryMetrics {
+    /// Create the metrics and register them in Alumet.
+    pub fn create(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        let cpu_time = alumet.create_metric::<u64>(
+            "job_total_cpu_time",
+            PrefixedUnit::nano(Unit::Second),
+            "Time spent by the job on the CPU, from the creation of its cgroup to its end",
+        )?;
+        let memory_peak = alumet.create_metric::<u64>(
+            "job_total_memory_peak",
+            Unit::Byte,
+            "Maximum amount of memory used by the job",
+        )?;
+        let duration = alumet.create_metric::<f64>(
+            "job_total_duration",
+            Unit::Second,
+            "Time elapsed between the detection of the job and its end",
+        )?;
+        let attributed_energy = alumet.create_metric::<f64>(
+            "job_total_attributed_energy",
+            Unit::Joule,
+            "Total energy attributed to the job, by attribution formula",
+        )?;
+        Ok(Self {
+            cpu_time,
+            memory_peak,
+            duration,
+            attributed_energy,
+        })
+    }
+}
+
+/// Keeps track of the jobs that are running and of the jobs that have ended.
+///
+/// `JobSummaries` is `Clone`, `Send` and `Sync`: the same summaries are shared by the probe setup
+/// (which registers the jobs), the cgroup probes (which report the CPU usage), the removal callback
+/// (which marks them as ended), the [`JobSummaryTransform`] (which accumulates the measurements)
+/// and the [`JobSummarySource`] (which produces the summaries).
+#[derive(Clone, Default)]
+pub struct JobSummaries {
+    state: Arc<Mutex<SummaryState>>,
+}
+
+#[derive(Default)]
+struct SummaryState {
+    /// Running jobs, by cgroup path.
+    running: HashMap<String, JobTotals>,
+    /// Jobs that have ended, by cgroup path, whose summary has not been produced yet.
+    ended: HashMap<String, EndedJob>,
+}
+
+#[derive(Debug, PartialEq)]
+struct JobTotals {
+    attributes: Vec<(String, AttributeValue)>,
+    start: Timestamp,
+    /// Last value of the CPU usage counter of the cgroup, which counts since the creation of the cgroup.
+    cpu_usage: Option<u64>,
+    memory_peak: Option<u64>,
+    /// Attributed energy, by index in the list of energy metrics.
+    energy: Vec<f64>,
+}
+
+#[derive(Debug)]
+struct EndedJob {
+    totals: JobTotals,
+    end: Timestamp,
+    /// `true` once the source has been polled after the end of the job.
+    ///
+    /// The measurements of the last moments of the job can still be in the pipeline when the job ends,
+    /// and the attributed energy arrives even later. They are accumulated until the next poll.
+    polled: bool,
+}
+
+impl JobSummaries {
+    /// Starts to track the job that runs in the given cgroup.
+    ///
+    /// The `attributes` are attached to the summary of the job.
+    /// If the job is already tracked, for instance because it has a cgroup in several v1 hierarchies,
+    /// this does nothing.
+    pub fn job_started(&self, cgroup: &Cgroup, attributes: Vec<(String, AttributeValue)>) {
+        self.start_at(cgroup.canonical_path().to_owned(), attributes, Timestamp::now());
+    }
+
+    /// Marks the job that runs in the given cgroup as ended.
+    ///
+    /// Its summary will be produced by the next poll of the [`JobSummarySource`].
+    pub fn job_ended(&self, cgroup: &Cgroup) {
+        self.end_at(cgroup.canonical_path(), Timestamp::now());
+    }
+
+    /// Adds attributes to the summary of the job that runs in the given cgroup, if they are not set yet.
+    ///
+    /// This works until the summary is produced, even if the job has ended.
+    pub fn add_attributes(&self, cgroup: &Cgroup, attributes: Vec<(String, AttributeValue)>) {
+        if let Some(totals) = self.state.lock().unwrap().totals_mut(cgroup.canonical_path()) {
+            for (k, v) in attributes {
+                if !totals.attributes.iter().any(|(key, _)| *key == k) {
+                    totals.attributes.push((k, v));
+                }
+            }
+        }
+    }
+
+    /// Records the value of the CPU usage counter of the job that runs in the given cgroup.
+    ///
+    /// Unlike the CPU time deltas, the counter includes the CPU time used before the first
+    /// measurement of the cgroup. Does nothing if the cgroup is not the cgroup of a job.
+    pub fn cpu_usage(&self, cgroup_path: &str, usage: u64) {
+        if let Some(totals) = self.state.lock().unwrap().totals_mut(cgroup_path) {
+            totals.cpu_usage = Some(usage);
+        }
+    }
+
+    fn start_at(&self, path: String, attributes: Vec<(String, AttributeValue)>, t: Timestamp) {
+        self.state
+            .lock()
+            .unwrap()
+            .running
+            .entry(path)
+            .or_insert_with(|| JobTotals {
+                attributes,
+                start: t,
+                cpu_usage: None,
+                memory_peak: None,
+                energy: Vec::new(),
+            });
+    }
+
+    fn end_at(&self, path: &str, t: Timestamp) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(totals) = state.running.remove(path) {
+            let job = EndedJob {
+                totals,
+                end: t,
+                polled: false,
+            };
+            state.ended.insert(path.to_owned(), job);
+        }
+    }
+
+    /// Accumulates the measurements of the running jobs, and of the jobs that have just ended.
+    fn accumulate(&self, measurements: &MeasurementBuffer, inputs: &SummaryInputs) {
+        let mut state = self.state.lock().unwrap();
+        for m in measurements.iter() {
+            if let ResourceConsumer::ControlGroup { path } = &m.consumer
+                && let Some(totals) = state.totals_mut(path.as_ref())
+            {
+                totals.update(m, inputs);
+            }
+        }
+    }
+
+    /// Returns the summaries of the jobs that have ended before the previous call.
+    ///
+    /// The jobs that have ended since the previous call are only finalized by the next call,
+    /// in order to take into account the measurements that were still in the pipeline.
+    fn take_summaries(&self, outputs: &SummaryOutputs) -> Vec<MeasurementPoint> {
+        let mut state = self.state.lock().unwrap();
+        let mut summaries = Vec::new();
+        state.ended.retain(|path, job| {
+            if !job.polled {
+                job.polled = true;
+                return true;
+            }
+            log::debug!("job in cgroup {path} has ended, producing its summary");
+            job.totals.push_summary(&mut summaries, path, job.end, outputs);
+            false
+        });
+        summaries
+    }
+}
+
+impl SummaryState {
+    fn totals_mut(&mut self, path: &str) -> Option<&mut JobTotals> {
+        match self.running.get_mut(path) {
+            Some(totals) => Some(totals),
+            None => self.ended.get_mut(path).map(|job| &mut job.totals),
+        }
+    }
+}
+
+impl JobTotals {
+    fn update(&mut self, m: &MeasurementPoint, inputs: &SummaryInputs) {
+        if m.metric == inputs.memory_usage || m.metric == inputs.memory_peak {
+            if let WrappedMeasurementValue::U64(v) = m.value {
+                self.memory_peak = Some(self.memory_peak.map_or(v, |peak| peak.max(v)));
+            }
+        } else if let Some(i) = inputs.energy.iter().position(|id| *id == m.metric) {
+            let v = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            if self.energy.len() <= i {
+                self.energy.resize(i + 1, 0.0);
+            }
+            self.energy[i] += v;
+        }
+    }
+
+    fn push_summary(
+        &self,
+        summaries: &mut Vec<MeasurementPoint>,
+        path: &str,
+        end: Timestamp,
+        outputs: &SummaryOutputs,
+    ) {
+        let consumer = ResourceConsumer::ControlGroup {
+            path: path.to_owned().into(),
+        };
+        let point = |metric: RawMetricId, value: WrappedMeasurementValue| {
+            MeasurementPoint::new_untyped(end, metric, Resource::LocalMachine, consumer.clone(), value)
+                .with_attr_slice(&self.attributes)
+        };
+
+        let duration = end.duration_since(self.start).unwrap_or_default().as_secs_f64();
+        summaries.push(point(outputs.duration, WrappedMeasurementValue::F64(duration)));
+        summaries.push(point(
+            outputs.cpu_time,
+            WrappedMeasurementValue::U64(self.cpu_usage.unwrap_or_default()),
+        ));
+        if let Some(peak) = self.memory_peak {
+            summaries.push(point(outputs.memory_peak, WrappedMeasurementValue::U64(peak)));
+        }
+        for (i, formula) in outputs.formulas.iter().enumerate() {
+            let energy = self.energy.get(i).copied().unwrap_or_default();
+            summaries.push(
+                point(outputs.attributed_energy, WrappedMeasurementValue::F64(energy))
+                    .with_attr("formula", formula.clone()),
+            );
+        }
+    }
+}
+
+/// Metrics that contribute to the summaries.
+struct SummaryInputs {
+    memory_usage: RawMetricId,
+    memory_peak: RawMetricId,
+    /// Metrics of attributed energy, in the order of the configuration.
+    energy: Vec<RawMetricId>,
+}
+
+/// Metrics of the summaries.
+struct SummaryOutputs {
+    cpu_time: RawMetricId,
+    memory_peak: RawMetricId,
+    duration: RawMetricId,
+    attributed_energy: RawMetricId,
+    /// Names of the metrics of attributed energy, in the order of the configuration.
+    formulas: Vec<String>,
+}
+
+/// Accumulates the measurements of the running jobs, for their summary.
+pub struct JobSummaryTransform {
+    summaries: JobSummaries,
+    inputs: SummaryInputs,
+}
+
+impl JobSummaryTransform {
+    /// Creates a new transform.
+    ///
+    /// Because it needs to look up the energy metrics by name, this must be called in a transform builder.
+    pub fn new(
+        summaries: JobSummaries,
+        metrics: &Metrics,
+        energy_metrics: &[String],
+        ctx: &dyn TransformBuildContext,
+    ) -> anyhow::Result<Self> {
+        let energy = energy_metrics
+            .iter()
+            .map(|name| match ctx.metric_by_name(name) {
+                Some((id, _)) => Ok(id),
+                None => Err(anyhow!(
+                    "energy metric {name} not found, is the energy-attribution plugin enabled?"
+                )),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            summaries,
+            inputs: SummaryInputs {
+                memory_usage: metrics.memory_usage.untyped_id(),
+                memory_peak: metrics.memory_peak.untyped_id(),
+                energy,
+            },
+        })
+    }
+}
+
+impl Transform for JobSummaryTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        self.summaries.accumulate(measurements, &self.inputs);
+        Ok(())
+    }
+}
+
+/// Produces the summaries of the jobs that have ended.
+///
+/// The summary of a job is a set of measurement points, which carry the attributes of the job:
+/// - `job_total_duration`
+/// - `job_total_cpu_time`
+/// - `job_total_memory_peak`, if the memory of the job has been measured
+/// - `job_total_attributed_energy`, once per attribution formula, with the attribute `formula`
+///
+/// The summaries do not depend on the arrival of other measurements: they are produced on the
+/// second poll after the end of the job, so that the last measurements of the job (and the energy
+/// attributed to them) are counted.
+pub struct JobSummarySource {
+    summaries: JobSummaries,
+    outputs: SummaryOutputs,
+}
+
+impl JobSummarySource {
+    /// Creates a new source.
+    ///
+    /// `energy_metrics` must be the same list as the one given to [`JobSummaryTransform::new`].
+    pub fn new(summaries: JobSummaries, summary_metrics: &JobSummaryMetrics, energy_metrics: Vec<String>) -> Self {
+        Self {
+            summaries,
+            outputs: SummaryOutputs {
+                cpu_time: summary_metrics.cpu_time.untyped_id(),
+                memory_peak: summary_metrics.memory_peak.untyped_id(),
+                duration: summary_metrics.duration.untyped_id(),
+                attributed_energy: summary_metrics.attributed_energy.untyped_id(),
+                formulas: energy_metrics,
+            },
+        }
+    }
+}
+
+impl Source for JobSummarySource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, _timestamp: Timestamp) -> Result<(), PollError> {
+        for point in self.summaries.take_summaries(&self.outputs) {
+            measurements.push(point);
+        }
+        Ok(())
+    }
+}
+
+/// Marks the jobs as ended when their cgroup is deleted.
+impl CgroupRemovalCallback for JobSummaries {
+    fn on_cgroups_removed(&mut self, cgroups: Vec<Cgroup>) -> anyhow::Result<()> {
+        for cgroup in cgroups {
+            self.job_ended(&cgroup);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(i: u64) -> RawMetricId {
+        RawMetricId::from_u64(i)
+    }
+
+    fn inputs() -> SummaryInputs {
+        SummaryInputs {
+            memory_usage: id(1),
+            memory_peak: id(2),
+            energy: vec![id(3)],
+        }
+    }
+
+    fn outputs() -> SummaryOutputs {
+        SummaryOutputs {
+            cpu_time: id(10),
+            memory_peak: id(11),
+            duration: id(12),
+            attributed_energy: id(13),
+            formulas: vec![String::from("attributed_energy")],
+        }
+    }
+
+    fn point(t: Timestamp, path: &'static str, metric: u64, value: WrappedMeasurementValue) -> MeasurementPoint {
+        let consumer = ResourceConsumer::ControlGroup { path: path.into() };
+        MeasurementPoint::new_untyped(t, id(metric), Resource::LocalMachine, consumer, value)
+    }
+
+    fn find(buf: &[MeasurementPoint], metric: u64) -> Option<&MeasurementPoint> {
+        buf.iter().find(|p| p.metric == id(metric))
+    }
+
+    #[test]
+    fn summary_of_ended_job() {
+        let summaries = JobSummaries::default();
+        let (inputs, outputs) = (inputs(), outputs());
+        let job_attrs = vec![(String::from("job_id"), AttributeValue::U64(42))];
+        let t0 = Timestamp::from_unix_timestamp(1000, 0);
+        let t1 = Timestamp::from_unix_timestamp(1001, 0);
+        summaries.start_at(String::from("/job_42"), job_attrs.clone(), t0);
+
+        summaries.cpu_usage("/job_42", 500);
+        summaries.cpu_usage("/other", 999);
+        let buf = MeasurementBuffer::from(vec![
+            point(t1, "/job_42", 1, WrappedMeasurementValue::U64(2048)),
+            point(t1, "/job_42", 3, WrappedMeasurementValue::F64(1.5)),
+            point(t1, "/other", 1, WrappedMeasurementValue::U64(8192)),
+        ]);
+        summaries.accumulate(&buf, &inputs);
+        assert!(
+            summaries.take_summaries(&outputs).is_empty(),
+            "no summary before the end of the job"
+        );
+
+        summaries.cpu_usage("/job_42", 750);
+        let buf = MeasurementBuffer::from(vec![
+            point(t1, "/job_42", 2, WrappedMeasurementValue::U64(4096)),
+            point(t1, "/job_42", 1, WrappedMeasurementValue::U64(1024)),
+        ]);
+        summaries.accumulate(&buf, &inputs);
+        summaries.end_at("/job_42", Timestamp::from_unix_timestamp(1010, 0));
+        assert!(
+            summaries.take_summaries(&outputs).is_empty(),
+            "no summary on the first poll after the end of the job"
+        );
+
+        // measurements that were still in the pipeline when the job ended
+        let buf = MeasurementBuffer::from(vec![point(t1, "/job_42", 3, WrappedMeasurementValue::F64(0.5))]);
+        summaries.accumulate(&buf, &inputs);
+
+        let buf = summaries.take_summaries(&outputs);
+        assert_eq!(buf.len(), 4);
+        let duration = find(&buf, 12).unwrap();
+        assert_eq!(duration.value, WrappedMeasurementValue::F64(10.0));
+        assert_eq!(
+            duration.attributes().collect::<Vec<_>>(),
+            vec![("job_id", &AttributeValue::U64(42))]
+        );
+        assert_eq!(find(&buf, 10).unwrap().value, WrappedMeasurementValue::U64(750));
+        assert_eq!(find(&buf, 11).unwrap().value, WrappedMeasurementValue::U64(4096));
+        let energy = find(&buf, 13).unwrap();
+        assert_eq!(energy.value, WrappedMeasurementValue::F64(2.0));
+        assert!(
+            energy
+                .attributes()
+                .any(|(k, v)| k == "formula" && v == &AttributeValue::String(String::from("attributed_energy")))
+        );
+
+        // the summary is only produced once
+        assert!(summaries.take_summaries(&outputs).is_empty());
+    }
+
+    #[test]
+    fn job_started_twice() {
+        let summaries = JobSummaries::default();
+        let t0 = Timestamp::from_unix_timestamp(1000, 0);
+        let t1 = Timestamp::from_unix_timestamp(1001, 0);
+        summaries.start_at(String::from("/job_1"), Vec::new(), t0);
+        summaries.cpu_usage("/job_1", 100);
+
+        // the job has a cgroup in another v1 hierarchy
+        summaries.start_at(String::from("/job_1"), Vec::new(), t1);
+        summaries.end_at("/job_1", Timestamp::from_unix_timestamp(1002, 0));
+
+        assert!(summaries.take_summaries(&outputs()).is_empty());
+        let buf = summaries.take_summaries(&outputs());
+        assert_eq!(find(&buf, 12).unwrap().value, WrappedMeasurementValue::F64(2.0));
+        assert_eq!(find(&buf, 10).unwrap().value, WrappedMeasurementValue::U64(100));
+    }
+
+    #[test]
+    fn unknown_job_ended() {
+        let summaries = JobSummaries::default();
+        summaries.end_at("/not_a_job", Timestamp::now());
+        assert!(summaries.take_summaries(&outputs()).is_empty());
+    }
+
+    #[test]
+    fn job_without_measurements() {
+        let summaries = JobSummaries::default();
+        let t0 = Timestamp::from_unix_timestamp(1000, 0);
+        summaries.start_at(String::from("/job_1"), Vec::new(), t0);
+        summaries.end_at("/job_1", Timestamp::from_unix_timestamp(1002, 0));
+
+        assert!(summaries.take_summaries(&outputs()).is_empty());
+        let buf = summaries.take_summaries(&outputs());
+        // duration, cpu time and attributed energy, but no memory peak
+        assert_eq!(buf.len(), 3);
+        assert_eq!(find(&buf, 10).unwrap().value, WrappedMeasurementValue::U64(0));
+        assert!(find(&buf, 11).is_none());
+    }
+}
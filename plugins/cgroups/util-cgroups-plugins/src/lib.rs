@@ -8,6 +8,7 @@ pub mod v2;
 mod cpus;
 pub mod delta;
 pub mod job_annotation_transform;
+pub mod job_summary;
 pub mod metrics;
 pub mod regex;
 mod self_stop;
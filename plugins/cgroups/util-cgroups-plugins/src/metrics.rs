@@ -22,6 +22,18 @@ pub struct Metrics {
     pub memory_kernel_stack: TypedMetricId<u64>,
     /// Memory used to manage correspondence between virtual and physical addresses.
     pub memory_pagetables: TypedMetricId<u64>,
+    /// Maximum memory used by the cgroup since its creation.
+    pub memory_peak: TypedMetricId<u64>,
+    /// Number of memory events (OOM, OOM kill) since the creation of the cgroup.
+    pub memory_events: TypedMetricId<u64>,
+    /// Number of processes in the cgroup.
+    pub pids: TypedMetricId<u64>,
+    /// Bytes read or written by the cgroup since last measurement.
+    pub io_bytes_delta: TypedMetricId<u64>,
+    /// Number of I/O operations performed by the cgroup since last measurement.
+    pub io_ops_delta: TypedMetricId<u64>,
+    /// Time during which the tasks of the cgroup were stalled since last measurement (pressure stall information).
+    pub pressure_stall_delta: TypedMetricId<u64>,
 }
 
 /// Used by probes to configure how cgroup measurements will be mapped to Alumet measurement points.
@@ -61,6 +73,18 @@ pub struct AugmentedMetrics {
     pub memory_kernel_stack: AugmentedMetric<u64>,
     /// Memory used to manage correspondence between virtual and physical addresses.
     pub memory_pagetables: AugmentedMetric<u64>,
+    /// Maximum memory used by the cgroup since its creation.
+    pub memory_peak: AugmentedMetric<u64>,
+    /// Number of memory events (OOM, OOM kill) since the creation of the cgroup.
+    pub memory_events: AugmentedMetric<u64>,
+    /// Number of processes in the cgroup.
+    pub pids: AugmentedMetric<u64>,
+    /// Bytes read or written by the cgroup since last measurement.
+    pub io_bytes_delta: AugmentedMetric<u64>,
+    /// Number of I/O operations performed by the cgroup since last measurement.
+    pub io_ops_delta: AugmentedMetric<u64>,
+    /// Time during which the tasks of the cgroup were stalled since last measurement (pressure stall information).
+    pub pressure_stall_delta: AugmentedMetric<u64>,
 
     /// Common attributes, added to the points of all metrics.
     pub common_attrs: Vec<(String, AttributeValue)>,
@@ -104,6 +128,36 @@ impl Metrics {
             Unit::Byte,
             "Amount of memory allocated for page tables (which map virtual addresses to physical addresses).",
         )?;
+        let memory_peak = alumet.create_metric::<u64>(
+            "cgroup_memory_peak",
+            Unit::Byte,
+            "Maximum amount of memory used by the cgroup and its descendants since the creation of the cgroup.",
+        )?;
+        let memory_events = alumet.create_metric::<u64>(
+            "cgroup_memory_events",
+            Unit::Unity,
+            "Number of times a memory event occurred in the cgroup since its creation (see the `event` attribute).",
+        )?;
+        let pids = alumet.create_metric::<u64>(
+            "cgroup_pids",
+            Unit::Unity,
+            "Number of processes currently in the cgroup and its descendants.",
+        )?;
+        let io_bytes_delta = alumet.create_metric::<u64>(
+            "cgroup_io_bytes_delta",
+            Unit::Byte,
+            "Amount of data transferred by the cgroup on a device since the previous measurement",
+        )?;
+        let io_ops_delta = alumet.create_metric::<u64>(
+            "cgroup_io_ops_delta",
+            Unit::Unity,
+            "Number of I/O operations performed by the cgroup on a device since the previous measurement",
+        )?;
+        let pressure_stall_delta = alumet.create_metric::<u64>(
+            "cgroup_pressure_stall_delta",
+            PrefixedUnit::micro(Unit::Second),
+            "Time during which tasks of the cgroup were stalled on a resource since the previous measurement (PSI)",
+        )?;
         Ok(Self {
             cpu_time_delta,
             cpu_percent,
@@ -112,22 +166,19 @@ impl Metrics {
             memory_file,
             memory_kernel_stack,
             memory_pagetables,
+            memory_peak,
+            memory_events,
+            pids,
+            io_bytes_delta,
+            io_ops_delta,
+            pressure_stall_delta,
         })
     }
 }
 
 impl AugmentedMetrics {
     pub fn no_additional_attribute(metrics: &Metrics) -> Self {
-        Self {
-            cpu_time_delta: AugmentedMetric::simple(metrics.cpu_time_delta),
-            cpu_percent: AugmentedMetric::simple(metrics.cpu_percent),
-            memory_usage: AugmentedMetric::simple(metrics.memory_usage),
-            memory_anonymous: AugmentedMetric::simple(metrics.memory_anonymous),
-            memory_file: AugmentedMetric::simple(metrics.memory_file),
-            memory_kernel_stack: AugmentedMetric::simple(metrics.memory_kernel_stack),
-            memory_pagetables: AugmentedMetric::simple(metrics.memory_pagetables),
-            common_attrs: Vec::new(),
-        }
+        Self::with_common_attr_vec(metrics, Vec::new())
     }
 
     pub fn with_common_attr_slice(
@@ -152,6 +203,12 @@ impl AugmentedMetrics {
             memory_file: AugmentedMetric::simple(metrics.memory_file),
             memory_kernel_stack: AugmentedMetric::simple(metrics.memory_kernel_stack),
             memory_pagetables: AugmentedMetric::simple(metrics.memory_pagetables),
+            memory_peak: AugmentedMetric::simple(metrics.memory_peak),
+            memory_events: AugmentedMetric::simple(metrics.memory_events),
+            pids: AugmentedMetric::simple(metrics.pids),
+            io_bytes_delta: AugmentedMetric::simple(metrics.io_bytes_delta),
+            io_ops_delta: AugmentedMetric::simple(metrics.io_ops_delta),
+            pressure_stall_delta: AugmentedMetric::simple(metrics.pressure_stall_delta),
             common_attrs,
         }
     }
@@ -6,10 +6,12 @@ use alumet::{
 use util_cgroups::{Cgroup, measure::v1::V1Collector};
 
 use super::{
-    delta::CpuDeltaCounters, metrics::AugmentedMetric, metrics::AugmentedMetrics, self_stop::analyze_io_result,
+    delta::CpuDeltaCounters, job_summary::JobSummaries, metrics::AugmentedMetric, metrics::AugmentedMetrics,
+    self_stop::analyze_io_result,
 };
 
 pub struct CgroupV1Probe {
+    cgroup_path: String,
     consumer: ResourceConsumer,
     delta_counters: CpuDeltaCounters,
     metrics: AugmentedMetrics,
@@ -17,6 +19,7 @@ pub struct CgroupV1Probe {
     io_buf: Vec<u8>,
     last_timestamp: Option<Timestamp>,
     n_cores: usize,
+    summaries: Option<JobSummaries>,
 }
 
 impl CgroupV1Probe {
@@ -33,6 +36,7 @@ impl CgroupV1Probe {
         let n_cores = crate::cpus::online_cpus()?.len();
 
         Ok(Self {
+            cgroup_path: cgroup_canon_path,
             consumer,
             delta_counters: Default::default(),
             metrics,
@@ -40,9 +44,16 @@ impl CgroupV1Probe {
             io_buf,
             last_timestamp: None,
             n_cores,
+            summaries: None,
         })
     }
 
+    /// Reports the CPU usage of the cgroup to the given job summaries.
+    pub fn with_summaries(mut self, summaries: Option<JobSummaries>) -> Self {
+        self.summaries = summaries;
+        self
+    }
+
     fn new_point<T: MeasurementType<T = T>>(
         &self,
         metric: &AugmentedMetric<T>,
@@ -68,6 +79,9 @@ impl Source for CgroupV1Probe {
         let resource = Resource::LocalMachine; // TODO more precise, but we don't know the pkg id
 
         // Cpu statistics
+        if let (Some(summaries), Some(usage)) = (&self.summaries, data.cpuacct_usage) {
+            summaries.cpu_usage(&self.cgroup_path, usage);
+        }
         if let Some(value) = data
             .cpuacct_usage
             .map(|v| self.delta_counters.usage.update(v).difference())
@@ -1,55 +1,103 @@
 use alumet::{
+    agent::schema::JsonSchema,
     measurement::{MeasurementAccumulator, MeasurementPoint, MeasurementType, Timestamp},
     pipeline::{Source, elements::error::PollError},
     resources::{Resource, ResourceConsumer},
 };
+use serde::{Deserialize, Serialize};
 use util_cgroups::{
     Cgroup,
-    measure::v2::{V2Collector, cpu::CpuStatCollectorSettings, memory::MemoryStatCollectorSettings},
+    measure::v2::{V2Collector, V2CollectorSettings, pressure::PressureStats},
 };
 
 use super::{
-    delta::CpuDeltaCounters, metrics::AugmentedMetric, metrics::AugmentedMetrics, self_stop::analyze_io_result,
+    delta::{CpuDeltaCounters, KeyedDeltaCounters},
+    job_summary::JobSummaries,
+    metrics::AugmentedMetric,
+    metrics::AugmentedMetrics,
+    self_stop::analyze_io_result,
 };
 
+/// Optional metrics of cgroup v2, in the configuration of the plugins.
+///
+/// They are disabled by default, because they multiply the number of measurements per cgroup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptionalMetricsConfig {
+    /// Measures the I/O of the cgroup on each block device (`io.stat`).
+    pub io: bool,
+    /// Measures the number of processes in the cgroup (`pids.current`).
+    pub pids: bool,
+    /// Measures the peak memory usage and the OOM events (`memory.peak` and `memory.events`).
+    pub memory_events: bool,
+    /// Measures the pressure stall information of the CPU, memory and I/O (`*.pressure`).
+    pub pressure: bool,
+}
+
+impl OptionalMetricsConfig {
+    /// Returns the settings of the cgroup v2 collectors.
+    pub fn collector_settings(&self) -> V2CollectorSettings {
+        V2CollectorSettings {
+            memory_events: self.memory_events.then(Default::default),
+            io_stat: self.io.then(Default::default),
+            pressure: self.pressure.then(Default::default),
+            memory_peak: self.memory_events,
+            pids_current: self.pids,
+            ..Default::default()
+        }
+    }
+}
+
 pub struct CgroupV2Probe {
+    cgroup_path: String,
     consumer: ResourceConsumer,
     delta_counters: CpuDeltaCounters,
+    /// Counters of `io.stat`, by device and key.
+    io_counters: KeyedDeltaCounters<(String, &'static str)>,
+    /// Counters of the `total` stall time of the pressure files, by controller and kind (some or full).
+    pressure_counters: KeyedDeltaCounters<(&'static str, &'static str)>,
     metrics: AugmentedMetrics,
     collector: V2Collector,
     io_buf: Vec<u8>,
     last_timestamp: Option<Timestamp>,
     n_cores: usize,
+    summaries: Option<JobSummaries>,
 }
 
 impl CgroupV2Probe {
-    pub fn new(cgroup: Cgroup<'_>, metrics: AugmentedMetrics) -> anyhow::Result<Self> {
+    pub fn new(cgroup: Cgroup<'_>, metrics: AugmentedMetrics, settings: V2CollectorSettings) -> anyhow::Result<Self> {
+        let cgroup_path = cgroup.canonical_path().to_owned();
         let consumer = ResourceConsumer::ControlGroup {
-            path: cgroup.canonical_path().to_owned().into(),
+            path: cgroup_path.clone().into(),
         };
         let mut io_buf = Vec::new();
-        let collector = V2Collector::new(
-            cgroup,
-            MemoryStatCollectorSettings::default(),
-            CpuStatCollectorSettings::default(),
-            &mut io_buf,
-        )?;
+        let collector = V2Collector::with_settings(cgroup, settings, &mut io_buf)?;
 
         // To get the number of logical core, one could think about calling num_cpus::get().
         // However, this is affected by the constraints set on the Alumet process (sched affinity, cgroups cpuset), which is not what we want.
         let n_cores = crate::cpus::online_cpus()?.len();
 
         Ok(Self {
+            cgroup_path,
             consumer,
             delta_counters: Default::default(),
+            io_counters: Default::default(),
+            pressure_counters: Default::default(),
             metrics,
             collector,
             io_buf,
             last_timestamp: None,
             n_cores,
+            summaries: None,
         })
     }
 
+    /// Reports the CPU usage of the cgroup to the given job summaries.
+    pub fn with_summaries(mut self, summaries: Option<JobSummaries>) -> Self {
+        self.summaries = summaries;
+        self
+    }
+
     fn new_point<T: MeasurementType<T = T>>(
         &self,
         metric: &AugmentedMetric<T>,
@@ -82,6 +130,9 @@ impl Source for CgroupV2Probe {
 
         // CPU statistics
         if let Some(cpu_stat) = data.cpu_stat {
+            if let (Some(summaries), Some(usage)) = (&self.summaries, cpu_stat.usage) {
+                summaries.cpu_usage(&self.cgroup_path, usage);
+            }
             if let Some(value) = cpu_stat
                 .usage
                 .map(|v| self.delta_counters.usage.update(v).difference())
@@ -167,11 +218,77 @@ impl Source for CgroupV2Probe {
                 measurements.push(self.new_point(&self.metrics.memory_pagetables, t, &resource, value));
             }
         }
+        if let Some(value) = data.memory_peak {
+            measurements.push(self.new_point(&self.metrics.memory_peak, t, &resource, value));
+        }
+        if let Some(events) = data.memory_events {
+            for (event, value) in [("oom", events.oom), ("oom_kill", events.oom_kill)] {
+                if let Some(value) = value {
+                    measurements.push(
+                        self.new_point(&self.metrics.memory_events, t, &resource, value)
+                            .with_attr("event", event),
+                    );
+                }
+            }
+        }
+
+        // Processes
+        if let Some(value) = data.pids_current {
+            measurements.push(self.new_point(&self.metrics.pids, t, &resource, value));
+        }
+
+        // I/O statistics, for each device
+        if let Some(io_stat) = data.io_stat {
+            for device in io_stat.devices {
+                let counters = [
+                    ("rbytes", device.rbytes, &self.metrics.io_bytes_delta, "read"),
+                    ("wbytes", device.wbytes, &self.metrics.io_bytes_delta, "write"),
+                    ("dbytes", device.dbytes, &self.metrics.io_bytes_delta, "discard"),
+                    ("rios", device.rios, &self.metrics.io_ops_delta, "read"),
+                    ("wios", device.wios, &self.metrics.io_ops_delta, "write"),
+                    ("dios", device.dios, &self.metrics.io_ops_delta, "discard"),
+                ];
+                for (key, value, metric, kind) in counters {
+                    let Some(value) = value else { continue };
+                    if let Some(diff) = self.io_counters.update((device.device.clone(), key), value) {
+                        measurements.push(
+                            self.new_point(metric, t, &resource, diff)
+                                .with_attr("device", device.device.clone())
+                                .with_attr("kind", kind),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Pressure stall information
+        let pressures = [
+            ("cpu", data.cpu_pressure),
+            ("memory", data.memory_pressure),
+            ("io", data.io_pressure),
+        ];
+        for (controller, stats) in pressures {
+            let Some(PressureStats { some, full }) = stats else {
+                continue;
+            };
+            for (kind, pressure) in [("some", some), ("full", full)] {
+                let Some(pressure) = pressure else { continue };
+                if let Some(diff) = self.pressure_counters.update((controller, kind), pressure.total) {
+                    measurements.push(
+                        self.new_point(&self.metrics.pressure_stall_delta, t, &resource, diff)
+                            .with_attr("controller", controller)
+                            .with_attr("kind", kind),
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
     fn reset(&mut self) -> anyhow::Result<()> {
         self.delta_counters.reset();
+        self.io_counters.reset();
+        self.pressure_counters.reset();
         self.last_timestamp = None;
         Ok(())
     }
@@ -0,0 +1,129 @@
+use std::{sync::LazyLock, time::Duration};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{AttributeValue, WrappedMeasurementValue},
+    pipeline::{elements::source::trigger::TriggerSpec, naming::SourceName},
+    plugin::{AlumetPluginStart, ConfigTable, PluginMetadata, rust::AlumetPlugin},
+    resources::ResourceConsumer,
+    test::{RuntimeExpectations, runtime::SourceCheckOutputContext},
+};
+use util_cgroups::{Cgroup, CgroupHierarchy, CgroupVersion};
+use util_cgroups_plugins::{
+    cgroup_events::CgroupRemovalCallback,
+    job_summary::{JobSummaries, JobSummaryMetrics, JobSummarySource, JobSummaryTransform},
+    metrics::Metrics,
+};
+
+const JOB_CGROUP: &str = "/oar.slice/oar-u1000.scope/oar-u1000-j123456";
+
+/// Summaries shared by the plugin and the test.
+static SUMMARIES: LazyLock<JobSummaries> = LazyLock::new(JobSummaries::default);
+
+// Registers the summary transform and source, like the job plugins do.
+struct DumbJobPlugin;
+
+impl AlumetPlugin for DumbJobPlugin {
+    fn name() -> &'static str {
+        "jobs"
+    }
+
+    fn version() -> &'static str {
+        "0.1.0"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metrics = Metrics::create(alumet)?;
+        let summary_metrics = JobSummaryMetrics::create(alumet)?;
+        let source = JobSummarySource::new(SUMMARIES.clone(), &summary_metrics, Vec::new());
+        alumet.add_source(
+            "job-summary",
+            Box::new(source),
+            TriggerSpec::at_interval(Duration::from_secs(1)),
+        )?;
+        alumet.add_transform_builder("job-summary", move |ctx| {
+            let transform = JobSummaryTransform::new(SUMMARIES.clone(), &metrics, &[], ctx)?;
+            Ok(Box::new(transform))
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn summary_produced_when_the_job_ends() {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<DumbJobPlugin>(),
+        enabled: true,
+        config: None,
+    });
+
+    // Start the job, then remove its cgroup, without any other measurement.
+    let end_job = || {
+        let hierarchy = CgroupHierarchy::manually_unchecked("/", CgroupVersion::V2, vec!["cpu"]);
+        let cgroup = Cgroup::from_cgroup_path(&hierarchy, JOB_CGROUP.to_owned());
+        SUMMARIES.job_started(
+            &cgroup,
+            vec![(String::from("job_id"), AttributeValue::String(String::from("123456")))],
+        );
+        SUMMARIES.cpu_usage(JOB_CGROUP, 1500);
+        SUMMARIES
+            .clone()
+            .on_cgroups_removed(vec![cgroup])
+            .expect("removal callback should succeed");
+    };
+
+    // The job is only finalized by the next poll, in case some of its measurements are still in the pipeline.
+    let check_no_summary = |ctx: &mut SourceCheckOutputContext| {
+        assert!(
+            ctx.measurements().is_empty(),
+            "no summary on the first poll after the end of the job"
+        );
+    };
+
+    let check_summary = |ctx: &mut SourceCheckOutputContext| {
+        let points = ctx.points_by_metric_and_consumer(None);
+        let consumer = ResourceConsumer::ControlGroup {
+            path: JOB_CGROUP.into(),
+        };
+        let duration = points
+            .get(&("job_total_duration", consumer.clone(), None))
+            .expect("the summary should contain the duration of the job");
+        assert!(
+            duration
+                .attributes()
+                .any(|(k, v)| k == "job_id" && v == &AttributeValue::String(String::from("123456")))
+        );
+        let cpu_time = points
+            .get(&("job_total_cpu_time", consumer, None))
+            .expect("the summary should contain the cpu time of the job");
+        assert_eq!(cpu_time.value, WrappedMeasurementValue::U64(1500));
+    };
+
+    let source = SourceName::from_str("jobs", "job-summary");
+    let runtime_expectations = RuntimeExpectations::new()
+        .test_source(source.clone(), end_job, check_no_summary)
+        .test_source(source, || (), check_summary);
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(runtime_expectations)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(Duration::from_secs(2)).unwrap();
+}
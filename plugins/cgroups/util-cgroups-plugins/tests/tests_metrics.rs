@@ -72,6 +72,12 @@ fn test_metrics_sets() {
         .expect_metric::<u64>("cgroup_memory_file", Unit::Byte)
         .expect_metric::<u64>("cgroup_memory_kernel_stack", Unit::Byte)
         .expect_metric::<u64>("cgroup_memory_pagetables", Unit::Byte)
+        .expect_metric::<u64>("cgroup_memory_peak", Unit::Byte)
+        .expect_metric::<u64>("cgroup_memory_events", Unit::Unity)
+        .expect_metric::<u64>("cgroup_pids", Unit::Unity)
+        .expect_metric::<u64>("cgroup_io_bytes_delta", Unit::Byte)
+        .expect_metric::<u64>("cgroup_io_ops_delta", Unit::Unity)
+        .expect_metric::<u64>("cgroup_pressure_stall_delta", PrefixedUnit::micro(Unit::Second))
         .expect_source(PLUGIN_NAME, SOURCE_NAME);
 
     let runtime = RuntimeExpectations::new().test_source(
@@ -87,6 +87,36 @@ pub unsafe fn parse_space_kv_at_lines(
     Ok(())
 }
 
+/// Parses a list of nested key-values from `io_buf`.
+///
+/// Calls `on_nkv` for every key-value pair found, with `(line_name, key, value)`.
+/// The value is not parsed, because its type depends on the file and on the key.
+/// Empty lines are ignored.
+///
+/// # Input format
+/// ```text
+/// 8:0 rbytes=1459200 wbytes=314773504
+/// some avg10=0.00 avg60=0.00 avg300=0.00 total=158
+/// ```
+///
+/// # Safety
+/// The bytes passed in must be valid UTF-8.
+pub unsafe fn parse_nested_kv(io_buf: &[u8], mut on_nkv: impl FnMut(&str, &str, &str)) -> io::Result<()> {
+    let content = unsafe { std::str::from_utf8_unchecked(io_buf) };
+    for line in content.split('\n') {
+        let mut fields = line.split_ascii_whitespace();
+        if let Some(name) = fields.next() {
+            for field in fields {
+                let (key, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+                on_nkv(name, key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Helper for reading a file that contains a single `u64` value.
 pub struct U64File {
     file: File,
@@ -283,6 +313,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn nested_kv() -> anyhow::Result<()> {
+        // sample data from cgroup v2 "io.stat"
+        const IO_STAT: &str = "8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0
+8:0 rbytes=90430464 wbytes=299008000 rios=8950 wios=1252 dbytes=50331648 dios=3021
+
+";
+        let mut entries = Vec::new();
+        unsafe {
+            parse_nested_kv(IO_STAT.as_bytes(), |name, key, value| {
+                entries.push(format!("{name} {key} {value}"));
+            })
+        }?;
+        assert_eq!(entries.len(), 12);
+        assert_eq!(entries[0], "8:16 rbytes 1459200");
+        assert_eq!(entries[11], "8:0 dios 3021");
+
+        let err = unsafe { parse_nested_kv(b"some avg10", |_, _, _| ()) }.expect_err("expected error");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        Ok(())
+    }
+
     #[test]
     fn selective_stat() -> anyhow::Result<()> {
         // sample data from cgroup v2 "cpu.stat"
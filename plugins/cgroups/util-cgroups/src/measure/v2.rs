@@ -6,6 +6,15 @@ pub mod cpu;
 /// Memory statistics for cgroup v2.
 pub mod memory;
 
+/// I/O statistics for cgroup v2.
+pub mod io;
+
+/// Number of processes for cgroup v2.
+pub mod pids;
+
+/// Pressure stall information (PSI) for cgroup v2.
+pub mod pressure;
+
 /// Small zero-cost wrapper around line index.
 mod line_index;
 
@@ -19,110 +28,189 @@ mod serde_util;
 #[cfg(feature = "manually")]
 pub mod mock;
 
-pub use common::{V2Collector, V2Stats};
+pub use common::{V2Collector, V2CollectorSettings, V2Stats};
 
 mod common {
-    use std::io::{self, ErrorKind};
+    use std::{
+        io::{self, ErrorKind},
+        path::Path,
+    };
 
     use anyhow::Context;
 
     use crate::{
         Cgroup,
         measure::v2::{
-            cpu::{self, CpuStatCollectorSettings},
-            memory::{self, MemoryStatCollectorSettings},
+            cpu::CpuStatCollectorSettings,
+            io::{IoStatCollectorSettings, IoStats},
+            memory::{
+                CollectorCreationError, MemoryEvents, MemoryEventsCollectorSettings, MemoryStatCollectorSettings,
+            },
+            pressure::{PressureCollectorSettings, PressureStats},
         },
     };
 
     use super::{
         cpu::{CpuStatCollector, CpuStats},
-        memory::{MemoryCurrentCollector, MemoryStatCollector, MemoryStats},
+        io::IoStatCollector,
+        memory::{
+            MemoryCurrentCollector, MemoryEventsCollector, MemoryPeakCollector, MemoryStatCollector, MemoryStats,
+        },
+        pids::PidsCurrentCollector,
+        pressure::PressureCollector,
     };
 
     /// Collects cgroup v2 measurements.
     pub struct V2Collector {
         memory_current: Option<MemoryCurrentCollector>,
+        memory_peak: Option<MemoryPeakCollector>,
         memory_stat: Option<MemoryStatCollector>,
+        memory_events: Option<MemoryEventsCollector>,
         cpu_stat: Option<CpuStatCollector>,
+        io_stat: Option<IoStatCollector>,
+        pids_current: Option<PidsCurrentCollector>,
+        cpu_pressure: Option<PressureCollector>,
+        memory_pressure: Option<PressureCollector>,
+        io_pressure: Option<PressureCollector>,
+    }
+
+    /// Settings of the collectors of a [`V2Collector`].
+    ///
+    /// By default, only `memory.current`, `memory.stat` and `cpu.stat` are collected.
+    #[derive(Default)]
+    pub struct V2CollectorSettings {
+        pub memory_stat: MemoryStatCollectorSettings,
+        pub cpu_stat: CpuStatCollectorSettings,
+        /// Settings of `memory.events`, or `None` to skip this file.
+        pub memory_events: Option<MemoryEventsCollectorSettings>,
+        /// Settings of `io.stat`, or `None` to skip this file.
+        pub io_stat: Option<IoStatCollectorSettings>,
+        /// Settings of `cpu.pressure`, `memory.pressure` and `io.pressure`, or `None` to skip these files.
+        pub pressure: Option<PressureCollectorSettings>,
+        /// Collects `memory.peak`.
+        pub memory_peak: bool,
+        /// Collects `pids.current`.
+        pub pids_current: bool,
     }
 
     pub struct V2Stats {
         pub memory_current: Option<u64>,
+        pub memory_peak: Option<u64>,
         pub memory_stat: Option<MemoryStats>,
+        pub memory_events: Option<MemoryEvents>,
         pub cpu_stat: Option<CpuStats>,
+        pub io_stat: Option<IoStats>,
+        pub pids_current: Option<u64>,
+        pub cpu_pressure: Option<PressureStats>,
+        pub memory_pressure: Option<PressureStats>,
+        pub io_pressure: Option<PressureStats>,
     }
 
     impl V2Collector {
+        /// Creates a new `V2Collector` for the given cgroup.
+        ///
+        /// The collectors of the files that are not configured by the parameters use their default settings.
+        /// See [`V2Collector::with_settings`].
+        pub fn new(
+            cgroup: Cgroup<'_>,
+            memory_stat_settings: MemoryStatCollectorSettings,
+            cpu_stat_settings: CpuStatCollectorSettings,
+            io_buf: &mut Vec<u8>,
+        ) -> anyhow::Result<Self> {
+            let settings = V2CollectorSettings {
+                memory_stat: memory_stat_settings,
+                cpu_stat: cpu_stat_settings,
+                ..Default::default()
+            };
+            Self::with_settings(cgroup, settings, io_buf)
+        }
+
         /// Creates a new `V2Collector` for the given cgroup.
         ///
         /// # Available metrics
         ///
         /// The metrics that will be measured depends on:
         /// - the cgroup controllers that are enabled
-        /// - the configuration of the Linux kernel
+        /// - the configuration of the Linux kernel (for instance, PSI can be disabled)
         /// - the collectors' settings passed to this method
-        pub fn new(
+        pub fn with_settings(
             cgroup: Cgroup<'_>,
-            memory_stat_settings: MemoryStatCollectorSettings,
-            cpu_stat_settings: CpuStatCollectorSettings,
+            settings: V2CollectorSettings,
             io_buf: &mut Vec<u8>,
         ) -> anyhow::Result<Self> {
             let cgroup_path = cgroup.fs_path();
-            let memory_current_file = cgroup_path.join("memory.current");
-            let memory_stat_file = cgroup_path.join("memory.stat");
-            let cpu_stat_file = cgroup_path.join("cpu.stat");
-
-            let prepare_memory_current = || -> anyhow::Result<Option<MemoryCurrentCollector>> {
-                match MemoryCurrentCollector::new(&memory_current_file) {
-                    Ok(res) => Ok(Some(res)),
-                    Err(e) if e.kind() == ErrorKind::NotFound => {
-                        // the file does not exist, ignore
-                        log::warn!(
-                            "{} does not exist, some metrics will not be available",
-                            memory_current_file.display()
-                        );
-                        Ok(None)
-                    }
-                    Err(e) => Err(e.into()),
-                }
-            };
+            let file = |name: &str| cgroup_path.join(name);
+            let error_msg = || format!("collector creation failed for cgroup {}", cgroup.unique_name());
 
-            let prepare_memory_stat = |io_buf: &mut Vec<u8>| -> anyhow::Result<Option<MemoryStatCollector>> {
-                match MemoryStatCollector::new(&memory_stat_file, memory_stat_settings, io_buf) {
-                    Ok(res) => Ok(Some(res)),
-                    Err(memory::CollectorCreationError::Io(e, _)) if e.kind() == ErrorKind::NotFound => {
-                        // the file does not exist, ignore
-                        log::warn!(
-                            "{} does not exist, some metrics will not be available",
-                            memory_stat_file.display()
-                        );
-                        Ok(None)
-                    }
-                    Err(e) => Err(e.into()),
+            let memory_current_file = file("memory.current");
+            let memory_peak_file = file("memory.peak");
+            let memory_stat_file = file("memory.stat");
+            let memory_events_file = file("memory.events");
+            let cpu_stat_file = file("cpu.stat");
+            let io_stat_file = file("io.stat");
+            let pids_current_file = file("pids.current");
+            let cpu_pressure_file = file("cpu.pressure");
+            let memory_pressure_file = file("memory.pressure");
+            let io_pressure_file = file("io.pressure");
+            let pressure = |path: &Path, io_buf: &mut Vec<u8>| match &settings.pressure {
+                Some(pressure_settings) => {
+                    optional(PressureCollector::new(path, pressure_settings.clone(), io_buf), path)
                 }
+                None => Ok(None),
             };
 
-            let prepare_cpu_stat = |io_buf: &mut Vec<u8>| -> anyhow::Result<Option<CpuStatCollector>> {
-                match CpuStatCollector::new(&cpu_stat_file, cpu_stat_settings, io_buf) {
-                    Ok(res) => Ok(Some(res)),
-                    Err(cpu::CollectorCreationError::Io(e, _)) if e.kind() == ErrorKind::NotFound => {
-                        // the file does not exist, ignore
-                        log::warn!(
-                            "{} does not exist, some metrics will not be available",
-                            cpu_stat_file.display()
-                        );
-                        Ok(None)
-                    }
-                    Err(e) => Err(e.into()),
-                }
-            };
-
-            let error_msg = || format!("collector creation failed for cgroup {}", cgroup.unique_name());
-
             Ok(Self {
-                memory_current: prepare_memory_current().with_context(error_msg)?,
-                memory_stat: prepare_memory_stat(io_buf).with_context(error_msg)?,
-                cpu_stat: prepare_cpu_stat(io_buf).with_context(error_msg)?,
+                memory_current: optional(
+                    single_value(MemoryCurrentCollector::new(&memory_current_file), &memory_current_file),
+                    &memory_current_file,
+                )
+                .with_context(error_msg)?,
+                memory_peak: if settings.memory_peak {
+                    optional(
+                        single_value(MemoryPeakCollector::new(&memory_peak_file), &memory_peak_file),
+                        &memory_peak_file,
+                    )
+                    .with_context(error_msg)?
+                } else {
+                    None
+                },
+                memory_stat: optional(
+                    MemoryStatCollector::new(&memory_stat_file, settings.memory_stat, io_buf),
+                    &memory_stat_file,
+                )
+                .with_context(error_msg)?,
+                memory_events: match settings.memory_events {
+                    Some(events_settings) => optional(
+                        MemoryEventsCollector::new(&memory_events_file, events_settings, io_buf),
+                        &memory_events_file,
+                    )
+                    .with_context(error_msg)?,
+                    None => None,
+                },
+                cpu_stat: optional(
+                    CpuStatCollector::new(&cpu_stat_file, settings.cpu_stat, io_buf),
+                    &cpu_stat_file,
+                )
+                .with_context(error_msg)?,
+                io_stat: match settings.io_stat {
+                    Some(io_settings) => {
+                        optional(IoStatCollector::new(&io_stat_file, io_settings, io_buf), &io_stat_file)
+                            .with_context(error_msg)?
+                    }
+                    None => None,
+                },
+                pids_current: if settings.pids_current {
+                    optional(
+                        single_value(PidsCurrentCollector::new(&pids_current_file), &pids_current_file),
+                        &pids_current_file,
+                    )
+                    .with_context(error_msg)?
+                } else {
+                    None
+                },
+                cpu_pressure: pressure(&cpu_pressure_file, io_buf).with_context(error_msg)?,
+                memory_pressure: pressure(&memory_pressure_file, io_buf).with_context(error_msg)?,
+                io_pressure: pressure(&io_pressure_file, io_buf).with_context(error_msg)?,
             })
         }
 
@@ -131,14 +219,52 @@ mod common {
             // TODO take &mut V2Stats as a parameter to reduce allocations? Profile.
 
             let memory_current = self.memory_current.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let memory_peak = self.memory_peak.as_mut().map(|c| c.measure(io_buf)).transpose()?;
             let memory_stat = self.memory_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let memory_events = self.memory_events.as_mut().map(|c| c.measure(io_buf)).transpose()?;
             let cpu_stat = self.cpu_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let io_stat = self.io_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let pids_current = self.pids_current.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let cpu_pressure = self.cpu_pressure.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let memory_pressure = self.memory_pressure.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let io_pressure = self.io_pressure.as_mut().map(|c| c.measure(io_buf)).transpose()?;
 
             Ok(V2Stats {
                 memory_current,
+                memory_peak,
                 memory_stat,
+                memory_events,
                 cpu_stat,
+                io_stat,
+                pids_current,
+                cpu_pressure,
+                memory_pressure,
+                io_pressure,
             })
         }
     }
+
+    /// Attaches the path of the file to the error of a collector that reads a single value.
+    fn single_value<C>(res: io::Result<C>, path: &Path) -> Result<C, CollectorCreationError> {
+        res.map_err(|e| CollectorCreationError::Io(e, path.into()))
+    }
+
+    /// Ignores the files that do not exist, or that cannot be read because the feature is disabled (e.g. PSI).
+    ///
+    /// The available files depend on the enabled controllers and on the configuration of the kernel.
+    fn optional<C>(res: Result<C, CollectorCreationError>, path: &Path) -> anyhow::Result<Option<C>> {
+        match res {
+            Ok(collector) => Ok(Some(collector)),
+            Err(CollectorCreationError::Io(e, _))
+                if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::Unsupported) =>
+            {
+                log::warn!(
+                    "cannot read {} ({e}), some metrics will not be available",
+                    path.display()
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
 }
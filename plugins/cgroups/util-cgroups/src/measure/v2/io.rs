@@ -0,0 +1,203 @@
+use std::{fs::File, io, path::Path};
+
+use serde::Serialize;
+
+use crate::measure::{
+    parse::{parse_nested_kv, read_fully},
+    v2::settings::EnabledKeys,
+};
+
+pub type CollectorCreationError = super::memory::CollectorCreationError;
+
+/// Collects measurements from `io.stat`.
+///
+/// Unlike `cpu.stat` or `memory.stat`, `io.stat` contains one line per device, and the lines
+/// appear as soon as the cgroup performs I/O on a device. The line indices cannot be cached,
+/// hence the file is fully parsed at each measurement.
+pub struct IoStatCollector {
+    file: File,
+    keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IoStatCollectorSettings {
+    pub rbytes: bool,
+    pub wbytes: bool,
+    pub rios: bool,
+    pub wios: bool,
+    pub dbytes: bool,
+    pub dios: bool,
+}
+
+impl EnabledKeys for IoStatCollectorSettings {}
+
+impl Default for IoStatCollectorSettings {
+    fn default() -> Self {
+        Self {
+            rbytes: true,
+            wbytes: true,
+            rios: true,
+            wios: true,
+            dbytes: false,
+            dios: false,
+        }
+    }
+}
+
+/// Represents the measurements extracted from the `io.stat` file.
+#[derive(Debug, Default)]
+pub struct IoStats {
+    pub devices: Vec<DeviceIoStats>,
+}
+
+/// I/O counters of one device, since the creation of the cgroup.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DeviceIoStats {
+    /// Device numbers, in the `major:minor` format.
+    pub device: String,
+    /// Bytes read.
+    pub rbytes: Option<u64>,
+    /// Bytes written.
+    pub wbytes: Option<u64>,
+    /// Number of read operations.
+    pub rios: Option<u64>,
+    /// Number of write operations.
+    pub wios: Option<u64>,
+    /// Bytes discarded.
+    pub dbytes: Option<u64>,
+    /// Number of discard operations.
+    pub dios: Option<u64>,
+}
+
+impl IoStatCollector {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        settings: IoStatCollectorSettings,
+        io_buf: &mut Vec<u8>,
+    ) -> Result<Self, CollectorCreationError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        let keys = settings.enabled_keys()?;
+        let mut collector = Self { file, keys };
+
+        // this is initialization time, we can afford to check that the file is valid to avoid problems later
+        read_fully(&mut collector.file, io_buf).map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        std::str::from_utf8(io_buf)
+            .map_err(|e| CollectorCreationError::Io(io::Error::new(io::ErrorKind::InvalidData, e), path.into()))?;
+        collector
+            .parse(io_buf)
+            .map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        Ok(collector)
+    }
+
+    /// Collects measurements from the underlying "file", using `io_buf` as an intermediary I/O buffer.
+    pub fn measure(&mut self, io_buf: &mut Vec<u8>) -> io::Result<IoStats> {
+        read_fully(&mut self.file, io_buf)?;
+        self.parse(io_buf)
+    }
+
+    fn parse(&self, io_buf: &[u8]) -> io::Result<IoStats> {
+        let mut res = IoStats::default();
+        let mut error = None;
+        // SAFETY: the content is generated by the kernel and is always valid ASCII (hence valid UTF-8)
+        unsafe {
+            parse_nested_kv(io_buf, |device, key, value| {
+                if !self.keys.iter().any(|k| k == key) {
+                    return;
+                }
+                let Ok(value) = value.parse::<u64>() else {
+                    error = Some(io::Error::from(io::ErrorKind::InvalidData));
+                    return;
+                };
+                let stats = match res.devices.last_mut() {
+                    Some(last) if last.device == device => last,
+                    _ => {
+                        res.devices.push(DeviceIoStats {
+                            device: device.to_owned(),
+                            ..Default::default()
+                        });
+                        res.devices.last_mut().unwrap()
+                    }
+                };
+                let field = match key {
+                    "rbytes" => &mut stats.rbytes,
+                    "wbytes" => &mut stats.wbytes,
+                    "rios" => &mut stats.rios,
+                    "wios" => &mut stats.wios,
+                    "dbytes" => &mut stats.dbytes,
+                    "dios" => &mut stats.dios,
+                    _ => return,
+                };
+                *field = Some(value);
+            })
+        }?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(res),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::{DeviceIoStats, IoStatCollector, IoStatCollectorSettings};
+    use crate::measure::v2::memory::CollectorCreationError;
+
+    #[test]
+    fn collect_io_stat() -> anyhow::Result<()> {
+        let tmp = tempfile::NamedTempFile::new()?;
+
+        // no I/O yet
+        std::fs::write(tmp.path(), "")?;
+        let mut io_buf = Vec::new();
+        let mut collector = IoStatCollector::new(tmp.path(), IoStatCollectorSettings::default(), io_buf.as_mut())?;
+        assert!(collector.measure(io_buf.as_mut())?.devices.is_empty());
+
+        std::fs::write(
+            tmp.path(),
+            "8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0\n\
+             8:0 rbytes=90430464 wbytes=299008000 rios=8950 wios=1252 dbytes=50331648 dios=3021\n",
+        )?;
+        let stats = collector.measure(io_buf.as_mut())?;
+        assert_eq!(
+            stats.devices,
+            vec![
+                DeviceIoStats {
+                    device: String::from("8:16"),
+                    rbytes: Some(1459200),
+                    wbytes: Some(314773504),
+                    rios: Some(192),
+                    wios: Some(353),
+                    dbytes: None,
+                    dios: None,
+                },
+                DeviceIoStats {
+                    device: String::from("8:0"),
+                    rbytes: Some(90430464),
+                    wbytes: Some(299008000),
+                    rios: Some(8950),
+                    wios: Some(1252),
+                    dbytes: None,
+                    dios: None,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_io_stat() -> anyhow::Result<()> {
+        let tmp = tempfile::NamedTempFile::new()?;
+        std::fs::write(tmp.path(), "8:0 rbytes=abc wbytes=12\n")?;
+
+        let mut io_buf = Vec::new();
+        let res = IoStatCollector::new(tmp.path(), IoStatCollectorSettings::default(), io_buf.as_mut());
+        match res {
+            Err(CollectorCreationError::Io(e, _)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            _ => panic!("invalid io.stat should be rejected"),
+        }
+        Ok(())
+    }
+}
@@ -20,6 +20,9 @@ pub struct MemoryCurrentCollector {
     file: U64File,
 }
 
+/// Collects measurements from `memory.peak`, which has the same format as `memory.current`.
+pub type MemoryPeakCollector = MemoryCurrentCollector;
+
 /// Collects measurements from `memory.stat`.
 pub struct MemoryStatCollector {
     stat_file: SelectiveStatFile,
@@ -44,6 +47,45 @@ struct MemoryStatMapping {
     page_tables: LineIndex,
 }
 
+/// Collects measurements from `memory.events`.
+pub struct MemoryEventsCollector {
+    stat_file: SelectiveStatFile,
+    mapping: MemoryEventsMapping,
+}
+
+/// Represents the measurements extracted from the `memory.events` file.
+///
+/// The values are the number of times each event occurred since the creation of the cgroup.
+#[derive(Debug, Default)]
+pub struct MemoryEvents {
+    pub oom: Option<u64>,
+    pub oom_kill: Option<u64>,
+    // could be extended to manage other events (low, high, max, oom_group_kill)
+}
+
+#[derive(Default)]
+struct MemoryEventsMapping {
+    oom: LineIndex,
+    oom_kill: LineIndex,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryEventsCollectorSettings {
+    pub oom: bool,
+    pub oom_kill: bool,
+}
+
+impl EnabledKeys for MemoryEventsCollectorSettings {}
+
+impl Default for MemoryEventsCollectorSettings {
+    fn default() -> Self {
+        Self {
+            oom: true,
+            oom_kill: true,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct MemoryStatCollectorSettings {
     pub anon: bool,
@@ -150,12 +192,66 @@ impl MemoryStatCollector {
     }
 }
 
+impl MemoryEventsCollector {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        settings: MemoryEventsCollectorSettings,
+        io_buf: &mut Vec<u8>,
+    ) -> Result<Self, CollectorCreationError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+
+        let keys = settings.enabled_keys()?;
+        let (stat_file, stat_mapping) = StatFileBuilder::new(file, &keys)
+            .build(io_buf.as_mut())
+            .map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+
+        let mut mapping = MemoryEventsMapping::default();
+        if let Some(i) = stat_mapping.line_index("oom") {
+            mapping.oom = i.into();
+        }
+        if let Some(i) = stat_mapping.line_index("oom_kill") {
+            mapping.oom_kill = i.into();
+        }
+
+        if !stat_mapping.keys_not_found().is_empty() {
+            log::warn!(
+                "keys not found in {}: {}",
+                path.display(),
+                stat_mapping.keys_not_found().join(", ")
+            )
+        }
+
+        Ok(Self { stat_file, mapping })
+    }
+
+    /// Collects measurements from the underlying "file", using `io_buf` as an intermediary I/O buffer.
+    pub fn measure(&mut self, io_buf: &mut Vec<u8>) -> io::Result<MemoryEvents> {
+        let mut res = MemoryEvents::default();
+        unsafe {
+            self.stat_file.read(io_buf, |i, _k, v| match i {
+                i if i == self.mapping.oom.0 => {
+                    res.oom = Some(v);
+                }
+                i if i == self.mapping.oom_kill.0 => {
+                    res.oom_kill = Some(v);
+                }
+                _ => (),
+            })
+        }?;
+        Ok(res)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::ErrorKind;
 
     use crate::measure::v2::{
-        memory::{MemoryCurrentCollector, MemoryStatCollector, MemoryStatCollectorSettings},
+        memory::{
+            MemoryCurrentCollector, MemoryEventsCollector, MemoryEventsCollectorSettings, MemoryStatCollector,
+            MemoryStatCollectorSettings,
+        },
         mock::{MemoryStatMock, MockFileCgroupKV},
     };
 
@@ -263,4 +359,35 @@ mod tests {
         assert_eq!(res.kind(), ErrorKind::InvalidData);
         Ok(())
     }
+
+    #[test]
+    fn collect_memory_events() -> anyhow::Result<()> {
+        let tmp = tempfile::NamedTempFile::new()?;
+        std::fs::write(
+            tmp.path(),
+            "low 0\nhigh 12\nmax 7\noom 2\noom_kill 1\noom_group_kill 0\n",
+        )?;
+
+        let mut io_buf = Vec::new();
+        let mut collector = MemoryEventsCollector::new(
+            tmp.path(),
+            MemoryEventsCollectorSettings {
+                oom: true,
+                oom_kill: true,
+            },
+            io_buf.as_mut(),
+        )?;
+        let events = collector.measure(io_buf.as_mut())?;
+        assert_eq!(events.oom, Some(2));
+        assert_eq!(events.oom_kill, Some(1));
+
+        std::fs::write(
+            tmp.path(),
+            "low 0\nhigh 12\nmax 7\noom 3\noom_kill 3\noom_group_kill 0\n",
+        )?;
+        let events = collector.measure(io_buf.as_mut())?;
+        assert_eq!(events.oom, Some(3));
+        assert_eq!(events.oom_kill, Some(3));
+        Ok(())
+    }
 }
@@ -0,0 +1,41 @@
+use std::{io, path::Path};
+
+use crate::measure::parse::U64File;
+
+/// Collects measurements from `pids.current`.
+pub struct PidsCurrentCollector {
+    file: U64File,
+}
+
+impl PidsCurrentCollector {
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = U64File::open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Collects measurements from the underlying "file", using `io_buf` as an intermediary I/O buffer.
+    pub fn measure(&mut self, io_buf: &mut Vec<u8>) -> io::Result<u64> {
+        // SAFETY: the content is generated by the kernel and is always valid ASCII (hence valid UTF-8)
+        unsafe { self.file.read(io_buf) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PidsCurrentCollector;
+
+    #[test]
+    fn collect_pids_current() -> anyhow::Result<()> {
+        let tmp = tempfile::NamedTempFile::new()?;
+
+        let mut io_buf = Vec::new();
+        let mut collector = PidsCurrentCollector::new(tmp.path())?;
+
+        std::fs::write(tmp.path(), "17\n")?;
+        assert_eq!(collector.measure(io_buf.as_mut())?, 17);
+
+        std::fs::write(tmp.path(), "1")?;
+        assert_eq!(collector.measure(io_buf.as_mut())?, 1);
+        Ok(())
+    }
+}
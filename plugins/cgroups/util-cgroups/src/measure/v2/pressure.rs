@@ -0,0 +1,167 @@
+use std::{fs::File, io, path::Path};
+
+use serde::Serialize;
+
+use crate::measure::{
+    parse::{parse_nested_kv, read_fully},
+    v2::settings::EnabledKeys,
+};
+
+pub type CollectorCreationError = super::memory::CollectorCreationError;
+
+/// Collects measurements from a pressure stall information (PSI) file:
+/// `cpu.pressure`, `memory.pressure` or `io.pressure`.
+///
+/// If PSI is disabled in the kernel, the files exist but cannot be read, and the creation of
+/// the collector fails with an error of kind [`io::ErrorKind::Unsupported`].
+pub struct PressureCollector {
+    file: File,
+    keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PressureCollectorSettings {
+    pub some: bool,
+    pub full: bool,
+}
+
+impl EnabledKeys for PressureCollectorSettings {}
+
+impl Default for PressureCollectorSettings {
+    fn default() -> Self {
+        Self { some: true, full: true }
+    }
+}
+
+/// Represents the measurements extracted from a pressure file.
+#[derive(Debug, Default, PartialEq)]
+pub struct PressureStats {
+    /// Stall of some tasks of the cgroup.
+    pub some: Option<Pressure>,
+    /// Stall of all the non-idle tasks of the cgroup at the same time.
+    pub full: Option<Pressure>,
+}
+
+/// One line of a pressure file.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pressure {
+    /// Share of the time during which the tasks were stalled, over the last 10 seconds (0 to 100).
+    pub avg10: f64,
+    /// Share of the time during which the tasks were stalled, over the last 60 seconds (0 to 100).
+    pub avg60: f64,
+    /// Share of the time during which the tasks were stalled, over the last 300 seconds (0 to 100).
+    pub avg300: f64,
+    /// Total stall time since the creation of the cgroup, in microseconds.
+    pub total: u64,
+}
+
+impl PressureCollector {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        settings: PressureCollectorSettings,
+        io_buf: &mut Vec<u8>,
+    ) -> Result<Self, CollectorCreationError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        let keys = settings.enabled_keys()?;
+        let mut collector = Self { file, keys };
+
+        // this is initialization time, we can afford to check that the file is valid to avoid problems later
+        read_fully(&mut collector.file, io_buf).map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        std::str::from_utf8(io_buf)
+            .map_err(|e| CollectorCreationError::Io(io::Error::new(io::ErrorKind::InvalidData, e), path.into()))?;
+        collector
+            .parse(io_buf)
+            .map_err(|e| CollectorCreationError::Io(e, path.into()))?;
+        Ok(collector)
+    }
+
+    /// Collects measurements from the underlying "file", using `io_buf` as an intermediary I/O buffer.
+    pub fn measure(&mut self, io_buf: &mut Vec<u8>) -> io::Result<PressureStats> {
+        read_fully(&mut self.file, io_buf)?;
+        self.parse(io_buf)
+    }
+
+    fn parse(&self, io_buf: &[u8]) -> io::Result<PressureStats> {
+        let mut res = PressureStats::default();
+        let mut valid = true;
+        // SAFETY: the content is generated by the kernel and is always valid ASCII (hence valid UTF-8)
+        unsafe {
+            parse_nested_kv(io_buf, |name, key, value| {
+                if !self.keys.iter().any(|k| k == name) {
+                    return;
+                }
+                let pressure = match name {
+                    "some" => res.some.get_or_insert_default(),
+                    "full" => res.full.get_or_insert_default(),
+                    _ => return,
+                };
+                let parsed = match key {
+                    "avg10" => value.parse().map(|v| pressure.avg10 = v).is_ok(),
+                    "avg60" => value.parse().map(|v| pressure.avg60 = v).is_ok(),
+                    "avg300" => value.parse().map(|v| pressure.avg300 = v).is_ok(),
+                    "total" => value.parse().map(|v| pressure.total = v).is_ok(),
+                    _ => true,
+                };
+                valid &= parsed;
+            })
+        }?;
+        if valid {
+            Ok(res)
+        } else {
+            Err(io::Error::from(io::ErrorKind::InvalidData))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pressure, PressureCollector, PressureCollectorSettings, PressureStats};
+
+    #[test]
+    fn collect_pressure() -> anyhow::Result<()> {
+        let tmp = tempfile::NamedTempFile::new()?;
+        std::fs::write(
+            tmp.path(),
+            "some avg10=1.50 avg60=0.25 avg300=0.00 total=158004\n\
+             full avg10=0.00 avg60=0.00 avg300=0.00 total=4210\n",
+        )?;
+
+        let mut io_buf = Vec::new();
+        let mut collector = PressureCollector::new(tmp.path(), PressureCollectorSettings::default(), io_buf.as_mut())?;
+        let stats = collector.measure(io_buf.as_mut())?;
+        assert_eq!(
+            stats,
+            PressureStats {
+                some: Some(Pressure {
+                    avg10: 1.5,
+                    avg60: 0.25,
+                    avg300: 0.0,
+                    total: 158004
+                }),
+                full: Some(Pressure {
+                    total: 4210,
+                    ..Default::default()
+                }),
+            }
+        );
+
+        let mut collector = PressureCollector::new(
+            tmp.path(),
+            PressureCollectorSettings {
+                some: true,
+                full: false,
+            },
+            io_buf.as_mut(),
+        )?;
+        let stats = collector.measure(io_buf.as_mut())?;
+        assert_eq!(stats.some.map(|p| p.total), Some(158004));
+        assert_eq!(stats.full, None);
+
+        std::fs::write(tmp.path(), "some avg10=1.50 avg60=0.25 avg300=0.00 total=-1\n")?;
+        collector
+            .measure(io_buf.as_mut())
+            .expect_err("invalid total should be rejected");
+        Ok(())
+    }
+}
@@ -3,7 +3,10 @@ use std::{fs::File, io::Write};
 use tempfile::tempdir;
 use util_cgroups::{
     Cgroup, CgroupHierarchy, CgroupVersion,
-    measure::v2::{V2Collector, cpu::CpuStatCollectorSettings, memory::MemoryStatCollectorSettings},
+    measure::v2::{
+        V2Collector, V2CollectorSettings, cpu::CpuStatCollectorSettings, memory::MemoryStatCollectorSettings,
+        pressure::PressureCollectorSettings,
+    },
 };
 
 #[test]
@@ -240,3 +243,80 @@ pub fn test_new_and_measure_bad_file_content_value() -> anyhow::Result<()> {
     assert!(collector_res.is_err());
     Ok(())
 }
+
+#[test]
+pub fn test_io_pids_and_pressure() -> anyhow::Result<()> {
+    let root = tempdir().expect("Failed to create a temporary directory");
+    let files = [
+        ("memory.peak", "4096\n"),
+        (
+            "memory.events",
+            "low 0\nhigh 0\nmax 3\noom 1\noom_kill 1\noom_group_kill 0\n",
+        ),
+        ("pids.current", "12\n"),
+        (
+            "io.stat",
+            "8:0 rbytes=90430464 wbytes=299008000 rios=8950 wios=1252 dbytes=50331648 dios=3021\n",
+        ),
+        (
+            "cpu.pressure",
+            "some avg10=0.50 avg60=0.10 avg300=0.02 total=41000\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=2000\n",
+        ),
+        (
+            "io.pressure",
+            "some avg10=0.00 avg60=0.00 avg300=0.00 total=300\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=200\n",
+        ),
+    ];
+    for (name, content) in files {
+        std::fs::write(root.path().join(name), content)?;
+    }
+
+    let hierarchy = CgroupHierarchy::manually_unchecked(root.path(), CgroupVersion::V2, vec!["io", "memory", "pids"]);
+    let cgroup = Cgroup::from_fs_path(&hierarchy, root.path().to_path_buf());
+
+    let mut io_buf = Vec::new();
+
+    // the additional files are skipped by default
+    let mut collector = V2Collector::with_settings(cgroup.clone(), V2CollectorSettings::default(), &mut io_buf)?;
+    let v2stat = collector.measure(&mut io_buf)?;
+    assert!(v2stat.memory_peak.is_none());
+    assert!(v2stat.memory_events.is_none());
+    assert!(v2stat.pids_current.is_none());
+    assert!(v2stat.io_stat.is_none());
+    assert!(v2stat.cpu_pressure.is_none());
+
+    let settings = V2CollectorSettings {
+        memory_events: Some(Default::default()),
+        io_stat: Some(Default::default()),
+        pressure: Some(PressureCollectorSettings {
+            some: true,
+            full: false,
+        }),
+        memory_peak: true,
+        pids_current: true,
+        ..Default::default()
+    };
+    let mut collector = V2Collector::with_settings(cgroup, settings, &mut io_buf)?;
+    let v2stat = collector.measure(&mut io_buf)?;
+
+    assert!(v2stat.cpu_stat.is_none());
+    assert!(v2stat.memory_current.is_none());
+    assert_eq!(v2stat.memory_peak, Some(4096));
+    let events = v2stat.memory_events.expect("memory.events should be read");
+    assert_eq!((events.oom, events.oom_kill), (Some(1), Some(1)));
+    assert_eq!(v2stat.pids_current, Some(12));
+
+    let io_stat = v2stat.io_stat.expect("io.stat should be read");
+    assert_eq!(io_stat.devices.len(), 1);
+    assert_eq!(io_stat.devices[0].device, "8:0");
+    assert_eq!(io_stat.devices[0].rbytes, Some(90430464));
+    assert_eq!(io_stat.devices[0].wios, Some(1252));
+    assert_eq!(io_stat.devices[0].dbytes, None, "dbytes is disabled by default");
+
+    let cpu_pressure = v2stat.cpu_pressure.expect("cpu.pressure should be read");
+    assert_eq!(cpu_pressure.some.map(|p| p.total), Some(41000));
+    assert!(cpu_pressure.full.is_none());
+    assert!(v2stat.memory_pressure.is_none());
+    assert_eq!(v2stat.io_pressure.and_then(|p| p.some).map(|p| p.total), Some(300));
+    Ok(())
+}
@@ -12,6 +12,9 @@ pub struct CsvWriter {
 
     /// CSV options,
     params: CsvParams,
+
+    /// Name of the last column, which contains the attributes that have no column of their own.
+    late_column: String,
 }
 
 pub struct CsvParams {
@@ -34,9 +37,42 @@ impl CsvWriter {
             file,
             header: Vec::new(),
             params,
+            late_column: String::from("__late_attributes"),
         }
     }
 
+    /// Sets the name of the last column, which contains the attributes that have no column of their own.
+    pub fn set_late_column_name(&mut self, name: impl Into<String>) {
+        self.late_column = name.into();
+    }
+
+    /// Returns the columns of the header (without the last column).
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    /// Forgets the current header, so that a new one can be written with [`CsvWriter::write_header`].
+    pub fn clear_header(&mut self) {
+        self.header.clear();
+    }
+
+    /// Replaces the underlying file, and returns the previous one.
+    ///
+    /// If the header has already been written, it is written again to the new file.
+    pub fn replace_file(&mut self, file: File) -> anyhow::Result<File> {
+        let previous = std::mem::replace(&mut self.file, file);
+        if self.is_initialized() {
+            let header = std::mem::take(&mut self.header);
+            self.write_header(header)?;
+        }
+        Ok(previous)
+    }
+
+    /// Returns the current size of the file.
+    pub fn file_size(&self) -> anyhow::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
     pub fn is_initialized(&self) -> bool {
         !self.header.is_empty()
     }
@@ -46,7 +82,7 @@ impl CsvWriter {
         for column in &header {
             write!(&mut self.file, "{column}{}", self.params.delimiter)?;
         }
-        writeln!(&mut self.file, "__late_attributes")?;
+        writeln!(&mut self.file, "{}", self.late_column)?;
         self.header = header;
         Ok(())
     }
@@ -4,14 +4,17 @@ mod output;
 
 use std::path::PathBuf;
 
+use alumet::agent::schema::JsonSchema;
 use alumet::plugin::{
     ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use output::CsvOutput;
 use serde::{Deserialize, Serialize};
 
 use crate::{csv::CsvParams, output::CsvOutputSettings};
+pub use output::HeaderStrategy;
+pub use util_file_rotation::{Compression, RotationConfig};
 
 pub struct CsvPlugin {
     config: Config,
@@ -27,11 +30,16 @@ impl AlumetPlugin for CsvPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config: Config = deserialize_config(config)?;
+        if config.header_strategy == HeaderStrategy::Fixed && config.attribute_columns.is_empty() {
+            log::warn!(
+                "csv header_strategy is 'fixed' but attribute_columns is empty: every attribute will go in the last column"
+            );
+        }
         Ok(Box::new(CsvPlugin { config }))
     }
 
@@ -44,6 +52,9 @@ impl AlumetPlugin for CsvPlugin {
                 delimiter: self.config.csv_delimiter,
                 late_delimiter: self.config.csv_late_delimiter,
             },
+            rotation: self.config.rotation.clone(),
+            header_strategy: self.config.header_strategy,
+            attribute_columns: self.config.attribute_columns.clone(),
         };
         let output = Box::new(CsvOutput::new(&self.config.output_path, settings)?);
         alumet.add_blocking_output("out", output)?;
@@ -55,7 +66,7 @@ impl AlumetPlugin for CsvPlugin {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Absolute or relative path to the output_file
@@ -70,6 +81,15 @@ pub struct Config {
     pub csv_delimiter: char,
     /// The delimiter between the entries in `__late_attributes`.
     pub csv_late_delimiter: char,
+    /// Rotation of the output file. If unset, the file is never rotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<RotationConfig>,
+    /// How to choose the attribute columns of the header.
+    #[serde(default)]
+    pub header_strategy: HeaderStrategy,
+    /// The attribute columns, when `header_strategy` is `fixed`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attribute_columns: Vec<String>,
 }
 
 impl Default for Config {
@@ -81,6 +101,9 @@ impl Default for Config {
             append_unit_to_metric_name: true,
             csv_delimiter: ';',
             csv_late_delimiter: ',',
+            rotation: None,
+            header_strategy: HeaderStrategy::default(),
+            attribute_columns: Vec::new(),
         }
     }
 }
@@ -2,14 +2,28 @@ use std::{collections::HashSet, fs::File, path::Path, time::SystemTime};
 
 use crate::csv::{CsvParams, CsvWriter};
 use alumet::{
+    agent::schema::JsonSchema,
     measurement::MeasurementBuffer,
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
 use alumet::{measurement::WrappedMeasurementValue, pipeline::Output};
 use anyhow::Context;
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
+use util_file_rotation::{Rotation, RotationConfig};
+
+/// The columns that come before the attributes.
+const BASE_COLUMNS: [&str; 7] = [
+    "metric",
+    "timestamp",
+    "value",
+    "resource_kind",
+    "resource_id",
+    "consumer_kind",
+    "consumer_id",
+];
 
 pub struct CsvOutput {
     /// parameter: do we flush after each write(measurements)?
@@ -21,6 +35,35 @@ pub struct CsvOutput {
 
     /// CSV writer
     writer: CsvWriter,
+
+    /// Rotation of the output file, if enabled.
+    rotation: Option<Rotation>,
+
+    /// How to choose the attribute columns.
+    header_strategy: HeaderStrategy,
+    /// Attribute columns, for [`HeaderStrategy::Fixed`].
+    attribute_columns: Vec<String>,
+}
+
+/// How to choose the attribute columns of the CSV header.
+///
+/// The attributes that have no column of their own are written in the last column, in the `key=value` format.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HeaderStrategy {
+    /// One column per attribute that appears in the first measurements.
+    #[default]
+    FirstBuffer,
+    /// One column per attribute listed in `attribute_columns`.
+    Fixed,
+    /// No attribute column: every attribute is written in the last column, named `attributes`.
+    Long,
+    /// Like `first_buffer`, but when new attributes appear, the file is rotated
+    /// and the new file gets an extended header.
+    NewFile,
+    /// Like `first_buffer`, but when new attributes appear, an extended header is written
+    /// in the same file, starting a new section.
+    NewSection,
 }
 
 pub struct CsvOutputSettings {
@@ -28,55 +71,142 @@ pub struct CsvOutputSettings {
     pub append_unit_to_metric_name: bool,
     pub use_unit_display_name: bool,
     pub params: CsvParams,
+    pub rotation: Option<RotationConfig>,
+    pub header_strategy: HeaderStrategy,
+    pub attribute_columns: Vec<String>,
 }
 
 impl CsvOutput {
     pub fn new(output_file: impl AsRef<Path>, settings: CsvOutputSettings) -> anyhow::Result<Self> {
         let path = output_file.as_ref();
         let file = File::create(path).with_context(|| format!("failed to open file for writing {path:?}"))?;
-        let writer = CsvWriter::new(file, settings.params);
+        let mut writer = CsvWriter::new(file, settings.params);
+        if settings.header_strategy == HeaderStrategy::Long {
+            writer.set_late_column_name("attributes");
+        }
+        let mut rotation = settings.rotation.map(|config| Rotation::new(config, path.to_owned()));
+        if settings.header_strategy == HeaderStrategy::NewFile && rotation.is_none() {
+            // the rotation is needed to start new files, even if it is not triggered by size or time
+            rotation = Some(Rotation::new(RotationConfig::default(), path.to_owned()));
+        }
         Ok(Self {
             force_flush: settings.force_flush,
             append_unit_to_metric_name: settings.append_unit_to_metric_name,
             use_unit_display_name: settings.use_unit_display_name,
             writer,
+            rotation,
+            header_strategy: settings.header_strategy,
+            attribute_columns: settings.attribute_columns,
         })
     }
+
+    /// Writes the header, with the given attribute columns.
+    fn write_header(&mut self, attributes: Vec<&str>) -> anyhow::Result<()> {
+        let mut header = Vec::with_capacity(BASE_COLUMNS.len() + attributes.len());
+        header.extend(BASE_COLUMNS);
+        header.extend(attributes);
+        let header = header.into_iter().map(String::from).collect();
+        log::trace!("writing header {header:?}");
+        self.writer.write_header(header)
+    }
+
+    /// Returns the attribute keys of the measurements that are not in the header yet, sorted.
+    fn new_attribute_keys(&self, measurements: &MeasurementBuffer) -> Vec<String> {
+        let known: HashSet<&str> = self.writer.header().iter().map(|c| c.as_str()).collect();
+        let mut new_keys: Vec<String> = collect_attribute_keys(measurements)
+            .into_iter()
+            .filter(|k| !known.contains(k.as_str()))
+            .collect();
+        new_keys.sort();
+        new_keys
+    }
+
+    /// Closes the current file and replaces it by a new one.
+    fn rotate(&mut self, now: SystemTime) -> anyhow::Result<()> {
+        let rotation = self.rotation.as_mut().expect("rotation should be enabled");
+        let path = rotation.path().to_owned();
+        self.writer.flush()?;
+        rotation.rotate(now)?;
+        let file = File::create(&path).with_context(|| format!("failed to open file for writing {path:?}"))?;
+        drop(self.writer.replace_file(file)?);
+        Ok(())
+    }
+
+    /// Writes an extended header if new attributes have appeared, according to the header strategy.
+    fn extend_header(&mut self, measurements: &MeasurementBuffer) -> anyhow::Result<()> {
+        let new_keys = self.new_attribute_keys(measurements);
+        if new_keys.is_empty() {
+            return Ok(());
+        }
+        log::debug!("new attributes {new_keys:?}, extending the csv header");
+
+        // Keep the previous attribute columns in the same order, and add the new ones at the end.
+        let previous: Vec<String> = self.writer.header()[BASE_COLUMNS.len()..].to_vec();
+        self.writer.flush()?;
+        self.writer.clear_header();
+        if self.header_strategy == HeaderStrategy::NewFile {
+            self.rotate(SystemTime::now())?;
+        }
+        let attributes = previous.iter().chain(new_keys.iter()).map(|k| k.as_str()).collect();
+        self.write_header(attributes)
+    }
 }
 
+/// Returns the columns of the attributes that are present in the measurements (see [`attribute_column`]).
 fn collect_attribute_keys(buf: &MeasurementBuffer) -> HashSet<String> {
     let mut res = HashSet::new();
     for m in buf.iter() {
-        res.extend(m.attributes_keys().map(|k| k.to_owned()));
+        res.extend(m.attributes_keys().map(attribute_column));
     }
     res
 }
 
+/// Returns the name of the column of an attribute.
+///
+/// The attributes whose key is the name of a base column are prefixed with `attr_`,
+/// so that they don't replace the base columns.
+fn attribute_column(key: &str) -> String {
+    if BASE_COLUMNS.contains(&key) {
+        format!("attr_{key}")
+    } else {
+        key.to_owned()
+    }
+}
+
 impl Output for CsvOutput {
     fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
         log::trace!("writing csv measurements {measurements:?}");
+        if let Some(rotation) = &self.rotation {
+            let now = SystemTime::now();
+            if self.writer.is_initialized() && rotation.should_rotate(self.writer.file_size()?, now) {
+                log::trace!("rotating csv file");
+                self.rotate(now)?;
+            }
+        }
         if !self.writer.is_initialized() {
             log::trace!("initializing csv header");
-            // Collect the attributes that are present in the measurements.
-            // Then, sort the keys to ensure a consistent order between calls to `CsvOutput::write`.
-            let attr_keys: HashSet<String> = collect_attribute_keys(measurements).into_iter().collect();
-            let mut attr_sorted: Vec<&str> = attr_keys.iter().map(|k| k.as_str()).collect();
-            attr_sorted.sort();
-
-            let mut header = Vec::with_capacity(8 + attr_sorted.len());
-            header.extend(&[
-                "metric",
-                "timestamp",
-                "value",
-                "resource_kind",
-                "resource_id",
-                "consumer_kind",
-                "consumer_id",
-            ]);
-            header.extend(attr_sorted);
-            let header = header.into_iter().map(String::from).collect();
-            log::trace!("writing header {header:?}");
-            self.writer.write_header(header)?;
+            match self.header_strategy {
+                HeaderStrategy::FirstBuffer | HeaderStrategy::NewFile | HeaderStrategy::NewSection => {
+                    // Collect the attributes that are present in the measurements.
+                    // Then, sort the keys to ensure a consistent order between calls to `CsvOutput::write`.
+                    let attr_keys = collect_attribute_keys(measurements);
+                    let mut attr_sorted: Vec<&str> = attr_keys.iter().map(|k| k.as_str()).collect();
+                    attr_sorted.sort();
+                    self.write_header(attr_sorted)?;
+                }
+                HeaderStrategy::Fixed => {
+                    let columns: Vec<String> = self.attribute_columns.iter().map(|k| attribute_column(k)).collect();
+                    self.write_header(columns.iter().map(|k| k.as_str()).collect())?;
+                }
+                HeaderStrategy::Long => {
+                    self.write_header(Vec::new())?;
+                }
+            }
+        } else if matches!(
+            self.header_strategy,
+            HeaderStrategy::NewFile | HeaderStrategy::NewSection
+        ) {
+            self.extend_header(measurements)?;
         }
 
         for m in measurements {
@@ -123,7 +253,7 @@ impl Output for CsvOutput {
             data.insert("consumer_id".to_owned(), consumer_id);
 
             for (k, v) in m.attributes() {
-                data.insert(k.to_owned(), v.to_string());
+                data.insert(attribute_column(k), v.to_string());
             }
 
             self.writer.write_line(&mut data)?;
@@ -151,7 +281,7 @@ mod tests {
     };
     use std::{collections::HashSet, time::UNIX_EPOCH};
 
-    use super::{collect_attribute_keys, escape_late_attribute};
+    use super::{attribute_column, collect_attribute_keys, escape_late_attribute};
 
     fn simple_point(metric: RawMetricId, value: WrappedMeasurementValue) -> MeasurementPoint {
         MeasurementPoint::new_untyped(
@@ -193,4 +323,19 @@ mod tests {
         let expected = HashSet::from_iter(["k1".to_string(), "k2".to_string()]);
         assert_eq!(result, expected)
     }
+
+    #[test]
+    fn collect_attribute_keys_base_column() {
+        let metric = RawMetricId::from_u64(0);
+        let point = simple_point(metric, WrappedMeasurementValue::U64(0))
+            .with_attr("metric", "other")
+            .with_attr("k1", 123);
+        let buf = MeasurementBuffer::from_iter([point]);
+
+        let result = collect_attribute_keys(&buf);
+        let expected = HashSet::from_iter(["attr_metric".to_string(), "k1".to_string()]);
+        assert_eq!(result, expected);
+        assert_eq!(attribute_column("timestamp"), "attr_timestamp");
+        assert_eq!(attribute_column("attr_timestamp"), "attr_timestamp");
+    }
 }
@@ -16,7 +16,7 @@ use alumet::{
 };
 use indoc::indoc;
 
-use plugin_csv::{Config, CsvPlugin};
+use plugin_csv::{Config, CsvPlugin, HeaderStrategy};
 use tempfile;
 
 use pretty_assertions::assert_eq;
@@ -266,3 +266,172 @@ fn write_output_config_no_unit_in_metric_name() {
 
     agent.wait_for_shutdown(TIMEOUT).unwrap();
 }
+
+/// Writes two buffers with the given configuration, the second one having a new attribute,
+/// and checks the content of the output file after each of them.
+fn check_header_strategy(config: Config, expected_1: &'static str, check_2: impl Fn(String) + Send + 'static) {
+    let result_file = config.output_path.clone();
+    let result_file_2 = result_file.clone();
+
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<CsvPlugin>(),
+        enabled: true,
+        config: Some(config_to_toml_table(&config)),
+    });
+
+    let output = OutputName::from_str("csv", "out");
+    let runtime_expectations = RuntimeExpectations::new()
+        .create_metric::<u64>("test_metric_u64", Unit::Unity)
+        .create_metric::<f64>("test_metric_f64", Unit::Unity)
+        .test_output(
+            output.clone(),
+            move |ctx| {
+                let metrics = TestMetrics::get(ctx);
+                let point = simple_point(metrics.metric_u64, WrappedMeasurementValue::U64(1)).with_attr("b", "v1");
+                MeasurementBuffer::from(vec![point])
+            },
+            move || {
+                assert_eq!(fs::read_to_string(&result_file).unwrap(), expected_1);
+            },
+        )
+        .test_output(
+            output.clone(),
+            move |ctx| {
+                let metrics = TestMetrics::get(ctx);
+                let point = simple_point(metrics.metric_u64, WrappedMeasurementValue::U64(2))
+                    .with_attr("a", "v2")
+                    .with_attr("b", "v3");
+                MeasurementBuffer::from(vec![point])
+            },
+            move || {
+                check_2(fs::read_to_string(&result_file_2).unwrap());
+            },
+        );
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(runtime_expectations)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn header_strategy_fixed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        header_strategy: HeaderStrategy::Fixed,
+        attribute_columns: vec![String::from("b"), String::from("c")],
+        ..Config::default()
+    };
+    check_header_strategy(
+        config,
+        indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;c;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;v1;;
+            "#
+        },
+        |content| {
+            let expected = indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;c;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;v1;;
+               test_metric_u64;1970-01-01T00:00:00Z;2;local_machine;;local_machine;;v3;;a=v2
+            "#
+            };
+            assert_eq!(content, expected);
+        },
+    );
+}
+
+#[test]
+fn header_strategy_long() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        header_strategy: HeaderStrategy::Long,
+        ..Config::default()
+    };
+    check_header_strategy(
+        config,
+        indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;b=v1
+            "#
+        },
+        |content| {
+            let (first, last) = content.trim_end().rsplit_once('\n').unwrap();
+            assert_eq!(
+                first,
+                "metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;attributes\n\
+                 test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;b=v1"
+            );
+            let (columns, attributes) = last.rsplit_once(';').unwrap();
+            assert_eq!(
+                columns,
+                "test_metric_u64;1970-01-01T00:00:00Z;2;local_machine;;local_machine;"
+            );
+            let mut attributes: Vec<&str> = attributes.split(',').collect();
+            attributes.sort();
+            assert_eq!(attributes, vec!["a=v2", "b=v3"]);
+        },
+    );
+}
+
+#[test]
+fn header_strategy_new_section() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        header_strategy: HeaderStrategy::NewSection,
+        ..Config::default()
+    };
+    check_header_strategy(
+        config,
+        indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;v1;
+            "#
+        },
+        |content| {
+            let expected = indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;v1;
+               metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;a;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;2;local_machine;;local_machine;;v3;v2;
+            "#
+            };
+            assert_eq!(content, expected);
+        },
+    );
+}
+
+#[test]
+fn header_strategy_new_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        header_strategy: HeaderStrategy::NewFile,
+        ..Config::default()
+    };
+    check_header_strategy(
+        config,
+        indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;1;local_machine;;local_machine;;v1;
+            "#
+        },
+        |content| {
+            let expected = indoc! {
+            r#"metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;b;a;__late_attributes
+               test_metric_u64;1970-01-01T00:00:00Z;2;local_machine;;local_machine;;v3;v2;
+            "#
+            };
+            assert_eq!(content, expected);
+        },
+    );
+    // the first file has been rotated
+    let n_files = fs::read_dir(tmp.path()).unwrap().count();
+    assert_eq!(n_files, 2);
+}
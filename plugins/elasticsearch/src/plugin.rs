@@ -6,7 +6,7 @@ use alumet::{
     },
     plugin::{
         AlumetPluginStart, ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 use anyhow::Context;
@@ -33,7 +33,7 @@ impl AlumetPlugin for ElasticSearchPlugin {
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         let default = config::Config::default();
-        Ok(Some(serialize_config(default)?))
+        Ok(Some(serialize_config_with_schema(default)?))
     }
 
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
@@ -97,12 +97,13 @@ impl Output for ElasticSearchOutput {
 pub mod config {
     use std::path::PathBuf;
 
+    use alumet::agent::schema::JsonSchema;
     use anyhow::Context;
     use serde::{Deserialize, Serialize};
 
     use crate::api;
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Serialize, Deserialize, JsonSchema)]
     pub struct Config {
         /// The url of the database instance.
         pub server_url: String,
@@ -117,7 +118,7 @@ pub mod config {
         pub metric_unit_as_index_suffix: bool,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Serialize, Deserialize, JsonSchema)]
     #[serde(rename_all = "snake_case")]
     pub enum AuthConfig {
         ApiKey { key: String },
@@ -1,8 +1,9 @@
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::{RawMetricId, TypedMetricId},
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::Unit,
 };
@@ -42,7 +43,7 @@ impl AlumetPlugin for EnergyEstimationTdpPlugin {
 
     // We use the default config by default and on initialization.
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -86,9 +87,10 @@ impl AlumetPlugin for EnergyEstimationTdpPlugin {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 struct Config {
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     poll_interval: Duration,
     tdp: f64,
     nb_vcpu: f64,
@@ -1,10 +1,11 @@
 use super::EmissionIntensityProvider;
 
+use alumet::agent::schema::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 /// Configuration for the country-based emission intensity provider.
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct CountryConfig {
     /// Country code in ISO 3166-1 alpha-3 format (e.g. `"FRA"`, `"DEU"`).
     code: String,
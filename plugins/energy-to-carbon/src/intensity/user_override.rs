@@ -1,8 +1,9 @@
 use super::EmissionIntensityProvider;
+use alumet::agent::schema::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Configuration for the user-override emission intensity provider.
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct OverrideConfig {
     /// Override the emission intensity value (in gCO₂/kWh).
     intensity: f64,
@@ -2,10 +2,11 @@ mod intensity;
 mod transform;
 
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::def::MetricId,
     plugin::{
         AlumetPluginStart, ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::Unit,
 };
@@ -23,7 +24,7 @@ pub struct EnergyToCarbonPlugin {
 }
 
 /// Determines which emission intensity provider to use.
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 enum Mode {
     /// Use a fixed intensity value supplied by the user.
@@ -34,7 +35,7 @@ enum Mode {
     WorldAvg,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(default)]
 struct Config {
     mode: Mode,
@@ -64,7 +65,7 @@ impl AlumetPlugin for EnergyToCarbonPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
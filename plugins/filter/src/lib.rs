@@ -1,12 +1,15 @@
+use alumet::agent::schema::JsonSchema;
 use alumet::plugin::{
     ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
+use rule::{Action, Rule, RuleConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use transform::FilterTransform;
 
+mod rule;
 mod transform;
 
 pub struct FilterPlugin {
@@ -23,7 +26,7 @@ impl AlumetPlugin for FilterPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -33,16 +36,27 @@ impl AlumetPlugin for FilterPlugin {
                 "filter transform cannot have both include and exclude configuration parameters defined"
             ));
         }
+        if (config.include.is_some() || config.exclude.is_some()) && !config.rules.is_empty() {
+            return Err(anyhow::anyhow!(
+                "filter transform cannot have both include/exclude and rules configuration parameters defined"
+            ));
+        }
+        // check the patterns as early as possible
+        for (i, rule) in config.rules.iter().enumerate() {
+            Rule::try_from(rule.clone()).with_context(|| format!("invalid filter rule #{i}"))?;
+        }
         Ok(Box::new(FilterPlugin { config }))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let include = self.config.include.clone();
         let exclude = self.config.exclude.clone();
+        let rules = self.config.rules.clone();
+        let default_action = self.config.default_action;
 
-        if include.is_none() && exclude.is_none() {
+        if include.is_none() && exclude.is_none() && rules.is_empty() {
             log::warn!(
-                "filter plugin was started but as there's neither 'include' or 'exclude' configuration set, this will do nothing"
+                "filter plugin was started but as there's neither 'include', 'exclude' or 'rules' configuration set, this will do nothing"
             );
             return Ok(());
         }
@@ -76,8 +90,18 @@ impl AlumetPlugin for FilterPlugin {
                 None
             };
 
-            let transform = Box::new(FilterTransform::new(include_metrics_ids, exclude_metrics_ids)?);
-            Ok(transform)
+            let transform = match (include_metrics_ids, exclude_metrics_ids) {
+                (Some(ids), _) => FilterTransform::new(vec![Rule::for_metric_ids(Action::Keep, ids)], Action::Drop),
+                (_, Some(ids)) => FilterTransform::new(vec![Rule::for_metric_ids(Action::Drop, ids)], Action::Keep),
+                (None, None) => {
+                    let rules = rules
+                        .iter()
+                        .map(|rule| Rule::try_from(rule.clone()))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    FilterTransform::new(rules, default_action)
+                }
+            };
+            Ok(Box::new(transform))
         })?;
         Ok(())
     }
@@ -87,8 +111,14 @@ impl AlumetPlugin for FilterPlugin {
     }
 }
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     include: Option<Vec<String>>,
     exclude: Option<Vec<String>>,
+    /// Ordered list of rules. The first rule that matches a point decides what to do with it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<RuleConfig>,
+    /// What to do with the points that match no rule.
+    #[serde(default)]
+    default_action: Action,
 }
@@ -0,0 +1,226 @@
+use alumet::{
+    agent::schema::JsonSchema,
+    measurement::MeasurementPoint,
+    metrics::{RawMetricId, registry::MetricRegistry},
+};
+use anyhow::Context;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// What to do with the measurement points that match a rule.
+#[derive(Default, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    #[default]
+    Keep,
+    Drop,
+}
+
+/// A filtering rule, as written in the configuration.
+///
+/// Every pattern is optional. A point matches the rule if it is accepted by all the patterns.
+/// Unless stated otherwise, the patterns are globs: `*` matches any sequence of characters
+/// and `?` matches exactly one character.
+#[derive(Default, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Keep or drop the matching points.
+    #[serde(default)]
+    pub action: Action,
+    /// Pattern on the metric name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric: Option<String>,
+    /// Regular expression on the metric name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric_regex: Option<String>,
+    /// Pattern on the kind of the resource, e.g. `cpu_package`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_kind: Option<String>,
+    /// Pattern on the id of the resource. The resources that have no id are matched by `""`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_id: Option<String>,
+    /// Pattern on the kind of the consumer, e.g. `process`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_kind: Option<String>,
+    /// Pattern on the id of the consumer. The consumers that have no id are matched by `""`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_id: Option<String>,
+    /// Patterns on the attributes: the point must have each attribute, with a matching value.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+    /// Attributes to remove from the kept points.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub strip_attributes: Vec<String>,
+    /// Attributes to rename in the kept points (old key → new key).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rename_attributes: BTreeMap<String, String>,
+}
+
+/// Matches some metrics.
+enum MetricMatcher {
+    /// Matches a fixed set of metrics.
+    Ids(HashSet<RawMetricId>),
+    /// Matches the metrics whose name is accepted by the regex.
+    Name {
+        regex: Regex,
+        /// Cache of the results, by metric id. Metric ids are never reused, therefore the cache is always valid.
+        cache: HashMap<RawMetricId, bool>,
+    },
+}
+
+/// A filtering rule, ready to be applied.
+pub struct Rule {
+    pub action: Action,
+    metric: Vec<MetricMatcher>,
+    resource_kind: Option<Regex>,
+    resource_id: Option<Regex>,
+    consumer_kind: Option<Regex>,
+    consumer_id: Option<Regex>,
+    attributes: Vec<(String, Regex)>,
+    strip_attributes: Vec<String>,
+    rename_attributes: Vec<(String, String)>,
+}
+
+impl Rule {
+    /// Creates a rule that matches a fixed set of metrics.
+    pub fn for_metric_ids(action: Action, ids: HashSet<RawMetricId>) -> Self {
+        Self {
+            action,
+            metric: vec![MetricMatcher::Ids(ids)],
+            resource_kind: None,
+            resource_id: None,
+            consumer_kind: None,
+            consumer_id: None,
+            attributes: Vec::new(),
+            strip_attributes: Vec::new(),
+            rename_attributes: Vec::new(),
+        }
+    }
+
+    /// Checks whether the point matches the rule.
+    pub fn matches(&mut self, point: &MeasurementPoint, metrics: &MetricRegistry) -> bool {
+        fn is_match(pattern: &Option<Regex>, value: impl FnOnce() -> String) -> bool {
+            match pattern {
+                Some(regex) => regex.is_match(&value()),
+                None => true,
+            }
+        }
+
+        let metric_ok = self.metric.iter_mut().all(|m| match m {
+            MetricMatcher::Ids(ids) => ids.contains(&point.metric),
+            MetricMatcher::Name { regex, cache } => *cache.entry(point.metric).or_insert_with(|| {
+                metrics
+                    .by_id(&point.metric)
+                    .is_some_and(|metric| regex.is_match(&metric.name))
+            }),
+        });
+        metric_ok
+            && is_match(&self.resource_kind, || point.resource.kind().to_owned())
+            && is_match(&self.resource_id, || point.resource.id_string().unwrap_or_default())
+            && is_match(&self.consumer_kind, || point.consumer.kind().to_owned())
+            && is_match(&self.consumer_id, || point.consumer.id_string().unwrap_or_default())
+            && self.attributes.iter().all(|(key, regex)| {
+                point
+                    .attributes()
+                    .any(|(k, v)| k == key && regex.is_match(&v.to_string()))
+            })
+    }
+
+    /// Modifies the attributes of a point that has been kept by the rule.
+    pub fn rewrite_attributes(&self, point: &mut MeasurementPoint) {
+        for key in &self.strip_attributes {
+            point.remove_attr(key);
+        }
+        for (key, new_key) in &self.rename_attributes {
+            point.rename_attr(key, new_key.clone());
+        }
+    }
+}
+
+impl TryFrom<RuleConfig> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(config: RuleConfig) -> Result<Self, Self::Error> {
+        fn glob(pattern: Option<String>, what: &str) -> anyhow::Result<Option<Regex>> {
+            pattern
+                .map(|p| glob_to_regex(&p).with_context(|| format!("invalid {what} pattern: {p}")))
+                .transpose()
+        }
+
+        let mut metric = Vec::new();
+        if let Some(pattern) = config.metric {
+            let regex = glob_to_regex(&pattern).with_context(|| format!("invalid metric pattern: {pattern}"))?;
+            metric.push(MetricMatcher::Name {
+                regex,
+                cache: HashMap::new(),
+            });
+        }
+        if let Some(pattern) = config.metric_regex {
+            let regex = Regex::new(&pattern).with_context(|| format!("invalid metric regex: {pattern}"))?;
+            metric.push(MetricMatcher::Name {
+                regex,
+                cache: HashMap::new(),
+            });
+        }
+        let attributes = config
+            .attributes
+            .into_iter()
+            .map(|(key, pattern)| {
+                let regex = glob_to_regex(&pattern)
+                    .with_context(|| format!("invalid pattern for attribute {key}: {pattern}"))?;
+                Ok((key, regex))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            action: config.action,
+            metric,
+            resource_kind: glob(config.resource_kind, "resource_kind")?,
+            resource_id: glob(config.resource_id, "resource_id")?,
+            consumer_kind: glob(config.consumer_kind, "consumer_kind")?,
+            consumer_id: glob(config.consumer_id, "consumer_id")?,
+            attributes,
+            strip_attributes: config.strip_attributes,
+            rename_attributes: config.rename_attributes.into_iter().collect(),
+        })
+    }
+}
+
+/// Turns a glob pattern (`*` and `?` wildcards) into an anchored regular expression.
+fn glob_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut res = String::with_capacity(pattern.len() + 8);
+    res.push('^');
+    let mut literal = [0u8; 4];
+    for c in pattern.chars() {
+        match c {
+            '*' => res.push_str(".*"),
+            '?' => res.push('.'),
+            c => res.push_str(&regex::escape(c.encode_utf8(&mut literal))),
+        }
+    }
+    res.push('$');
+    Regex::new(&res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_to_regex;
+
+    #[test]
+    fn glob() {
+        let re = glob_to_regex("rapl_*").unwrap();
+        assert!(re.is_match("rapl_consumed_energy"));
+        assert!(re.is_match("rapl_"));
+        assert!(!re.is_match("cpu_rapl_energy"));
+
+        let re = glob_to_regex("cpu.?").unwrap();
+        assert!(re.is_match("cpu.0"));
+        assert!(!re.is_match("cpux0"));
+        assert!(!re.is_match("cpu.10"));
+
+        let re = glob_to_regex("").unwrap();
+        assert!(re.is_match(""));
+        assert!(!re.is_match("a"));
+    }
+}
@@ -1,53 +1,51 @@
 use alumet::{
     measurement::MeasurementBuffer,
-    metrics::RawMetricId,
     pipeline::{
         Transform,
         elements::{error::TransformError, transform::TransformContext},
     },
 };
-use std::collections::HashSet;
 
-enum FilterMode {
-    Include(HashSet<RawMetricId>),
-    Exclude(HashSet<RawMetricId>),
-}
+use crate::rule::{Action, Rule};
 
+/// Filters the measurements with an ordered list of rules.
+///
+/// The first rule that matches a point decides whether it is kept or dropped.
+/// The points that match no rule are subject to the default action.
 pub struct FilterTransform {
-    mode: FilterMode,
+    rules: Vec<Rule>,
+    default_action: Action,
 }
 
 impl FilterTransform {
-    pub fn new(include: Option<HashSet<RawMetricId>>, exclude: Option<HashSet<RawMetricId>>) -> anyhow::Result<Self> {
-        let mode = match (include, exclude) {
-            (Some(ids), None) => FilterMode::Include(ids),
-            (None, Some(ids)) => FilterMode::Exclude(ids),
-            (None, None) => {
-                // shouldn't happen as it's validated at plugin init stage
-                unreachable!("filter transform cannot have both include and exclude empty");
-            }
-            (Some(_), Some(_)) => {
-                // shouldn't happen as it's validated at plugin init stage
-                unreachable!("filter transform cannot have both include and exclude set");
-            }
-        };
-
-        Ok(Self { mode })
+    pub fn new(rules: Vec<Rule>, default_action: Action) -> Self {
+        Self { rules, default_action }
     }
 }
 
 impl Transform for FilterTransform {
-    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
-        match &self.mode {
-            FilterMode::Include(set) => {
-                measurements.retain(|p| set.contains(&p.metric));
-            }
-
-            FilterMode::Exclude(set) => {
-                measurements.retain(|p| !set.contains(&p.metric));
-            }
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, ctx: &TransformContext) -> Result<(), TransformError> {
+        // Decide which points to keep, and rewrite their attributes if needed.
+        let mut keep = Vec::with_capacity(measurements.len());
+        for point in measurements.iter_mut() {
+            let decision = match self
+                .rules
+                .iter_mut()
+                .find_map(|rule| rule.matches(point, ctx.metrics).then_some(rule))
+            {
+                Some(rule) if rule.action == Action::Keep => {
+                    rule.rewrite_attributes(point);
+                    true
+                }
+                Some(_) => false,
+                None => self.default_action == Action::Keep,
+            };
+            keep.push(decision);
         }
 
+        // retain visits the points in order, exactly once
+        let mut decisions = keep.into_iter();
+        measurements.retain(|_| decisions.next().unwrap_or(true));
         Ok(())
     }
 }
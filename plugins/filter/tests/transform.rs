@@ -8,7 +8,7 @@ use alumet::{
     measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
     metrics::{RawMetricId, registry::MetricRegistry},
     pipeline::naming::TransformName,
-    plugin::PluginMetadata,
+    plugin::{ConfigTable, PluginMetadata},
     resources::{Resource, ResourceConsumer},
     test::RuntimeExpectations,
     units::Unit,
@@ -104,6 +104,95 @@ fn test_filter_exclude() {
     run_agent(runtime, CONFIG_EXCLUDE);
 }
 
+const CONFIG_RULES: &str = r#"
+default_action = "drop"
+
+[[rules]]
+action = "drop"
+metric = "metric_*"
+consumer_kind = "process"
+consumer_id = "666"
+
+[[rules]]
+action = "keep"
+metric_regex = "^metric_(a|b)$"
+attributes = { domain = "pack*" }
+strip_attributes = ["pid"]
+rename_attributes = { domain = "rapl_domain" }
+"#;
+
+#[test]
+fn test_filter_rules() {
+    let transform_name = TransformName::from_str("filter", "transform");
+    let ts1 = Timestamp::now();
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("metric_a", Unit::Unity)
+        .create_metric::<u64>("metric_b", Unit::Unity)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let metrics = TestMetrics::find_in(input.metrics());
+                let mut buf = MeasurementBuffer::new();
+
+                let process = |pid| ResourceConsumer::Process { pid };
+                // dropped by the first rule
+                buf.push(
+                    with_consumer(new_point(&metrics, metrics.metric_a, ts1, 1), process(666))
+                        .with_attr("domain", "package"),
+                );
+                // kept by the second rule
+                buf.push(
+                    with_consumer(new_point(&metrics, metrics.metric_a, ts1, 2), process(1))
+                        .with_attr("domain", "package")
+                        .with_attr("pid", 1_u64),
+                );
+                buf.push(new_point(&metrics, metrics.metric_b, ts1, 3).with_attr("domain", "package-0"));
+                // no matching rule: dropped by default
+                buf.push(new_point(&metrics, metrics.metric_b, ts1, 4).with_attr("domain", "dram"));
+                buf.push(new_point(&metrics, metrics.metric_b, ts1, 5));
+
+                buf
+            },
+            move |output| {
+                let metrics = TestMetrics::find_in(output.metrics());
+                let m = output.measurements().to_vec();
+
+                assert_eq!(
+                    m,
+                    vec![
+                        with_consumer(
+                            new_point(&metrics, metrics.metric_a, ts1, 2),
+                            ResourceConsumer::Process { pid: 1 }
+                        )
+                        .with_attr("rapl_domain", "package"),
+                        new_point(&metrics, metrics.metric_b, ts1, 3).with_attr("rapl_domain", "package-0"),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG_RULES);
+}
+
+#[test]
+fn test_filter_invalid_rule() {
+    let config = toml::from_str(
+        r#"
+        [[rules]]
+        metric_regex = "metric_("
+        "#,
+    )
+    .unwrap();
+    let res = <FilterPlugin as alumet::plugin::rust::AlumetPlugin>::init(ConfigTable(config));
+    assert!(res.is_err());
+}
+
+fn with_consumer(mut point: MeasurementPoint, consumer: ResourceConsumer) -> MeasurementPoint {
+    point.consumer = consumer;
+    point
+}
+
 fn run_agent(runtime: RuntimeExpectations, config: &str) {
     let mut plugins = PluginSet::new();
     plugins.add_plugin(PluginInfo {
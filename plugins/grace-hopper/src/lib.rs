@@ -6,11 +6,12 @@ use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, time::Duration};
 
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::TypedMetricId,
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         AlumetPluginStart, ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::{PrefixedUnit, Unit},
 };
@@ -37,7 +38,7 @@ impl AlumetPlugin for GraceHopperPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -64,11 +65,12 @@ impl AlumetPlugin for GraceHopperPlugin {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Initial interval between two measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
 
     /// Path to check hwmon.
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use alumet::{
+    agent::schema::JsonSchema,
     measurement::{AttributeValue, MeasurementBuffer, WrappedMeasurementValue},
     pipeline::{
         Output,
@@ -9,7 +10,7 @@ use alumet::{
             output::{OutputContext, error::WriteRetry},
         },
     },
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
@@ -32,7 +33,7 @@ impl AlumetPlugin for InfluxDbPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -206,7 +207,7 @@ fn ensure_valid_field_key<'a>(reserved_field: &'a str, field_key: &'a str) -> &'
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Address of the host where InfluxDB is running
@@ -226,7 +227,7 @@ pub struct Config {
 }
 
 /// How to serialize Alumet attributes by default?
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AttributeAs {
     /// Serialize attributes as InfluxDB tags, except if their key
@@ -1,6 +1,7 @@
 // This file contains the main implementation of the Kwollect input plugin for Alumet.
 
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::TypedMetricId,
     pipeline::{
         control::{matching::SourceMatcher, request},
@@ -10,7 +11,7 @@ use alumet::{
     plugin::{
         AlumetPluginStart, AlumetPostStart, ConfigTable,
         event::{self},
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::{PrefixedUnit, Unit, UnitPrefix},
 };
@@ -46,7 +47,7 @@ impl AlumetPlugin for KwollectPluginInput {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -295,7 +296,7 @@ fn fetch_data(url: &str, config: &Config) -> Result<Value, anyhow::Error> {
 }
 
 /// A structure that stores the configuration parameters necessary to interact with the Grid'5000 API (to build the request)
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct Config {
     pub site: String,
     pub hostname: String,
@@ -1,7 +1,8 @@
 mod kwollect;
 mod output;
 
-use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::agent::schema::JsonSchema;
+use alumet::plugin::rust::{deserialize_config, serialize_config_with_schema};
 use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
 use serde::{Deserialize, Serialize};
 
@@ -21,7 +22,7 @@ impl AlumetPlugin for KwollectPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -48,7 +49,7 @@ impl AlumetPlugin for KwollectPlugin {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     pub url: String,
     pub login: Option<String>,
@@ -1,10 +1,11 @@
 use alumet::{
+    agent::schema::JsonSchema,
     measurement::{AttributeValue, MeasurementBuffer, WrappedMeasurementValue},
     pipeline::{
         Output,
         elements::{error::WriteError, output::OutputContext},
     },
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 
 use mongodb::{
@@ -30,7 +31,7 @@ impl AlumetPlugin for MongoDbPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -170,7 +171,7 @@ fn u64_to_bson(v: u64) -> String {
     format!("{v}u")
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     host: String,
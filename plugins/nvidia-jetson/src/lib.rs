@@ -6,10 +6,11 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 
@@ -45,7 +46,7 @@ impl AlumetPlugin for JetsonPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -100,15 +101,17 @@ impl AlumetPlugin for JetsonPlugin {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     /// Initial interval between two measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     poll_interval: Duration,
 
     /// Initial interval between two measurement flushes.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     flush_interval: Duration,
 
     #[cfg(test)]
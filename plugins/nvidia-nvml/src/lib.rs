@@ -3,10 +3,11 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 
@@ -43,7 +44,7 @@ impl<P: NvmlProvider + 'static> AlumetPlugin for NvmlPlugin<P> {
 
     #[cfg(not(tarpaulin_include))]
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -114,15 +115,17 @@ impl<P: NvmlProvider + 'static> AlumetPlugin for NvmlPlugin<P> {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     /// Initial interval between two Nvidia measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     poll_interval: Duration,
 
     /// Initial interval between two flushing of Nvidia measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     flush_interval: Duration,
 
     /// On startup, the plugin inspects the GPU devices and detect their features.
@@ -138,7 +141,7 @@ struct Config {
     mode: Mode,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 enum Mode {
     /// Gathers many NVML metrics.
@@ -173,7 +176,7 @@ mod tests {
         agent::plugin::{PluginInfo, PluginSet},
         measurement::{AttributeValue, MeasurementPoint},
         pipeline::naming::SourceName,
-        plugin::PluginMetadata,
+        plugin::{PluginMetadata, rust::serialize_config},
         resources::ResourceConsumer,
         test::{RuntimeExpectations, StartupExpectations, runtime::SourceCheckOutputContext},
         units::{PrefixedUnit, Unit},
@@ -1,6 +1,7 @@
 mod output;
 
-use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::agent::schema::JsonSchema;
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema};
 use output::OpenTelemetryOutput;
 use serde::{Deserialize, Serialize};
 
@@ -18,7 +19,7 @@ impl AlumetPlugin for OpenTelemetryPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -44,7 +45,7 @@ impl AlumetPlugin for OpenTelemetryPlugin {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub collector_host: String,
@@ -0,0 +1,107 @@
+mod output;
+mod schema;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::agent::schema::JsonSchema;
+use alumet::plugin::{
+    ConfigTable,
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
+};
+use serde::{Deserialize, Serialize};
+
+use output::{ColumnarOutput, ColumnarOutputSettings};
+pub use output::{Compression, FileFormat};
+pub use util_file_rotation::RotationConfig;
+
+pub struct ParquetPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for ParquetPlugin {
+    fn name() -> &'static str {
+        "parquet"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config_with_schema(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.row_group_size == 0 {
+            return Err(anyhow::anyhow!("row_group_size must be greater than zero"));
+        }
+        schema::check_attribute_columns(&config.attribute_columns)?;
+        if let Some(rotation) = &config.rotation
+            && rotation.compression != util_file_rotation::Compression::None
+        {
+            return Err(anyhow::anyhow!(
+                "rotation.compression is not supported, the files are compressed according to `compression`"
+            ));
+        }
+        if config.format == FileFormat::ArrowIpc && config.compression != Compression::None {
+            log::warn!("compression is not supported with the Arrow IPC format, it will be ignored");
+        }
+        Ok(Box::new(ParquetPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let settings = ColumnarOutputSettings {
+            format: self.config.format,
+            compression: self.config.compression,
+            attribute_columns: self.config.attribute_columns.clone(),
+            row_group_size: self.config.row_group_size,
+            flush_interval: self.config.flush_interval,
+            rotation: self.config.rotation.clone(),
+        };
+        let output = Box::new(ColumnarOutput::new(&self.config.output_path, settings)?);
+        alumet.add_blocking_output("out", output)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Absolute or relative path to the output file.
+    pub output_path: PathBuf,
+    /// Format of the output file.
+    pub format: FileFormat,
+    /// Compression of the data, only for the Parquet format.
+    pub compression: Compression,
+    /// Attributes that get their own column. The other attributes are stored in the `attributes` map column.
+    #[serde(default)]
+    pub attribute_columns: Vec<String>,
+    /// Maximum number of rows in a row group (Parquet) or record batch (Arrow IPC).
+    pub row_group_size: usize,
+    /// Maximum time to keep the rows in memory before writing them.
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub flush_interval: Duration,
+    /// Rotation of the output file. If unset, the file is never rotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<RotationConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_path: PathBuf::from("alumet-output.parquet"),
+            format: FileFormat::Parquet,
+            compression: Compression::Zstd,
+            attribute_columns: Vec::new(),
+            row_group_size: 100_000,
+            flush_interval: Duration::from_secs(10),
+            rotation: None,
+        }
+    }
+}
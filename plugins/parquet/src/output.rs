@@ -0,0 +1,246 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use alumet::{
+    agent::schema::JsonSchema,
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Output,
+        elements::{error::WriteError, output::OutputContext},
+    },
+};
+use anyhow::Context;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use parquet::{arrow::ArrowWriter, basic::ZstdLevel, file::properties::WriterProperties};
+use serde::{Deserialize, Serialize};
+
+use util_file_rotation::{Rotation, RotationConfig};
+
+use crate::schema::RowBuilder;
+
+/// Format of the output file.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    /// Apache Parquet.
+    Parquet,
+    /// Arrow IPC file format, also known as Feather V2.
+    ArrowIpc,
+}
+
+/// Compression of the Parquet files.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+pub struct ColumnarOutputSettings {
+    pub format: FileFormat,
+    pub compression: Compression,
+    pub attribute_columns: Vec<String>,
+    pub row_group_size: usize,
+    pub flush_interval: Duration,
+    pub rotation: Option<RotationConfig>,
+}
+
+/// Writes the measurements to Parquet or Arrow IPC files.
+///
+/// The rows are accumulated in memory, and written as a row group (or record batch)
+/// when there are enough of them, or when they have been kept for too long.
+pub struct ColumnarOutput {
+    path: PathBuf,
+    format: FileFormat,
+    compression: Compression,
+    row_group_size: usize,
+    flush_interval: Duration,
+
+    /// Rows that have not been written yet.
+    rows: RowBuilder,
+    /// When the rows have been written for the last time.
+    last_flush: Instant,
+    /// Writer of the current file. It is `None` after a failed rotation.
+    writer: Option<FileWriter>,
+    /// Rotation of the output file, if enabled.
+    rotation: Option<Rotation>,
+}
+
+impl ColumnarOutput {
+    pub fn new(output_file: impl AsRef<Path>, settings: ColumnarOutputSettings) -> anyhow::Result<Self> {
+        let path = output_file.as_ref().to_owned();
+        let rows = RowBuilder::new(settings.attribute_columns);
+        let writer = FileWriter::create(
+            &path,
+            settings.format,
+            settings.compression,
+            settings.row_group_size,
+            rows.schema(),
+        )?;
+        let rotation = settings.rotation.map(|config| Rotation::new(config, path.clone()));
+        Ok(Self {
+            path,
+            format: settings.format,
+            compression: settings.compression,
+            row_group_size: settings.row_group_size,
+            flush_interval: settings.flush_interval,
+            rows,
+            last_flush: Instant::now(),
+            writer: Some(writer),
+            rotation,
+        })
+    }
+
+    /// Writes the accumulated rows to the current file.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.last_flush = Instant::now();
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        log::trace!("writing {} rows", self.rows.len());
+        let batch = self.rows.finish()?;
+        let writer = self.writer.as_mut().context("no output file")?;
+        writer.write(&batch)
+    }
+
+    /// Closes the current file, moves it and opens a new one.
+    fn rotate(&mut self, now: SystemTime) -> anyhow::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        if let Some(rotation) = &mut self.rotation {
+            rotation.rotate(now)?;
+        }
+        let writer = FileWriter::create(
+            &self.path,
+            self.format,
+            self.compression,
+            self.row_group_size,
+            self.rows.schema(),
+        )?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+}
+
+impl Output for ColumnarOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        for m in measurements {
+            match ctx.metrics.by_id(&m.metric) {
+                Some(metric) => self.rows.append(m, metric)?,
+                None => log::warn!("skipping a measurement of unknown metric {:?}", m.metric),
+            }
+        }
+
+        if self.rows.len() >= self.row_group_size || self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+
+            // Only check the rotation after a flush, because the size of the file does not change otherwise.
+            let now = SystemTime::now();
+            let must_rotate = match (&self.rotation, &self.writer) {
+                (Some(rotation), Some(writer)) => rotation.should_rotate(writer.size()?, now),
+                (_, None) => true,
+                (None, Some(_)) => false,
+            };
+            if must_rotate {
+                log::trace!("rotating output file");
+                self.rotate(now)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ColumnarOutput {
+    fn drop(&mut self) {
+        // Write the remaining rows and the footer, otherwise the file cannot be read.
+        let res = self.flush().and_then(|_| match self.writer.take() {
+            Some(writer) => writer.close(),
+            None => Ok(()),
+        });
+        if let Err(e) = res {
+            log::error!("failed to close the output file {:?}: {e:?}", self.path);
+        }
+    }
+}
+
+/// Writes record batches to a file.
+enum FileWriter {
+    Parquet(ArrowWriter<File>),
+    ArrowIpc(arrow_ipc::writer::FileWriter<BufWriter<File>>),
+}
+
+impl FileWriter {
+    fn create(
+        path: &Path,
+        format: FileFormat,
+        compression: Compression,
+        row_group_size: usize,
+        schema: &SchemaRef,
+    ) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("failed to open file for writing {path:?}"))?;
+        let writer = match format {
+            FileFormat::Parquet => {
+                let compression = match compression {
+                    Compression::None => parquet::basic::Compression::UNCOMPRESSED,
+                    Compression::Snappy => parquet::basic::Compression::SNAPPY,
+                    Compression::Zstd => parquet::basic::Compression::ZSTD(ZstdLevel::default()),
+                };
+                let props = WriterProperties::builder()
+                    .set_compression(compression)
+                    .set_max_row_group_size(row_group_size)
+                    .build();
+                let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+                FileWriter::Parquet(writer)
+            }
+            FileFormat::ArrowIpc => {
+                let writer = arrow_ipc::writer::FileWriter::try_new_buffered(file, schema)?;
+                FileWriter::ArrowIpc(writer)
+            }
+        };
+        Ok(writer)
+    }
+
+    /// Writes a batch, as a new row group (Parquet) or record batch (Arrow IPC).
+    fn write(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        match self {
+            FileWriter::Parquet(w) => {
+                w.write(batch)?;
+                w.flush()?;
+            }
+            FileWriter::ArrowIpc(w) => {
+                w.write(batch)?;
+                w.get_mut().flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of bytes written to the file so far.
+    fn size(&self) -> anyhow::Result<u64> {
+        match self {
+            FileWriter::Parquet(w) => Ok(w.bytes_written() as u64),
+            FileWriter::ArrowIpc(w) => Ok(w.get_ref().get_ref().metadata()?.len()),
+        }
+    }
+
+    /// Writes the footer of the file, and closes it.
+    fn close(self) -> anyhow::Result<()> {
+        match self {
+            FileWriter::Parquet(w) => {
+                w.close()?;
+            }
+            FileWriter::ArrowIpc(mut w) => {
+                w.finish()?;
+                w.into_inner()?.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
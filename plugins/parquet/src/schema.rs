@@ -0,0 +1,196 @@
+//! Arrow schema of the measurements, and conversion of the measurements to Arrow arrays.
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use anyhow::{Context, anyhow};
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{Float64Builder, MapBuilder, StringBuilder, TimestampNanosecondBuilder, UInt64Builder},
+};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+
+const TIMEZONE: &str = "UTC";
+
+/// Returns the schema of the measurements, with one column per attribute of `attribute_columns`.
+///
+/// The value is stored in `value_u64` or `value_f64`, depending on the type of the metric (the other column is null).
+/// The attributes that have no column of their own are stored in the `attributes` column, which is a map.
+pub fn measurement_schema(attribute_columns: &[String]) -> SchemaRef {
+    let mut fields = vec![
+        Field::new("metric", DataType::Utf8, false),
+        Field::new("unit", DataType::Utf8, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some(TIMEZONE.into())),
+            false,
+        ),
+        Field::new("value_u64", DataType::UInt64, true),
+        Field::new("value_f64", DataType::Float64, true),
+        Field::new("resource_kind", DataType::Utf8, false),
+        Field::new("resource_id", DataType::Utf8, true),
+        Field::new("consumer_kind", DataType::Utf8, false),
+        Field::new("consumer_id", DataType::Utf8, true),
+    ];
+    for key in attribute_columns {
+        fields.push(Field::new(key, DataType::Utf8, true));
+    }
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    fields.push(Field::new(
+        "attributes",
+        DataType::Map(Arc::new(Field::new("entries", DataType::Struct(entries), false)), false),
+        false,
+    ));
+    Arc::new(Schema::new(fields))
+}
+
+/// Checks that the attribute columns have distinct names, which are not the names of the other columns.
+pub fn check_attribute_columns(attribute_columns: &[String]) -> anyhow::Result<()> {
+    let schema = measurement_schema(&[]);
+    let mut seen = HashSet::new();
+    for key in attribute_columns {
+        if schema.field_with_name(key).is_ok() {
+            return Err(anyhow!("attribute column \"{key}\" has the same name as a base column"));
+        }
+        if !seen.insert(key) {
+            return Err(anyhow!("attribute column \"{key}\" is listed more than once"));
+        }
+    }
+    Ok(())
+}
+
+/// Accumulates measurement points in Arrow builders, in order to produce [`RecordBatch`]es.
+pub struct RowBuilder {
+    schema: SchemaRef,
+    attribute_columns: Vec<String>,
+
+    metric: StringBuilder,
+    unit: StringBuilder,
+    timestamp: TimestampNanosecondBuilder,
+    value_u64: UInt64Builder,
+    value_f64: Float64Builder,
+    resource_kind: StringBuilder,
+    resource_id: StringBuilder,
+    consumer_kind: StringBuilder,
+    consumer_id: StringBuilder,
+    /// One builder per attribute column, in the same order as `attribute_columns`.
+    attributes: Vec<StringBuilder>,
+    /// The other attributes.
+    other_attributes: MapBuilder<StringBuilder, StringBuilder>,
+
+    len: usize,
+}
+
+impl RowBuilder {
+    pub fn new(attribute_columns: Vec<String>) -> Self {
+        Self {
+            schema: measurement_schema(&attribute_columns),
+            attributes: attribute_columns.iter().map(|_| StringBuilder::new()).collect(),
+            attribute_columns,
+            metric: StringBuilder::new(),
+            unit: StringBuilder::new(),
+            timestamp: TimestampNanosecondBuilder::new().with_timezone(TIMEZONE),
+            value_u64: UInt64Builder::new(),
+            value_f64: Float64Builder::new(),
+            resource_kind: StringBuilder::new(),
+            resource_id: StringBuilder::new(),
+            consumer_kind: StringBuilder::new(),
+            consumer_id: StringBuilder::new(),
+            other_attributes: MapBuilder::new(None, StringBuilder::new(), StringBuilder::new()),
+            len: 0,
+        }
+    }
+
+    pub fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    /// Returns the number of rows that have not been turned into a batch yet.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a row.
+    pub fn append(&mut self, point: &MeasurementPoint, metric: &Metric) -> anyhow::Result<()> {
+        let timestamp = SystemTime::from(point.timestamp)
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| anyhow!("timestamp before the Unix epoch: {:?}", point.timestamp))?;
+        let timestamp = i64::try_from(timestamp.as_nanos()).context("timestamp too far in the future")?;
+
+        self.metric.append_value(&metric.name);
+        self.unit.append_value(metric.unit.unique_name());
+        self.timestamp.append_value(timestamp);
+        match point.value {
+            WrappedMeasurementValue::U64(x) => {
+                self.value_u64.append_value(x);
+                self.value_f64.append_null();
+            }
+            WrappedMeasurementValue::F64(x) => {
+                self.value_u64.append_null();
+                self.value_f64.append_value(x);
+            }
+        }
+        self.resource_kind.append_value(point.resource.kind());
+        self.resource_id.append_option(point.resource.id_string());
+        self.consumer_kind.append_value(point.consumer.kind());
+        self.consumer_id.append_option(point.consumer.id_string());
+
+        let mut has_column = vec![false; self.attribute_columns.len()];
+        for (key, value) in point.attributes() {
+            match self.attribute_columns.iter().position(|k| k == key) {
+                Some(i) if !has_column[i] => {
+                    self.attributes[i].append_value(value.to_string());
+                    has_column[i] = true;
+                }
+                _ => {
+                    self.other_attributes.keys().append_value(key);
+                    self.other_attributes.values().append_value(value.to_string());
+                }
+            }
+        }
+        for (i, present) in has_column.into_iter().enumerate() {
+            if !present {
+                self.attributes[i].append_null();
+            }
+        }
+        self.other_attributes.append(true)?;
+
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Turns the accumulated rows into a batch, and resets the builder.
+    pub fn finish(&mut self) -> anyhow::Result<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.metric.finish()),
+            Arc::new(self.unit.finish()),
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.value_u64.finish()),
+            Arc::new(self.value_f64.finish()),
+            Arc::new(self.resource_kind.finish()),
+            Arc::new(self.resource_id.finish()),
+            Arc::new(self.consumer_kind.finish()),
+            Arc::new(self.consumer_id.finish()),
+        ];
+        for builder in &mut self.attributes {
+            columns.push(Arc::new(builder.finish()));
+        }
+        columns.push(Arc::new(self.other_attributes.finish()));
+        self.len = 0;
+        RecordBatch::try_new(self.schema.clone(), columns).context("invalid record batch")
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::naming::OutputName,
+    plugin::PluginMetadata,
+    resources::{Resource, ResourceConsumer},
+    test::{RuntimeExpectations, runtime::OutputCheckInputContext},
+    units::Unit,
+};
+use arrow_array::{
+    Array, MapArray, RecordBatch, StringArray, TimestampNanosecondArray, UInt64Array, cast::AsArray, types::Float64Type,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use plugin_parquet::{Config, FileFormat, ParquetPlugin, RotationConfig};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn config_to_toml_table(config: &Config) -> toml::Table {
+    toml::Value::try_from(config).unwrap().as_table().unwrap().clone()
+}
+
+fn point(metric: RawMetricId, value: WrappedMeasurementValue) -> MeasurementPoint {
+    MeasurementPoint::new_untyped(
+        Timestamp::from(UNIX_EPOCH + Duration::from_secs(1)),
+        metric,
+        Resource::CpuPackage { id: 0 },
+        ResourceConsumer::LocalMachine,
+        value,
+    )
+}
+
+/// Runs the plugin with the given config, and writes the given number of buffers.
+fn run(config: Config, n_buffers: usize) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<ParquetPlugin>(),
+        enabled: true,
+        config: Some(config_to_toml_table(&config)),
+    });
+
+    let output = OutputName::from_str("parquet", "out");
+    let mut expectations = RuntimeExpectations::new()
+        .create_metric::<u64>("test_metric_u64", Unit::Joule)
+        .create_metric::<f64>("test_metric_f64", Unit::Watt);
+    for _ in 0..n_buffers {
+        expectations = expectations.test_output(
+            output.clone(),
+            |ctx: &mut OutputCheckInputContext| {
+                let metric_u64 = ctx.metrics().by_name("test_metric_u64").unwrap().0;
+                let metric_f64 = ctx.metrics().by_name("test_metric_f64").unwrap().0;
+                MeasurementBuffer::from(vec![
+                    point(metric_u64, WrappedMeasurementValue::U64(12))
+                        .with_attr("domain", "package")
+                        .with_attr("other", 1u64),
+                    point(metric_f64, WrappedMeasurementValue::F64(0.5)),
+                ])
+            },
+            || {},
+        );
+    }
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(expectations)
+        .build_and_start()
+        .unwrap();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+/// Reads a Parquet file, with one batch per row group.
+fn read_parquet(path: &Path) -> Vec<RecordBatch> {
+    let file = File::open(path).unwrap();
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+    let n_row_groups = builder.metadata().num_row_groups();
+    let reader = builder.with_batch_size(2).build().unwrap();
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(batches.len(), n_row_groups);
+    batches
+}
+
+/// Checks the content of a batch written by [`run`], with `domain` as an attribute column.
+fn check_batch(batch: &RecordBatch) {
+    assert_eq!(batch.num_rows(), 2);
+    let strings = |name: &str| batch.column_by_name(name).unwrap().as_string::<i32>().clone();
+
+    assert_eq!(
+        strings("metric"),
+        StringArray::from(vec!["test_metric_u64", "test_metric_f64"])
+    );
+    assert_eq!(strings("unit"), StringArray::from(vec!["J", "W"]));
+    assert_eq!(
+        strings("resource_kind"),
+        StringArray::from(vec!["cpu_package", "cpu_package"])
+    );
+    assert_eq!(strings("resource_id"), StringArray::from(vec!["0", "0"]));
+    assert_eq!(strings("consumer_id"), StringArray::from(vec![None::<&str>, None]));
+    assert_eq!(strings("domain"), StringArray::from(vec![Some("package"), None]));
+
+    let timestamp = batch.column_by_name("timestamp").unwrap();
+    let timestamp = timestamp.as_any().downcast_ref::<TimestampNanosecondArray>().unwrap();
+    assert_eq!(timestamp.value(0), 1_000_000_000);
+
+    let value_u64 = batch.column_by_name("value_u64").unwrap();
+    let value_u64 = value_u64.as_any().downcast_ref::<UInt64Array>().unwrap();
+    assert_eq!(value_u64, &UInt64Array::from(vec![Some(12), None]));
+    let value_f64 = batch.column_by_name("value_f64").unwrap().as_primitive::<Float64Type>();
+    assert!(value_f64.is_null(0));
+    assert_eq!(value_f64.value(1), 0.5);
+
+    let attributes = batch.column_by_name("attributes").unwrap();
+    let attributes = attributes.as_any().downcast_ref::<MapArray>().unwrap();
+    assert_eq!(attributes.value_length(0), 1);
+    assert_eq!(attributes.value_length(1), 0);
+    assert_eq!(attributes.keys().as_string::<i32>().value(0), "other");
+    assert_eq!(attributes.values().as_string::<i32>().value(0), "1");
+}
+
+#[test]
+fn parquet_output() {
+    let _ = env_logger::Builder::from_default_env().try_init();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("alumet-output.parquet");
+    let config = Config {
+        output_path: path.clone(),
+        attribute_columns: vec![String::from("domain")],
+        flush_interval: Duration::ZERO,
+        ..Default::default()
+    };
+    run(config, 2);
+
+    // one row group per buffer
+    let batches = read_parquet(&path);
+    assert_eq!(batches.len(), 2);
+    for batch in &batches {
+        check_batch(batch);
+    }
+}
+
+#[test]
+fn arrow_ipc_output() {
+    let _ = env_logger::Builder::from_default_env().try_init();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("alumet-output.arrow");
+    let config = Config {
+        output_path: path.clone(),
+        format: FileFormat::ArrowIpc,
+        attribute_columns: vec![String::from("domain")],
+        row_group_size: 10,
+        flush_interval: Duration::from_secs(3600),
+        ..Default::default()
+    };
+    run(config, 1);
+
+    // the rows are written when the output is dropped
+    let reader = arrow_ipc::reader::FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(batches.len(), 1);
+    check_batch(&batches[0]);
+}
+
+#[test]
+fn parquet_rotation() {
+    let _ = env_logger::Builder::from_default_env().try_init();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("alumet-output.parquet");
+    let config = Config {
+        output_path: path.clone(),
+        attribute_columns: vec![String::from("domain")],
+        flush_interval: Duration::ZERO,
+        rotation: Some(RotationConfig {
+            max_size: Some(1),
+            keep_files: Some(2),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    run(config, 3);
+
+    // every file is rotated after its first row group, and only 2 rotated files are kept
+    let files: Vec<_> = fs::read_dir(tmp.path()).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(files.len(), 3, "unexpected files {files:?}");
+    for file in files {
+        let batches = read_parquet(&file);
+        if file == path {
+            assert!(batches.is_empty());
+        } else {
+            assert_eq!(batches.len(), 1);
+            check_batch(&batches[0]);
+        }
+    }
+}
+
+#[test]
+fn invalid_attribute_columns() {
+    use alumet::plugin::{ConfigTable, rust::AlumetPlugin};
+
+    for columns in [vec!["timestamp"], vec!["attributes"], vec!["domain", "domain"]] {
+        let config = Config {
+            attribute_columns: columns.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        let res = ParquetPlugin::init(ConfigTable(config_to_toml_table(&config)));
+        assert!(res.is_err(), "columns {columns:?} should be rejected");
+    }
+}
@@ -5,11 +5,12 @@ use std::{
 };
 
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::TypedMetricId,
     pipeline::{control::request, elements::source::trigger::TriggerSpec},
     plugin::{
         AlumetPostStart, event,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::Unit,
 };
@@ -42,7 +43,7 @@ impl AlumetPlugin for PerfPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -182,12 +183,14 @@ impl AlumetPlugin for PerfPlugin {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     poll_interval: Duration,
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     flush_interval: Duration,
 
     hardware_events: Vec<String>,
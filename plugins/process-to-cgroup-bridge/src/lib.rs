@@ -1,8 +1,9 @@
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::RawMetricId,
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
 };
 use anyhow::Context;
@@ -31,7 +32,7 @@ impl AlumetPlugin for ProcessToCgroupBridgePlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -77,7 +78,7 @@ impl AlumetPlugin for ProcessToCgroupBridgePlugin {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     /// The metrics names we want to find the cgroup for
     pub processes_metrics: Vec<String>,
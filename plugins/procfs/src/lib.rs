@@ -2,7 +2,7 @@ use alumet::{
     pipeline::{control::request, elements::source::trigger::TriggerSpec},
     plugin::{
         event,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     resources::ResourceConsumer,
     units::{PrefixedUnit, Unit},
@@ -32,7 +32,7 @@ impl AlumetPlugin for ProcfsPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(config::Config::default())?))
+        Ok(Some(serialize_config_with_schema(config::Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -222,10 +222,11 @@ mod config {
     use std::time::Duration;
 
     use crate::{process::MemoryStatsMode, serde_regex};
+    use alumet::agent::schema::JsonSchema;
     use regex::Regex;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Serialize, Deserialize, Default)]
+    #[derive(Serialize, Deserialize, Default, JsonSchema)]
     #[serde(deny_unknown_fields)]
     pub struct Config {
         pub kernel: KernelStatsMonitoring,
@@ -234,33 +235,36 @@ mod config {
         pub processes: ProcessMonitoring,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct KernelStatsMonitoring {
         #[serde(default = "default_enabled")]
         pub enabled: bool,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub poll_interval: Duration,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct NetworkMonitoring {
         #[serde(default = "default_enabled")]
         pub enabled: bool,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub poll_interval: Duration,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct MeminfoMonitoring {
         #[serde(default = "default_enabled")]
         pub enabled: bool,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub poll_interval: Duration,
         /// The entry to parse from /proc/meminfo.
         pub metrics: Vec<String>,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct ProcessMonitoring {
         /// `true` to enable the monitoring of processes.
         #[serde(default = "default_enabled")]
@@ -268,6 +272,7 @@ mod config {
 
         /// Watcher refresh interval.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub refresh_interval: Duration,
 
         /// Groups of processes to monitor when detected.
@@ -279,7 +284,7 @@ mod config {
         pub events: EventModeProcessMonitoring,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub enum ProcessWatchStrategy {
         #[serde(rename = "watcher")]
         SystemWatcher,
@@ -287,16 +292,18 @@ mod config {
         InternalEvent,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct EventModeProcessMonitoring {
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub poll_interval: Duration,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub flush_interval: Duration,
         pub memory_mode: MemoryStatsMode,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     pub struct ProcessMonitoringGroup {
         /// Only monitor the process that has this pid.
         pub pid: Option<u32>,
@@ -306,14 +313,17 @@ mod config {
 
         /// Only monitor the processes whose executable path matches this regex.
         #[serde(with = "serde_regex::option")]
+        #[schemars(with = "Option<String>")]
         pub exe_regex: Option<Regex>,
 
         /// How frequently should the processes information be refreshed.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub poll_interval: Duration,
 
         /// How frequently should the processes information be flushed to the rest of the pipeline.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub flush_interval: Duration,
 
         /// Which method to use to obtain memory statistics.
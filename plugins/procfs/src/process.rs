@@ -7,6 +7,7 @@ use std::{
     time::Duration,
 };
 
+use alumet::agent::schema::JsonSchema;
 use alumet::{
     measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
     metrics::TypedMetricId,
@@ -27,7 +28,7 @@ use procfs::{self, FromRead, ProcError, process::Process};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum MemoryStatsMode {
     Quick,
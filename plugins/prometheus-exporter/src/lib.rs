@@ -1,12 +1,14 @@
 mod output;
 
-use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::agent::schema::JsonSchema;
+use alumet::plugin::AlumetPreStart;
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema};
 use hyper::http::StatusCode;
 use hyper::{
     Body, Request, Response, Server,
     service::{make_service_fn, service_fn},
 };
-use output::PrometheusOutput;
+use output::{PrometheusOutput, RetiredMetricsCleaner};
 use prometheus_client::encoding::text::encode;
 use serde::{Deserialize, Serialize};
 use tokio::runtime::Builder;
@@ -15,6 +17,7 @@ use tokio::sync::oneshot;
 pub struct PrometheusPlugin {
     config: Config,
     shutdown_tx_server: Option<oneshot::Sender<()>>,
+    metrics_cleaner: Option<RetiredMetricsCleaner>,
 }
 
 impl AlumetPlugin for PrometheusPlugin {
@@ -27,7 +30,7 @@ impl AlumetPlugin for PrometheusPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
-        Ok(Some(serialize_config(Config::default())?))
+        Ok(Some(serialize_config_with_schema(Config::default())?))
     }
 
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -35,6 +38,7 @@ impl AlumetPlugin for PrometheusPlugin {
         Ok(Box::new(PrometheusPlugin {
             config: plugin_config,
             shutdown_tx_server: None,
+            metrics_cleaner: None,
         }))
     }
 
@@ -118,12 +122,22 @@ impl AlumetPlugin for PrometheusPlugin {
         // Store the shutdown tx handle for later shutdown
         self.shutdown_tx_server = Some(shutdown_tx_server);
 
+        // Prepare the listener that will drop the retired metrics
+        self.metrics_cleaner = Some(output.retired_metrics_cleaner());
+
         // Add output for processing measurements
         alumet.add_blocking_output("out", output)?;
 
         Ok(())
     }
 
+    fn pre_pipeline_start(&mut self, alumet: &mut AlumetPreStart) -> anyhow::Result<()> {
+        if let Some(cleaner) = self.metrics_cleaner.take() {
+            alumet.add_metric_listener("retired_metrics_cleaner", cleaner)?;
+        }
+        Ok(())
+    }
+
     fn stop(&mut self) -> anyhow::Result<()> {
         log::info!("Shutting down the Prometheus plugin...");
         if let Some(tx) = self.shutdown_tx_server.take() {
@@ -147,7 +161,7 @@ impl AlumetPlugin for PrometheusPlugin {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     host: String,
@@ -1,6 +1,6 @@
 use alumet::{
     measurement::{MeasurementBuffer, WrappedMeasurementValue},
-    metrics::Metric,
+    metrics::{Metric, RawMetricId, online::listener::MetricListener},
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
 use anyhow::Context;
@@ -11,14 +11,17 @@ use prometheus_client::{
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    sync::{Arc, atomic::AtomicU64},
+    sync::{Arc, Mutex, atomic::AtomicU64},
 };
 use tokio::sync::RwLock;
 
+type GaugeFamily = Family<Vec<(String, String)>, Gauge<f64, AtomicU64>>;
+
 #[derive(Clone)]
 pub struct MetricState {
     pub registry: Arc<RwLock<Registry>>,
-    metrics: Arc<RwLock<HashMap<String, Family<Vec<(String, String)>, Gauge<f64, AtomicU64>>>>>,
+    // std Mutex because it is also accessed by the metric listener, which runs in an async context
+    metrics: Arc<Mutex<HashMap<String, GaugeFamily>>>,
 }
 
 #[derive(Clone)]
@@ -40,7 +43,7 @@ impl PrometheusOutput {
     ) -> anyhow::Result<PrometheusOutput> {
         // Create metric state
         let registry = Arc::new(RwLock::new(Registry::default()));
-        let metrics = Arc::new(RwLock::new(HashMap::new()));
+        let metrics = Arc::new(Mutex::new(HashMap::new()));
         let state = MetricState { registry, metrics };
 
         // Configure the HTTP server to expose the metrics
@@ -56,6 +59,44 @@ impl PrometheusOutput {
             addr,
         })
     }
+
+    /// Returns a metric listener that drops the time series of the retired metrics.
+    pub fn retired_metrics_cleaner(&self) -> RetiredMetricsCleaner {
+        RetiredMetricsCleaner {
+            state: self.state.clone(),
+            prefix: self.prefix.clone(),
+            suffix: self.suffix.clone(),
+        }
+    }
+}
+
+/// Drops the time series of the metrics that have been retired, so that they are no longer exposed.
+///
+/// The metric family stays registered (with no time series), because the Prometheus
+/// registry does not support unregistering.
+pub struct RetiredMetricsCleaner {
+    state: MetricState,
+    prefix: String,
+    suffix: String,
+}
+
+impl MetricListener for RetiredMetricsCleaner {
+    fn on_metrics_registered(&mut self, _metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+        // families are lazily created by the output
+        Ok(())
+    }
+
+    fn on_metrics_retired(&mut self, metrics: Vec<(RawMetricId, Metric)>) -> anyhow::Result<()> {
+        let families = self.state.metrics.lock().unwrap();
+        for (_, metric) in metrics {
+            let metric_name = full_metric_name(&self.prefix, &metric.name, &self.suffix);
+            if let Some(family) = families.get(&metric_name) {
+                log::debug!("Dropping the time series of retired metric {metric_name}");
+                family.clear();
+            }
+        }
+        Ok(())
+    }
 }
 
 impl alumet::pipeline::Output for PrometheusOutput {
@@ -65,7 +106,7 @@ impl alumet::pipeline::Output for PrometheusOutput {
         }
 
         // Ensure threads reading and writing are handled correctly
-        let mut metrics = self.state.metrics.blocking_write();
+        let mut metrics = self.state.metrics.lock().unwrap();
         let mut registry = self.state.registry.blocking_write();
 
         for m in measurements {
@@ -76,7 +117,7 @@ impl alumet::pipeline::Output for PrometheusOutput {
                 .metrics
                 .by_id(&m.metric)
                 .with_context(|| format!("Unknown metric {:?}", m.metric))?;
-            let metric_name = sanitize_name(format!("{}{}{}", self.prefix, full_metric.name.clone(), self.suffix));
+            let metric_name = full_metric_name(&self.prefix, &full_metric.name, &self.suffix);
 
             // Create the default labels for all metrics and optionally add attributes
             let mut labels = vec![
@@ -102,7 +143,7 @@ impl alumet::pipeline::Output for PrometheusOutput {
                 family
             } else {
                 let unit_string = get_unit_string(full_metric);
-                let family = Family::<Vec<(String, String)>, Gauge<f64, AtomicU64>>::default();
+                let family = GaugeFamily::default();
 
                 if unit_string.is_empty() {
                     registry.register_with_unit(
@@ -131,6 +172,11 @@ impl alumet::pipeline::Output for PrometheusOutput {
     }
 }
 
+/// Helper function that returns the name of the metric, as exposed to Prometheus.
+fn full_metric_name(prefix: &str, name: &str, suffix: &str) -> String {
+    sanitize_name(format!("{prefix}{name}{suffix}"))
+}
+
 /// Helper function to ensure metric/label names follow Prometheus
 /// [naming rules](https://prometheus.io/docs/concepts/data_model/#metric-names-and-labels).
 fn sanitize_name(name: String) -> String {
@@ -1,9 +1,10 @@
 // This file contains the main implementation of the Quarch plugin for Alumet.
 use alumet::{
+    agent::schema::JsonSchema,
     pipeline::elements::source::trigger,
     plugin::{
         ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::Unit,
 };
@@ -37,7 +38,7 @@ impl AlumetPlugin for QuarchPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -173,7 +174,7 @@ pub fn poll_to_sample(poll: Duration) -> (u32, Duration) {
     (last.sample, last.window)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     // --- Quarch connection configuration ---
@@ -184,8 +185,10 @@ pub struct Config {
     pub qis_jar_path: String,
     // --- Measurement configuration ---
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     poll_interval: Duration,
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     flush_interval: Duration,
 }
 
@@ -1,9 +1,10 @@
 use alumet::{
+    agent::schema::JsonSchema,
     metrics::TypedMetricId,
     pipeline::elements::source::{Source, trigger::builder},
     plugin::{
         AlumetPluginStart, ConfigTable,
-        rust::{AlumetPlugin, deserialize_config, serialize_config},
+        rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
     },
     units::Unit,
 };
@@ -75,7 +76,7 @@ impl AlumetPlugin for RaplPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
@@ -190,15 +191,17 @@ fn setup_perf_events_probe_or_fallback(
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Initial interval between two RAPL measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub poll_interval: Duration,
 
     /// Initial interval between two flushing of RAPL measurements.
     #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub flush_interval: Duration,
 
     /// Set to true to disable perf_events and always use the powercap sysfs.
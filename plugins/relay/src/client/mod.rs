@@ -1,5 +1,7 @@
 mod output;
 mod plugin;
 mod retry;
+mod spool;
 
 pub use plugin::RelayClientPlugin;
+pub use spool::{DropPolicy, SpoolConfig};
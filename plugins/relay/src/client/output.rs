@@ -10,19 +10,31 @@ use alumet::{
     pipeline::elements::output::{AsyncOutputStream, interface::StreamRecvError},
 };
 use futures::StreamExt;
-use tokio::{net::TcpStream, sync::mpsc};
+use tokio::sync::mpsc;
 
-use crate::{client::retry::RetryState, protocol, serde_impl};
+use crate::{
+    client::retry::{Backoff, RetryState},
+    protocol, serde_impl,
+    tls::{Connection, Connector},
+};
 
-use super::retry::ExponentialRetryPolicy;
+use super::{
+    retry::ExponentialRetryPolicy,
+    spool::{self, Spool},
+};
 
 /// Exports Alumet measurements to a relay server via TCP.
 pub struct TcpOutput {
     settings: Settings,
     alumet: AlumetLink,
-    out_relay: protocol::MessageStream<TcpStream>,
+    /// Connection to the server. It is `None` when the server is unreachable (only if the spool is enabled).
+    out_relay: Option<protocol::MessageStream<Connection>>,
     buffer: MeasurementBuffer,
     buffer_last_send: Instant,
+    /// Measurements that could not be sent yet.
+    spool: Option<Spool>,
+    /// Delays the reconnection attempts while the server is unreachable (only if the spool is enabled).
+    reconnect: Backoff,
 }
 
 /// Links between the Alumet pipeline and the relay output.
@@ -39,9 +51,15 @@ pub struct AlumetLink {
 pub struct Settings {
     pub client_name: String,
     pub server_address: String,
+    /// Opens the connections to the server, with or without TLS.
+    pub connector: Connector,
+    /// Token that authenticates the client to the server.
+    pub auth_token: Option<String>,
     pub buffer: BufferSettings,
     pub msg_retry: ExponentialRetryPolicy,
     pub init_retry: ExponentialRetryPolicy,
+    /// If set, the measurements that cannot be sent are written to this spool, instead of being lost.
+    pub spool: Option<Spool>,
 }
 
 pub struct BufferSettings {
@@ -61,40 +79,61 @@ pub enum RetryAction {
 
 impl TcpOutput {
     /// Opens a connection to a remote relay server.
-    pub async fn connect(alumet: AlumetLink, settings: Settings) -> Result<TcpOutput, protocol::Error> {
+    ///
+    /// If the spool is enabled, a connection failure is not fatal: the output starts
+    /// without a connection, and the measurements are spooled until the server is reachable.
+    pub async fn connect(alumet: AlumetLink, mut settings: Settings) -> Result<TcpOutput, protocol::Error> {
         log::info!("Connecting to relay server {}...", settings.server_address);
 
         // --- connecting
         let mut retry_state = RetryState::new(&settings.init_retry);
-        let mut res = connect_to_server(&settings.server_address, &settings.client_name, &alumet.metrics_reader).await;
-        while let Err(e) = res {
+        let mut res = connect_to_server(&settings, &alumet.metrics_reader).await;
+        while let Err(e) = &res {
             if !retry_state.can_retry() {
-                return Err(e);
+                break;
             }
             log::error!("Connection failed: {e:?} - retrying...");
             retry_state.after_attempt().await;
-            match retry_action(&e) {
-                RetryAction::Fail => return Err(e),
+            match retry_action(e) {
+                RetryAction::Fail => break,
                 RetryAction::RetryOp | RetryAction::Reconnect => {
-                    res = connect_to_server(&settings.server_address, &settings.client_name, &alumet.metrics_reader)
-                        .await;
+                    res = connect_to_server(&settings, &alumet.metrics_reader).await;
                 }
             }
         }
         // ---
 
-        let out_relay = res.unwrap();
-        log::info!("Successfully connected to relay server.");
+        let out_relay = match (res, &settings.spool) {
+            (Ok(stream), _) => {
+                log::info!("Successfully connected to relay server.");
+                Some(stream)
+            }
+            (Err(e), Some(_)) if can_spool(&e) => {
+                log::error!(
+                    "Connection failed: {e:?} - the measurements will be spooled until the relay server is reachable."
+                );
+                None
+            }
+            (Err(e), _) => return Err(e),
+        };
 
         // Create a buffer for sending measurements in a more efficient way.
         let buffer = MeasurementBuffer::with_capacity(settings.buffer.initial_capacity);
 
+        let mut reconnect = Backoff::new(&settings.msg_retry);
+        if out_relay.is_none() {
+            reconnect.failed();
+        }
+
+        let spool = settings.spool.take();
         Ok(TcpOutput {
             settings,
             alumet,
             out_relay,
             buffer,
             buffer_last_send: Instant::now(),
+            spool,
+            reconnect,
         })
     }
 
@@ -113,49 +152,123 @@ impl TcpOutput {
 
         if size_limit_reached || timeout_expired {
             self.buffer_last_send = now;
+            if self.spool.is_some() {
+                self.send_or_spool_buffer().await?;
+            } else {
+                let msg = protocol::MessageBody {
+                    sender: self.settings.client_name.clone(),
+                    content: protocol::MessageEnum::SendMeasurements(protocol::SendMeasurements {
+                        buf: serde_impl::SerdeMeasurementBuffer::Borrowed(&self.buffer),
+                    }),
+                };
+                write_with_retry(
+                    &mut self.out_relay,
+                    &self.settings,
+                    &self.settings.msg_retry,
+                    &self.alumet.metrics_reader,
+                    &msg,
+                    "measurements",
+                )
+                .await?;
+            }
+            self.buffer.clear();
+            if size_limit_reached {
+                self.buffer.merge(&mut measurements);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the buffer to the server after the spooled measurements, or adds it to the spool
+    /// if the server is unreachable.
+    ///
+    /// The retry policy is not applied here: blocking the output for a long time would
+    /// make it lag behind the pipeline, and lose measurements. Instead, while the server
+    /// is unreachable, the reconnection is only attempted when the backoff delay has elapsed.
+    async fn send_or_spool_buffer(&mut self) -> Result<(), protocol::Error> {
+        if self.out_relay.is_none() && !self.reconnect.ready() {
+            return self.spool_buffer().await;
+        }
+
+        let spool = self.spool.as_mut().expect("spool should be enabled");
+
+        // Send the spooled measurements first, in order.
+        let mut res = Ok(());
+        while res.is_ok() {
+            let metrics = self.alumet.metrics_reader.read().await;
+            let spooled = spool
+                .peek(|name| metrics.by_name(name).map(|(id, m)| (id, m.value_type.clone())))
+                .await;
+            drop(metrics);
+            let spooled = match spooled {
+                Ok(Some(buf)) => buf,
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Failed to read spooled measurements, they will be dropped: {e}");
+                    spool.pop().await?;
+                    continue;
+                }
+            };
             let msg = protocol::MessageBody {
                 sender: self.settings.client_name.clone(),
                 content: protocol::MessageEnum::SendMeasurements(protocol::SendMeasurements {
-                    buf: serde_impl::SerdeMeasurementBuffer::Borrowed(&self.buffer),
+                    buf: serde_impl::SerdeMeasurementBuffer::Borrowed(&spooled),
                 }),
             };
-            // --- writing
-            let mut retry_state = RetryState::new(&self.settings.msg_retry);
-            let mut res = self.out_relay.write_message(&msg).await;
-            while let Err(e) = res {
-                if !retry_state.can_retry() {
-                    return Err(e);
-                }
-                log::error!("Sending measurements failed: {e:?} - retrying...");
-                retry_state.after_attempt().await;
-                match retry_action(&e) {
-                    RetryAction::Fail => return Err(e),
-                    RetryAction::RetryOp => res = self.out_relay.write_message(&msg).await,
-                    RetryAction::Reconnect => {
-                        res = async {
-                            self.out_relay = connect_to_server(
-                                &self.settings.server_address,
-                                &self.settings.client_name,
-                                &self.alumet.metrics_reader,
-                            )
-                            .await?;
-                            self.out_relay.write_message(&msg).await
-                        }
-                        .await;
-                    }
+            res = write_or_connect(&mut self.out_relay, &self.settings, &self.alumet.metrics_reader, &msg).await;
+            if res.is_ok() {
+                spool.pop().await?;
+                if spool.is_empty() {
+                    log::info!("All the spooled measurements have been sent to the relay server.");
                 }
             }
-            // ---
-            self.buffer.clear();
-            if size_limit_reached {
-                self.buffer.merge(&mut measurements);
+        }
+
+        // Then send the current buffer.
+        if res.is_ok() {
+            let msg = protocol::MessageBody {
+                sender: self.settings.client_name.clone(),
+                content: protocol::MessageEnum::SendMeasurements(protocol::SendMeasurements {
+                    buf: serde_impl::SerdeMeasurementBuffer::Borrowed(&self.buffer),
+                }),
+            };
+            res = write_or_connect(&mut self.out_relay, &self.settings, &self.alumet.metrics_reader, &msg).await;
+        }
+
+        if let Err(e) = res {
+            if !can_spool(&e) {
+                return Err(e);
             }
+            log::warn!(
+                "The relay server is unreachable ({e}), spooling {} measurements.",
+                self.buffer.len()
+            );
+            self.out_relay = None;
+            self.reconnect.failed();
+            self.spool_buffer().await?;
+        } else {
+            self.reconnect.succeeded();
         }
         Ok(())
     }
 
+    /// Adds the current buffer to the spool.
+    async fn spool_buffer(&mut self) -> Result<(), protocol::Error> {
+        let spool = self.spool.as_mut().expect("spool should be enabled");
+        let metrics = spool::metric_definitions(&self.buffer, &*self.alumet.metrics_reader.read().await);
+        spool.push(&self.buffer, metrics).await?;
+        log::debug!("{} batches of measurements in the spool", spool.len());
+        Ok(())
+    }
+
     /// Sends metric definitions via TCP.
     async fn send_metrics(&mut self, metrics_buf: &mut Vec<Vec<(RawMetricId, Metric)>>) -> Result<(), protocol::Error> {
+        if self.spool.is_some() && self.out_relay.is_none() {
+            // Disconnected: all the metrics will be sent on reconnection.
+            metrics_buf.clear();
+            return Ok(());
+        }
+
         let iterable = metrics_buf.drain(..).flatten();
         let to_send: Vec<_> = iterable.into_iter().map(protocol::Metric::from).collect();
 
@@ -164,34 +277,25 @@ impl TcpOutput {
             content: protocol::MessageEnum::RegisterMetrics(protocol::RegisterMetrics { metrics: to_send }),
         };
 
-        // NOTE: To make this code generic on the operation, we need either a macro,
-        // or the upcoming async closures (https://github.com/rust-lang/rust/pull/132706).
-        let mut retry_state = RetryState::new(&self.settings.msg_retry);
-        let mut res = self.out_relay.write_message(&msg).await;
-        while let Err(e) = res {
-            if !retry_state.can_retry() {
-                return Err(e);
-            }
-            log::error!("Sending metrics failed: {e:?} - retrying...");
-            retry_state.after_attempt().await;
-            match retry_action(&e) {
-                RetryAction::Fail => return Err(e),
-                RetryAction::RetryOp => res = self.out_relay.write_message(&msg).await,
-                RetryAction::Reconnect => {
-                    res = async {
-                        self.out_relay = connect_to_server(
-                            &self.settings.server_address,
-                            &self.settings.client_name,
-                            &self.alumet.metrics_reader,
-                        )
-                        .await?;
-                        self.out_relay.write_message(&msg).await
-                    }
-                    .await;
-                }
+        let res = write_with_retry(
+            &mut self.out_relay,
+            &self.settings,
+            &self.settings.msg_retry,
+            &self.alumet.metrics_reader,
+            &msg,
+            "metrics",
+        )
+        .await;
+        match res {
+            Err(e) if self.spool.is_some() && can_spool(&e) => {
+                // All the metrics will be sent on reconnection.
+                log::warn!("The relay server is unreachable ({e}), the metrics will be sent on reconnection.");
+                self.out_relay = None;
+                self.reconnect.failed();
+                Ok(())
             }
+            res => res,
         }
-        Ok(())
     }
 
     /// Continuously polls new measurements and metrics, and sends them via TCP.
@@ -231,11 +335,74 @@ impl TcpOutput {
                     },
                 };
             }
+
+            // Don't lose the measurements that are still in the buffer: send or spool them.
+            if self.spool.is_some() && !self.buffer.is_empty() {
+                self.send_or_spool_buffer().await?;
+                self.buffer.clear();
+            }
             Ok(())
         }
     }
 }
 
+/// Writes a message to the server, reconnecting if needed, and applies the retry policy.
+async fn write_with_retry(
+    out_relay: &mut Option<protocol::MessageStream<Connection>>,
+    settings: &Settings,
+    retry_policy: &ExponentialRetryPolicy,
+    metrics_reader: &MetricReader,
+    msg: &protocol::MessageBody<'_>,
+    what: &str,
+) -> Result<(), protocol::Error> {
+    let mut retry_state = RetryState::new(retry_policy);
+    let mut res = write_or_connect(out_relay, settings, metrics_reader, msg).await;
+    while let Err(e) = res {
+        if !retry_state.can_retry() {
+            return Err(e);
+        }
+        log::error!("Sending {what} failed: {e:?} - retrying...");
+        retry_state.after_attempt().await;
+        match retry_action(&e) {
+            RetryAction::Fail => return Err(e),
+            RetryAction::RetryOp => res = write_or_connect(out_relay, settings, metrics_reader, msg).await,
+            RetryAction::Reconnect => {
+                *out_relay = None;
+                res = write_or_connect(out_relay, settings, metrics_reader, msg).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a message to the server, connecting first if needed, in one attempt.
+// NOTE: To make `write_with_retry` generic on the operation, we need either a macro,
+// or the upcoming async closures (https://github.com/rust-lang/rust/pull/132706).
+async fn write_or_connect(
+    out_relay: &mut Option<protocol::MessageStream<Connection>>,
+    settings: &Settings,
+    metrics_reader: &MetricReader,
+    msg: &protocol::MessageBody<'_>,
+) -> Result<(), protocol::Error> {
+    let stream = match out_relay {
+        Some(stream) => stream,
+        None => out_relay.insert(connect_to_server(settings, metrics_reader).await?),
+    };
+    stream.write_message(msg).await
+}
+
+/// Returns `true` if the error is caused by the server being unreachable (or restarting),
+/// in which case the measurements can be spooled and sent later.
+fn can_spool(err: &protocol::Error) -> bool {
+    match err {
+        protocol::Error::Io(_) | protocol::Error::Disconnected => true,
+        protocol::Error::Serde(_)
+        | protocol::Error::VersionMismatch { .. }
+        | protocol::Error::Unexpected
+        | protocol::Error::Rejected(_) => false,
+    }
+}
+
 fn retry_action(err: &protocol::Error) -> RetryAction {
     match err {
         protocol::Error::Io(error) => {
@@ -263,17 +430,18 @@ fn retry_action(err: &protocol::Error) -> RetryAction {
 
 #[must_use]
 async fn connect_to_server(
-    server_addr: &str,
-    client_name: &str,
+    settings: &Settings,
     metrics_reader: &MetricReader,
-) -> Result<protocol::MessageStream<TcpStream>, protocol::Error> {
-    // open the TCP connection
+) -> Result<protocol::MessageStream<Connection>, protocol::Error> {
+    let client_name = &settings.client_name;
+
+    // open the TCP connection (and do the TLS handshake, if enabled)
     log::debug!("Opening TCP connection...");
-    let stream = TcpStream::connect(server_addr).await?;
+    let stream = settings.connector.connect(&settings.server_address).await?;
 
     // do the protocol handshake
     log::debug!("Doing protocol handshake...");
-    let mut stream = handshake_client2server(client_name.to_owned(), stream).await?;
+    let mut stream = handshake_client2server(client_name.to_owned(), settings.auth_token.clone(), stream).await?;
 
     // send the metric definitions (for metrics that are known at this point)
     log::debug!("Sending initial metrics...");
@@ -294,8 +462,9 @@ async fn connect_to_server(
 
 async fn handshake_client2server(
     client_name: String,
-    stream: TcpStream,
-) -> Result<protocol::MessageStream<TcpStream>, protocol::Error> {
+    auth_token: Option<String>,
+    stream: Connection,
+) -> Result<protocol::MessageStream<Connection>, protocol::Error> {
     let mut out_relay = protocol::MessageStream::new(stream);
 
     // send greeting
@@ -306,6 +475,7 @@ async fn handshake_client2server(
                 alumet_core_version: String::from(alumet::VERSION),
                 relay_plugin_version: String::from(crate::PLUGIN_VERSION),
                 protocol_version: protocol::PROTOCOL_VERSION,
+                auth_token,
             }),
         })
         .await?;
@@ -323,6 +493,11 @@ async fn handshake_client2server(
                 response.protocol_version
             );
             Ok(out_relay)
+        } else if let Some(reason @ (protocol::RejectReason::MissingToken | protocol::RejectReason::InvalidToken)) =
+            response.reject_reason
+        {
+            log::error!("Cannot connect: the relay server rejected the client: {reason}.");
+            Err(protocol::Error::Rejected(reason))
         } else {
             log::error!(
                 "Cannot connect: client and server are incompatible.
@@ -2,14 +2,14 @@ use alumet::metrics::{Metric, RawMetricId};
 use alumet::pipeline::elements::output::BoxedAsyncOutput;
 use alumet::plugin::{
     AlumetPluginStart, ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
 use anyhow::Context;
 use tokio::sync::mpsc;
 
-use crate::client::output;
+use crate::{client::output, tls::Connector};
 
-use super::retry::ExponentialRetryPolicy;
+use super::{retry::ExponentialRetryPolicy, spool::Spool};
 
 pub struct RelayClientPlugin {
     config: Option<config::Config>,
@@ -18,9 +18,12 @@ pub struct RelayClientPlugin {
 mod config {
     use std::time::Duration;
 
+    use alumet::agent::schema::JsonSchema;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Serialize, Deserialize)]
+    use crate::{client::spool::SpoolConfig, tls::ClientTlsConfig};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
     #[serde(deny_unknown_fields)]
     pub struct Config {
         /// The name that this client will use to identify itself to the collector server.
@@ -32,20 +35,35 @@ mod config {
         #[serde(default = "default_relay_server_address")]
         pub relay_server: String,
 
+        /// TLS settings. If unset, the connection is not encrypted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tls: Option<ClientTlsConfig>,
+
+        /// Token that authenticates this client to the server, if the server requires it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub auth_token: Option<String>,
+
         /// Maximum number of elements to keep in the output buffer before sending it.
         pub buffer_max_length: usize,
 
         /// Maximum amount of time to wait before sending the measurements to the server.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub buffer_timeout: Duration,
 
         /// Parameter of the exponential backoff strategy that is applied when a network operation fails.
         ///
         /// The delay is multiplied by two after each attempt.
         pub retry: RetryConfig,
+
+        /// Persistent buffer for the measurements that cannot be sent to the server.
+        ///
+        /// If unset, the measurements are lost when the server is unreachable after all the retries.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub spool: Option<SpoolConfig>,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     #[serde(deny_unknown_fields)]
     pub struct RetryConfig {
         /// Maximum number of retries before giving up.
@@ -53,10 +71,12 @@ mod config {
 
         /// Initial delay between two attempts.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub initial_delay: Duration,
 
         /// Maximum delay between two attempts.
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         pub max_delay: Duration,
     }
 
@@ -65,9 +85,12 @@ mod config {
             Self {
                 client_name: default_client_name(),
                 relay_server: default_relay_server_address(),
+                tls: None,
+                auth_token: None,
                 buffer_max_length: 4096,
                 buffer_timeout: Duration::from_secs(30),
                 retry: RetryConfig::default(),
+                spool: None,
             }
         }
     }
@@ -103,7 +126,7 @@ impl AlumetPlugin for RelayClientPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        Ok(Some(serialize_config(config::Config::default())?))
+        Ok(Some(serialize_config_with_schema(config::Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
@@ -117,9 +140,18 @@ impl AlumetPlugin for RelayClientPlugin {
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
         // Prepare the values that will be moved to the closure.
         let config = self.config.take().unwrap();
+        let spool = config
+            .spool
+            .map(Spool::open)
+            .transpose()
+            .context("failed to open the spool")?;
+        let connector =
+            Connector::new(config.tls.as_ref(), &config.relay_server).context("invalid TLS configuration")?;
         let client_settings = output::Settings {
             client_name: config.client_name,
             server_address: config.relay_server,
+            connector,
+            auth_token: config.auth_token,
             buffer: output::BufferSettings {
                 initial_capacity: 512,
                 max_length: config.buffer_max_length,
@@ -137,6 +169,7 @@ impl AlumetPlugin for RelayClientPlugin {
                 max_delay: config.retry.max_delay,
                 multiplier: 2,
             },
+            spool,
         };
 
         // Create a channel for the metrics.
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct ExponentialRetryPolicy {
@@ -44,3 +44,37 @@ impl RetryState {
         self.count_and_increase_delay();
     }
 }
+
+/// Non-blocking exponential backoff: tells when the next attempt is allowed, without sleeping.
+pub struct Backoff {
+    policy: ExponentialRetryPolicy,
+    delay: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn new(policy: &ExponentialRetryPolicy) -> Self {
+        Self {
+            policy: policy.clone(),
+            delay: policy.initial_delay,
+            next_attempt: None,
+        }
+    }
+
+    /// Returns `true` if a new attempt can be made now.
+    pub fn ready(&self) -> bool {
+        self.next_attempt.is_none_or(|t| Instant::now() >= t)
+    }
+
+    /// Records a failed attempt: the next one is delayed, and the delay increases.
+    pub fn failed(&mut self) {
+        self.next_attempt = Some(Instant::now() + self.delay);
+        self.delay = (self.delay * self.policy.multiplier.into()).min(self.policy.max_delay);
+    }
+
+    /// Records a successful attempt, which resets the delay.
+    pub fn succeeded(&mut self) {
+        self.delay = self.policy.initial_delay;
+        self.next_attempt = None;
+    }
+}
@@ -0,0 +1,406 @@
+//! Persistent storage of the measurements that could not be sent to the relay server.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    agent::schema::JsonSchema,
+    measurement::{MeasurementBuffer, WrappedMeasurementType},
+    metrics::{RawMetricId, registry::MetricRegistry},
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{protocol, serde_impl::SerdeMeasurementBuffer};
+
+const FILE_EXTENSION: &str = "batch";
+const TMP_EXTENSION: &str = "tmp";
+
+/// Configuration of the spool directory.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SpoolConfig {
+    /// Directory where the unsent measurements are written.
+    pub directory: PathBuf,
+
+    /// Maximum total size of the spooled measurements, in bytes.
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+
+    /// What to do when the spool is full.
+    #[serde(default)]
+    pub drop_policy: DropPolicy,
+}
+
+/// Which measurements to drop when the spool is full.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Delete the oldest spooled measurements to make room for the new ones.
+    #[default]
+    DropOldest,
+    /// Keep the spooled measurements and drop the new ones.
+    DropNewest,
+}
+
+fn default_max_size() -> u64 {
+    100_000_000 // 100 MB
+}
+
+/// A batch of measurements, with the definition of its metrics.
+///
+/// The metric ids can change when Alumet restarts, therefore we keep the definitions in order
+/// to find the right metrics when the batch is replayed.
+#[derive(Debug, Serialize, Deserialize)]
+struct SpoolEntry<'a> {
+    metrics: Vec<protocol::Metric>,
+    buf: SerdeMeasurementBuffer<'a>,
+}
+
+/// A queue of measurement batches, stored in a directory (one file per batch).
+///
+/// The batches that are left in the directory when Alumet stops are loaded again on the next start.
+/// Except for [`Spool::open`], which is called before the pipeline starts, the files are accessed
+/// with [`tokio::fs`], in order not to block the output task.
+pub struct Spool {
+    config: SpoolConfig,
+    /// Spooled batches, from the oldest to the newest: sequence number and file size.
+    files: VecDeque<(u64, u64)>,
+    /// Total size of the spooled batches.
+    total_size: u64,
+    /// Sequence number of the next batch.
+    next_seq: u64,
+}
+
+impl Spool {
+    /// Opens the spool directory, creating it if needed, and loads the batches that it contains.
+    ///
+    /// The temporary files of the batches that were being written when Alumet stopped are deleted.
+    pub fn open(config: SpoolConfig) -> anyhow::Result<Self> {
+        let dir = &config.directory;
+        fs::create_dir_all(dir).with_context(|| format!("failed to create spool directory {dir:?}"))?;
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).with_context(|| format!("failed to list spool directory {dir:?}"))? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == TMP_EXTENSION) {
+                log::debug!("Deleting incomplete spool file {path:?}.");
+                fs::remove_file(&path).with_context(|| format!("failed to delete incomplete spool file {path:?}"))?;
+                continue;
+            }
+            let seq = path
+                .extension()
+                .filter(|ext| *ext == FILE_EXTENSION)
+                .and_then(|_| path.file_stem()?.to_str()?.parse::<u64>().ok());
+            if let Some(seq) = seq {
+                files.push((seq, entry.metadata()?.len()));
+            }
+        }
+        files.sort();
+
+        let total_size = files.iter().map(|(_, size)| size).sum();
+        let next_seq = files.last().map(|(seq, _)| seq + 1).unwrap_or(0);
+        if !files.is_empty() {
+            log::info!(
+                "Found {} unsent measurement batches ({total_size} bytes) in spool directory {dir:?}.",
+                files.len()
+            );
+        }
+        Ok(Self {
+            config,
+            files: VecDeque::from(files),
+            total_size,
+            next_seq,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Writes a batch of measurements at the end of the queue.
+    ///
+    /// `metrics` must contain the definition of the metrics used in the batch, see [`metric_definitions`].
+    /// If the spool is full, some measurements are dropped according to the drop policy.
+    pub async fn push(&mut self, buf: &MeasurementBuffer, metrics: Vec<protocol::Metric>) -> io::Result<()> {
+        let entry = SpoolEntry {
+            metrics,
+            buf: SerdeMeasurementBuffer::Borrowed(buf),
+        };
+        let bytes = postcard::to_allocvec(&entry).map_err(io::Error::other)?;
+        let size = bytes.len() as u64;
+
+        // Make room for the new batch, if possible.
+        if size > self.config.max_size {
+            log::warn!(
+                "Dropping {} measurements: the batch is larger than the maximum size of the spool.",
+                buf.len()
+            );
+            return Ok(());
+        }
+        while self.total_size + size > self.config.max_size {
+            match self.config.drop_policy {
+                DropPolicy::DropOldest => {
+                    log::warn!("The spool is full, dropping the oldest batch of measurements.");
+                    self.pop().await?;
+                }
+                DropPolicy::DropNewest => {
+                    log::warn!("The spool is full, dropping {} measurements.", buf.len());
+                    return Ok(());
+                }
+            }
+        }
+
+        // Write to a temporary file first, so that we never read a partial batch.
+        let seq = self.next_seq;
+        let path = self.path(seq);
+        let tmp_path = path.with_extension(TMP_EXTENSION);
+        tokio::fs::write(&tmp_path, &bytes).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        self.files.push_back((seq, size));
+        self.total_size += size;
+        self.next_seq += 1;
+        log::debug!("{} measurements written to spool file {path:?}", buf.len());
+        Ok(())
+    }
+
+    /// Reads the oldest batch of measurements, without removing it from the queue.
+    ///
+    /// The metric ids are translated to the current ids, which are given by `find_metric` (see [`remap_metrics`]).
+    /// Returns `None` if the spool is empty.
+    pub async fn peek(
+        &self,
+        find_metric: impl Fn(&str) -> Option<MetricIdAndType>,
+    ) -> io::Result<Option<MeasurementBuffer>> {
+        let Some((seq, _)) = self.files.front() else {
+            return Ok(None);
+        };
+        let bytes = tokio::fs::read(self.path(*seq)).await?;
+        let entry: SpoolEntry =
+            postcard::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut buf = entry.buf.owned();
+        remap_metrics(entry.metrics, &mut buf, find_metric);
+        Ok(Some(buf))
+    }
+
+    /// Removes the oldest batch of measurements.
+    pub async fn pop(&mut self) -> io::Result<()> {
+        if let Some((seq, size)) = self.files.pop_front() {
+            self.total_size -= size;
+            match tokio::fs::remove_file(self.path(seq)).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        Path::new(&self.config.directory).join(format!("{seq:020}.{FILE_EXTENSION}"))
+    }
+}
+
+/// Returns the definitions of the metrics that are used in the buffer.
+pub fn metric_definitions(buf: &MeasurementBuffer, metrics: &MetricRegistry) -> Vec<protocol::Metric> {
+    let ids: HashSet<RawMetricId> = buf.iter().map(|p| p.metric).collect();
+    ids.into_iter()
+        .filter_map(|id| Some(protocol::Metric::from((id, metrics.by_id(&id)?.clone()))))
+        .collect()
+}
+
+/// The current id of a metric, and its type.
+pub type MetricIdAndType = (RawMetricId, WrappedMeasurementType);
+
+/// Replaces the metric ids of the spooled measurements by the current ids of the same metrics,
+/// which are found by name with `find_metric`.
+///
+/// The points whose metric does not exist anymore (or has changed) are dropped.
+fn remap_metrics(
+    spooled: Vec<protocol::Metric>,
+    buf: &mut MeasurementBuffer,
+    find_metric: impl Fn(&str) -> Option<MetricIdAndType>,
+) {
+    let mapping: HashMap<u64, RawMetricId> = spooled
+        .into_iter()
+        .filter_map(|m| {
+            let (id, value_type) = find_metric(&m.name)?;
+            let same_type = value_type == WrappedMeasurementType::from(m.value_type);
+            same_type.then_some((m.id, id))
+        })
+        .collect();
+
+    let len_before = buf.len();
+    let mut keep = Vec::with_capacity(buf.len());
+    for p in buf.iter_mut() {
+        match mapping.get(&p.metric.as_u64()) {
+            Some(id) => {
+                p.metric = *id;
+                keep.push(true);
+            }
+            None => keep.push(false),
+        }
+    }
+    // retain visits the points in order, exactly once
+    let mut decisions = keep.into_iter();
+    buf.retain(|_| decisions.next().unwrap_or(true));
+    let n_dropped = len_before - buf.len();
+    if n_dropped > 0 {
+        log::warn!("Dropping {n_dropped} spooled measurements because their metric is not registered anymore.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{
+            MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue,
+        },
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::{DropPolicy, MetricIdAndType, Spool, SpoolConfig};
+    use crate::protocol;
+
+    fn metric() -> RawMetricId {
+        RawMetricId::from_u64(3)
+    }
+
+    fn buffer(values: &[u64]) -> MeasurementBuffer {
+        let points: Vec<MeasurementPoint> = values
+            .iter()
+            .map(|v| {
+                MeasurementPoint::new_untyped(
+                    Timestamp::from(UNIX_EPOCH),
+                    metric(),
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    WrappedMeasurementValue::U64(*v),
+                )
+            })
+            .collect();
+        MeasurementBuffer::from(points)
+    }
+
+    fn definitions() -> Vec<protocol::Metric> {
+        vec![protocol::Metric {
+            id: metric().as_u64(),
+            name: String::from("spooled"),
+            value_type: protocol::MetricType::U64,
+            unit: protocol::MetricUnit {
+                base: String::from("J"),
+                prefix: String::new(),
+            },
+        }]
+    }
+
+    /// Finds the metric with the same id as before.
+    fn same_metric(name: &str) -> Option<MetricIdAndType> {
+        (name == "spooled").then_some((metric(), WrappedMeasurementType::U64))
+    }
+
+    fn values(buf: &MeasurementBuffer) -> Vec<u64> {
+        buf.iter()
+            .map(|p| match p.value {
+                WrappedMeasurementValue::U64(x) => x,
+                WrappedMeasurementValue::F64(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn config(dir: &std::path::Path, max_size: u64, drop_policy: DropPolicy) -> SpoolConfig {
+        SpoolConfig {
+            directory: dir.to_owned(),
+            max_size,
+            drop_policy,
+        }
+    }
+
+    #[tokio::test]
+    async fn push_and_replay_in_order() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let mut spool = Spool::open(config(tmp.path(), 1_000_000, DropPolicy::DropOldest))?;
+        assert!(spool.is_empty());
+        spool.push(&buffer(&[1, 2]), definitions()).await?;
+        spool.push(&buffer(&[3]), definitions()).await?;
+        assert_eq!(spool.len(), 2);
+
+        // the batches survive a restart, the incomplete ones are deleted
+        drop(spool);
+        fs::write(tmp.path().join("00000000000000000002.tmp"), b"partial")?;
+        let mut spool = Spool::open(config(tmp.path(), 1_000_000, DropPolicy::DropOldest))?;
+        assert_eq!(spool.len(), 2);
+
+        assert_eq!(values(&spool.peek(same_metric).await?.unwrap()), vec![1, 2]);
+        spool.pop().await?;
+        assert_eq!(values(&spool.peek(same_metric).await?.unwrap()), vec![3]);
+        spool.pop().await?;
+        assert!(spool.peek(same_metric).await?.is_none());
+        assert_eq!(fs::read_dir(tmp.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drop_policy() -> anyhow::Result<()> {
+        let tmp_oldest = tempfile::tempdir()?;
+        let tmp_newest = tempfile::tempdir()?;
+
+        // find the size of a batch
+        let mut spool = Spool::open(config(tmp_oldest.path(), u64::MAX, DropPolicy::DropOldest))?;
+        spool.push(&buffer(&[1]), definitions()).await?;
+        let batch_size = spool.total_size;
+        spool.pop().await?;
+
+        // room for two batches
+        for (dir, policy, expected) in [
+            (tmp_oldest.path(), DropPolicy::DropOldest, vec![2, 3]),
+            (tmp_newest.path(), DropPolicy::DropNewest, vec![1, 2]),
+        ] {
+            let mut spool = Spool::open(config(dir, 2 * batch_size, policy))?;
+            for v in 1..=3 {
+                spool.push(&buffer(&[v]), definitions()).await?;
+            }
+            assert_eq!(spool.len(), 2);
+            let mut replayed = Vec::new();
+            while let Some(buf) = spool.peek(same_metric).await? {
+                replayed.extend(values(&buf));
+                spool.pop().await?;
+            }
+            assert_eq!(replayed, expected, "wrong batches kept with {policy:?}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remap_metrics() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let mut spool = Spool::open(config(tmp.path(), 1_000_000, DropPolicy::DropOldest))?;
+        spool.push(&buffer(&[1]), definitions()).await?;
+
+        // the same metric has another id, for instance after a restart
+        let new_id = RawMetricId::from_u64(8);
+        let buf = spool
+            .peek(|name| (name == "spooled").then_some((new_id, WrappedMeasurementType::U64)))
+            .await?;
+        assert_eq!(buf.unwrap().iter().next().unwrap().metric, new_id);
+
+        // the metric has changed
+        let buf = spool.peek(|_| Some((new_id, WrappedMeasurementType::F64))).await?;
+        assert!(buf.unwrap().is_empty());
+
+        // the metric does not exist anymore
+        let buf = spool.peek(|_| None).await?;
+        assert!(buf.unwrap().is_empty());
+        Ok(())
+    }
+}
@@ -5,5 +5,6 @@ pub mod server;
 
 mod protocol;
 mod serde_impl;
+mod tls;
 
 pub const PLUGIN_VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -8,16 +8,15 @@ use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    net::TcpStream,
     time::error::Elapsed,
 };
 
-use crate::serde_impl;
+use crate::{serde_impl, tls::Connection};
 
 /// Version number of the current protocol.
 ///
 /// IMPORTANT: you must increase this number when the protocol changes.
-pub const PROTOCOL_VERSION: u32 = 2;
+pub const PROTOCOL_VERSION: u32 = 3;
 
 /// Maximum size (in bytes) of a message body.
 ///
@@ -54,6 +53,10 @@ pub enum Error {
 
     #[error("received an unexpected response")]
     Unexpected,
+
+    /// The server refused the client.
+    #[error("the server rejected the client: {0}")]
+    Rejected(RejectReason),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,6 +82,8 @@ pub struct Greet {
     pub alumet_core_version: String,
     pub relay_plugin_version: String,
     pub protocol_version: u32,
+    /// Secret token that authenticates the client, if the server requires it.
+    pub auth_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,6 +92,19 @@ pub struct GreetResponse {
     pub server_alumet_core_version: String,
     pub server_relay_plugin_version: String,
     pub protocol_version: u32,
+    /// Why the client has been rejected (`None` if `accept` is true).
+    pub reject_reason: Option<RejectReason>,
+}
+
+/// Reason of the rejection of a client by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum RejectReason {
+    #[error("incompatible protocol version")]
+    IncompatibleProtocol,
+    #[error("authentication required, but the client did not provide a token")]
+    MissingToken,
+    #[error("invalid authentication token")]
+    InvalidToken,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -119,6 +137,74 @@ pub struct SendMeasurements<'s> {
     pub buf: serde_impl::SerdeMeasurementBuffer<'s>,
 }
 
+/// The beginning of a [`MessageBody`] that contains a [`Greet`] or a [`GreetResponse`].
+///
+/// The fields that come first, including the protocol version, are the same in every version of the protocol.
+/// They are decoded before the rest of the handshake, so that a peer that uses another version of the
+/// protocol gets a proper rejection, instead of a deserialization error.
+#[derive(Deserialize)]
+struct HandshakePrefix {
+    sender: String,
+    content: HandshakePrefixEnum,
+}
+
+/// Same variants, in the same order, as the beginning of [`MessageEnum`].
+#[derive(Deserialize)]
+enum HandshakePrefixEnum {
+    Greet {
+        alumet_core_version: String,
+        relay_plugin_version: String,
+        protocol_version: u32,
+    },
+    GreetResponse {
+        accept: bool,
+        server_alumet_core_version: String,
+        server_relay_plugin_version: String,
+        protocol_version: u32,
+    },
+}
+
+impl HandshakePrefix {
+    /// Decodes the handshake message of a peer that uses another version of the protocol.
+    ///
+    /// Returns `None` if the message is not a handshake, or if it uses the current version of the protocol.
+    fn decode_other_version(body_bytes: &[u8]) -> Option<MessageBody<'static>> {
+        let (prefix, _): (HandshakePrefix, _) = postcard::take_from_bytes(body_bytes).ok()?;
+        let content = match prefix.content {
+            HandshakePrefixEnum::Greet { protocol_version, .. } if protocol_version == PROTOCOL_VERSION => return None,
+            HandshakePrefixEnum::GreetResponse { protocol_version, .. } if protocol_version == PROTOCOL_VERSION => {
+                return None;
+            }
+            HandshakePrefixEnum::Greet {
+                alumet_core_version,
+                relay_plugin_version,
+                protocol_version,
+            } => MessageEnum::Greet(Greet {
+                alumet_core_version,
+                relay_plugin_version,
+                protocol_version,
+                auth_token: None,
+            }),
+            HandshakePrefixEnum::GreetResponse {
+                accept,
+                server_alumet_core_version,
+                server_relay_plugin_version,
+                protocol_version,
+            } => MessageEnum::GreetResponse(GreetResponse {
+                accept,
+                server_alumet_core_version,
+                server_relay_plugin_version,
+                protocol_version,
+                reject_reason: None,
+            }),
+        };
+        Some(MessageBody {
+            sender: prefix.sender,
+            content,
+        })
+    }
+}
+
 /// Allows to read/write protocol messages from/to an asynchronous IO stream.
 ///
 /// # Coherency
@@ -128,6 +214,8 @@ pub struct MessageStream<S: AsyncRead + AsyncWrite + Unpin> {
     stream: S,
     serializer: postcard::Serializer<OpenVecFlavor>,
     deserialization_buffer: BytesMut,
+    /// Total size of the messages read from the stream.
+    bytes_read: u64,
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
@@ -138,6 +226,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
                 output: OpenVecFlavor::new(Vec::with_capacity(BUFFER_CAPACITY)),
             },
             deserialization_buffer: BytesMut::with_capacity(BUFFER_CAPACITY),
+            bytes_read: 0,
         }
     }
 
@@ -170,6 +259,11 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
         Ok(())
     }
 
+    /// Returns the total size of the messages that have been read, including their headers.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
     #[allow(unused)]
     pub async fn read_timeout(&mut self, timeout: Duration) -> Result<Result<MessageBody<'static>, Error>, Elapsed> {
         tokio::time::timeout(timeout, self.read_message()).await
@@ -242,10 +336,18 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
         //                                       buffer length
         //
         let message_bytes = self.deserialization_buffer.split_to(message_len);
+        self.bytes_read += message_len as u64;
         let body_bytes = &message_bytes[4..]; // body = message without the header
         debug_assert_eq!(body_bytes.len(), body_len as usize);
         log::trace!("body bytes: {body_bytes:?}");
 
+        // A handshake from another version of the protocol cannot be fully decoded: only keep the fields
+        // that are common to every version, in order to reject the peer properly.
+        if let Some(body_msg) = HandshakePrefix::decode_other_version(body_bytes) {
+            log::trace!("deserialized handshake of another protocol version: {body_msg:?}");
+            return Ok(body_msg);
+        }
+
         // Deserialize the message body (skipping the header). Note: this could be done on another thread/task.
         let (body_msg, unused_bytes): (MessageBody, &[u8]) = postcard::take_from_bytes(body_bytes)?;
         if !unused_bytes.is_empty() {
@@ -263,7 +365,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
     }
 }
 
-impl MessageStream<TcpStream> {
+impl MessageStream<Connection> {
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr, std::io::Error> {
         self.stream.peer_addr()
     }
@@ -366,10 +468,101 @@ impl From<(RawMetricId, alumet::metrics::Metric)> for Metric {
 
 #[cfg(test)]
 mod tests {
+    use serde::Serialize;
+    use tokio::io::AsyncWriteExt;
+
+    use super::{MessageEnum, MessageStream, PROTOCOL_VERSION};
 
     #[test]
     fn test_message_rw_simple() -> anyhow::Result<()> {
         // TODO
         Ok(())
     }
+
+    /// Messages of the version 2 of the protocol, which had no authentication.
+    mod v2 {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        pub struct MessageBody {
+            pub sender: String,
+            pub content: MessageEnum,
+        }
+
+        #[derive(Serialize)]
+        pub enum MessageEnum {
+            Greet(Greet),
+            GreetResponse(GreetResponse),
+        }
+
+        #[derive(Serialize)]
+        pub struct Greet {
+            pub alumet_core_version: String,
+            pub relay_plugin_version: String,
+            pub protocol_version: u32,
+        }
+
+        #[derive(Serialize)]
+        pub struct GreetResponse {
+            pub accept: bool,
+            pub server_alumet_core_version: String,
+            pub server_relay_plugin_version: String,
+            pub protocol_version: u32,
+        }
+    }
+
+    /// Writes a message in the given stream, with its header.
+    async fn write_raw(stream: &mut tokio::io::DuplexStream, msg: &impl Serialize) -> anyhow::Result<()> {
+        let body = postcard::to_allocvec(msg)?;
+        stream.write_all(&(body.len() as u32).to_be_bytes()).await?;
+        stream.write_all(&body).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_handshake_of_previous_version() -> anyhow::Result<()> {
+        let (mut peer, stream) = tokio::io::duplex(1024);
+        let mut stream = MessageStream::new(stream);
+
+        // a v2 client greets the server
+        let greet = v2::MessageBody {
+            sender: String::from("old-client"),
+            content: v2::MessageEnum::Greet(v2::Greet {
+                alumet_core_version: String::from("0.8.0"),
+                relay_plugin_version: String::from("0.5.0"),
+                protocol_version: 2,
+            }),
+        };
+        write_raw(&mut peer, &greet).await?;
+        let msg = stream.read_message().await?;
+        assert_eq!(msg.sender, "old-client");
+        match msg.content {
+            MessageEnum::Greet(greet) => {
+                assert_eq!(greet.protocol_version, 2);
+                assert_eq!(greet.auth_token, None);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+
+        // a v2 server rejects the client
+        let response = v2::MessageBody {
+            sender: String::new(),
+            content: v2::MessageEnum::GreetResponse(v2::GreetResponse {
+                accept: false,
+                server_alumet_core_version: String::from("0.8.0"),
+                server_relay_plugin_version: String::from("0.5.0"),
+                protocol_version: 2,
+            }),
+        };
+        write_raw(&mut peer, &response).await?;
+        match stream.read_message().await?.content {
+            MessageEnum::GreetResponse(response) => {
+                assert!(!response.accept);
+                assert_eq!(response.protocol_version, 2);
+                assert_ne!(response.protocol_version, PROTOCOL_VERSION);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+        Ok(())
+    }
 }
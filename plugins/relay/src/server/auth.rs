@@ -0,0 +1,81 @@
+//! Authentication of the relay clients.
+
+use std::collections::HashMap;
+
+use alumet::agent::schema::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::RejectReason;
+
+/// Credentials that the clients must provide in their greeting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Token shared by all the clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// Tokens specific to some clients, by client name.
+    ///
+    /// A client that has its own token can also use the shared token, if there is one.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub client_tokens: HashMap<String, String>,
+}
+
+impl AuthConfig {
+    /// Checks the token provided by the client `client_name`.
+    pub fn check(&self, client_name: &str, token: Option<&str>) -> Result<(), RejectReason> {
+        let Some(token) = token else {
+            return Err(RejectReason::MissingToken);
+        };
+        let shared_ok = self.token.as_deref().is_some_and(|t| constant_time_eq(t, token));
+        let client_ok = self
+            .client_tokens
+            .get(client_name)
+            .is_some_and(|t| constant_time_eq(t, token));
+        if shared_ok || client_ok {
+            Ok(())
+        } else {
+            Err(RejectReason::InvalidToken)
+        }
+    }
+}
+
+/// Compares two strings in a time that does not depend on the position of the first difference,
+/// to avoid leaking the expected token through timing.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::AuthConfig;
+    use crate::protocol::RejectReason;
+
+    #[test]
+    fn check_tokens() {
+        let auth = AuthConfig {
+            token: Some(String::from("shared")),
+            client_tokens: HashMap::from([(String::from("node-1"), String::from("secret-1"))]),
+        };
+        assert_eq!(auth.check("node-1", None), Err(RejectReason::MissingToken));
+        assert_eq!(auth.check("node-1", Some("secret-1")), Ok(()));
+        assert_eq!(auth.check("node-1", Some("shared")), Ok(()));
+        assert_eq!(auth.check("node-2", Some("shared")), Ok(()));
+        assert_eq!(auth.check("node-2", Some("secret-1")), Err(RejectReason::InvalidToken));
+        assert_eq!(auth.check("node-1", Some("secret")), Err(RejectReason::InvalidToken));
+
+        let auth = AuthConfig {
+            token: None,
+            client_tokens: HashMap::from([(String::from("node-1"), String::from("secret-1"))]),
+        };
+        assert_eq!(auth.check("node-1", Some("secret-1")), Ok(()));
+        assert_eq!(auth.check("node-2", Some("secret-1")), Err(RejectReason::InvalidToken));
+    }
+}
@@ -0,0 +1,226 @@
+//! State and statistics of the clients connected to the relay server.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::control::status::ElementStatus,
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+
+/// Statistics about one client, identified by its name.
+///
+/// The counters are never reset, even when the client disconnects.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientStats {
+    /// Number of open connections with this client name.
+    pub connections: u32,
+    pub messages_received: u64,
+    pub measurements_received: u64,
+    pub bytes_received: u64,
+    pub protocol_errors: u64,
+    /// Time of the last message received from the client.
+    pub last_seen: Option<Timestamp>,
+}
+
+/// Statistics about all the clients that have been accepted by the server.
+#[derive(Clone, Default)]
+pub struct ClientRegistry(Arc<Mutex<BTreeMap<String, ClientStats>>>);
+
+/// Metrics about the clients, emitted by the server.
+#[derive(Clone)]
+pub struct ClientMetrics {
+    connected: TypedMetricId<u64>,
+    messages_received: TypedMetricId<u64>,
+    measurements_received: TypedMetricId<u64>,
+    bytes_received: TypedMetricId<u64>,
+    protocol_errors: TypedMetricId<u64>,
+    last_seen: TypedMetricId<u64>,
+}
+
+impl ClientRegistry {
+    fn update(&self, client: &str, f: impl FnOnce(&mut ClientStats)) {
+        let mut clients = self.0.lock().unwrap();
+        match clients.get_mut(client) {
+            Some(stats) => f(stats),
+            None => f(clients.entry(client.to_owned()).or_default()),
+        }
+    }
+
+    /// Records a new connection from the client (after its greeting).
+    pub fn connected(&self, client: &str) {
+        self.update(client, |stats| {
+            stats.connections += 1;
+            stats.last_seen = Some(Timestamp::now());
+        });
+    }
+
+    /// Records the end of a connection with the client.
+    pub fn disconnected(&self, client: &str) {
+        self.update(client, |stats| stats.connections = stats.connections.saturating_sub(1));
+    }
+
+    /// Records a message received from the client.
+    pub fn message_received(&self, client: &str, bytes: u64, measurements: usize) {
+        self.update(client, |stats| {
+            stats.messages_received += 1;
+            stats.measurements_received += measurements as u64;
+            stats.bytes_received += bytes;
+            stats.last_seen = Some(Timestamp::now());
+        });
+    }
+
+    /// Records an invalid message, or an error in the communication with the client.
+    pub fn protocol_error(&self, client: &str) {
+        self.update(client, |stats| stats.protocol_errors += 1);
+    }
+
+    /// Returns a copy of the statistics, sorted by client name.
+    pub fn snapshot(&self) -> Vec<(String, ClientStats)> {
+        let clients = self.0.lock().unwrap();
+        clients
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.clone()))
+            .collect()
+    }
+}
+
+impl ClientMetrics {
+    /// Registers the metrics.
+    pub fn create(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            connected: alumet.create_metric(
+                "relay_client_connected",
+                Unit::Unity,
+                "1 if the relay client is connected to the server, 0 otherwise",
+            )?,
+            messages_received: alumet.create_metric(
+                "relay_client_messages_received",
+                Unit::Unity,
+                "total number of messages received from the relay client",
+            )?,
+            measurements_received: alumet.create_metric(
+                "relay_client_measurements_received",
+                Unit::Unity,
+                "total number of measurements received from the relay client",
+            )?,
+            bytes_received: alumet.create_metric(
+                "relay_client_bytes_received",
+                Unit::Byte,
+                "total number of bytes received from the relay client",
+            )?,
+            protocol_errors: alumet.create_metric(
+                "relay_client_protocol_errors",
+                Unit::Unity,
+                "total number of protocol errors in the communication with the relay client",
+            )?,
+            last_seen: alumet.create_metric(
+                "relay_client_last_seen",
+                Unit::Second,
+                "time of the last message received from the relay client, as a Unix timestamp",
+            )?,
+        })
+    }
+
+    /// Turns the statistics into measurements, with one `relay_client` attribute per client.
+    pub fn measurements(&self, clients: &[(String, ClientStats)], timestamp: Timestamp) -> MeasurementBuffer {
+        let mut buf = MeasurementBuffer::with_capacity(clients.len() * 6);
+        for (name, stats) in clients {
+            let mut push = |metric: TypedMetricId<u64>, value: u64| {
+                buf.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        metric,
+                        Resource::LocalMachine,
+                        ResourceConsumer::LocalMachine,
+                        value,
+                    )
+                    .with_attr("relay_client", name.clone()),
+                );
+            };
+            push(self.connected, u64::from(stats.connections > 0));
+            push(self.messages_received, stats.messages_received);
+            push(self.measurements_received, stats.measurements_received);
+            push(self.bytes_received, stats.bytes_received);
+            push(self.protocol_errors, stats.protocol_errors);
+            if let Some(last_seen) = stats.last_seen {
+                push(self.last_seen, last_seen.to_unix_timestamp().0);
+            }
+        }
+        buf
+    }
+}
+
+/// Turns the statistics into a status, for the introspection of the server.
+pub fn status(clients: &[(String, ClientStats)]) -> ElementStatus {
+    fn int(n: u64) -> toml::Value {
+        toml::Value::Integer(i64::try_from(n).unwrap_or(i64::MAX))
+    }
+
+    let clients = clients
+        .iter()
+        .map(|(name, stats)| {
+            let mut client = toml::Table::new();
+            client.insert(String::from("name"), toml::Value::String(name.clone()));
+            client.insert(String::from("connected"), toml::Value::Boolean(stats.connections > 0));
+            client.insert(String::from("connections"), int(u64::from(stats.connections)));
+            client.insert(String::from("messages_received"), int(stats.messages_received));
+            client.insert(String::from("measurements_received"), int(stats.measurements_received));
+            client.insert(String::from("bytes_received"), int(stats.bytes_received));
+            client.insert(String::from("protocol_errors"), int(stats.protocol_errors));
+            if let Some(last_seen) = stats.last_seen {
+                client.insert(String::from("last_seen"), int(last_seen.to_unix_timestamp().0));
+            }
+            toml::Value::Table(client)
+        })
+        .collect();
+    let mut details = toml::Table::new();
+    details.insert(String::from("clients"), toml::Value::Array(clients));
+    ElementStatus { details }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientRegistry, status};
+
+    #[test]
+    fn registry() {
+        let registry = ClientRegistry::default();
+        registry.connected("node-b");
+        registry.connected("node-a");
+        registry.message_received("node-a", 100, 10);
+        registry.message_received("node-a", 50, 2);
+        registry.protocol_error("node-b");
+        registry.disconnected("node-b");
+
+        let clients = registry.snapshot();
+        let names: Vec<_> = clients.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["node-a", "node-b"]);
+
+        let a = &clients[0].1;
+        assert_eq!(a.connections, 1);
+        assert_eq!(a.messages_received, 2);
+        assert_eq!(a.measurements_received, 12);
+        assert_eq!(a.bytes_received, 150);
+        assert_eq!(a.protocol_errors, 0);
+        assert!(a.last_seen.is_some());
+
+        let b = &clients[1].1;
+        assert_eq!(b.connections, 0);
+        assert_eq!(b.protocol_errors, 1);
+
+        let status = status(&clients);
+        let status_clients = status.details["clients"].as_array().unwrap();
+        assert_eq!(status_clients.len(), 2);
+        assert_eq!(status_clients[0]["name"].as_str(), Some("node-a"));
+        assert_eq!(status_clients[0]["connected"].as_bool(), Some(true));
+        assert_eq!(status_clients[0]["bytes_received"].as_integer(), Some(150));
+        assert_eq!(status_clients[1]["connected"].as_bool(), Some(false));
+    }
+}
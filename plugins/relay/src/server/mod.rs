@@ -1,3 +1,5 @@
+mod auth;
+mod clients;
 mod metrics;
 mod plugin;
 mod source;
@@ -1,20 +1,24 @@
-use std::net::ToSocketAddrs;
+use std::{net::ToSocketAddrs, time::Duration};
 
+use alumet::agent::schema::JsonSchema;
 use alumet::plugin::{
     AlumetPluginStart, ConfigTable,
-    rust::{AlumetPlugin, deserialize_config, serialize_config},
+    rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema},
 };
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
 
-use crate::server::source;
+use crate::{
+    server::{auth::AuthConfig, clients::ClientMetrics, source},
+    tls::{Acceptor, ServerTlsConfig},
+};
 
 pub struct RelayServerPlugin {
     config: Config,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct Config {
     /// Address to listen on.
@@ -23,12 +27,32 @@ struct Config {
     /// For information, ip6-localhost is `::1`.
     /// To listen to all your network interfaces please use `0.0.0.0` or `::`.
     address: String,
+
+    /// TLS settings. If unset, the connections are not encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<ServerTlsConfig>,
+
+    /// Authentication of the clients. If unset, all the clients are accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auth: Option<AuthConfig>,
+
+    /// Interval between two reports of the state of the clients (as measurements).
+    #[serde(with = "humantime_serde", default = "default_report_interval")]
+    #[schemars(with = "String")]
+    report_interval: Duration,
+}
+
+fn default_report_interval() -> Duration {
+    Duration::from_secs(10)
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             address: String::from("[::]:50051"), // "any" on ipv6
+            tls: None,
+            auth: None,
+            report_interval: default_report_interval(),
         }
     }
 }
@@ -43,13 +67,23 @@ impl AlumetPlugin for RelayServerPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config: Config = deserialize_config(config)?;
-
+        if config.report_interval.is_zero() {
+            return Err(anyhow!("report_interval must be greater than zero"));
+        }
+        if let Some(auth) = &config.auth {
+            if auth.token.is_none() && auth.client_tokens.is_empty() {
+                return Err(anyhow!("authentication is enabled, but no token is configured"));
+            }
+            if config.tls.is_none() {
+                log::warn!("Client authentication is enabled without TLS: the tokens will be sent in plain text.");
+            }
+        }
         Ok(Box::new(RelayServerPlugin { config }))
     }
 
@@ -61,14 +95,28 @@ impl AlumetPlugin for RelayServerPlugin {
             .with_context(|| format!("invalid socket address: {addr}"))?
             .collect();
 
+        // Load the certificates now, for the same reason.
+        let acceptor = Acceptor::new(self.config.tls.as_ref()).context("invalid TLS configuration")?;
+        let auth = self.config.auth.take();
+
+        // Register the metrics about the clients.
+        let client_metrics = ClientMetrics::create(alumet)?;
+        let report_interval = self.config.report_interval;
+
         // Register the source builder.
         alumet.add_autonomous_source_builder("tcp_server", move |ctx, cancel_token, out_tx| {
             log::info!("Starting relay server on: {addr:?}");
             let metrics_tx = ctx.metrics_sender();
+            let reporting = source::Reporting {
+                interval: report_interval,
+                metrics: client_metrics,
+                status: ctx.status_reporter(),
+            };
             let source = Box::pin(async move {
                 // `bind` loops through all the addresses that correspond to the string
                 let listener = TcpListener::bind(addr.as_slice()).await.context("tcp binding failed")?;
-                let server = source::TcpServer::new(cancel_token, listener, out_tx, metrics_tx);
+                let server =
+                    source::TcpServer::new(cancel_token, listener, acceptor, auth, out_tx, metrics_tx, reporting);
                 server.accept_loop().await
             });
             Ok(source)
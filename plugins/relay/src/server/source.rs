@@ -1,71 +1,125 @@
-use std::{future::Future, net::SocketAddr};
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
 
-use alumet::{measurement::MeasurementBuffer, metrics::Metric, metrics::online::MetricSender};
+use alumet::{
+    measurement::{MeasurementBuffer, Timestamp},
+    metrics::{Metric, online::MetricSender},
+    pipeline::control::status::StatusReporter,
+};
+use anyhow::{Context, anyhow};
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::mpsc,
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::protocol::{self, GreetResponse, MessageBody, MessageEnum, MessageStream, PROTOCOL_VERSION};
+use crate::{
+    protocol::{self, GreetResponse, MessageBody, MessageEnum, MessageStream, PROTOCOL_VERSION, RejectReason},
+    tls::{Acceptor, Connection},
+};
 
-use super::metrics::MetricConverter;
+use super::{
+    auth::AuthConfig,
+    clients::{self, ClientMetrics, ClientRegistry},
+    metrics::MetricConverter,
+};
+
+/// Maximum amount of time to wait for the TLS handshake of a new client.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct TcpSource {
     cancel_token: CancellationToken,
-    tcp: MessageStream<TcpStream>,
+    tcp: MessageStream<Connection>,
     out_tx: mpsc::Sender<MeasurementBuffer>,
     metrics: MetricConverter,
+    auth: Option<Arc<AuthConfig>>,
+    /// Name of the client, set when the client is accepted after its greeting.
+    client_name: Option<String>,
+    clients: ClientRegistry,
 }
 
 pub struct TcpServer {
     cancel_token: CancellationToken,
     listener: TcpListener,
+    acceptor: Acceptor,
+    auth: Option<Arc<AuthConfig>>,
     measurement_tx: mpsc::Sender<MeasurementBuffer>,
     metrics_tx: MetricSender,
+    /// Reporting of the clients state.
+    reporting: Reporting,
+    clients: ClientRegistry,
+}
+
+/// Periodic reporting of the state of the clients, as measurements and as the status of the source.
+pub struct Reporting {
+    pub interval: Duration,
+    pub metrics: ClientMetrics,
+    pub status: StatusReporter,
 }
 
 impl TcpSource {
-    async fn process_message(&mut self, msg: MessageBody<'_>) -> anyhow::Result<()> {
+    /// Processes a message of the given size (in bytes).
+    async fn process_message(&mut self, msg: MessageBody<'_>, size: u64) -> anyhow::Result<()> {
         let remote_name = msg.sender;
+        if let Some(client) = &self.client_name {
+            let n_measurements = match &msg.content {
+                MessageEnum::SendMeasurements(m) => m.buf.borrowed().len(),
+                _ => 0,
+            };
+            self.clients.message_received(client, size, n_measurements);
+        }
         match msg.content {
             MessageEnum::Greet(greet) => {
-                // Ensure that the client and server are compatible and respond.
+                // Ensure that the client and server are compatible, authenticate the client, and respond.
                 log::debug!("Received {greet:?}");
-                let accept = greet.protocol_version == PROTOCOL_VERSION; // TODO check alumet and plugin are compatible?
                 let remote_addr = self
                     .tcp
                     .peer_addr()
                     .map_or_else(|err| format!("? ({err})"), |s| s.to_string());
-                if accept {
+                let reject_reason = if greet.protocol_version != PROTOCOL_VERSION {
+                    // TODO check alumet and plugin are compatible?
+                    log::warn!(
+                        "Client {remote_name} ({remote_addr}) is NOT compatible: it uses protocol version {}, which is not compatible with our protocol version {}. Rejecting.",
+                        greet.protocol_version,
+                        PROTOCOL_VERSION
+                    );
+                    Some(RejectReason::IncompatibleProtocol)
+                } else if let Some(auth) = &self.auth {
+                    auth.check(&remote_name, greet.auth_token.as_deref()).err()
+                } else {
+                    None
+                };
+                if reject_reason.is_none() {
                     log::info!(
                         "Client {remote_name} ({remote_addr}) is compatible: Alumet v{}, relay plugin v{}, protocol version {}",
                         greet.alumet_core_version,
                         greet.relay_plugin_version,
                         greet.protocol_version
                     );
-                } else {
-                    log::warn!(
-                        "Client {remote_name} ({remote_addr}) is NOT compatible: it uses protocol version {}, which is not compatible with our protocol version {}. Rejecting.",
-                        greet.protocol_version,
-                        PROTOCOL_VERSION
-                    );
-                    return Ok(());
                 }
                 self.tcp
                     .write_message(&MessageBody {
                         sender: String::from(""),
                         content: MessageEnum::GreetResponse(GreetResponse {
-                            accept,
+                            accept: reject_reason.is_none(),
                             server_alumet_core_version: alumet::VERSION.to_string(),
                             server_relay_plugin_version: crate::PLUGIN_VERSION.to_string(),
                             protocol_version: PROTOCOL_VERSION,
+                            reject_reason,
                         }),
                     })
                     .await?;
-                if !accept {
+                if let Some(reason) = reject_reason {
                     self.tcp.shutdown().await?;
+                    return Err(anyhow!("client {remote_name} ({remote_addr}) rejected: {reason}"));
                 }
+                self.clients.connected(&remote_name);
+                self.clients.message_received(&remote_name, size, 0);
+                self.client_name = Some(remote_name);
+            }
+            _ if self.client_name.is_none() => {
+                return Err(anyhow!(
+                    "client {remote_name} sent a message before being accepted, closing the connection"
+                ));
             }
             MessageEnum::RegisterMetrics(register_metrics) => {
                 let mut metric_ids = Vec::with_capacity(register_metrics.metrics.len());
@@ -111,29 +165,37 @@ impl TcpSource {
                 protocol::Error::Disconnected => false,
                 protocol::Error::VersionMismatch { .. } => true,
                 protocol::Error::Unexpected => true,
+                protocol::Error::Rejected(_) => true,
             }
         }
 
         async move {
-            loop {
+            let mut bytes_read = 0;
+            let res = loop {
                 tokio::select! {
                     biased;
                     _ = self.cancel_token.cancelled() => {
-                        break;
+                        break Ok(());
                     },
                     message = self.tcp.read_message() => {
                         match message {
                             Ok(msg) => {
-                                self.process_message(msg).await?;
+                                let size = self.tcp.bytes_read() - bytes_read;
+                                bytes_read = self.tcp.bytes_read();
+                                if let Err(e) = self.process_message(msg, size).await {
+                                    self.protocol_error();
+                                    break Err(e);
+                                }
                             },
                             Err(protocol::Error::Disconnected) => {
                                 // stop the loop normally
-                                break;
+                                break Ok(());
                             },
                             Err(err) => {
+                                self.protocol_error();
                                 if is_fatal_error(&err) {
                                     // stop the loop with an error
-                                    return Err(err.into());
+                                    break Err(err.into());
                                 } else {
                                     // try to continue (TODO maybe we should not do this?)
                                     log::error!("error while processing message from client: {err:?}");
@@ -142,8 +204,17 @@ impl TcpSource {
                         };
                     }
                 }
+            };
+            if let Some(client) = &self.client_name {
+                self.clients.disconnected(client);
             }
-            Ok(())
+            res
+        }
+    }
+
+    fn protocol_error(&self) {
+        if let Some(client) = &self.client_name {
+            self.clients.protocol_error(client);
         }
     }
 }
@@ -152,26 +223,65 @@ impl TcpServer {
     pub fn new(
         cancel_token: CancellationToken,
         listener: TcpListener,
+        acceptor: Acceptor,
+        auth: Option<AuthConfig>,
         measurement_tx: mpsc::Sender<MeasurementBuffer>,
         metrics_tx: MetricSender,
+        reporting: Reporting,
     ) -> Self {
         Self {
             cancel_token,
             listener,
+            acceptor,
+            auth: auth.map(Arc::new),
             measurement_tx,
             metrics_tx,
+            reporting,
+            clients: ClientRegistry::default(),
+        }
+    }
+
+    /// Sends the statistics about the clients to the pipeline, and updates the status of the source.
+    async fn report_clients(&self) -> anyhow::Result<()> {
+        let clients = self.clients.snapshot();
+        self.reporting.status.report(clients::status(&clients));
+        if !clients.is_empty() {
+            let measurements = self.reporting.metrics.measurements(&clients, Timestamp::now());
+            self.measurement_tx.send(measurements).await?;
         }
+        Ok(())
     }
 
     fn start_receiving(&mut self, tcp_stream: TcpStream, remote_addr: SocketAddr) {
         log::info!("New incoming connection from {remote_addr}");
-        let source = TcpSource {
-            cancel_token: self.cancel_token.child_token(),
-            tcp: MessageStream::new(tcp_stream),
-            out_tx: self.measurement_tx.clone(),
-            metrics: MetricConverter::new(self.metrics_tx.clone()),
-        };
+        let acceptor = self.acceptor.clone();
+        let cancel_token = self.cancel_token.child_token();
+        let out_tx = self.measurement_tx.clone();
+        let metrics = MetricConverter::new(self.metrics_tx.clone());
+        let auth = self.auth.clone();
+        let clients = self.clients.clone();
         tokio::spawn(async move {
+            // The TLS handshake is done here, in order not to block the acceptation of other clients.
+            let connection = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp_stream))
+                .await
+                .context("TLS handshake timed out")
+                .and_then(|res| res.context("TLS handshake failed"));
+            let connection = match connection {
+                Ok(c) => c,
+                Err(e) => {
+                    log::error!("Cannot accept relay client {remote_addr}: {e:?}");
+                    return;
+                }
+            };
+            let source = TcpSource {
+                cancel_token,
+                tcp: MessageStream::new(connection),
+                out_tx,
+                metrics,
+                auth,
+                client_name: None,
+                clients,
+            };
             if let Err(e) = source.receive_loop().await {
                 log::error!("Error in relay source connected to client {remote_addr}: {e:?}");
             }
@@ -181,12 +291,20 @@ impl TcpServer {
 
     pub fn accept_loop(mut self) -> impl Future<Output = anyhow::Result<()>> + Send {
         async move {
+            let mut report_interval = tokio::time::interval(self.reporting.interval);
+            report_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
             loop {
                 tokio::select! {
                     biased;
                     _ = self.cancel_token.cancelled() => {
                         break;
                     }
+                    _ = report_interval.tick() => {
+                        // A failed report must not stop the server: keep accepting the clients.
+                        if let Err(e) = self.report_clients().await {
+                            log::error!("Failed to report the state of the relay clients: {e:?}");
+                        }
+                    }
                     incoming = self.listener.accept() => {
                         match incoming {
                             Ok((tcp_stream, remote_addr)) => {
@@ -0,0 +1,343 @@
+//! Optional TLS layer of the relay connections.
+
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use alumet::agent::schema::JsonSchema;
+use anyhow::{Context as _, anyhow};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    TlsAcceptor, TlsConnector, TlsStream,
+    rustls::{
+        self, ClientConfig, RootCertStore, ServerConfig,
+        crypto::CryptoProvider,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
+        server::WebPkiClientVerifier,
+    },
+};
+
+/// TLS settings of the relay server.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ServerTlsConfig {
+    /// Certificate chain of the server (PEM file).
+    pub certificate: PathBuf,
+
+    /// Private key of the server (PEM file).
+    pub private_key: PathBuf,
+
+    /// Certificates of the authorities that sign the client certificates (PEM file).
+    ///
+    /// If set, the clients must present a valid certificate (mutual TLS).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+}
+
+/// TLS settings of the relay client.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ClientTlsConfig {
+    /// Certificates of the authorities that sign the server certificate (PEM file).
+    pub server_ca: PathBuf,
+
+    /// Name to check against the server certificate.
+    /// Defaults to the host part of the server address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+
+    /// Certificate chain of the client (PEM file), for mutual TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<PathBuf>,
+
+    /// Private key of the client (PEM file), for mutual TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<PathBuf>,
+}
+
+/// A connection between a relay client and a relay server, with or without TLS.
+pub enum Connection {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Connection {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Connection::Plain(tcp) => tcp,
+            Connection::Tls(tls) => tls.get_ref().0,
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp().peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp().local_addr()
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(tcp) => Pin::new(tcp).poll_read(cx, buf),
+            Connection::Tls(tls) => Pin::new(tls.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Plain(tcp) => Pin::new(tcp).poll_write(cx, buf),
+            Connection::Tls(tls) => Pin::new(tls.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(tcp) => Pin::new(tcp).poll_flush(cx),
+            Connection::Tls(tls) => Pin::new(tls.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Plain(tcp) => Pin::new(tcp).poll_shutdown(cx),
+            Connection::Tls(tls) => Pin::new(tls.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Accepts the incoming connections of the server, with TLS if it is enabled.
+#[derive(Clone)]
+pub enum Acceptor {
+    Plain,
+    Tls(TlsAcceptor),
+}
+
+impl Acceptor {
+    pub fn new(config: Option<&ServerTlsConfig>) -> anyhow::Result<Self> {
+        match config {
+            None => Ok(Acceptor::Plain),
+            Some(config) => Ok(Acceptor::Tls(TlsAcceptor::from(Arc::new(server_config(config)?)))),
+        }
+    }
+
+    /// Performs the TLS handshake, if needed.
+    pub async fn accept(&self, tcp: TcpStream) -> io::Result<Connection> {
+        match self {
+            Acceptor::Plain => Ok(Connection::Plain(tcp)),
+            Acceptor::Tls(acceptor) => {
+                let tls = acceptor.accept(tcp).await?;
+                Ok(Connection::Tls(Box::new(tls.into())))
+            }
+        }
+    }
+}
+
+/// Opens connections to the server, with TLS if it is enabled.
+#[derive(Clone)]
+pub enum Connector {
+    Plain,
+    Tls {
+        connector: TlsConnector,
+        server_name: ServerName<'static>,
+    },
+}
+
+impl Connector {
+    /// Creates a new connector for the given server address.
+    pub fn new(config: Option<&ClientTlsConfig>, server_address: &str) -> anyhow::Result<Self> {
+        let Some(config) = config else {
+            return Ok(Connector::Plain);
+        };
+        let server_name = match &config.server_name {
+            Some(name) => name.to_owned(),
+            None => server_host(server_address).to_owned(),
+        };
+        let server_name = ServerName::try_from(server_name).map_err(|e| anyhow!("invalid TLS server name: {e}"))?;
+        Ok(Connector::Tls {
+            connector: TlsConnector::from(Arc::new(client_config(config)?)),
+            server_name,
+        })
+    }
+
+    /// Opens a TCP connection and performs the TLS handshake, if needed.
+    pub async fn connect(&self, server_address: &str) -> io::Result<Connection> {
+        let tcp = TcpStream::connect(server_address).await?;
+        match self {
+            Connector::Plain => Ok(Connection::Plain(tcp)),
+            Connector::Tls { connector, server_name } => {
+                let tls = connector.connect(server_name.clone(), tcp).await?;
+                Ok(Connection::Tls(Box::new(tls.into())))
+            }
+        }
+    }
+}
+
+/// Returns the host part of an address of the form `host:port`.
+fn server_host(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _port)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn server_config(config: &ServerTlsConfig) -> anyhow::Result<ServerConfig> {
+    let builder = ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca {
+        Some(ca) => {
+            let roots = load_roots(ca)?;
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+                .build()
+                .context("invalid client CA")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let certs = load_certs(&config.certificate)?;
+    let key = load_key(&config.private_key)?;
+    builder
+        .with_single_cert(certs, key)
+        .context("invalid server certificate or private key")
+}
+
+fn client_config(config: &ClientTlsConfig) -> anyhow::Result<ClientConfig> {
+    let roots = load_roots(&config.server_ca)?;
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    match (&config.certificate, &config.private_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+            .context("invalid client certificate or private key"),
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(anyhow!(
+            "the client certificate and private key must be specified together"
+        )),
+    }
+}
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read certificates from {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).with_context(|| format!("failed to read private key from {}", path.display()))
+}
+
+fn load_roots(path: &Path) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .with_context(|| format!("invalid CA certificate in {}", path.display()))?;
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{Acceptor, ClientTlsConfig, Connector, ServerTlsConfig, server_host};
+
+    fn cert(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/certs").join(name)
+    }
+
+    fn server_config(mutual: bool) -> ServerTlsConfig {
+        ServerTlsConfig {
+            certificate: cert("server.pem"),
+            private_key: cert("server.key"),
+            client_ca: mutual.then(|| cert("ca.pem")),
+        }
+    }
+
+    fn client_config(with_cert: bool) -> ClientTlsConfig {
+        ClientTlsConfig {
+            server_ca: cert("ca.pem"),
+            server_name: None,
+            certificate: with_cert.then(|| cert("client.pem")),
+            private_key: with_cert.then(|| cert("client.key")),
+        }
+    }
+
+    /// Connects a client to a server, and sends a few bytes from the client to the server.
+    fn exchange(server: ServerTlsConfig, client: ClientTlsConfig) -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        rt.block_on(async move {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let address = listener.local_addr()?.to_string();
+            let acceptor = Acceptor::new(Some(&server))?;
+            let connector = Connector::new(Some(&client), &address)?;
+
+            let server = tokio::spawn(async move {
+                let (tcp, _) = listener.accept().await?;
+                let mut connection = acceptor.accept(tcp).await?;
+                let mut buf = [0u8; 5];
+                connection.read_exact(&mut buf).await?;
+                anyhow::Ok(buf)
+            });
+            let mut connection = connector.connect(&address).await?;
+            connection.write_all(b"hello").await?;
+            connection.flush().await?;
+
+            let received = server.await??;
+            assert_eq!(&received, b"hello");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn tls() {
+        exchange(server_config(false), client_config(false)).unwrap();
+    }
+
+    #[test]
+    fn mutual_tls() {
+        exchange(server_config(true), client_config(true)).unwrap();
+        exchange(server_config(true), client_config(false)).expect_err("the client has no certificate");
+    }
+
+    #[test]
+    fn wrong_server_name() {
+        let client = ClientTlsConfig {
+            server_name: Some(String::from("relay.example.com")),
+            ..client_config(false)
+        };
+        exchange(server_config(false), client).expect_err("the server name does not match its certificate");
+    }
+
+    #[test]
+    fn host_of_address() {
+        assert_eq!(server_host("127.0.0.1:50051"), "127.0.0.1");
+        assert_eq!(server_host("[::1]:50051"), "::1");
+        assert_eq!(server_host("relay.example.com:50051"), "relay.example.com");
+        assert_eq!(server_host("relay.example.com"), "relay.example.com");
+    }
+}
@@ -0,0 +1,23 @@
+#!/bin/sh
+# Generates the certificates used by the TLS tests of the relay plugins.
+# They are valid for 100 years and must NEVER be used outside of the tests.
+set -e
+cd "$(dirname "$0")"
+
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days 36500 \
+    -subj "/CN=alumet-relay-test-ca" -keyout ca.key -out ca.pem
+
+for name in server client; do
+    openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+        -subj "/CN=alumet-relay-test-$name" -keyout "$name.key" -out "$name.csr"
+    if [ "$name" = server ]; then
+        ext="subjectAltName=DNS:localhost,IP:127.0.0.1\nextendedKeyUsage=serverAuth"
+    else
+        ext="extendedKeyUsage=clientAuth"
+    fi
+    printf "basicConstraints=CA:FALSE\n$ext\n" > "$name.ext"
+    openssl x509 -req -in "$name.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 \
+        -extfile "$name.ext" -out "$name.pem"
+    rm "$name.csr" "$name.ext"
+done
+rm -f ca.srl
@@ -7,11 +7,8 @@ use alumet::pipeline::control::AnonymousControlHandle;
 use alumet::pipeline::control::handle::DispatchError;
 use alumet::pipeline::control::request::{self, any::AnyAnonymousControlRequest};
 use alumet::pipeline::elements::source::trigger::TriggerSpec;
-use alumet::pipeline::matching::{
-    ElementNamePattern, OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern,
-};
+use alumet::pipeline::matching::{ElementNamePattern, OutputNamePattern, SourceNamePattern, TransformNamePattern};
 use alumet::pipeline::naming::ElementKind;
-use alumet::pipeline::naming::parsing::parse_kind;
 
 use anyhow::{Context, anyhow};
 use humantime::parse_duration;
@@ -185,25 +182,7 @@ pub fn parse(command: &str) -> anyhow::Result<Command> {
 }
 
 pub fn parse_pattern(pat: &str) -> anyhow::Result<ElementNamePattern> {
-    let parts: Vec<_> = pat.splitn(3, '/').collect();
-    match parts[..] {
-        [kind, plugin_pat, element_pat] => {
-            let kind = parse_kind(kind).with_context(|| format!("bad kind: '{kind}'"))?;
-            let plugin = StringPattern::from_str(plugin_pat).with_context(|| format!("bad pattern: '{plugin_pat}'"))?;
-            let element =
-                StringPattern::from_str(element_pat).with_context(|| format!("bad pattern: '{element_pat}'"))?;
-            Ok(ElementNamePattern { kind, plugin, element })
-        }
-        [kind] => {
-            let kind = parse_kind(kind).with_context(|| format!("bad kind: '{kind}'"))?;
-            Ok(ElementNamePattern {
-                kind,
-                plugin: StringPattern::Any,
-                element: StringPattern::Any,
-            })
-        }
-        _ => Err(anyhow!("bad pattern, expected kind/plugin/element but got '{pat}'")),
-    }
+    Ok(ElementNamePattern::from_str(pat)?)
 }
 
 #[cfg(test)]
@@ -1,12 +1,13 @@
 mod command;
 mod socket;
 
-use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::agent::schema::JsonSchema;
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config_with_schema};
 use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
 use serde::{Deserialize, Serialize};
 use socket::SocketControl;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub socket_path: String,
@@ -27,7 +28,7 @@ impl AlumetPlugin for SocketControlPlugin {
     }
 
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
+        let config = serialize_config_with_schema(Config::default())?;
         Ok(Some(config))
     }
 
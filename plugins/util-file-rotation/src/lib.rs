@@ -0,0 +1,371 @@
+//! Rotation of the files written by the output plugins.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// When and how to rotate the output file.
+///
+/// The current file is always written at `output_path`.
+/// On rotation, it is renamed to `{stem}.{opening time}.{extension}`, then compressed (if enabled),
+/// and a new file is created at `output_path`.
+/// The opening time is formatted as `YYYYMMDDTHHMMSSZ`, followed by `-{n}` if several files have the same time.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RotationConfig {
+    /// Rotate the file when its size exceeds this number of bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Rotate the file at every time window (e.g. "1h"). The windows are aligned on the Unix epoch.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub interval: Option<Duration>,
+    /// Compression of the rotated files.
+    #[serde(default)]
+    pub compression: Compression,
+    /// Maximum number of rotated files to keep. The oldest files are deleted.
+    /// If unset, every file is kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_files: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+}
+
+/// Decides when to rotate the output file, moves the rotated files, compresses and deletes them.
+pub struct Rotation {
+    config: RotationConfig,
+    /// Path of the current file.
+    path: PathBuf,
+    /// When the current file has been opened.
+    opened_at: SystemTime,
+    /// Compression and deletion of the previously rotated files, done in the background.
+    cleanup: Option<JoinHandle<()>>,
+}
+
+impl Rotation {
+    pub fn new(config: RotationConfig, path: PathBuf) -> Self {
+        Self {
+            config,
+            path,
+            opened_at: SystemTime::now(),
+            cleanup: None,
+        }
+    }
+
+    /// Returns the path of the current file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the current file, of size `file_size`, must be rotated now.
+    pub fn should_rotate(&self, file_size: u64, now: SystemTime) -> bool {
+        let too_big = self.config.max_size.is_some_and(|max| file_size >= max);
+        let too_old = self
+            .config
+            .interval
+            .is_some_and(|interval| time_window(self.opened_at, interval) != time_window(now, interval));
+        too_big || too_old
+    }
+
+    /// Moves the current file out of the way.
+    ///
+    /// The file must have been flushed (or closed), and the caller is responsible for creating
+    /// a new file at the same path. Nothing must be written to the moved file afterwards.
+    ///
+    /// The compression of the rotated file and the deletion of the old files are done in a background thread.
+    pub fn rotate(&mut self, now: SystemTime) -> anyhow::Result<()> {
+        let rotated = self.rotated_path();
+        fs::rename(&self.path, &rotated).with_context(|| format!("failed to rename {:?} to {rotated:?}", self.path))?;
+        self.opened_at = now;
+        log::debug!("output file rotated to {rotated:?}");
+
+        if self.config.compression == Compression::None && self.config.keep_files.is_none() {
+            return Ok(());
+        }
+        // Don't compress several files at the same time.
+        self.wait_cleanup();
+        let compression = self.config.compression;
+        let keep_files = self.config.keep_files;
+        let path = self.path.clone();
+        let cleanup = thread::Builder::new()
+            .name(String::from("file-rotation"))
+            .spawn(move || {
+                if let Err(e) = cleanup(&path, rotated, compression, keep_files) {
+                    log::error!("failed to clean up the rotated files: {e:#}");
+                }
+            })
+            .context("failed to spawn the compression thread")?;
+        self.cleanup = Some(cleanup);
+        Ok(())
+    }
+
+    /// Waits for the compression and deletion of the rotated files to finish.
+    pub fn wait_cleanup(&mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            let _ = cleanup.join();
+        }
+    }
+
+    /// Returns the path to move the current file to, which does not exist yet.
+    fn rotated_path(&self) -> PathBuf {
+        let (stem, ext) = self.stem_and_extension();
+        let datetime = OffsetDateTime::from(self.opened_at);
+        let date = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            datetime.year(),
+            datetime.month() as u8,
+            datetime.day(),
+            datetime.hour(),
+            datetime.minute(),
+            datetime.second()
+        );
+        let mut n = 0;
+        loop {
+            let name = if n == 0 {
+                format!("{stem}.{date}{ext}")
+            } else {
+                format!("{stem}.{date}-{n}{ext}")
+            };
+            let candidate = self.path.with_file_name(name);
+            let exists = |p: &Path| p.exists();
+            let taken = exists(&candidate)
+                || [Compression::Gzip, Compression::Zstd]
+                    .iter()
+                    .any(|c| exists(&append_extension(&candidate, c.extension().unwrap())));
+            if !taken {
+                return candidate;
+            }
+            n += 1;
+        }
+    }
+
+    /// Returns the stem of the file name, and its extension with a leading dot (or an empty string).
+    fn stem_and_extension(&self) -> (String, String) {
+        stem_and_extension(&self.path)
+    }
+}
+
+impl Drop for Rotation {
+    fn drop(&mut self) {
+        // Don't leave a partially compressed file behind.
+        self.wait_cleanup();
+    }
+}
+
+/// Compresses the rotated file (if enabled), then deletes the oldest rotated files.
+fn cleanup(path: &Path, rotated: PathBuf, compression: Compression, keep_files: Option<usize>) -> anyhow::Result<()> {
+    if let Some(ext) = compression.extension() {
+        let compressed = append_extension(&rotated, ext);
+        compress(&rotated, &compressed, compression).with_context(|| format!("failed to compress {rotated:?}"))?;
+        fs::remove_file(&rotated).with_context(|| format!("failed to remove {rotated:?}"))?;
+    }
+    if let Some(n) = keep_files {
+        delete_old_files(path, n)?;
+    }
+    Ok(())
+}
+
+/// Deletes the oldest rotated files of `path`, in order to keep at most `n` of them.
+fn delete_old_files(path: &Path, n: usize) -> anyhow::Result<()> {
+    let (stem, ext) = stem_and_extension(path);
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    let mut rotated = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to list {dir:?}"))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if is_rotated_name(name, &stem, &ext) {
+            let modified = entry.metadata()?.modified()?;
+            rotated.push((modified, entry.path()));
+        }
+    }
+
+    rotated.sort();
+    let n_to_delete = rotated.len().saturating_sub(n);
+    for (_, path) in rotated.into_iter().take(n_to_delete) {
+        log::debug!("deleting old output file {path:?}");
+        fs::remove_file(&path).with_context(|| format!("failed to remove {path:?}"))?;
+    }
+    Ok(())
+}
+
+/// Returns the stem of the file name, and its extension with a leading dot (or an empty string).
+fn stem_and_extension(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, ext)
+}
+
+/// Returns `true` if `name` has the format of a rotated file, i.e.
+/// `{stem}.{YYYYMMDDTHHMMSSZ}[-{n}]{ext}[.gz|.zst]`.
+fn is_rotated_name(name: &str, stem: &str, ext: &str) -> bool {
+    let Some(rest) = name.strip_prefix(stem).and_then(|r| r.strip_prefix('.')) else {
+        return false;
+    };
+    let rest = [".gz", ".zst"]
+        .iter()
+        .find_map(|c| rest.strip_suffix(c))
+        .unwrap_or(rest);
+    let Some(date_and_index) = rest.strip_suffix(ext) else {
+        return false;
+    };
+    let (date, index) = match date_and_index.split_once('-') {
+        Some((date, index)) => (date, Some(index)),
+        None => (date_and_index, None),
+    };
+    let date = date.as_bytes();
+    let date_ok = date.len() == 16
+        && date[8] == b'T'
+        && date[15] == b'Z'
+        && date[..8].iter().chain(&date[9..15]).all(u8::is_ascii_digit);
+    let index_ok = index.is_none_or(|i| !i.is_empty() && i.bytes().all(|b| b.is_ascii_digit()));
+    date_ok && index_ok
+}
+
+/// Returns the index of the time window that contains `t`.
+fn time_window(t: SystemTime, interval: Duration) -> u128 {
+    let since_epoch = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_nanos() / interval.as_nanos().max(1)
+}
+
+fn append_extension(path: &Path, ext: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
+}
+
+fn compress(input: &Path, output: &Path, compression: Compression) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let writer = BufWriter::new(File::create(output)?);
+    match compression {
+        Compression::None => unreachable!("no compression"),
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.flush()
+        }
+        Compression::Zstd => zstd::stream::copy_encode(reader, writer, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::{Compression, Rotation, RotationConfig, is_rotated_name, time_window};
+
+    #[test]
+    fn windows() {
+        let hour = Duration::from_secs(3600);
+        let t0 = UNIX_EPOCH + Duration::from_secs(3600 * 10 + 5);
+        assert_eq!(time_window(t0, hour), 10);
+        assert_eq!(time_window(t0 + Duration::from_secs(3590), hour), 10);
+        assert_eq!(time_window(t0 + Duration::from_secs(3595), hour), 11);
+    }
+
+    #[test]
+    fn should_rotate() {
+        let t0 = UNIX_EPOCH + Duration::from_secs(100);
+        let mut rotation = Rotation::new(
+            RotationConfig {
+                max_size: Some(1000),
+                interval: Some(Duration::from_secs(60)),
+                ..Default::default()
+            },
+            "out.csv".into(),
+        );
+        rotation.opened_at = t0;
+        assert!(!rotation.should_rotate(10, t0));
+        assert!(rotation.should_rotate(1000, t0));
+        assert!(!rotation.should_rotate(10, t0 + Duration::from_secs(19)));
+        assert!(rotation.should_rotate(10, t0 + Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn rotate_and_keep() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("out.csv");
+
+        let config = RotationConfig {
+            compression: Compression::Gzip,
+            keep_files: Some(2),
+            ..Default::default()
+        };
+        let mut rotation = Rotation::new(config, path.clone());
+        // a file of the user that looks like a rotated file, it must be kept
+        fs::write(tmp.path().join("out.backup.csv"), "keep me")?;
+        for i in 0..3 {
+            fs::write(&path, format!("file {i}"))?;
+            rotation.rotate(UNIX_EPOCH)?;
+        }
+        rotation.wait_cleanup();
+        fs::write(&path, "current")?;
+
+        // only 2 compressed files have been kept
+        let mut files: Vec<String> = fs::read_dir(tmp.path())?
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 4, "unexpected files {files:?}");
+        assert_eq!(files[3], "out.csv");
+        assert_eq!(files[2], "out.backup.csv");
+        assert!(files[0].starts_with("out.") && files[0].ends_with(".csv.gz"));
+        assert!(files[1].starts_with("out.") && files[1].ends_with(".csv.gz"));
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_names() {
+        assert!(is_rotated_name("out.19700101T000000Z.csv", "out", ".csv"));
+        assert!(is_rotated_name("out.19700101T000000Z-2.csv.gz", "out", ".csv"));
+        assert!(is_rotated_name("out.20250210T131905Z.csv.zst", "out", ".csv"));
+        assert!(is_rotated_name("out.20250210T131905Z", "out", ""));
+        assert!(!is_rotated_name("out.csv", "out", ".csv"));
+        assert!(!is_rotated_name("out.backup.csv", "out", ".csv"));
+        assert!(!is_rotated_name("out.19700101T000000Z-.csv", "out", ".csv"));
+        assert!(!is_rotated_name("output.19700101T000000Z.csv", "out", ".csv"));
+        assert!(!is_rotated_name("out.19700101T000000Z.csv.bak", "out", ".csv"));
+    }
+}